@@ -0,0 +1,1428 @@
+//! End-to-end flows through `App` against a synthetic `~/.claude`.
+
+use crate::retention::RetentionRule;
+use crate::sessions::{Config, SessionManager, SortBy, TriageOrder};
+use crate::test_support::{config, set_age, Fixture};
+use crate::{Action, App, Mode, Verdict};
+use std::time::{Duration, Instant};
+
+fn fixture() -> Fixture {
+    let fx = Fixture::new();
+    fx.session("-repo-alpha", "a1", &["Fix the parser", "Add tests"], 300);
+    fx.session("-repo-alpha", "a2", &["Refactor the alpha cache layer for speed"], 100);
+    fx.session("-repo-beta", "b1", &["Why is beta slow?", "Profile it", "Ship it"], 200);
+    fx.write("projects/-repo-beta/empty.jsonl", "");
+    fx.related("a1");
+    fx.history(&[("a1", "Fix the parser"), ("b1", "Why is beta slow?"), ("gone", "lost prompt")]);
+    fx
+}
+
+fn app(fx: &Fixture) -> App {
+    loaded(fx.manager(), config())
+}
+
+fn ids(app: &App) -> Vec<&str> {
+    app.filtered.iter().map(|&i| app.sessions[i].id.as_str()).collect()
+}
+
+/// An app past its first background load, as the TUI is once the list appears.
+pub(crate) fn loaded(manager: SessionManager, config: Config) -> App {
+    let mut app = App::loading(manager, config);
+    settle(&mut app);
+    app
+}
+
+/// Drives the background load a change started until it is in place, as the event loop would.
+pub(crate) fn settle(app: &mut App) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !app.poll_loading() {
+        assert!(Instant::now() < deadline, "load never finished");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn sessions_load_in_the_background_and_keep_checked_rows_across_reloads() {
+    let fx = fixture();
+    let mut app = App::loading(fx.manager(), config());
+    app.open_after_load = Some("-repo-alpha".into());
+    assert!(app.sessions.is_empty() && app.loading.is_some());
+    settle(&mut app);
+    assert!(app.loading.is_none());
+    assert_eq!(ids(&app), ["a2", "a1"], "--project opens once the list is in");
+
+    app.filter.clear();
+    app.apply_filter();
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.selected.insert(b1);
+    fx.session("-repo-alpha", "a3", &["New one"], 10);
+    app.spawn_load(false);
+    settle(&mut app);
+    assert!(ids(&app).contains(&"a3"));
+    let checked: Vec<_> = app.selected.iter().map(|&i| app.sessions[i].id.as_str()).collect();
+    assert_eq!(checked, ["b1"]);
+}
+
+#[test]
+fn a_finished_load_waits_until_no_popup_points_at_rows() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.report_kind = crate::ReportKind::Health;
+    app.repairable = vec![app.sessions.iter().position(|s| s.id == "b1").unwrap()];
+    app.mode = Mode::Report;
+    fx.session("-repo-alpha", "a0", &["Sorts before the rest"], 0);
+    app.spawn_load(false);
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!app.poll_loading() && app.loading.is_some());
+    assert_eq!(app.sessions[app.repairable[0]].id, "b1", "the report's rows still hold");
+    app.mode = Mode::Normal;
+    settle(&mut app);
+    assert!(ids(&app).contains(&"a0"));
+}
+
+#[test]
+fn sessions_written_while_open_appear_through_the_watcher() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.start_watching();
+    assert!(app.watch.is_some());
+    app.last_load -= Duration::from_secs(5);
+    fx.session("-repo-beta", "b2", &["Written meanwhile"], 0);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !ids(&app).contains(&"b2") {
+        assert!(Instant::now() < deadline, "the new session never showed up");
+        app.poll_watch();
+        app.poll_loading();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn manual_reload_picks_up_new_sessions_and_keeps_the_view() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.filter = "project:-repo-alpha".into();
+    app.apply_filter();
+    app.state.select(Some(1));
+    assert_eq!(ids(&app), ["a2", "a1"]);
+    fx.session("-repo-alpha", "a3", &["Written meanwhile"], 0);
+    fx.session("-repo-beta", "b2", &["Elsewhere"], 0);
+    app.manual_reload();
+    assert_eq!(app.status.as_deref(), Some("Reloading sessions"));
+    settle(&mut app);
+    assert_eq!(ids(&app), ["a3", "a2", "a1"], "the filter still applies");
+    assert_eq!(app.state.selected(), Some(2), "a1 stays highlighted");
+}
+
+#[test]
+fn filter_prefixes_scope_to_one_field() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    for (query, expected) in [
+        ("project:alpha", &["a2", "a1"][..]),
+        ("project:pha", &[][..]),
+        ("project:-REPO-BETA", &["empty", "b1"][..]),
+        ("id:a", &["a2", "a1"][..]),
+        ("id:1", &[][..]),
+        ("project:beta id:b", &["b1"][..]),
+    ] {
+        app.filter = query.into();
+        app.apply_filter();
+        assert_eq!(ids(&app), expected, "{}", query);
+    }
+    let a1 = app.sessions.iter().position(|s| s.id == "a1").unwrap();
+    app.sessions[a1].user_name = Some("Alpha release".into());
+    app.filter = "name:release".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["a1"]);
+}
+
+#[test]
+fn regex_filter_matches_names_ids_and_projects() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.filter = r"re:^[ab]\d$".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["a2", "b1", "a1"]);
+    app.filter = "project:alpha re:A1|BETA".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["a1"], "case-insensitive and combined with tokens");
+    app.filter = "re:(".into();
+    app.apply_filter();
+    assert!(ids(&app).is_empty());
+    assert!(app.status.as_deref().is_some_and(|s| s.starts_with("Invalid regex")), "{:?}", app.status);
+
+    let mut input = "tag:x fix parser".to_string();
+    crate::toggle_regex(&mut input);
+    assert_eq!(input, "tag:x re:fix parser");
+    crate::toggle_regex(&mut input);
+    assert_eq!(input, "tag:x fix parser");
+}
+
+#[test]
+fn fuzzy_filter_finds_abbreviations_and_ranks_close_matches_first() {
+    let fx = fixture();
+    fx.session("-repo-gamma", "c1", &["Fix the bug in parser"], 50);
+    fx.session("-repo-gamma", "c2", &["Find it next week"], 10);
+    let mut app = app(&fx);
+    app.filter = "fxbug".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["c1"]);
+    app.filter = "fix".into();
+    app.apply_filter();
+    let found = ids(&app);
+    assert_eq!(found[0], "c1", "the whole word outranks the newer scattered match");
+    assert!(found.contains(&"c2"), "{:?}", found);
+    app.filter = "gamma parser".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["c1"], "every word has to match");
+}
+
+#[test]
+fn numeric_conditions_filter_by_size_messages_and_age() {
+    use crate::{Condition, Field};
+    let fx = fixture();
+    let mut app = app(&fx);
+    for (query, expected) in [
+        ("msgs=0", &["empty"][..]),
+        ("age>250s", &["a1"][..]),
+        ("alpha age<=150s", &["a2"][..]),
+        ("size>1gb", &[][..]),
+        ("msgs>=1 size>0", &["a2", "b1", "a1"][..]),
+    ] {
+        app.filter = query.into();
+        app.apply_filter();
+        assert_eq!(ids(&app), expected, "{}", query);
+    }
+    app.filter = "size>ten".into();
+    app.apply_filter();
+    assert!(ids(&app).is_empty());
+    assert!(app.status.as_deref().is_some_and(|s| s.contains("size>ten")), "{:?}", app.status);
+
+    let c = Condition::parse("size>1.5MB").unwrap().unwrap();
+    assert_eq!((c.field, c.value), (Field::Size, 1536 * 1024));
+    assert_eq!(Condition::parse("age>2w").unwrap().unwrap().value, 14 * 86400);
+    assert!(Condition::parse("a>b").is_none(), "unknown fields stay free text");
+}
+
+#[test]
+fn saved_filters_are_kept_in_the_config_and_recalled() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.open_saved_filters();
+    app.start_save_filter();
+    assert!(matches!(app.mode, Mode::Filters), "an empty filter is not saved");
+    app.filter = "msgs=0".into();
+    app.apply_filter();
+    app.start_save_filter();
+    app.input = "empty ones".into();
+    app.save_filter();
+    assert!(matches!(app.mode, Mode::Filters));
+    assert_eq!(app.config.saved_filters.get("empty ones").map(String::as_str), Some("msgs=0"));
+
+    app.filter.clear();
+    app.apply_filter();
+    app.open_saved_filters();
+    app.apply_saved_filter();
+    assert!(matches!(app.mode, Mode::Normal));
+    assert_eq!((app.filter.as_str(), ids(&app)), ("msgs=0", vec!["empty"]));
+    app.open_saved_filters();
+    app.delete_saved_filter();
+    assert!(app.config.saved_filters.is_empty() && app.filter_state.selected().is_none());
+}
+
+#[test]
+fn excluded_words_hide_sessions_that_mention_them() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    for (query, expected) in [
+        ("alpha -parser", &["a2"][..]),
+        ("!beta", &["a2", "a1"][..]),
+        ("repo -ALPHA -beta", &[][..]),
+        ("re:^a -cache", &["a1"][..]),
+    ] {
+        app.filter = query.into();
+        app.apply_filter();
+        assert_eq!(ids(&app), expected, "{}", query);
+    }
+}
+
+#[test]
+fn secondary_sort_orders_ties_and_is_remembered() {
+    let fx = fixture();
+    fx.session("-repo-gamma", "c1", &["A much longer single prompt than the alpha cache one, to weigh more"], 500);
+    let mut app = app(&fx);
+    app.sort = SortBy::Messages;
+    app.sort_then = Some(SortBy::Size);
+    app.apply_sort();
+    app.apply_filter();
+    let order = ids(&app);
+    let (c1, a2) = (order.iter().position(|&i| i == "c1").unwrap(), order.iter().position(|&i| i == "a2").unwrap());
+    assert!(c1 < a2, "{:?}", order);
+
+    app.open_sort_menu();
+    app.sort_state.select(SortBy::ALL.iter().position(|&o| o == SortBy::Date));
+    app.pick_sort(true);
+    assert_eq!(app.config.sort_then, Some(SortBy::Date));
+    let order = ids(&app);
+    let (c1, a2) = (order.iter().position(|&i| i == "c1").unwrap(), order.iter().position(|&i| i == "a2").unwrap());
+    assert!(a2 < c1, "{:?}", order);
+
+    app.sort_state.select(SortBy::ALL.iter().position(|&o| o == SortBy::Messages));
+    app.pick_sort(false);
+    assert!(app.sort_reversed, "picking the primary key again flips it");
+    assert_eq!(ids(&app)[0], "empty");
+    app.sort_state.select(SortBy::ALL.iter().position(|&o| o == SortBy::Date));
+    app.pick_sort(true);
+    assert_eq!(app.config.sort_then, None);
+}
+
+#[test]
+fn sorts_by_name_and_project_from_a_to_z() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.sort = SortBy::Name;
+    app.apply_sort();
+    app.apply_filter();
+    let named: Vec<&str> = ids(&app).into_iter().filter(|&i| i != "empty").collect();
+    assert_eq!(named, ["a1", "a2", "b1"], "Fix, Refactor, Why");
+
+    app.sort = SortBy::Project;
+    app.sort_then = Some(SortBy::Date);
+    app.apply_sort();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["a2", "a1", "empty", "b1"]);
+}
+
+#[test]
+fn pinned_sessions_stay_on_top_across_sorts_and_runs() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let b1 = app.filtered.iter().position(|&i| app.sessions[i].id == "b1");
+    app.select_pos(b1);
+    app.toggle_pinned().unwrap();
+    assert_eq!(ids(&app), ["b1", "empty", "a2", "a1"]);
+    assert_eq!(app.current().map(|i| app.sessions[i].id.as_str()), Some("b1"), "the highlight follows the session");
+    app.sort_reversed = true;
+    app.apply_sort();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["b1", "a1", "a2", "empty"]);
+
+    let mut app = self::app(&fx);
+    assert_eq!(ids(&app)[0], "b1", "pins are kept in the sidecar");
+    app.select_pos(Some(0));
+    app.toggle_pinned().unwrap();
+    assert_eq!(ids(&app), ["empty", "a2", "b1", "a1"]);
+}
+
+#[test]
+fn protected_sessions_survive_deletes_and_prunes() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let pos = |app: &App, id: &str| app.sessions.iter().position(|s| s.id == id).unwrap();
+    app.selected = [pos(&app, "a1"), pos(&app, "empty")].into();
+    app.toggle_protected().unwrap();
+    assert_eq!(app.status.as_deref(), Some("Protected 2 sessions"));
+    assert!(app.prunable_empty().is_empty(), "prunes do not offer them");
+
+    app.selected = [pos(&app, "a1"), pos(&app, "a2")].into();
+    app.action = Action::Delete;
+    app.perform_action().unwrap();
+    assert!(app.msg.contains("- a1: protected, skipped"), "{}", app.msg);
+    assert!(fx.exists("projects/-repo-alpha/a1.jsonl") && !fx.exists("projects/-repo-alpha/a2.jsonl"));
+
+    let mut reopened = self::app(&fx);
+    assert!(reopened.sessions.iter().filter(|s| s.protected).count() == 2, "kept in the sidecar");
+    reopened.selected = [pos(&reopened, "empty")].into();
+    reopened.toggle_protected().unwrap();
+    assert_eq!(reopened.prunable_empty().len(), 1);
+}
+
+#[test]
+fn notes_are_saved_matched_by_the_filter_and_removed_when_emptied() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let b1 = app.filtered.iter().position(|&i| app.sessions[i].id == "b1");
+    app.select_pos(b1);
+    app.input = "  kept for the profiling numbers ".into();
+    app.save_note().unwrap();
+    app.filter = "profiling".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["b1"]);
+
+    let mut reopened = self::app(&fx);
+    let b1 = reopened.sessions.iter().find(|s| s.id == "b1").unwrap();
+    assert_eq!(b1.note.as_deref(), Some("kept for the profiling numbers"));
+    reopened.filter = "-numbers".into();
+    reopened.apply_filter();
+    assert_eq!(ids(&reopened), ["empty", "a2", "a1"]);
+    reopened.filter.clear();
+    reopened.apply_filter();
+    let b1 = reopened.filtered.iter().position(|&i| reopened.sessions[i].id == "b1");
+    reopened.select_pos(b1);
+    reopened.input.clear();
+    reopened.save_note().unwrap();
+    assert!(fx.manager().load_sidecar().notes.is_empty());
+}
+
+#[test]
+fn grouped_view_folds_projects_and_checks_across_groups() {
+    use crate::ListRow;
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.state.select(Some(2));
+    app.toggle_grouped();
+    let heading = |app: &App, k: usize| match &app.rows[k] {
+        ListRow::Project { name, count, collapsed, .. } => Some((name.clone(), *count, *collapsed)),
+        ListRow::Session(_) => None,
+    };
+    assert_eq!(ids(&app), ["empty", "b1", "a2", "a1"], "groups follow their newest session");
+    assert_eq!(heading(&app, 0), Some(("-repo-beta".into(), 2, false)));
+    assert_eq!(heading(&app, 3), Some(("-repo-alpha".into(), 2, false)));
+    assert_eq!(app.current().map(|i| app.sessions[i].id.as_str()), Some("b1"), "the highlight stays put");
+
+    app.fold(Some(true));
+    assert_eq!(app.rows.len(), 4);
+    assert_eq!(app.current_heading(), Some("-repo-beta"));
+    assert!(app.targets().is_empty(), "a heading is not a session to act on");
+    app.toggle();
+    app.move_sel(2);
+    assert_eq!(app.current().map(|i| app.sessions[i].id.as_str()), Some("a2"));
+    app.toggle();
+    let mut checked: Vec<_> = app.selection().iter().map(|&i| app.sessions[i].id.as_str()).collect();
+    checked.sort();
+    assert_eq!(checked, ["a2", "b1", "empty"], "space on a heading checks its whole project");
+
+    app.jump_in_project(-1);
+    app.jump_in_project(-1);
+    assert_eq!(app.current().map(|i| app.sessions[i].id.as_str()), Some("a2"));
+    let b1 = app.filtered.iter().position(|&i| app.sessions[i].id == "b1");
+    app.select_pos(b1);
+    assert_eq!(heading(&app, 0), Some(("-repo-beta".into(), 2, false)), "highlighting into a folded project unfolds it");
+
+    app.fold_all(true);
+    assert_eq!(app.rows.len(), 2);
+    assert_eq!(app.current_heading(), Some("-repo-beta"));
+    assert_eq!(app.config.collapsed_projects.len(), 2, "folds are kept in the config");
+    app.move_sel(1);
+    app.fold_all(false);
+    assert_eq!((app.rows.len(), app.current_heading()), (6, Some("-repo-alpha")));
+
+    app.toggle_grouped();
+    assert_eq!(ids(&app), ["empty", "a2", "b1", "a1"]);
+    assert_eq!(app.current().map(|i| app.sessions[i].id.as_str()), Some("a2"), "a heading hands over to its first session");
+}
+
+#[test]
+fn loads_sessions_newest_first_with_related_files() {
+    let fx = fixture();
+    let app = app(&fx);
+    assert_eq!(ids(&app), ["empty", "a2", "b1", "a1"]);
+    let a1 = app.sessions.iter().find(|s| s.id == "a1").unwrap();
+    assert_eq!(a1.message_count, 2);
+    assert_eq!(a1.related_files.len(), 4);
+    assert_eq!(app.sessions.iter().find(|s| s.id == "empty").unwrap().message_count, 0);
+}
+
+#[test]
+fn filters_and_sorts() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.filter = "alpha".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["a2", "a1"]);
+
+    app.filter = "slow".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["b1"]);
+
+    app.filter.clear();
+    app.sort = SortBy::Messages;
+    app.apply_sort();
+    app.apply_filter();
+    assert_eq!(ids(&app)[0], "b1");
+}
+
+#[test]
+fn delete_removes_session_related_files_and_history() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let a1 = app.sessions.iter().position(|s| s.id == "a1").unwrap();
+    app.selected = [a1].into();
+    app.action = Action::Delete;
+    app.perform_action().unwrap();
+    settle(&mut app);
+
+    assert!(!fx.exists("projects/-repo-alpha/a1.jsonl"));
+    for rel in ["debug/a1.txt", "session-env/a1", "file-history/a1", "todos/a1-agent-a1.json"] {
+        assert!(!fx.exists(rel), "{} should be deleted", rel);
+    }
+    assert!(fx.exists("projects/-repo-alpha/a2.jsonl"));
+    let history = std::fs::read_to_string(fx.path("history.jsonl")).unwrap();
+    assert!(!history.contains("\"a1\"") && history.contains("\"b1\""));
+    assert_eq!(ids(&app), ["empty", "a2", "b1"]);
+    assert!(matches!(app.mode, Mode::Message));
+}
+
+#[test]
+fn deleting_a_project_takes_every_one_of_its_sessions() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let a1 = app.sessions.iter().position(|s| s.id == "a1").unwrap();
+    app.select_pos(app.filtered.iter().position(|&i| i == a1));
+    app.delete_project();
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.to_delete.len(), 2);
+    assert!(app.msg.contains("Delete all 2 sessions of -repo-alpha"), "{}", app.msg);
+    app.perform_action().unwrap();
+    settle(&mut app);
+
+    assert_eq!(ids(&app), ["empty", "b1"]);
+    assert!(!fx.exists("projects/-repo-alpha/a2.jsonl") && !fx.exists("debug/a1.txt"));
+}
+
+#[test]
+fn cancelling_a_project_delete_puts_the_checked_rows_back() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.selected = [b1].into();
+    let a1 = app.sessions.iter().position(|s| s.id == "a1").unwrap();
+    app.select_pos(app.filtered.iter().position(|&i| i == a1));
+    app.delete_project();
+    assert_eq!(app.to_delete.len(), 2);
+    app.cancel_confirm();
+
+    assert!(matches!(app.mode, Mode::Normal));
+    assert_eq!(app.selection(), [b1], "only what was checked before D stays checked");
+    assert!(fx.exists("projects/-repo-alpha/a1.jsonl") && fx.exists("projects/-repo-alpha/a2.jsonl"));
+}
+
+#[test]
+fn duplicates_hash_by_path_and_keep_what_a_same_id_copy_shares() {
+    let fx = fixture();
+    fx.related("b1");
+    fx.history(&[("b1", "Why is beta slow?")]);
+    fx.session("-repo-copy", "a1", &["Fix the parser", "Changed since the copy"], 500);
+    fx.session("-repo-copy", "b1", &["Why is beta slow?", "Profile it", "Ship it"], 400);
+    let mut app = app(&fx);
+    app.find_duplicates();
+    assert!(matches!(app.mode, Mode::Confirm), "{}", app.msg);
+    let victims: Vec<&str> = app.selection().iter().map(|&i| app.sessions[i].project.as_str()).collect();
+    assert_eq!(victims, ["-repo-copy"], "only the identical b1 copy; a1 differs from its copy");
+    assert!(app.to_delete.iter().all(|r| !r.label.contains("Fix the parser")));
+
+    app.perform_action().unwrap();
+    assert!(!fx.exists("projects/-repo-copy/b1.jsonl") && fx.exists("projects/-repo-beta/b1.jsonl"));
+    assert!(fx.exists("projects/-repo-copy/a1.jsonl"));
+    for rel in ["debug/b1.txt", "session-env/b1", "file-history/b1", "todos/b1-agent-b1.json"] {
+        assert!(fx.exists(rel), "{} belongs to the kept copy", rel);
+    }
+    assert!(std::fs::read_to_string(fx.path("history.jsonl")).unwrap().contains("\"b1\""));
+}
+
+#[test]
+fn prunes_only_orphaned_files() {
+    let fx = fixture();
+    fx.write("debug/ghost.txt", "x");
+    fx.write("session-env/ghost/env", "x");
+    fx.write("todos/ghost-agent-ghost.json", "[]");
+    fx.write("debug/latest", "keep");
+    let mut app = app(&fx);
+
+    app.review_orphans();
+    assert!(matches!(app.mode, Mode::Checklist));
+    assert_eq!(app.checklist.as_ref().unwrap().items.len(), 3);
+    app.apply_checklist();
+    assert!(matches!(app.mode, Mode::Confirm));
+    app.perform_action().unwrap();
+
+    assert!(!fx.exists("debug/ghost.txt") && !fx.exists("session-env/ghost") && !fx.exists("todos/ghost-agent-ghost.json"));
+    assert!(fx.exists("debug/latest") && fx.exists("debug/a1.txt") && fx.exists("session-env/a1"));
+}
+
+#[test]
+fn prune_empty_then_history_orphans() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.selected = app.prunable_empty().into_iter().collect();
+    assert_eq!(app.selected.len(), 1);
+    app.action = Action::Delete;
+    app.perform_action().unwrap();
+    assert!(!fx.exists("projects/-repo-beta/empty.jsonl"));
+
+    assert_eq!(app.manager.dangling_history().len(), 1);
+    assert_eq!(app.manager.prune_history_orphans(), 1);
+    assert!(app.manager.dangling_history().is_empty());
+}
+
+#[test]
+fn user_names_and_tags_survive_reload() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.state.select(Some(ids(&app).iter().position(|id| *id == "b1").unwrap()));
+    app.input = "Beta perf investigation".into();
+    app.rename_current().unwrap();
+    app.input = "keep, perf".into();
+    app.tag_targets().unwrap();
+
+    app.spawn_load(false);
+    settle(&mut app);
+    let b1 = app.sessions.iter().find(|s| s.id == "b1").unwrap();
+    assert_eq!(b1.display_name(), "Beta perf investigation");
+    assert_eq!(b1.tags, ["keep", "perf"]);
+    app.filter = "tag:perf".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["b1"]);
+}
+
+#[test]
+fn opening_a_project_restores_the_last_highlighted_session() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.open_project("-repo-alpha");
+    assert_eq!(ids(&app), ["a2", "a1"]);
+    assert_eq!(app.state.selected(), Some(0));
+
+    app.state.select(Some(1));
+    app.remember_position();
+    let mut again = loaded(fx.manager(), std::mem::take(&mut app.config));
+    again.open_project("-repo-alpha");
+    assert_eq!(again.state.selected(), Some(1));
+    assert_eq!(again.config.last_selected["-repo-alpha"], "a1");
+}
+
+#[test]
+fn delete_confirm_flags_open_todos() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let rows: Vec<usize> = ["a1", "a2"].iter().map(|id| app.sessions.iter().position(|s| s.id == *id).unwrap()).collect();
+    app.to_delete = rows.iter().map(|&i| app.delete_row(i, app.sessions[i].display_name())).collect();
+    assert_eq!(app.to_delete[0].open_todos, Some(1));
+    assert_eq!(app.open_todo_summary(), (1, 2));
+    assert!(!app.needs_capital_y());
+    app.config.block_delete_with_open_todos = Some(true);
+    assert!(app.needs_capital_y());
+}
+
+#[test]
+fn large_batches_ask_for_the_count_to_be_typed() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.to_delete = (0..3).map(|i| app.delete_row(i, app.sessions[i].display_name())).collect();
+    assert_eq!(app.typed_confirm(), None, "off by default");
+    app.config.confirm_threshold = Some(3);
+    assert_eq!(app.typed_confirm(), None, "a batch at the threshold keeps y/n");
+    app.config.confirm_threshold = Some(2);
+    assert_eq!(app.typed_confirm(), Some(3));
+}
+
+#[test]
+fn flags_sessions_whose_project_directory_is_gone() {
+    let fx = fixture();
+    let live = fx.root.display().to_string();
+    for (id, cwd) in [("gone1", "/nonexistent/old-repo"), ("gone2", "/nonexistent/old-repo"), ("live", live.as_str())] {
+        fx.write(&format!("projects/-x/{}.jsonl", id),
+            &format!("{}\n", serde_json::json!({"type": "user", "cwd": cwd, "message": {"role": "user", "content": "hi"}})));
+    }
+    let mut app = app(&fx);
+    let missing: Vec<&str> = app.sessions.iter().filter(|s| s.project_missing).map(|s| s.id.as_str()).collect();
+    assert_eq!(missing.len(), 2);
+    assert!(!app.sessions.iter().find(|s| s.id == "live").unwrap().project_missing);
+
+    app.filter = "is:missing-project".into();
+    app.apply_filter();
+    let mut shown = ids(&app);
+    shown.sort();
+    assert_eq!(shown, ["gone1", "gone2"]);
+    assert_eq!(app.prunable(|s| s.project_missing).len(), 2);
+}
+
+#[test]
+fn resume_runs_in_the_sessions_directory_when_it_still_exists() {
+    let fx = fixture();
+    let live = fx.root.display().to_string();
+    for (id, cwd) in [("gone", "/nonexistent/old-repo"), ("live", live.as_str())] {
+        fx.write(&format!("projects/-x/{}.jsonl", id),
+            &format!("{}\n", serde_json::json!({"type": "user", "cwd": cwd, "message": {"role": "user", "content": "hi"}})));
+    }
+    let mut app = app(&fx);
+    let mut at = |id: &str| {
+        app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == id));
+        app.resume_target()
+    };
+    assert_eq!(at("live"), Ok((fx.root.clone(), "live".to_string())));
+    assert_eq!(at("gone"), Err("The session's directory /nonexistent/old-repo no longer exists.".to_string()));
+    assert_eq!(at("a1"), Err("a1 does not record the directory it ran in.".to_string()));
+}
+
+#[test]
+fn deletions_and_history_rewrites_are_audited() {
+    let fx = fixture();
+    fx.write("debug/ghost.txt", "boo");
+    let mut app = app(&fx);
+    let a1 = app.sessions.iter().position(|s| s.id == "a1").unwrap();
+    app.selected = [a1].into();
+    app.action = Action::Delete;
+    app.perform_action().unwrap();
+    app.manager.remove_orphans(&[fx.path("debug/ghost.txt").display().to_string()]);
+
+    let log = app.manager.audit.recent(10);
+    let actions: Vec<&str> = log.iter().map(|e| e.action.as_str()).collect();
+    assert_eq!(actions, ["prune_orphan", "history_drop_session", "delete_session"]);
+    assert_eq!(log[0].paths, ["debug/ghost.txt"]);
+    assert_eq!(log[0].bytes, 3);
+    assert_eq!(log[2].session_id.as_deref(), Some("a1"));
+    assert_eq!(log[2].paths.len(), 5);
+    assert!(log.iter().all(|e| e.error.is_none() && !e.dry_run));
+
+    app.show_audit();
+    assert!(matches!(app.mode, Mode::Report));
+    assert!(app.report[0].contains("prune_orphan"));
+}
+
+#[test]
+fn imports_archives_asking_about_clashing_ids() {
+    let remote = fixture();
+    let remote_app = app(&remote);
+    let out = tempfile::tempdir().unwrap();
+    for id in ["a1", "b1"] {
+        let s = remote_app.sessions.iter().find(|s| s.id == id).unwrap();
+        let archived = remote_app.manager.archive_session(s).unwrap();
+        std::fs::rename(&archived, out.path().join(archived.file_name().unwrap())).unwrap();
+    }
+
+    let fx = Fixture::new();
+    fx.session("-repo-beta", "b1", &["Local b1"], 50);
+    let mut app = app(&fx);
+    app.input = out.path().display().to_string();
+    app.start_import().unwrap();
+    assert!(matches!(app.mode, Mode::ImportConflict));
+    assert!(fx.exists("projects/-repo-alpha/a1.jsonl") && fx.exists("debug/a1.txt"));
+
+    app.resolve_conflict(Some(true)).unwrap();
+    settle(&mut app);
+    assert!(matches!(app.mode, Mode::Message));
+    assert!(app.msg.starts_with("Imported 2 sessions"), "{}", app.msg);
+    let copy = app.sessions.iter().find(|s| s.project == "-repo-beta" && s.id != "b1").unwrap();
+    assert_eq!(copy.message_count, 3);
+    let content = std::fs::read_to_string(&copy.path).unwrap();
+    assert!(content.contains(&copy.id) && !content.contains("\"b1\""));
+    assert!(std::fs::read_to_string(fx.path("projects/-repo-beta/b1.jsonl")).unwrap().contains("Local b1"));
+
+    app.input = out.path().display().to_string();
+    app.start_import().unwrap();
+    app.resolve_conflict(None).unwrap();
+    assert_eq!(app.msg, "Imported 0 sessions (0KB), skipped 2.");
+}
+
+#[test]
+fn archives_go_to_the_configured_directory_then_the_originals_are_deleted() {
+    let fx = fixture();
+    let out = tempfile::tempdir().unwrap();
+    let mut config = config();
+    config.archive_dir = Some(out.path().display().to_string());
+    let mut app = loaded(fx.manager(), config);
+    let a1 = app.sessions.iter().position(|s| s.id == "a1").unwrap();
+    app.selected.insert(a1);
+    app.archive_targets();
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.msg, format!("Archived 1 to {}. Delete originals?", out.path().display()));
+    assert!(out.path().join("a1.tar.zst").exists() && !fx.exists("archives"));
+
+    app.perform_action().unwrap();
+    assert!(!fx.exists("projects/-repo-alpha/a1.jsonl") && !fx.exists("debug/a1.txt"));
+    assert_eq!(app.manager.list_archives()[0].manifest.files.len(), 5);
+}
+
+#[test]
+fn text_query_searches_message_contents_with_a_snippet() {
+    let fx = fixture();
+    let long = format!("{} then say \"ship it\" to the team", "padding ".repeat(30));
+    fx.session("-repo-beta", "quoted", &[&long], 50);
+    let mut app = app(&fx);
+    let finish = |app: &mut App| {
+        while !app.task.as_ref().unwrap().is_finished() { std::thread::sleep(Duration::from_millis(5)); }
+        app.poll_task();
+    };
+
+    app.filter = "alpha text:Answer to ADD".into();
+    app.refresh_searches();
+    assert!(matches!(app.mode, Mode::Progress));
+    finish(&mut app);
+    assert_eq!(ids(&app), ["a1"], "free text before text: still applies");
+    assert_eq!(app.content_search.as_ref().unwrap().hits.lock().unwrap()["a1"], "Answer to Add tests");
+
+    assert!(fx.exists("sessions_tui_index.json"));
+    app.filter = "text:say \"ship it\"".into();
+    app.refresh_searches();
+    assert_eq!(app.task.as_ref().unwrap().progress().total, 1, "the saved index rules out the other sessions");
+    finish(&mut app);
+    assert_eq!(ids(&app), ["quoted"]);
+    let snippet = app.content_search.as_ref().unwrap().hits.lock().unwrap()["quoted"].clone();
+    assert!(snippet.starts_with("...") && snippet.contains("say \"ship it\" to the team"), "{}", snippet);
+
+    app.filter = "text:".into();
+    app.apply_filter();
+    app.refresh_searches();
+    assert!(app.content_search.is_none() && app.task.is_none());
+    assert_eq!(app.filtered.len(), app.sessions.len());
+}
+
+#[test]
+fn file_query_finds_sessions_that_read_or_edited_the_path() {
+    use serde_json::json;
+    let fx = fixture();
+    let tool = |name: &str, path: &str| json!({"type": "assistant", "message": {"role": "assistant",
+        "content": [{"type": "tool_use", "name": name, "input": {"file_path": path}}]}}).to_string() + "\n";
+    fx.write("projects/-repo-alpha/edit.jsonl", &tool("Edit", "/repo/alpha/src/Parser.rs"));
+    fx.write("projects/-repo-alpha/read.jsonl", &(tool("Read", "/repo/alpha/./src/parser.rs") + &tool("Read", "/repo/alpha/README.md")));
+    fx.write("projects/-repo-beta/other.jsonl", &tool("Read", "/repo/beta/src/parser_test.py"));
+    let mut app = app(&fx);
+
+    app.filter = "file:src/parser.rs".into();
+    app.refresh_file_search();
+    assert!(matches!(app.mode, Mode::Progress));
+    assert_eq!(ids(&app), ["edit"], "cached edit paths match before the scan finishes");
+    assert_eq!(app.task.as_ref().unwrap().progress().total, app.sessions.len() - 1);
+    while !app.task.as_ref().unwrap().is_finished() { std::thread::sleep(std::time::Duration::from_millis(5)); }
+    app.poll_task();
+    let mut found = ids(&app);
+    found.sort();
+    assert_eq!(found, ["edit", "read"]);
+    assert_eq!(app.file_search.as_ref().unwrap().hits.lock().unwrap()["read"], ["/repo/alpha/./src/parser.rs"]);
+
+    app.filter.clear();
+    app.refresh_file_search();
+    assert!(app.file_search.is_none() && app.task.is_none());
+}
+
+#[test]
+fn stats_screen_totals_sessions_and_ranks_projects() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.open_stats();
+    assert!(matches!(app.mode, Mode::Stats));
+    assert_eq!(app.stats[0], "Sessions: 4 (0 hidden, 1 empty)");
+    assert_eq!(app.stats[3], "Projects: 2");
+    let ranked: Vec<&str> = app.stats[6..8].iter().map(|l| l.rsplit(' ').next().unwrap()).collect();
+    assert_eq!(ranked, ["-repo-alpha", "-repo-beta"]);
+    assert!(app.stats[9].starts_with("Metadata cache: "), "{:?}", app.stats);
+    assert!(app.stats[9].ends_with("detail: Normal)"), "{}", app.stats[9]);
+}
+
+#[test]
+fn stats_list_own_files_and_compaction_forgets_stale_highlights() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.config.last_selected.insert("-repo-alpha".into(), "a1".into());
+    app.config.last_selected.insert("-repo-gone".into(), "deleted".into());
+    app.open_stats();
+    assert!(app.stats.iter().any(|l| l.contains("last highlighted per project (config), 1 stale")), "{:?}", app.stats);
+    app.confirm_compact();
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.to_delete.len(), 2, "the stale highlight and the audit log rotation");
+    app.perform_action().unwrap();
+    assert_eq!(app.config.last_selected.len(), 1);
+    assert!(app.msg.starts_with("Compacted:"), "{}", app.msg);
+}
+
+#[test]
+fn custom_commands_skip_built_in_keys_and_quote_placeholders() {
+    use crate::sessions::CustomCommand;
+    let cmd = |key: &str, label: &str| CustomCommand { key: key.into(), label: label.into(), command: "gist {path} --desc {id}@{project}".into() };
+    let fx = fixture();
+    let mut config = config();
+    config.custom_commands = vec![cmd("G", "gist"), cmd("d", "clash"), cmd("G", "again"), cmd("xy", "long")];
+    let app = loaded(fx.manager(), config);
+    assert_eq!(app.commands.iter().map(|(k, c)| (*k, c.label.as_str())).collect::<Vec<_>>(), [('G', "gist")]);
+    assert!(matches!(app.mode, Mode::Message));
+    assert_eq!(app.msg.lines().collect::<Vec<_>>(), [
+        "Custom command \"clash\" rejected: 'd' is a built-in key",
+        "Custom command \"again\" rejected: 'G' is bound twice",
+        "Custom command \"long\" rejected: key \"xy\" must be a single character",
+    ]);
+
+    let mut s = app.sessions.iter().find(|s| s.id == "a1").unwrap().clone();
+    s.id = "it's".into();
+    let line = app.commands[0].1.expand(&s);
+    assert_eq!(line, format!("gist '{}' --desc 'it'\\''s'@'-repo-alpha'", s.path.display()));
+}
+
+#[test]
+fn action_reports_are_kept_with_per_item_outcomes() {
+    let fx = fixture();
+    fx.write("debug/ghost.txt", "boo");
+    let mut app = app(&fx);
+    app.orphans = vec![fx.path("debug/ghost.txt").display().to_string(), fx.path("debug/already-gone").display().to_string()];
+    app.selected = [app.sessions.iter().position(|s| s.id == "b1").unwrap()].into();
+    app.action = Action::PruneBoth;
+    app.perform_action().unwrap();
+    assert!(app.msg.starts_with("Deleted 1 sessions, 1 orphans."), "{}", app.msg);
+    assert!(app.msg.contains("- b1: 1 files"), "{}", app.msg);
+    assert!(app.msg.contains("already-gone: failed:"), "{}", app.msg);
+
+    app.msg = "Exported 2 of 2.".into();
+    app.remember();
+    app.open_history();
+    assert!(matches!(app.mode, Mode::History));
+    let titles: Vec<&str> = app.history.iter().map(|r| r.title()).collect();
+    assert_eq!(titles, ["Exported 2 of 2.", "Deleted 1 sessions, 1 orphans."]);
+    assert!(app.history[1].text.contains("ghost.txt"));
+
+    for _ in 0..30 { app.remember(); }
+    assert_eq!(app.history.len(), crate::HISTORY_REPORTS);
+}
+
+#[test]
+fn read_only_mode_refuses_writes() {
+    let fx = fixture();
+    let mut manager = fx.manager();
+    manager.read_only = true;
+    let mut app = loaded(manager, config());
+    assert!(!fx.exists("sessions_tui_cache.json"), "no cache written while read-only");
+    let before = std::fs::read_to_string(fx.path("history.jsonl")).unwrap();
+
+    app.selected = [app.sessions.iter().position(|s| s.id == "a1").unwrap()].into();
+    app.action = Action::Delete;
+    app.perform_action().unwrap();
+    assert!(app.msg.contains("a1: failed: read-only"), "{}", app.msg);
+    assert!(app.sessions.iter().any(|s| s.id == "a1"));
+    assert_eq!(app.manager.prune_history_orphans(), 0);
+    assert_eq!(std::fs::read_to_string(fx.path("history.jsonl")).unwrap(), before);
+
+    let path = fx.path("projects/-repo-alpha/a1.jsonl");
+    let truncated = std::fs::read_to_string(&path).unwrap() + "{\"type\": \"user\", \"mess";
+    std::fs::write(&path, &truncated).unwrap();
+    app.repairable = vec![app.sessions.iter().position(|s| s.id == "a1").unwrap()];
+    app.action = Action::Repair;
+    app.perform_action().unwrap();
+    assert!(app.msg.contains("a1: failed: read-only"), "{}", app.msg);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), truncated, "the health report's repair is refused too");
+
+    let files = || std::fs::read_dir(fx.path("projects/-repo-alpha")).unwrap().count();
+    let before = files();
+    app.duplicate(app.sessions.iter().position(|s| s.id == "a1").unwrap(), Some(1)).unwrap();
+    assert!(app.msg.starts_with("Duplicate failed: read-only"), "{}", app.msg);
+    assert_eq!(files(), before, "the viewer's cut-and-duplicate writes nothing");
+    let archive = fx.path("archives/a1.tar.zst");
+    assert_eq!(app.manager.restore_archive(&archive, true).unwrap_err().kind(), std::io::ErrorKind::PermissionDenied);
+    assert_eq!(app.manager.import_archive(&archive, true).unwrap_err().kind(), std::io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn bookmarks_jump_and_survive_reopening() {
+    let fx = fixture();
+    let persisted = || { let mut c = config(); c.persist_bookmarks = Some(true); c };
+    let mut app = loaded(fx.manager(), persisted());
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.open_transcript(b1, false);
+    let t = app.transcript.as_ref().unwrap();
+    let second = t.line_entry.iter().position(|&e| e == 2).unwrap();
+    let last = t.lines.len() - 1;
+    app.offset = second;
+    app.set_bookmark('a');
+    app.offset = last;
+    app.set_bookmark('z');
+    app.offset = 0;
+    app.jump_to_bookmark('a');
+    assert_eq!(app.offset, second);
+    app.jump_to_bookmark('q');
+    assert_eq!(app.offset, second, "unset letters do nothing");
+
+    // A shorter transcript (e.g. after a repair) clamps to its end.
+    let t = app.transcript.as_mut().unwrap();
+    t.line_entry.retain(|&e| e < 2);
+    t.lines.truncate(t.line_entry.len());
+    app.jump_to_bookmark('z');
+    assert_eq!(app.offset, app.transcript.as_ref().unwrap().lines.len() - 1);
+
+    let mut app = loaded(fx.manager(), persisted());
+    app.open_transcript(b1, false);
+    app.jump_to_bookmark('a');
+    assert_eq!(app.offset, second);
+}
+
+#[test]
+fn message_mode_steps_between_whole_messages() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.open_transcript(b1, false);
+    app.offset = 0;
+    app.toggle_message_mode();
+    assert_eq!(app.transcript.as_ref().unwrap().focus, Some(0));
+    app.move_focus(1);
+    app.move_focus(1);
+    let t = app.transcript.as_ref().unwrap();
+    assert_eq!(t.focus, Some(2));
+    assert_eq!(app.offset, t.first_line(2).unwrap());
+    for _ in 0..10 { app.move_focus(1); }
+    assert_eq!(app.transcript.as_ref().unwrap().focus, Some(app.transcript.as_ref().unwrap().entries.len() - 1));
+    app.move_focus(-1);
+    app.toggle_message_mode();
+    assert_eq!(app.transcript.as_ref().unwrap().focus, None);
+}
+
+#[test]
+fn redacting_marked_messages_rewrites_the_file_with_a_backup() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.open_transcript(b1, false);
+    app.confirm_redact();
+    assert!(matches!(app.mode, Mode::Expanded), "nothing marked, nothing asked");
+    let t = app.transcript.as_mut().unwrap();
+    let profile = t.entries.iter().position(|e| e.text == "Profile it").unwrap();
+    t.toggle_mark(profile);
+    app.confirm_redact();
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.to_delete.len(), 1);
+    assert!(app.to_delete[0].label.starts_with("[USER] line "), "{}", app.to_delete[0].label);
+    assert!(app.to_delete[0].label.ends_with("Profile it"));
+    assert!(app.msg.starts_with("Redact 1 messages in"), "{}", app.msg);
+
+    app.perform_action().unwrap();
+    let path = app.sessions.iter().find(|s| s.id == "b1").unwrap().path.clone();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains("\"content\":\"Profile it\""));
+    assert!(content.contains("Answer to Profile it"), "only the marked record changes");
+    assert!(content.contains("[redacted]"));
+    assert!(content.contains("Ship it"));
+    assert_eq!(std::fs::read_dir(app.manager.backup_dir()).unwrap().count(), 1);
+    assert!(app.msg.ends_with(&format!("(backup in {}).", app.manager.backup_dir().display())), "{}", app.msg);
+    assert!(app.transcript.is_none());
+}
+
+#[test]
+fn debug_log_opens_at_its_tail() {
+    let fx = fixture();
+    let lines: Vec<String> = (0..5000).map(|i| format!("[DEBUG] event {:05}", i)).collect();
+    fx.write("debug/b1.txt", &(lines.join("\n") + "\n"));
+    let mut app = app(&fx);
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "a2"));
+    app.open_debug_log(false);
+    assert!(matches!(app.mode, Mode::Message));
+    assert_eq!(app.msg, "No debug log for a2.");
+
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "b1"));
+    app.open_debug_log(false);
+    assert!(matches!(app.mode, Mode::Expanded));
+    let t = app.transcript.as_ref().unwrap();
+    let d = t.debug.as_ref().unwrap();
+    assert!(d.shown <= crate::DEBUG_TAIL_BYTES && d.shown < d.size);
+    assert!(t.lines[0].starts_with("[DEBUG] event "), "starts on a whole line: {}", t.lines[0]);
+    assert_eq!(t.lines.last().unwrap(), "[DEBUG] event 04999");
+
+    app.open_debug_log(true);
+    let t = app.transcript.as_ref().unwrap();
+    assert_eq!(t.lines.len(), 5000);
+    assert_eq!(t.debug.as_ref().unwrap().shown, t.debug.as_ref().unwrap().size);
+}
+
+#[test]
+fn large_transcripts_load_earlier_chunks_on_scrolling_up() {
+    let fx = fixture();
+    let prompts: Vec<String> = (0..40).map(|i| format!("Prompt number {:02} with some padding to make the line longer", i)).collect();
+    let prompts: Vec<&str> = prompts.iter().map(String::as_str).collect();
+    fx.session("-repo-alpha", "big", &prompts, 10);
+    let mut config = config();
+    config.transcript_chunk_bytes = Some(1024);
+    let mut app = loaded(fx.manager(), config);
+    let idx = app.sessions.iter().position(|s| s.id == "big").unwrap();
+    let full = app.manager.read_entries(&app.sessions[idx].path).unwrap();
+    let record = |t: &crate::Transcript| t.entries.iter().map(|e| e.record).collect::<Vec<_>>();
+
+    app.open_transcript(idx, false);
+    let t = app.transcript.as_ref().unwrap();
+    let w = t.window.as_ref().expect("only the tail is loaded");
+    assert!(w.start > 0 && t.entries.len() < full.len());
+    assert_eq!(t.entries.last().unwrap().text, full.last().unwrap().text);
+    assert_eq!(record(t), full[full.len() - t.entries.len()..].iter().map(|e| e.record).collect::<Vec<_>>(), "records count from the file start");
+    let tail = t.entries.len();
+
+    app.transcript.as_mut().unwrap().toggle_mark(tail - 1);
+    app.offset = 2;
+    app.scroll_up(1);
+    assert_eq!(app.offset, 1, "no read before the top");
+    assert_eq!(app.transcript.as_ref().unwrap().entries.len(), tail);
+    app.scroll_up(3);
+    let t = app.transcript.as_ref().unwrap();
+    assert!(t.entries.len() > tail);
+    assert_eq!(t.entries[t.marks[0]].record, full.last().unwrap().record, "marks follow their entry");
+    assert_eq!(t.lines[app.offset + 2], t.lines[t.first_line(t.entries.len() - tail).unwrap()], "the view stays on the same line");
+
+    app.transcript.as_mut().unwrap().bookmarks.insert('a', full[1].record);
+    app.jump_to_bookmark('a');
+    let t = app.transcript.as_ref().unwrap();
+    assert_eq!(t.window.as_ref().unwrap().start, 0);
+    assert_eq!(record(t), full.iter().map(|e| e.record).collect::<Vec<_>>());
+    assert_eq!(app.offset, t.first_line(1).unwrap());
+}
+
+#[test]
+fn brackets_hop_between_sessions_of_one_project() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let at = |app: &App| app.sessions[app.filtered[app.state.selected().unwrap()]].id.clone();
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "a2"));
+    assert!(app.jump_in_project(1));
+    assert_eq!(at(&app), "a1");
+    assert!(app.status.is_none());
+    assert!(app.jump_in_project(1));
+    assert_eq!(at(&app), "a2");
+    assert_eq!(app.status.take().as_deref(), Some("Wrapped to the first session of -repo-alpha"));
+
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "b1"));
+    app.filter = "slow".into();
+    app.apply_filter();
+    assert!(!app.jump_in_project(-1));
+    assert_eq!(app.status.as_deref(), Some("No other sessions in -repo-beta"));
+
+    app.filter.clear();
+    app.apply_filter();
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "a1"));
+    app.open_transcript(app.filtered[app.state.selected().unwrap()], false);
+    app.open_in_project(-1);
+    assert_eq!(app.transcript.as_ref().unwrap().session_id, "a2");
+    assert!(matches!(app.mode, Mode::Expanded));
+}
+
+#[test]
+fn size_delta_since_the_last_launch() {
+    let fx = fixture();
+    let app = app(&fx);
+    assert!(app.since_last.is_none() && app.status.is_none(), "first run has nothing to compare");
+    app.save_snapshot();
+    fx.session("-repo-beta", "b2", &[&"grow ".repeat(2000)], 10);
+    let mut app = loaded(fx.manager(), config());
+    let d = app.since_last.as_ref().unwrap();
+    assert_eq!(d.sessions, 1);
+    assert!(d.bytes > 10_000);
+    assert_eq!(d.projects[0].0, "-repo-beta");
+    assert!(app.status.as_deref().unwrap().starts_with("Sessions grew by "));
+    app.open_stats();
+    assert!(app.stats.iter().any(|l| l.ends_with("-repo-beta") && l.trim_start().starts_with('+')), "{:?}", app.stats);
+}
+
+#[test]
+fn digits_open_the_most_recent_sessions_past_sort_and_filter() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.sort = SortBy::Size;
+    app.apply_sort();
+    // The empty session was written last, so it ranks first.
+    app.filter = "b1".into();
+    app.apply_filter();
+    app.open_recent(2);
+    let t = app.transcript.as_ref().unwrap();
+    assert_eq!(t.session_id, "b1");
+    assert!(t.note.is_none(), "the filter already shows it");
+    assert!(matches!(app.mode, Mode::Expanded));
+    assert_eq!(app.offset, usize::MAX, "opens at the bottom");
+
+    app.mode = Mode::Normal;
+    app.open_recent(3);
+    assert_eq!(app.transcript.as_ref().unwrap().session_id, "a1");
+    assert_eq!(app.transcript.as_ref().unwrap().note.as_deref(), Some("Cleared filter \"b1\" to show it"));
+    assert!(app.filter.is_empty());
+    assert_eq!(app.sessions[app.filtered[app.state.selected().unwrap()]].id, "a1");
+
+    app.mode = Mode::Normal;
+    app.open_recent(9);
+    assert!(matches!(app.mode, Mode::Normal));
+    assert!(app.status.as_deref().unwrap().starts_with("There are only "));
+}
+
+#[test]
+fn retention_policy_lists_victims_by_rule_and_deletes_the_checked_ones() {
+    let fx = fixture();
+    let mut config = config();
+    config.retention = vec![
+        RetentionRule { project: "*alpha".into(), max_count: Some(1), max_age_days: None },
+        RetentionRule { project: "*".into(), max_count: Some(0), max_age_days: None },
+    ];
+    let mut app = loaded(fx.manager(), config);
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.sessions[b1].tags.push("keep".into());
+    app.review_retention();
+    let items: Vec<&str> = app.checklist.as_ref().unwrap().items.iter().map(|i| i.0.as_str()).collect();
+    assert_eq!(items.len(), 2, "{:?}", items);
+    assert!(items[0].starts_with("[*alpha: 1 newest] ") && items[0].ends_with("(-repo-alpha, a1)"));
+    assert!(items[1].starts_with("[*: 0 newest] ") && items[1].ends_with("(-repo-beta, empty)"));
+
+    app.checklist.as_mut().unwrap().items[1].1 = false;
+    app.apply_checklist();
+    assert!(matches!(app.mode, Mode::Confirm));
+    app.perform_action().unwrap();
+    assert!(!fx.exists("projects/-repo-alpha/a1.jsonl"));
+    assert!(fx.exists("projects/-repo-beta/empty.jsonl") && fx.exists("projects/-repo-beta/b1.jsonl"));
+}
+
+#[test]
+fn related_files_browser_deletes_single_snapshots() {
+    let fx = fixture();
+    let old = fx.write("file-history/a1/old@v1", &"x".repeat(4096));
+    set_age(&old, 9000);
+    let mut app = app(&fx);
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "a1"));
+    app.browse_related();
+    let c = app.checklist.as_ref().unwrap();
+    assert!(c.title.starts_with("Related Files of Fix the parser (footprint "));
+    assert_eq!(c.items.len(), 3);
+    assert!(c.items[0].0.ends_with("file-history/a1/old@v1"), "oldest first: {:?}", c.items);
+    assert!(c.items.iter().all(|i| !i.1), "nothing is checked up front");
+
+    app.checklist.as_mut().unwrap().items[0].1 = true;
+    app.apply_checklist();
+    assert!(matches!(app.mode, Mode::Confirm));
+    app.perform_action().unwrap();
+    assert!(app.msg.starts_with("Deleted 1 of 1 related files of Fix the parser; footprint "), "{}", app.msg);
+    assert!(!old.exists());
+    assert!(fx.exists("file-history/a1/snap") && fx.exists("session-env/a1/env") && fx.exists("projects/-repo-alpha/a1.jsonl"));
+    let audit = app.manager.audit.recent(1);
+    assert_eq!(audit[0].action, "delete_related_files");
+    assert_eq!(audit[0].paths, ["file-history/a1/old@v1"]);
+    assert_eq!(audit[0].bytes, 4096);
+
+    let a1 = app.sessions.iter().find(|s| s.id == "a1").unwrap();
+    let refused = app.manager.remove_related_entries(a1, &[a1.path.clone(), fx.path("file-history/a1/../../projects/-repo-alpha/a1.jsonl")]);
+    assert!(refused.iter().all(|r| r.as_ref().unwrap_err().kind() == std::io::ErrorKind::PermissionDenied));
+    assert!(fx.exists("projects/-repo-alpha/a1.jsonl"));
+}
+
+#[test]
+fn reclaimable_gauge_counts_empty_sessions_and_ages_its_orphan_scan() {
+    let fx = fixture();
+    fx.write("debug/ghost.txt", &"x".repeat(3 << 20));
+    let mut app = app(&fx);
+    assert_eq!(app.reclaimable(), "reclaimable: ~3.0MB (1 empty, 1 orphan)");
+
+    fx.write("session-env/ghost2/env", "x");
+    app.spawn_load(false);
+    settle(&mut app);
+    assert!(app.reclaimable().ends_with("1 orphan)"), "a reload inside the rescan interval keeps the last count");
+    app.orphan_scan.as_mut().unwrap().at = Instant::now() - Duration::from_secs(7 * 60);
+    assert!(app.reclaimable().ends_with("(1 empty, 1 orphan as of 7m ago)"));
+    app.spawn_load(false);
+    settle(&mut app);
+    assert!(app.reclaimable().ends_with("(1 empty, 2 orphans)"));
+
+    app.prune_both();
+    assert!(matches!(app.mode, Mode::Confirm));
+    app.perform_action().unwrap();
+    settle(&mut app);
+    assert_eq!(app.reclaimable(), "reclaimable: ~0KB (0 empty, 0 orphans)");
+}
+
+#[test]
+fn project_picker_shows_totals_and_opens_the_project() {
+    let fx = fixture();
+    fx.session("-repo-beta", "b2", &[&"big ".repeat(4000)], 50);
+    let mut app = app(&fx);
+    let beta = &app.projects[0];
+    assert_eq!((beta.name.as_str(), beta.count, beta.empty), ("-repo-beta", 3, 1), "largest first");
+    assert_eq!(beta.newest, app.sessions.iter().filter(|s| s.project == "-repo-beta").map(|s| s.modified).max().unwrap());
+    assert_eq!(beta.oldest, app.sessions.iter().filter(|s| s.project == "-repo-beta").map(|s| s.modified).min().unwrap());
+    assert!(beta.describe(app.now()).starts_with("3 sessions (1 empty), "));
+    assert_eq!(app.projects[1].size, app.sessions.iter().filter(|s| s.project == "-repo-alpha").map(|s| s.size).sum::<u64>());
+
+    app.state.select(app.filtered.iter().position(|&i| app.sessions[i].id == "a1"));
+    app.open_project_picker();
+    assert!(matches!(app.mode, Mode::Projects));
+    assert_eq!(app.picked_project().as_deref(), Some("-repo-alpha"), "starts on the highlighted session's project");
+    app.toggle_picker_order();
+    assert_eq!(app.picked_project().as_deref(), Some("-repo-alpha"), "the highlight follows the project");
+    assert_eq!(app.project_state.selected(), Some(0));
+
+    app.project_state.select(Some(1));
+    let picked = app.picked_project().unwrap();
+    app.open_project(&picked);
+    assert_eq!(app.filter, "project:-repo-beta");
+    assert!(ids(&app).iter().all(|id| id.starts_with('b') || *id == "empty"));
+}
+
+#[test]
+fn slow_projects_are_deferred_until_scanned_from_the_picker() {
+    let fx = fixture();
+    let first = app(&fx);
+    assert_eq!(first.scan_times.keys().collect::<Vec<_>>(), ["-repo-alpha", "-repo-beta"], "every load is timed");
+    assert!(first.deferred.is_empty(), "nothing is deferred without a threshold");
+    fx.manager().save_scan_times(&[("-repo-alpha".to_string(), 10), ("-repo-beta".to_string(), 5000)].into()).unwrap();
+
+    let mut config = config();
+    config.slow_scan_threshold_ms = Some(1000);
+    let mut app = loaded(fx.manager(), config);
+    assert_eq!(app.deferred, ["-repo-beta"]);
+    assert_eq!(ids(&app), ["a2", "a1"], "beta is left out of the load and of filtering");
+    assert!(app.status.as_deref().unwrap().contains("1 slow projects not scanned"));
+    app.open_stats();
+    assert!(app.stats.iter().any(|l| l.ends_with("5000ms  -repo-beta (not scanned)")));
+
+    app.open_project_picker();
+    app.project_state.select(Some(1));
+    assert_eq!(app.picked_project().as_deref(), Some("-repo-beta"), "deferred projects follow the loaded ones");
+    let p = app.picked_project().unwrap();
+    app.scan_deferred(p);
+    assert!(matches!(app.mode, Mode::Progress));
+    while !app.task.as_ref().unwrap().is_finished() { std::thread::sleep(std::time::Duration::from_millis(5)); }
+    app.poll_task();
+    settle(&mut app);
+    assert!(app.deferred.is_empty());
+    assert!(ids(&app).contains(&"b1"));
+    assert!(app.scan_times["-repo-beta"] < 5000, "the scan is timed again");
+    app.spawn_load(false);
+    settle(&mut app);
+    assert!(ids(&app).contains(&"b1"), "a scanned project stays loaded");
+}
+
+#[test]
+fn viewer_queues_deletions_by_id_and_confirms_them_on_leaving() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    assert_eq!(ids(&app), ["empty", "a2", "b1", "a1"]);
+    app.open_transcript(1, false);
+    assert_eq!(app.transcript.as_ref().unwrap().session_id, "a2");
+    app.queue_viewed();
+    app.open_adjacent(1);
+    assert_eq!(app.transcript.as_ref().unwrap().session_id, "b1");
+    app.toggle_viewed();
+    assert!(app.selected.contains(&2));
+    app.toggle_viewed();
+    assert!(app.selected.is_empty());
+    app.open_adjacent(1);
+    app.queue_viewed();
+    app.open_adjacent(1);
+    assert_eq!(app.transcript.as_ref().unwrap().session_id, "a1", "the last session stays open");
+    assert_eq!(app.transcript.as_ref().unwrap().note.as_deref(), Some("No next session"));
+    assert_eq!(app.queued, ["a2", "a1"]);
+
+    app.sort = SortBy::Size;
+    app.apply_sort();
+    app.apply_filter();
+    app.close_transcript();
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.msg, "Delete 2 sessions?");
+    let mut picked: Vec<&str> = app.selection().into_iter().map(|i| app.sessions[i].id.as_str()).collect();
+    picked.sort();
+    assert_eq!(picked, ["a1", "a2"], "ids survive the reorder");
+    assert!(app.queued.is_empty());
+}
+
+#[test]
+fn triage_walks_the_filtered_list_and_confirms_in_one_batch() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.filter = "alpha".into();
+    app.apply_filter();
+    app.start_triage();
+    assert!(matches!(app.mode, Mode::Triage));
+    assert_eq!(app.triage.as_ref().unwrap().ids, ["a1", "a2"], "oldest first, filtered to the project");
+    assert_eq!(app.triage.as_ref().unwrap().exchange.first().map(|e| e.role.as_str()), Some("user"));
+    app.decide(Verdict::Keep);
+    app.triage_back();
+    app.decide(Verdict::Delete);
+    app.decide(Verdict::Archive);
+    assert!(app.triage.is_none());
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.msg, format!("Delete 2 sessions (1 archived to {} first)?", fx.path("archives").display()));
+    let mut picked: Vec<&str> = app.selection().into_iter().map(|i| app.sessions[i].id.as_str()).collect();
+    picked.sort();
+    assert_eq!(picked, ["a1", "a2"]);
+    assert_eq!(fx.manager().list_archives().iter().map(|a| a.manifest.id.as_str()).collect::<Vec<_>>(), ["a2"]);
+
+    let mut config = config();
+    config.triage_order = Some(TriageOrder::Largest);
+    let mut app = loaded(fx.manager(), config);
+    app.start_triage();
+    app.decide(Verdict::Skip);
+    app.finish_triage();
+    assert!(matches!(app.mode, Mode::Normal));
+    assert_eq!(app.status.as_deref(), Some("Triage done: 0 kept, 0 to delete, 0 to archive, 1 skipped"));
+}
+
+#[test]
+fn filter_matches_every_name_a_session_has() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    let a2 = app.sessions.iter().position(|s| s.id == "a2").unwrap();
+    app.sessions[a2].summary_title = Some("Cache speedup".into());
+    app.sessions[a2].user_name = Some("Mine".into());
+    for q in ["speedup", "mine", "refactor"] {
+        app.filter = q.into();
+        app.apply_filter();
+        assert_eq!(ids(&app), ["a2"], "{}", q);
+    }
+}
+
+#[test]
+fn history_prompts_join_by_session_id_and_are_searchable() {
+    let fx = fixture();
+    fx.write("history.jsonl", concat!(
+        r#"{"display":"optimise the cache layer","sessionId":"b1","timestamp":1}"#, "\n",
+        "not json\n",
+        r#"{"display":"a later prompt","sessionId":"b1","timestamp":2}"#, "\n",
+        r#"{"display":"gone","sessionId":"deleted","timestamp":3}"#, "\n",
+    ));
+    let mut app = app(&fx);
+    let b1 = app.sessions.iter().find(|s| s.id == "b1").unwrap();
+    assert_eq!(b1.history_prompt.as_deref(), Some("optimise the cache layer"), "the first entry wins");
+    assert!(app.sessions.iter().filter(|s| s.id != "b1").all(|s| s.history_prompt.is_none()));
+    app.filter = "optimise".into();
+    app.apply_filter();
+    assert_eq!(ids(&app), ["b1"]);
+    let dangling = fx.manager().dangling_history();
+    assert_eq!(dangling.iter().map(|h| h.session_id.as_str()).collect::<Vec<_>>(), ["deleted"]);
+}
+
+#[test]
+fn dangling_history_entries_are_dropped_by_session_and_time_not_by_line() {
+    let fx = fixture();
+    let mut app = app(&fx);
+    app.review_history();
+    assert_eq!(app.checklist.as_ref().unwrap().items.len(), 1);
+    app.apply_checklist();
+    assert!(matches!(app.mode, Mode::Confirm));
+
+    // Claude Code rewrites the file while the confirm is up, moving every line down.
+    let history = fx.path("history.jsonl");
+    let before = std::fs::read_to_string(&history).unwrap();
+    let written = r#"{"display":"meanwhile","sessionId":"b1","timestamp":1800000000000}"#;
+    std::fs::write(&history, format!("{}\n{}", written, before)).unwrap();
+    app.perform_action().unwrap();
+
+    assert_eq!(app.msg, "Removed 1 history entries.");
+    let after = std::fs::read_to_string(&history).unwrap();
+    assert!(!after.contains("lost prompt") && after.contains("meanwhile"), "{}", after);
+    assert_eq!(after.lines().count(), 3);
+}
+
+#[test]
+fn old_trash_is_flagged_and_emptied_after_confirmation() {
+    let fx = fixture();
+    let old = fx.write("archives/backups/a1-old.jsonl", "old copy");
+    set_age(&old, 40 * 86400);
+    fx.write("archives/backups/a1-new.jsonl", "new copy");
+
+    let app = app(&fx);
+    assert!(matches!(app.mode, Mode::Normal), "nothing is deleted without trash_retention_days");
+    assert_eq!(app.status.as_deref(), Some("1 trash files are older than 30 days; p then t empties them"));
+
+    let mut config = config();
+    config.trash_retention_days = Some(30);
+    config.trash_warn_mb = Some(0);
+    let mut app = loaded(fx.manager(), config);
+    assert!(app.status.as_deref().unwrap().starts_with("Trash (archives/backups) holds "));
+    assert!(matches!(app.mode, Mode::Confirm));
+    assert_eq!(app.to_delete.len(), 1);
+    assert!(app.msg.starts_with("Empty 1 trash files older than 30 days"));
+    app.perform_action().unwrap();
+    assert!(!old.exists() && fx.exists("archives/backups/a1-new.jsonl"));
+    assert_eq!(app.manager.audit.recent(1)[0].action, "empty_trash");
+
+    app.open_stats();
+    let at = app.stats.iter().position(|l| l == "Tool output:").unwrap();
+    assert!(app.stats[at + 2].contains("trash: 1 backups, oldest "), "{:?}", app.stats);
+    app.offer_empty_trash(30, false);
+    assert_eq!(app.msg, "No trash older than 30 days.");
+}
@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// Name of the first tar entry, describing what the archive holds.
+pub const MANIFEST_NAME: &str = "manifest.json";
+pub const ARCHIVE_EXT: &str = ".tar.zst";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Manifest {
+    pub id: String,
+    pub project: String,
+    pub name: String,
+    pub message_count: usize,
+    pub archived_at: u64,
+    /// Archived paths, relative to the claude root.
+    pub files: Vec<String>,
+    /// Every regular file in the archive with its size and sha256, used to verify before restoring.
+    #[serde(default)]
+    pub entries: Vec<FileDigest>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileDigest {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ArchivedSession {
+    pub manifest: Manifest,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl ArchivedSession {
+    pub fn archived_str(&self) -> String {
+        chrono::DateTime::from_timestamp(self.manifest.archived_at as i64, 0)
+            .map(|d| d.with_timezone(&chrono::Local).format("%d %b %y %H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn sha256_hex(mut r: impl Read) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    let mut total = 0;
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 { break; }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+    Ok((total, format!("{:x}", hasher.finalize())))
+}
+
+/// Expands directories into the regular files they contain.
+fn walk_files(p: &Path, out: &mut Vec<PathBuf>) {
+    if p.is_dir() {
+        for e in fs::read_dir(p).into_iter().flatten().flatten() { walk_files(&e.path(), out); }
+    } else if p.is_file() {
+        out.push(p.to_path_buf());
+    }
+}
+
+/// Rejects archive paths that are absolute or climb out of the root.
+pub fn safe_relative(rel: &Path) -> bool {
+    rel.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Writes `files` (all under `root`) into a zstd-compressed tarball at `dest`, manifest first.
+/// The archive is assembled under a temporary name and renamed into place once complete.
+pub fn write_archive(root: &Path, dest: &Path, manifest: &Manifest, files: &[PathBuf]) -> io::Result<()> {
+    let tmp = dest.with_extension("zst.tmp");
+    let res = (|| {
+        let mut regular = Vec::new();
+        for p in files { walk_files(p, &mut regular); }
+        let mut manifest = manifest.clone();
+        manifest.entries.clear();
+        for p in &regular {
+            let Ok(rel) = p.strip_prefix(root) else { continue };
+            let (size, sha256) = sha256_hex(fs::File::open(p)?)?;
+            manifest.entries.push(FileDigest { path: rel.to_string_lossy().into_owned(), size, sha256 });
+        }
+
+        let enc = zstd::Encoder::new(fs::File::create(&tmp)?, 3)?;
+        let mut tar = tar::Builder::new(enc);
+        let body = serde_json::to_vec_pretty(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(body.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(manifest.archived_at);
+        header.set_cksum();
+        tar.append_data(&mut header, MANIFEST_NAME, body.as_slice())?;
+        for p in &regular {
+            let Ok(rel) = p.strip_prefix(root) else { continue };
+            tar.append_path_with_name(p, rel)?;
+        }
+        tar.into_inner()?.finish()?;
+        fs::rename(&tmp, dest)
+    })();
+    if res.is_err() { fs::remove_file(&tmp).ok(); }
+    res
+}
+
+pub fn read_manifest(path: &Path) -> io::Result<Manifest> {
+    let mut ar = tar::Archive::new(zstd::Decoder::new(fs::File::open(path)?)?);
+    let mut entry = ar.entries()?.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty archive"))??;
+    if entry.path()?.as_os_str() != MANIFEST_NAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing manifest"));
+    }
+    let mut body = String::new();
+    entry.read_to_string(&mut body)?;
+    serde_json::from_str(&body).map_err(io::Error::from)
+}
+
+pub fn list_archives(dir: &Path) -> Vec<ArchivedSession> {
+    let mut out: Vec<ArchivedSession> = fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(ARCHIVE_EXT))
+        .filter_map(|e| {
+            let path = e.path();
+            let manifest = read_manifest(&path).ok()?;
+            Some(ArchivedSession { size: e.metadata().ok()?.len(), manifest, path })
+        })
+        .collect();
+    out.sort_by_key(|a| std::cmp::Reverse(a.manifest.archived_at));
+    out
+}
+
+/// Rejects manifests whose id or paths could place files outside the root.
+fn validate_manifest(m: &Manifest) -> io::Result<()> {
+    if m.id.is_empty() || m.id.contains(['/', '\\']) || !safe_relative(Path::new(&m.id)) {
+        return Err(invalid(format!("invalid session id in manifest: {:?}", m.id)));
+    }
+    let paths = m.files.iter().chain(m.entries.iter().map(|d| &d.path));
+    if let Some(p) = paths.into_iter().find(|p| !safe_relative(Path::new(p))) {
+        return Err(invalid(format!("unsafe path in manifest: {}", p)));
+    }
+    Ok(())
+}
+
+/// Path of the session log within an archive.
+pub fn session_log(m: &Manifest) -> String {
+    format!("projects/{}/{}.jsonl", m.project, m.id)
+}
+
+/// The archives an import source names: the file itself, or every archive in a directory.
+pub fn archives_in(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() { return vec![path.to_path_buf()]; }
+    let mut out: Vec<PathBuf> = fs::read_dir(path).into_iter().flatten().flatten()
+        .map(|e| e.path())
+        .filter(|p| p.to_string_lossy().ends_with(ARCHIVE_EXT))
+        .collect();
+    out.sort();
+    out
+}
+
+/// Reads the whole archive and checks every entry against the manifest digests.
+pub fn verify_archive(path: &Path) -> io::Result<Manifest> {
+    let manifest = read_manifest(path).map_err(|e| invalid(format!("corrupted archive: {}", e)))?;
+    validate_manifest(&manifest)?;
+    let mut expected: HashMap<&str, &FileDigest> = manifest.entries.iter().map(|d| (d.path.as_str(), d)).collect();
+    let mut ar = tar::Archive::new(zstd::Decoder::new(fs::File::open(path)?)?);
+    for entry in ar.entries()?.skip(1) {
+        let entry = entry.map_err(|e| invalid(format!("corrupted archive: {}", e)))?;
+        let rel = entry.path()?.to_string_lossy().into_owned();
+        if !safe_relative(Path::new(&rel)) { return Err(invalid(format!("unsafe path in archive: {}", rel))); }
+        if !entry.header().entry_type().is_file() { continue; }
+        let digest = expected.remove(rel.as_str()).ok_or_else(|| invalid(format!("unexpected entry: {}", rel)))?;
+        let (size, sha256) = sha256_hex(entry).map_err(|e| invalid(format!("corrupted archive: {}", e)))?;
+        if size != digest.size || sha256 != digest.sha256 {
+            return Err(invalid(format!("checksum mismatch: {}", rel)));
+        }
+    }
+    if let Some(missing) = expected.keys().next() {
+        return Err(invalid(format!("missing entry: {}", missing)));
+    }
+    Ok(manifest)
+}
+
+/// Verifies the archive, unpacks it into a staging directory under `root`, then moves each
+/// archived path into place. Nothing in `root` is touched unless verification succeeds.
+pub fn restore_archive(root: &Path, path: &Path, overwrite: bool) -> io::Result<Manifest> {
+    let manifest = verify_archive(path)?;
+    place(root, path, &manifest, overwrite, &manifest.id)?;
+    Ok(manifest)
+}
+
+/// Like a restore, but for archives made elsewhere: the archive must contain its session log,
+/// existing files are never overwritten, and with `new_id` every path and `sessionId` is moved
+/// to that id. Returns the manifest as imported.
+pub fn import_archive(root: &Path, path: &Path, new_id: Option<&str>) -> io::Result<Manifest> {
+    let mut manifest = verify_archive(path)?;
+    if !manifest.entries.iter().any(|d| d.path == session_log(&manifest)) {
+        return Err(invalid(format!("archive does not contain {}", session_log(&manifest))));
+    }
+    let id = new_id.unwrap_or(&manifest.id).to_string();
+    place(root, path, &manifest, false, &id)?;
+    manifest.files = manifest.files.iter().map(|f| f.replace(&manifest.id, &id)).collect();
+    manifest.id = id;
+    Ok(manifest)
+}
+
+/// Unpacks into a staging directory and moves each archived path to its place under `root`,
+/// renaming from the manifest's id to `id` on the way.
+fn place(root: &Path, path: &Path, manifest: &Manifest, overwrite: bool, id: &str) -> io::Result<()> {
+    let dest = |f: &str| root.join(f.replace(&manifest.id, id));
+    if !overwrite {
+        if let Some(f) = manifest.files.iter().find(|f| dest(f).exists()) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", f)));
+        }
+    }
+    let staging = root.join("archives").join(format!(".restore-{}", manifest.id));
+    if staging.exists() { fs::remove_dir_all(&staging)?; }
+    let res = (|| {
+        fs::create_dir_all(&staging)?;
+        let mut ar = tar::Archive::new(zstd::Decoder::new(fs::File::open(path)?)?);
+        for entry in ar.entries()?.skip(1) {
+            entry?.unpack_in(&staging)?;
+        }
+        if id != manifest.id {
+            let log = staging.join(session_log(manifest));
+            fs::write(&log, crate::sessions::reassign_session_id(&fs::read_to_string(&log)?, id, None))?;
+        }
+        for f in &manifest.files {
+            let (from, to) = (staging.join(f), dest(f));
+            if !from.exists() { continue; }
+            if to.is_dir() { fs::remove_dir_all(&to)?; } else if to.exists() { fs::remove_file(&to)?; }
+            if let Some(parent) = to.parent() { fs::create_dir_all(parent)?; }
+            fs::rename(&from, &to)?;
+        }
+        Ok(())
+    })();
+    fs::remove_dir_all(&staging).ok();
+    res
+}
+
+pub fn now_ts() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "projects/-p/s1.jsonl";
+
+    fn manifest(files: &[&str]) -> Manifest {
+        Manifest {
+            id: "s1".into(), project: "-p".into(), name: "s1".into(), message_count: 1, archived_at: 1,
+            files: files.iter().map(|f| f.to_string()).collect(), entries: Vec::new(),
+        }
+    }
+
+    /// A claude root inside a scratch directory, so escapes land somewhere the test can see.
+    fn root() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("claude");
+        fs::create_dir_all(root.join("projects/-p")).unwrap();
+        fs::create_dir_all(root.join("archives")).unwrap();
+        fs::write(root.join(LOG), "{\"sessionId\":\"s1\"}\n").unwrap();
+        (dir, root)
+    }
+
+    fn archived(root: &Path) -> PathBuf {
+        let dest = root.join("archives").join(format!("s1{}", ARCHIVE_EXT));
+        write_archive(root, &dest, &manifest(&[LOG]), &[root.join(LOG)]).unwrap();
+        dest
+    }
+
+    /// Writes an archive by hand, with entry names set raw so `..` gets past the tar builder.
+    fn raw_archive(dest: &Path, manifest: &Manifest, entries: &[(&str, &str)]) {
+        let mut tar = tar::Builder::new(zstd::Encoder::new(fs::File::create(dest).unwrap(), 3).unwrap());
+        let body = serde_json::to_vec(manifest).unwrap();
+        let mut items = vec![(MANIFEST_NAME, body.as_slice())];
+        items.extend(entries.iter().map(|(name, data)| (*name, data.as_bytes())));
+        for (name, data) in items {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            tar.append(&header, data).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn err(res: io::Result<Manifest>) -> String {
+        res.map(|m| m.id).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn restores_a_verified_archive_only_over_nothing_unless_asked() {
+        let (_dir, root) = root();
+        let path = archived(&root);
+        assert_eq!(verify_archive(&path).unwrap().entries.len(), 1);
+
+        fs::write(root.join(LOG), "live\n").unwrap();
+        let e = restore_archive(&root, &path, false).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(root.join(LOG)).unwrap(), "live\n", "the live session is left alone");
+
+        restore_archive(&root, &path, true).unwrap();
+        assert_eq!(fs::read_to_string(root.join(LOG)).unwrap(), "{\"sessionId\":\"s1\"}\n");
+        assert!(!root.join("archives/.restore-s1").exists(), "staging is cleaned up");
+    }
+
+    #[test]
+    fn truncated_archives_are_refused_before_anything_is_placed() {
+        let (_dir, root) = root();
+        let path = archived(&root);
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        fs::remove_file(root.join(LOG)).unwrap();
+
+        assert!(err(verify_archive(&path)).contains("corrupted archive"));
+        assert!(restore_archive(&root, &path, false).is_err());
+        assert!(!root.join(LOG).exists());
+    }
+
+    #[test]
+    fn tampered_entries_fail_their_checksum() {
+        let (_dir, root) = root();
+        let mut m = manifest(&[LOG]);
+        let (size, sha256) = sha256_hex("original".as_bytes()).unwrap();
+        m.entries.push(FileDigest { path: LOG.into(), size, sha256 });
+        let path = root.join("archives/tampered.tar.zst");
+        raw_archive(&path, &m, &[(LOG, "tampered")]);
+
+        assert_eq!(err(verify_archive(&path)), format!("checksum mismatch: {}", LOG));
+        assert!(restore_archive(&root, &path, true).is_err());
+        assert_eq!(fs::read_to_string(root.join(LOG)).unwrap(), "{\"sessionId\":\"s1\"}\n");
+    }
+
+    #[test]
+    fn manifest_paths_may_not_climb_out_of_the_root() {
+        let (dir, root) = root();
+        let path = root.join("archives/climb.tar.zst");
+        raw_archive(&path, &manifest(&["../escape.txt"]), &[]);
+
+        assert_eq!(err(verify_archive(&path)), "unsafe path in manifest: ../escape.txt");
+        assert!(restore_archive(&root, &path, true).is_err());
+        assert!(!dir.path().join("escape.txt").exists());
+    }
+
+    #[test]
+    fn archive_entries_may_not_escape_the_root() {
+        let (dir, root) = root();
+        let path = root.join("archives/escape.tar.zst");
+        raw_archive(&path, &manifest(&[]), &[("../escape.txt", "gotcha")]);
+
+        assert_eq!(err(verify_archive(&path)), "unsafe path in archive: ../escape.txt");
+        assert!(restore_archive(&root, &path, true).is_err());
+        assert!(!dir.path().join("escape.txt").exists());
+    }
+}
@@ -0,0 +1,152 @@
+//! Append-only JSONL record of everything the tool deletes or rewrites, so a cleanup can be
+//! traced afterwards.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The log is rotated to `audit.log.1` once it passes this size, so at most two files exist.
+const MAX_BYTES: u64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AuditEntry {
+    /// RFC 3339, local time.
+    pub timestamp: String,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub dry_run: bool,
+    /// The paths went to the OS trash rather than being removed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trashed: bool,
+    /// Set when the operation failed part way; `paths` then lists what was removed before that.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str) -> Self {
+        AuditEntry { timestamp: chrono::Local::now().to_rfc3339(), action: action.to_string(), ..Default::default() }
+    }
+
+    /// Header line for the viewer; the paths follow indented.
+    pub fn describe(&self) -> String {
+        let when = chrono::DateTime::parse_from_rfc3339(&self.timestamp)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| self.timestamp.clone());
+        let mut out = format!("{}  {}", when, self.action);
+        if let Some(id) = &self.session_id { out.push_str(&format!("  {:.8}", id)); }
+        out.push_str(&format!("  {} path{}, {}", self.paths.len(), if self.paths.len() == 1 { "" } else { "s" }, crate::sessions::format_size(self.bytes)));
+        if self.dry_run { out.push_str("  [dry run]"); }
+        if self.trashed { out.push_str("  [to trash]"); }
+        if let Some(e) = &self.error { out.push_str(&format!("  FAILED: {}", e)); }
+        out
+    }
+}
+
+/// Size of a file, or of everything under a directory.
+pub fn disk_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::read_dir(path).into_iter().flatten().flatten().map(|e| disk_size(&e.path())).sum(),
+        Ok(m) => m.len(),
+        Err(_) => 0,
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    enabled: bool,
+    max_bytes: u64,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        AuditLog { path, enabled: true, max_bytes: MAX_BYTES }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn rotated(&self) -> PathBuf {
+        let mut p = self.path.as_os_str().to_owned();
+        p.push(".1");
+        PathBuf::from(p)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes taken by the log and its rotated predecessor.
+    pub fn size(&self) -> u64 {
+        [self.path.clone(), self.rotated()].iter().map(|p| fs::metadata(p).map_or(0, |m| m.len())).sum()
+    }
+
+    /// Moves the current log over the rotated one, dropping the oldest entries.
+    pub fn rotate(&self) -> io::Result<()> {
+        if !self.path.exists() { return Ok(()); }
+        fs::rename(&self.path, self.rotated())
+    }
+
+    /// Appends `entry`. Logging must never block the operation it describes, so failures here
+    /// are swallowed.
+    pub fn record(&self, entry: AuditEntry) {
+        if !self.enabled { return; }
+        self.append(&entry).ok();
+    }
+
+    fn append(&self, entry: &AuditEntry) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() { fs::create_dir_all(dir)?; }
+        if fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_bytes) {
+            fs::rename(&self.path, self.rotated())?;
+        }
+        let mut f = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{}", serde_json::to_string(entry)?)
+    }
+
+    /// Up to `n` entries, newest first, reaching into the rotated file when needed.
+    pub fn recent(&self, n: usize) -> Vec<AuditEntry> {
+        [self.path.clone(), self.rotated()].iter()
+            .flat_map(|p| {
+                let content = fs::read_to_string(p).unwrap_or_default();
+                content.lines().rev().filter_map(|l| serde_json::from_str(l).ok()).collect::<Vec<AuditEntry>>()
+            })
+            .take(n)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_newest_first_and_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = AuditLog::new(dir.path().join("audit.log"));
+        log.max_bytes = 300;
+        for i in 0..6 {
+            let mut e = AuditEntry::new("delete_session");
+            e.session_id = Some(format!("s{}", i));
+            e.paths = vec![format!("projects/p/s{}.jsonl", i)];
+            log.record(e);
+        }
+        assert!(dir.path().join("audit.log.1").exists());
+        assert!(fs::metadata(dir.path().join("audit.log")).unwrap().len() < 600);
+        let recent = log.recent(3);
+        let ids: Vec<_> = recent.iter().map(|e| e.session_id.clone().unwrap()).collect();
+        assert_eq!(ids, ["s5", "s4", "s3"]);
+
+        log.set_enabled(false);
+        log.record(AuditEntry::new("delete_session"));
+        assert_eq!(log.recent(1)[0].session_id.as_deref(), Some("s5"));
+    }
+}
@@ -0,0 +1,365 @@
+//! Command line: the TUI's own options and the headless commands for cron and scripts.
+
+use crate::{archive, lock, notify, retention, Import};
+use chrono::{DateTime, Local};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use claude_sessions::sessions::{self, format_size, Config, Session, SessionManager};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Browse, clean up and archive Claude Code sessions")]
+pub struct Cli {
+    /// Open on this project: a path, a project directory name or its last component.
+    pub project: Option<String>,
+    /// Append a diagnostic log to this file (overrides `log_file` in the config).
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Print the diagnostic log to stderr; headless commands only.
+    #[arg(long, global = true)]
+    pub verbose: bool,
+    /// Don't ask before deleting or overwriting.
+    #[arg(long, global = true)]
+    pub yes: bool,
+    /// Output of `list` and `prune`.
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    /// Restore an archived session by id.
+    #[arg(long, value_name = "ID")]
+    pub restore: Option<String>,
+    /// Delete what the configured retention rules allow.
+    #[arg(long)]
+    pub apply_retention: bool,
+    /// Import an archive or a directory of archives.
+    #[arg(long, value_name = "PATH")]
+    pub import: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    /// One JSON document on stdout, for jq and other tools.
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List sessions, newest first.
+    List(Filters),
+    /// Delete the sessions the filters pick; at least one of --older-than or --empty-only is
+    /// required. Sessions tagged `keep` are never pruned.
+    Prune(Filters),
+    /// Write a session's transcript as text.
+    Export {
+        id: String,
+        /// Directory to write `<id>.txt` into.
+        #[arg(long, default_value = "exports")]
+        dir: PathBuf,
+    },
+    /// Delete one session with its related files and history entries.
+    Delete { id: String },
+}
+
+#[derive(Args, Debug, Default)]
+pub struct Filters {
+    /// Only sessions of this project.
+    #[arg(long)]
+    pub project: Option<String>,
+    /// Only sessions last modified more than this many days ago.
+    #[arg(long, value_name = "DAYS")]
+    pub older_than: Option<u64>,
+    /// Only sessions without messages.
+    #[arg(long)]
+    pub empty_only: bool,
+}
+
+impl Filters {
+    /// The sessions these filters pick, in the order given.
+    fn pick<'a>(&self, manager: &SessionManager, sessions: &'a [Session], now: SystemTime) -> Result<Vec<&'a Session>, Box<dyn Error>> {
+        let project = match &self.project {
+            Some(p) => Some(manager.resolve_project(p).map_err(|close| no_project(p, &close))?),
+            None => None,
+        };
+        let cutoff = self.older_than.map(|d| now - Duration::from_secs(d * 86400));
+        Ok(sessions.iter()
+            .filter(|s| project.as_ref().is_none_or(|p| s.project == *p))
+            .filter(|s| cutoff.is_none_or(|c| s.modified < c))
+            .filter(|s| !self.empty_only || s.message_count == 0)
+            .collect())
+    }
+}
+
+impl Cli {
+    /// Whether a command that runs without the TUI was given.
+    pub fn is_headless(&self) -> bool {
+        self.command.is_some() || self.restore.is_some() || self.apply_retention || self.import.is_some()
+    }
+}
+
+/// Runs the headless command given, if any.
+pub fn run(cli: &Cli) -> Option<Result<(), Box<dyn Error>>> {
+    if let Some(id) = &cli.restore { return Some(restore(id, cli.yes)); }
+    if cli.apply_retention { return Some(apply_retention(cli.yes)); }
+    if let Some(source) = &cli.import { return Some(import(source)); }
+    Some(match cli.command.as_ref()? {
+        Command::List(f) => list(f, cli.format),
+        Command::Prune(f) => prune(f, cli.yes, cli.format),
+        Command::Export { id, dir } => export(id, dir),
+        Command::Delete { id } => delete(id, cli.yes),
+    })
+}
+
+pub fn no_project(arg: &str, close: &[String]) -> String {
+    let mut msg = format!("No project matches '{}'.", arg);
+    if !close.is_empty() { msg.push_str(&format!("\nDid you mean:\n  {}", close.join("\n  "))); }
+    msg
+}
+
+/// Asks a yes/no question on the terminal; anything but `y` is no.
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// The manager over `~/.claude`, set up as the config says.
+fn manager() -> SessionManager {
+    let mut manager = SessionManager::from_home();
+    manager.configure(&Config::load());
+    manager
+}
+
+/// Takes the instance lock for a destructive command, refusing while the TUI runs.
+fn lock() -> Result<lock::InstanceLock, Box<dyn Error>> {
+    let lock_path = sessions::config_dir().join("instance.lock");
+    Ok(match lock::acquire(&lock_path)? {
+        lock::Acquired::Locked(l) => l,
+        lock::Acquired::Held(h) if h.is_alive() => return Err(format!("another copy (pid {}) is running", h.pid).into()),
+        lock::Acquired::Held(_) => lock::take_over(&lock_path)?,
+    })
+}
+
+fn row(s: &Session) -> String {
+    let modified = DateTime::<Local>::from(s.modified).format("%Y-%m-%d %H:%M");
+    format!("{}  {}  {:>8}  {:>5}  {}  {}", s.id, modified, format_size(s.size), s.message_count, s.project, s.display_name())
+}
+
+/// Everything `list --format json` tells about a session.
+fn to_json(s: &Session) -> Value {
+    json!({
+        "id": s.id,
+        "name": s.display_name(),
+        "project": s.project,
+        "path": s.path,
+        "cwd": s.cwd,
+        "size": s.size,
+        "message_count": s.message_count,
+        "modified": DateTime::<Local>::from(s.modified).to_rfc3339(),
+        "tags": s.tags,
+        "hidden": s.hidden,
+        "related_files": s.related_files,
+    })
+}
+
+/// The session with this id, or the only one it is a prefix of.
+fn find<'a>(sessions: &'a [Session], id: &str) -> Result<&'a Session, Box<dyn Error>> {
+    if let Some(s) = sessions.iter().find(|s| s.id == id) { return Ok(s); }
+    match sessions.iter().filter(|s| s.id.starts_with(id)).collect::<Vec<_>>()[..] {
+        [s] => Ok(s),
+        [] => Err(format!("no session {}", id).into()),
+        ref many => Err(format!("{} sessions start with {}", many.len(), id).into()),
+    }
+}
+
+/// Deletes `victims`, printing failures, and hands the outcome to the notify command.
+fn delete_all(manager: &SessionManager, config: &Config, action: &'static str, victims: &[&Session], format: Format) -> Result<(), Box<dyn Error>> {
+    let (mut failed, mut freed) = (Vec::new(), 0);
+    let mut report = String::new();
+    let mut gone = Vec::new();
+    for s in victims {
+        match manager.delete_session(s) {
+            Ok(_) => { freed += s.size; report.push_str(&format!("  {}\n", row(s))); gone.push(to_json(s)); }
+            Err(e) => {
+                if format == Format::Text { eprintln!("{}: {}", s.id, e); }
+                report.push_str(&format!("  {}: failed: {}\n", s.id, e));
+                failed.push(json!({"id": s.id, "error": e.to_string()}));
+            }
+        }
+    }
+    let deleted = gone.len();
+    match format {
+        Format::Text => println!("Deleted {} sessions ({}).", deleted, format_size(freed)),
+        Format::Json => println!("{}", json!({"deleted": gone, "failed": failed, "freed_bytes": freed})),
+    }
+    let failed = failed.len();
+    report.insert_str(0, &format!("Deleted {} sessions ({}) with {}.\n\n", deleted, format_size(freed), action));
+    notify::send(config, &notify::Outcome { action, deleted, freed, report });
+    if failed == 0 { Ok(()) } else { Err("some sessions could not be deleted".into()) }
+}
+
+fn list(filters: &Filters, format: Format) -> Result<(), Box<dyn Error>> {
+    let manager = manager();
+    let mut sessions = manager.load_sessions()?;
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.modified));
+    let picked = filters.pick(&manager, &sessions, SystemTime::now())?;
+    match format {
+        Format::Text => for s in picked { println!("{}", row(s)); },
+        Format::Json => println!("{}", Value::Array(picked.into_iter().map(to_json).collect())),
+    }
+    Ok(())
+}
+
+fn prune(filters: &Filters, yes: bool, format: Format) -> Result<(), Box<dyn Error>> {
+    if filters.older_than.is_none() && !filters.empty_only { return Err("prune needs --older-than or --empty-only".into()); }
+    if format == Format::Json && !yes { return Err("prune --format json cannot ask; add --yes".into()); }
+    let _lock = lock()?;
+    let config = Config::load();
+    let manager = manager();
+    let mut sessions = manager.load_sessions()?;
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.modified));
+    let victims: Vec<&Session> = filters.pick(&manager, &sessions, SystemTime::now())?.into_iter().filter(|s| !s.has_tag("keep") && !s.pinned && !s.protected).collect();
+    if format == Format::Text {
+        if victims.is_empty() { println!("Nothing to prune."); return Ok(()); }
+        for s in &victims { println!("{}", row(s)); }
+    }
+    if !yes && !confirm(&format!("Delete {} sessions?", victims.len()))? { return Err("prune cancelled".into()); }
+    delete_all(&manager, &config, "prune", &victims, format)
+}
+
+fn export(id: &str, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let manager = manager();
+    let sessions = manager.load_sessions()?;
+    let s = find(&sessions, id)?;
+    std::fs::create_dir_all(dir)?;
+    let file = claude_sessions::export::export_session(&manager, s, dir)?;
+    println!("{}", dir.join(file.file).display());
+    Ok(())
+}
+
+fn delete(id: &str, yes: bool) -> Result<(), Box<dyn Error>> {
+    let _lock = lock()?;
+    let manager = manager();
+    let sessions = manager.load_sessions()?;
+    let s = find(&sessions, id)?;
+    if s.protected { return Err(format!("{} is protected; press L on it in the TUI to unprotect it", s.id).into()); }
+    println!("{}", row(s));
+    if !yes && !confirm("Delete this session?")? { return Err("delete cancelled".into()); }
+    delete_all(&manager, &Config::load(), "delete", &[s], Format::Text)
+}
+
+/// Handles `--import <archive|dir>`, asking on the terminal about each clashing session id.
+fn import(source: &Path) -> Result<(), Box<dyn Error>> {
+    let manager = manager();
+    let mut imp = Import::default();
+    for path in archive::archives_in(source) {
+        let res = match manager.import_archive(&path, false) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                print!("{}: {}. [s]kip or [r]ename? ", path.display(), e);
+                io::stdout().flush()?;
+                let mut answer = String::new();
+                io::stdin().read_line(&mut answer)?;
+                if !answer.trim().eq_ignore_ascii_case("r") { imp.skipped += 1; continue; }
+                manager.import_archive(&path, true)
+            }
+            res => res,
+        };
+        imp.record(&path, res);
+    }
+    println!("{}", imp.summary());
+    if imp.errors.is_empty() { Ok(()) } else { Err("some archives failed to import".into()) }
+}
+
+/// Handles `--restore <id> [--yes]` without starting the TUI.
+fn restore(id: &str, yes: bool) -> Result<(), Box<dyn Error>> {
+    let manager = manager();
+    let path = manager.find_archive(id).ok_or_else(|| format!("no archive for session {}", id))?;
+    let overwrite = match manager.restore_archive(&path, false) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            if !yes && !confirm(&format!("{}. Overwrite?", e))? { return Err("restore cancelled".into()); }
+            manager.restore_archive(&path, true)
+        }
+        res => res,
+    }?;
+    println!("Restored {} ({} files) into {}", overwrite.id, overwrite.entries.len(), overwrite.project);
+    Ok(())
+}
+
+/// Handles `--apply-retention [--yes]`: lists what the configured retention rules would delete,
+/// grouped by rule, and deletes it once confirmed. Sessions tagged `keep`, hidden, pinned or
+/// protected are exempt.
+fn apply_retention(yes: bool) -> Result<(), Box<dyn Error>> {
+    let config = Config::load();
+    if config.retention.is_empty() { return Err("no retention rules; add \"retention\" to the config".into()); }
+    let _lock = lock()?;
+    let manager = manager();
+    let sessions = manager.load_sessions()?;
+    let found = retention::victims(&config.retention, &sessions, SystemTime::now(), |s| s.has_tag("keep") || s.hidden || s.pinned || s.protected);
+    if found.is_empty() { println!("The retention policy is already met."); return Ok(()); }
+    for (r, victims) in &found {
+        println!("{} ({} sessions)", config.retention[*r].describe(), victims.len());
+        for &i in victims { println!("  {}  {:>8}  {}", sessions[i].id, format_size(sessions[i].size), sessions[i].display_name()); }
+    }
+    let victims: Vec<&Session> = found.iter().flat_map(|(_, v)| v).map(|&i| &sessions[i]).collect();
+    if !yes && !confirm(&format!("Delete {} sessions?", victims.len()))? { return Err("retention cancelled".into()); }
+    delete_all(&manager, &config, "apply-retention", &victims, Format::Text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommands_and_the_project_argument_parse() {
+        let cli = Cli::try_parse_from(["cst", "prune", "--older-than", "30", "--empty-only", "--yes"]).unwrap();
+        assert!(cli.is_headless() && cli.yes);
+        let Some(Command::Prune(f)) = cli.command else { panic!("{:?}", cli.command) };
+        assert_eq!((f.older_than, f.empty_only, f.project), (Some(30), true, None));
+
+        let cli = Cli::try_parse_from(["cst", "--log-file", "/tmp/x.log", "my-repo"]).unwrap();
+        assert_eq!(cli.project.as_deref(), Some("my-repo"));
+        assert!(!cli.is_headless());
+        assert!(Cli::try_parse_from(["cst", "--apply-retention", "--yes"]).unwrap().is_headless());
+        assert!(Cli::try_parse_from(["cst", "delete"]).is_err(), "delete needs an id");
+    }
+
+    #[test]
+    fn filters_pick_by_project_age_and_emptiness() {
+        let fx = crate::test_support::Fixture::new();
+        fx.session("-repo-a", "old", &["hi"], 40 * 86400);
+        fx.session("-repo-a", "new", &["hi"], 60);
+        fx.session("-repo-b", "other", &["hi"], 40 * 86400);
+        fx.write("projects/-repo-b/blank.jsonl", "");
+        let m = fx.manager();
+        let sessions = m.load_sessions().unwrap();
+        let ids = |f: Filters| {
+            let mut ids: Vec<String> = f.pick(&m, &sessions, SystemTime::now()).unwrap().iter().map(|s| s.id.clone()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(Filters { older_than: Some(30), ..Default::default() }), ["old", "other"]);
+        assert_eq!(ids(Filters { older_than: Some(30), project: Some("repo-a".into()), ..Default::default() }), ["old"]);
+        assert_eq!(ids(Filters { empty_only: true, ..Default::default() }), ["blank"]);
+        assert!(Filters { project: Some("nope".into()), ..Default::default() }.pick(&m, &sessions, SystemTime::now()).is_err());
+    }
+
+    #[test]
+    fn json_output_carries_the_session_metadata() {
+        assert_eq!(Cli::try_parse_from(["cst", "list", "--format", "json"]).unwrap().format, Format::Json);
+        let fx = crate::test_support::Fixture::new();
+        fx.session("-repo-a", "s1", &["hi", "again"], 60);
+        fx.related("s1");
+        let s = &fx.manager().load_sessions().unwrap()[0];
+        let v = to_json(s);
+        assert_eq!((v["id"].as_str(), v["project"].as_str(), v["message_count"].as_u64()), (Some("s1"), Some("-repo-a"), Some(2)));
+        assert_eq!(v["size"].as_u64(), Some(s.size));
+        assert_eq!(v["related_files"].as_array().unwrap().len(), 4);
+        assert!(DateTime::parse_from_rfc3339(v["modified"].as_str().unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,40 @@
+//! Where "now" comes from. `App` reads its clock once per tick and measures every age on
+//! screen against that one reading, so rows agree with each other and tests can pin it.
+
+use std::time::SystemTime;
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+/// The wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to; clones share the same time.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct ManualClock(std::rc::Rc<std::cell::Cell<SystemTime>>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn at(t: SystemTime) -> Self {
+        ManualClock(std::rc::Rc::new(std::cell::Cell::new(t)))
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        self.0.set(self.0.get() + by);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.0.get()
+    }
+}
@@ -0,0 +1,168 @@
+//! Columns of the session list and their `Config.columns` spelling: an ordered list of
+//! names, each optionally followed by `:width`, e.g. `["mark", "name", "size:9", "age"]`.
+
+use crate::sessions::SortBy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Mark,
+    Name,
+    Project,
+    Id,
+    Size,
+    Msgs,
+    Age,
+    Tokens,
+    Cost,
+    Todos,
+    Tags,
+}
+
+impl Column {
+    pub const ALL: [Column; 11] = [
+        Column::Mark, Column::Name, Column::Project, Column::Id, Column::Size,
+        Column::Msgs, Column::Age, Column::Tokens, Column::Cost, Column::Todos, Column::Tags,
+    ];
+
+    /// What the list shows when `Config.columns` is unset.
+    pub const DEFAULT: [Column; 5] = [Column::Mark, Column::Name, Column::Size, Column::Msgs, Column::Tags];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Column::Mark => "mark",
+            Column::Name => "name",
+            Column::Project => "project",
+            Column::Id => "id",
+            Column::Size => "size",
+            Column::Msgs => "msgs",
+            Column::Age => "age",
+            Column::Tokens => "tokens",
+            Column::Cost => "cost",
+            Column::Todos => "todos",
+            Column::Tags => "tags",
+        }
+    }
+
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Mark => "",
+            Column::Name => "Name",
+            Column::Project => "Project",
+            Column::Id => "Id",
+            Column::Size => "Size",
+            Column::Msgs => "Msgs",
+            Column::Age => "Age",
+            Column::Tokens => "Tokens",
+            Column::Cost => "Cost",
+            Column::Todos => "Todos",
+            Column::Tags => "Tags",
+        }
+    }
+
+    /// Width used when the config gives none; `None` means the column takes the leftover space
+    /// (name) or sizes itself to what is on screen (tags).
+    pub fn default_width(self) -> Option<u16> {
+        match self {
+            Column::Mark => Some(5),
+            Column::Project => Some(20),
+            Column::Id => Some(8),
+            Column::Size => Some(7),
+            Column::Msgs => Some(5),
+            Column::Age => Some(9),
+            Column::Tokens => Some(7),
+            Column::Cost => Some(8),
+            Column::Todos => Some(5),
+            Column::Name | Column::Tags => None,
+        }
+    }
+
+    /// The sort order this column stands for, if any.
+    pub fn sort(self) -> Option<SortBy> {
+        match self {
+            Column::Age => Some(SortBy::Date),
+            Column::Size => Some(SortBy::Size),
+            Column::Msgs => Some(SortBy::Messages),
+            Column::Tokens => Some(SortBy::Tokens),
+            Column::Cost => Some(SortBy::Cost),
+            Column::Name => Some(SortBy::Name),
+            Column::Project => Some(SortBy::Project),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width: Option<u16>,
+}
+
+impl ColumnSpec {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (name, width) = match s.split_once(':') {
+            Some((n, w)) => (n, Some(w.trim().parse().ok().filter(|w| *w > 0)?)),
+            None => (s, None),
+        };
+        let column = Column::ALL.into_iter().find(|c| c.key().eq_ignore_ascii_case(name.trim()))?;
+        Some(ColumnSpec { column, width })
+    }
+
+    pub fn to_config(self) -> String {
+        match self.width {
+            Some(w) => format!("{}:{}", self.column.key(), w),
+            None => self.column.key().to_string(),
+        }
+    }
+}
+
+/// The columns named in the config, in order, plus any entries that were not understood.
+/// Unset means the defaults; a repeated column keeps its first position.
+pub fn parse_columns(config: Option<&[String]>) -> (Vec<ColumnSpec>, Vec<String>) {
+    let Some(names) = config else {
+        return (Column::DEFAULT.iter().map(|&column| ColumnSpec { column, width: None }).collect(), Vec::new());
+    };
+    let mut specs: Vec<ColumnSpec> = Vec::new();
+    let mut unknown = Vec::new();
+    for name in names {
+        match ColumnSpec::parse(name) {
+            Some(spec) if !specs.iter().any(|s| s.column == spec.column) => specs.push(spec),
+            Some(_) => {}
+            None => unknown.push(name.clone()),
+        }
+    }
+    (specs, unknown)
+}
+
+/// The next sort order `s` should switch to: the sortable visible columns in display order,
+/// or every order when none is visible.
+pub fn next_sort(columns: &[ColumnSpec], current: SortBy) -> SortBy {
+    let mut orders: Vec<SortBy> = columns.iter().filter_map(|c| c.column.sort()).collect();
+    if orders.is_empty() { orders = SortBy::ALL.to_vec(); }
+    let at = orders.iter().position(|&o| o == current);
+    orders[at.map_or(0, |i| (i + 1) % orders.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_widths_and_reports_unknown() {
+        let config: Vec<String> = ["id", "AGE", "size:9", "bogus", "msgs:x", "id:4", "tags"].map(String::from).into();
+        let (specs, unknown) = parse_columns(Some(&config));
+        assert_eq!(specs.iter().map(|s| s.to_config()).collect::<Vec<_>>(), ["id", "age", "size:9", "tags"]);
+        assert_eq!(unknown, ["bogus", "msgs:x"]);
+        assert_eq!(parse_columns(None).0.len(), Column::DEFAULT.len());
+    }
+
+    #[test]
+    fn sort_cycles_through_visible_columns() {
+        let (specs, _) = parse_columns(Some(&["name".into(), "msgs".into(), "age".into()]));
+        assert_eq!(next_sort(&specs, SortBy::Name), SortBy::Messages);
+        assert_eq!(next_sort(&specs, SortBy::Messages), SortBy::Date);
+        assert_eq!(next_sort(&specs, SortBy::Date), SortBy::Name);
+        assert_eq!(next_sort(&specs, SortBy::Size), SortBy::Name);
+        let (specs, _) = parse_columns(Some(&["id".into()]));
+        assert_eq!(next_sort(&specs, SortBy::Size), SortBy::Messages, "no sortable column cycles through every key");
+    }
+}
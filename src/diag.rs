@@ -0,0 +1,68 @@
+//! Opt-in diagnostic log through the `log` facade. The TUI owns the terminal, so while it runs
+//! entries only go to a file (`--log-file` or `log_file` in the config); `--verbose` prints
+//! them to stderr for the headless subcommands. Without either nothing is recorded.
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+struct Sink(Mutex<Box<dyn Write + Send>>);
+
+impl Log for Sink {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, r: &Record) {
+        let line = format!("{} {:<5} {}: {}\n", chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"), r.level(), r.target(), r.args());
+        if let Ok(mut out) = self.0.lock() { let _ = out.write_all(line.as_bytes()); }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut out) = self.0.lock() { let _ = out.flush(); }
+    }
+}
+
+/// Appends entries to `path`, creating it.
+pub fn to_file(path: &Path) -> io::Result<()> {
+    install(Box::new(OpenOptions::new().create(true).append(true).open(path)?))
+}
+
+pub fn to_stderr() -> io::Result<()> {
+    install(Box::new(io::stderr()))
+}
+
+/// Only the first sink installed takes effect; later calls fail.
+fn install(out: Box<dyn Write + Send>) -> io::Result<()> {
+    log::set_boxed_logger(Box::new(Sink(Mutex::new(out)))).map_err(io::Error::other)?;
+    log::set_max_level(LevelFilter::Debug);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn scanner_entries_reach_the_log_file() {
+        let fx = Fixture::new();
+        fx.session("-repo", "s1", &["hi"], 0);
+        fx.write("projects/-repo/agent-x.jsonl", "{}");
+        let path = fx.path("diag.log");
+        to_file(&path).unwrap();
+        fx.manager().load_sessions().unwrap();
+        fx.manager().load_sessions().unwrap();
+        log::logger().flush();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let root = fx.path("projects").display().to_string();
+        let mine: Vec<&str> = text.lines().filter(|l| l.contains(&root)).collect();
+        assert!(mine.iter().any(|l| l.contains("DEBUG") && l.contains("agent-x.jsonl") && l.contains("sub-agent transcript")), "{}", text);
+        assert!(mine.iter().any(|l| l.contains("cache miss") && l.contains("s1.jsonl")), "{}", text);
+        assert!(mine.iter().any(|l| l.contains("cache hit") && l.contains("s1.jsonl")), "{}", text);
+        assert!(mine.iter().any(|l| l.contains("INFO") && l.contains("1 sessions, 0 cache hits, 1 misses")), "{}", text);
+        assert!(to_stderr().is_err(), "one sink per process");
+    }
+}
@@ -0,0 +1,82 @@
+//! `INDEX.md` written next to a multi-session export, so the batch can be understood without
+//! opening every file.
+
+use crate::sessions::{format_size, Session, SessionManager};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const INDEX_FILE: &str = "INDEX.md";
+
+/// One file of a finished export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exported {
+    /// File name inside the export directory.
+    pub file: String,
+    pub name: String,
+    pub project: String,
+    /// Timestamps of the first and last record, or the file's mtime twice when it has none.
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    pub messages: usize,
+    /// Size of the written file.
+    pub size: u64,
+}
+
+/// Writes `s` as text to `<dir>/<id>.txt` and describes the file for the index.
+pub fn export_session(manager: &SessionManager, s: &Session, dir: &Path) -> io::Result<Exported> {
+    let content = manager.read_log(&s.path);
+    let file = format!("{}.txt", s.id);
+    fs::write(dir.join(&file), &content)?;
+    let modified = DateTime::<Utc>::from(s.modified);
+    let (first, last) = manager.record_span(&s.path).unwrap_or((modified, modified));
+    Ok(Exported { file, name: s.display_name(), project: s.project.clone(), first, last, messages: s.message_count, size: content.len() as u64 })
+}
+
+/// The index over `files`, listed in the order given (the list's order at export time).
+pub fn index(files: &[Exported], written: DateTime<Utc>) -> String {
+    let day = |t: &DateTime<Utc>| t.format("%Y-%m-%d %H:%M").to_string();
+    let mut out = format!("# Exported sessions\n\nWritten {} UTC. Times are UTC.\n\n", day(&written));
+    out.push_str("| File | Session | Project | Dates | Messages | Size |\n|---|---|---|---|---:|---:|\n");
+    for f in files {
+        let dates = if day(&f.first) == day(&f.last) { day(&f.first) } else { format!("{} to {}", day(&f.first), day(&f.last)) };
+        out.push_str(&format!("| [{}]({}) | {} | {} | {} | {} | {} |\n",
+            cell(&f.file), f.file, cell(&f.name), cell(&f.project), dates, f.messages, format_size(f.size)));
+    }
+    out.push_str(&format!("\n{} files, {} messages, {} total.\n",
+        files.len(), files.iter().map(|f| f.messages).sum::<usize>(), format_size(files.iter().map(|f| f.size).sum())));
+    out
+}
+
+/// Keeps a value on one table row.
+fn cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn file(id: &str, name: &str, first: &str, last: &str, messages: usize, size: u64) -> Exported {
+        Exported { file: format!("{}.txt", id), name: name.into(), project: "-repo".into(), first: at(first), last: at(last), messages, size }
+    }
+
+    #[test]
+    fn index_lists_files_in_order_with_totals() {
+        let files = [
+            file("b", "Fix a | b", "2026-01-02T10:00:00Z", "2026-01-03T11:30:00Z", 12, 2048),
+            file("a", "Second", "2026-01-01T09:00:00Z", "2026-01-01T09:00:30Z", 3, 1024),
+        ];
+        let md = index(&files, at("2026-01-04T08:00:00Z"));
+        assert_eq!(md, "# Exported sessions\n\nWritten 2026-01-04 08:00 UTC. Times are UTC.\n\n\
+            | File | Session | Project | Dates | Messages | Size |\n|---|---|---|---|---:|---:|\n\
+            | [b.txt](b.txt) | Fix a \\| b | -repo | 2026-01-02 10:00 to 2026-01-03 11:30 | 12 | 2KB |\n\
+            | [a.txt](a.txt) | Second | -repo | 2026-01-01 09:00 | 3 | 1KB |\n\
+            \n2 files, 15 messages, 3KB total.\n");
+    }
+}
@@ -0,0 +1,91 @@
+//! Redraw pacing for the event loop: draw when something changed, but no more than one frame
+//! per `FRAME`, plus a slow tick so ages and progress stay current while idle.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Minimum time between frames (about 30 per second); changes inside it share one frame.
+pub const FRAME: Duration = Duration::from_millis(33);
+
+pub struct FrameClock {
+    dirty: bool,
+    last_draw: Option<Instant>,
+    last_tick: Instant,
+    /// Frames drawn since start.
+    pub total: u64,
+    /// Frame times within the last second.
+    recent: VecDeque<Instant>,
+}
+
+impl FrameClock {
+    pub fn new(now: Instant) -> Self {
+        FrameClock { dirty: true, last_draw: None, last_tick: now, total: 0, recent: VecDeque::new() }
+    }
+
+    /// Something on screen changed.
+    pub fn mark(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Whether to draw now, counting the frame if so. A due `tick` counts as a change.
+    pub fn should_draw(&mut self, now: Instant, tick: Duration) -> bool {
+        if now.duration_since(self.last_tick) >= tick {
+            self.dirty = true;
+            self.last_tick = now;
+        }
+        if !self.dirty || self.last_draw.is_some_and(|t| now.duration_since(t) < FRAME) { return false; }
+        self.dirty = false;
+        self.last_draw = Some(now);
+        self.total += 1;
+        self.recent.push_back(now);
+        while self.recent.front().is_some_and(|&t| now.duration_since(t) >= Duration::from_secs(1)) {
+            self.recent.pop_front();
+        }
+        true
+    }
+
+    /// How long to wait for input: until the next frame slot when a change is pending, so it
+    /// shows promptly, otherwise until the next tick.
+    pub fn timeout(&self, now: Instant, tick: Duration) -> Duration {
+        if self.dirty {
+            self.last_draw.map_or(Duration::ZERO, |t| FRAME.saturating_sub(now.duration_since(t)))
+        } else {
+            tick.saturating_sub(now.duration_since(self.last_tick))
+        }
+    }
+
+    /// Frames drawn in the second up to the last one.
+    pub fn per_second(&self) -> usize {
+        self.recent.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_are_coalesced_to_the_frame_rate() {
+        let tick = Duration::from_secs(1);
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        let mut drawn = 0;
+        // 1000 events over half a second.
+        for i in 0..1000 {
+            let now = start + Duration::from_micros(i * 500);
+            clock.mark();
+            if clock.should_draw(now, tick) { drawn += 1; }
+        }
+        assert!((15..=16).contains(&drawn), "drew {} frames", drawn);
+        assert_eq!(clock.per_second(), drawn);
+
+        let now = start + Duration::from_millis(510);
+        clock.mark();
+        assert!(clock.timeout(now, tick) <= FRAME, "pending change waits at most a frame");
+        assert!(clock.should_draw(now + clock.timeout(now, tick), tick));
+        let idle = now + Duration::from_millis(100);
+        assert!(!clock.should_draw(idle, tick));
+        assert_eq!(clock.timeout(idle, tick), Duration::from_millis(390));
+        assert!(clock.should_draw(start + tick, tick), "the tick redraws an idle screen");
+    }
+}
@@ -0,0 +1,105 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+
+/// Problems found in one session jsonl.
+#[derive(Debug, Default, Clone)]
+pub struct HealthReport {
+    /// 1-based line numbers that are not valid JSON.
+    pub unparsable: Vec<usize>,
+    /// The last record is incomplete JSON, typically from a crash mid-write.
+    pub truncated_tail: bool,
+    /// tool_use ids that never received a tool_result.
+    pub orphaned_tool_uses: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.unparsable.is_empty() && !self.truncated_tail && self.orphaned_tool_uses.is_empty()
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.truncated_tail { out.push("last record is truncated (repairable)".into()); }
+        if !self.unparsable.is_empty() {
+            let nums: Vec<String> = self.unparsable.iter().map(|n| n.to_string()).collect();
+            out.push(format!("unparsable lines: {}", nums.join(", ")));
+        }
+        if !self.orphaned_tool_uses.is_empty() {
+            out.push(format!("tool_use without result: {}", self.orphaned_tool_uses.join(", ")));
+        }
+        out
+    }
+}
+
+fn content_items(v: &Value) -> impl Iterator<Item = &Value> {
+    v.pointer("/message/content").and_then(Value::as_array).into_iter().flatten()
+}
+
+/// Streams a transcript line by line, never holding more than one record in memory.
+pub fn check(reader: impl BufRead) -> io::Result<HealthReport> {
+    let mut report = HealthReport::default();
+    let mut pending: Vec<String> = Vec::new();
+    let mut resolved: HashSet<String> = HashSet::new();
+    let mut last_bad = false;
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        let Ok(v) = serde_json::from_str::<Value>(&line) else {
+            report.unparsable.push(n + 1);
+            last_bad = true;
+            continue;
+        };
+        last_bad = false;
+        for item in content_items(&v) {
+            match item.get("type").and_then(Value::as_str) {
+                Some("tool_use") => if let Some(id) = item.get("id").and_then(Value::as_str) { pending.push(id.to_string()); },
+                Some("tool_result") => if let Some(id) = item.get("tool_use_id").and_then(Value::as_str) { resolved.insert(id.to_string()); },
+                _ => {}
+            }
+        }
+    }
+    if last_bad {
+        report.truncated_tail = true;
+        report.unparsable.pop();
+    }
+    report.orphaned_tool_uses = pending.into_iter().filter(|id| !resolved.contains(id)).collect();
+    Ok(report)
+}
+
+/// Drops the final line of `content` if, and only if, it is not complete JSON.
+pub fn strip_truncated_tail(content: &str) -> Option<String> {
+    let body = content.trim_end_matches('\n');
+    let (head, last) = body.rsplit_once('\n').unwrap_or(("", body));
+    if serde_json::from_str::<Value>(last).is_ok() { return None; }
+    Some(if head.is_empty() { String::new() } else { format!("{}\n", head) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD: &str = concat!(
+        r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"t1"}]}}"#, "\n",
+        r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1"}]}}"#, "\n",
+    );
+
+    #[test]
+    fn healthy_transcript_has_no_findings() {
+        assert!(check(GOOD.as_bytes()).unwrap().is_healthy());
+        assert_eq!(strip_truncated_tail(GOOD), None);
+    }
+
+    #[test]
+    fn detects_truncated_tail_and_orphans() {
+        let broken = format!("{}not json\n{}{{\"type\":\"assistant\",\"message\":{{\"content\":[{{\"type\":\"tool_use\",\"id\":\"t2\"}}]}}}}\n{{\"type\":\"user\",\"mess",
+            GOOD.lines().next().unwrap().to_owned() + "\n", GOOD.lines().nth(1).unwrap().to_owned() + "\n");
+        let report = check(broken.as_bytes()).unwrap();
+        assert!(report.truncated_tail);
+        assert_eq!(report.unparsable, vec![2]);
+        assert_eq!(report.orphaned_tool_uses, vec!["t2".to_string()]);
+        let repaired = strip_truncated_tail(&broken).unwrap();
+        assert_eq!(repaired.lines().count(), 4);
+        assert!(repaired.ends_with("}}\n"));
+    }
+}
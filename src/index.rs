@@ -0,0 +1,191 @@
+//! On-disk inverted index of the words in every session's messages, so a `text:` search only
+//! streams the transcripts that can contain the phrase. Each session is stored with the size
+//! and mtime it was indexed at and is redone when either changes.
+
+use crate::sessions::{atomic_write, jsonl_lines, message_text};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Doc {
+    id: String,
+    size: u64,
+    /// Seconds since the epoch.
+    modified: u64,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SearchIndex {
+    /// Indexed sessions by slot; a removed session leaves its slot empty for the next one.
+    docs: Vec<Option<Doc>>,
+    /// Lowercase word to the slots whose messages contain it, ascending.
+    words: HashMap<String, Vec<u32>>,
+    #[serde(skip)]
+    slots: HashMap<String, u32>,
+    /// Changed since it was loaded.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// The lowercase words of `text`: runs of letters and digits.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase)
+}
+
+/// Every word in a transcript's user and assistant messages. Streams the file.
+pub fn words_of(path: &Path) -> io::Result<HashSet<String>> {
+    let mut out = HashSet::new();
+    for (_, line) in jsonl_lines(path)? {
+        let Ok(v) = serde_json::from_str::<Value>(&line) else { continue };
+        let Some(text) = message_text(&v) else { continue };
+        out.extend(words(&text));
+    }
+    Ok(out)
+}
+
+fn secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl SearchIndex {
+    /// The saved index, or an empty one when the file is missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        let mut index: Self = fs::read(path).ok().and_then(|b| serde_json::from_slice(&b).ok()).unwrap_or_default();
+        index.slots = index.docs.iter().enumerate()
+            .filter_map(|(slot, d)| d.as_ref().map(|d| (d.id.clone(), slot as u32)))
+            .collect();
+        index
+    }
+
+    pub fn save(&mut self, path: &Path) -> io::Result<()> {
+        atomic_write(path, &serde_json::to_vec(self)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Number of indexed sessions.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Whether `id` was indexed from a file of this size and mtime.
+    pub fn is_fresh(&self, id: &str, size: u64, modified: SystemTime) -> bool {
+        self.slots.get(id)
+            .and_then(|&slot| self.docs[slot as usize].as_ref())
+            .is_some_and(|d| d.size == size && d.modified == secs(modified))
+    }
+
+    /// Replaces whatever was indexed for `id`.
+    pub fn update(&mut self, id: &str, size: u64, modified: SystemTime, words: HashSet<String>) {
+        self.remove(id);
+        let slot = match self.docs.iter().position(Option::is_none) {
+            Some(free) => free,
+            None => { self.docs.push(None); self.docs.len() - 1 }
+        };
+        self.docs[slot] = Some(Doc { id: id.to_string(), size, modified: secs(modified) });
+        self.slots.insert(id.to_string(), slot as u32);
+        for w in words {
+            let list = self.words.entry(w).or_default();
+            if let Err(at) = list.binary_search(&(slot as u32)) { list.insert(at, slot as u32); }
+        }
+        self.dirty = true;
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some(slot) = self.slots.remove(id) else { return };
+        self.docs[slot as usize] = None;
+        self.words.retain(|_, list| {
+            if let Ok(at) = list.binary_search(&slot) { list.remove(at); }
+            !list.is_empty()
+        });
+        self.dirty = true;
+    }
+
+    /// Drops sessions `keep` rejects, returning how many went.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) -> usize {
+        let gone: Vec<String> = self.slots.keys().filter(|id| !keep(id)).cloned().collect();
+        for id in &gone { self.remove(id); }
+        gone.len()
+    }
+
+    /// Indexed sessions that may contain `query` (lowercase), or `None` when it has no word the
+    /// index can narrow on. The first and last words of the phrase may be cut off mid-word, so
+    /// they only need to end or start an indexed word; the ones between must match whole.
+    pub fn candidates(&self, query: &str) -> Option<HashSet<String>> {
+        let parts: Vec<String> = words(query).collect();
+        let last = parts.len().saturating_sub(1);
+        let mut found: Option<HashSet<u32>> = None;
+        for (i, part) in parts.iter().enumerate() {
+            let fits = |w: &str| match (i == 0, i == last) {
+                (true, true) => w.contains(part.as_str()),
+                (true, false) => w.ends_with(part.as_str()),
+                (false, true) => w.starts_with(part.as_str()),
+                (false, false) => w == part,
+            };
+            let slots: HashSet<u32> = if i > 0 && i < last {
+                self.words.get(part).into_iter().flatten().copied().collect()
+            } else {
+                self.words.iter().filter(|(w, _)| fits(w)).flat_map(|(_, l)| l.iter().copied()).collect()
+            };
+            found = Some(match found {
+                Some(prev) => prev.intersection(&slots).copied().collect(),
+                None => slots,
+            });
+        }
+        found.map(|slots| slots.into_iter()
+            .filter_map(|s| self.docs[s as usize].as_ref().map(|d| d.id.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn set(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn narrows_phrases_and_reindexes_changed_sessions() {
+        let t = UNIX_EPOCH + Duration::from_secs(1000);
+        let mut index = SearchIndex::default();
+        index.update("a", 10, t, set(&["fix", "the", "parser"]));
+        index.update("b", 20, t, set(&["profile", "the", "parsers"]));
+        let ids = |index: &SearchIndex, q: &str| index.candidates(q).map(|c| { let mut v: Vec<_> = c.into_iter().collect(); v.sort(); v });
+
+        assert_eq!(ids(&index, "pars"), Some(vec!["a".to_string(), "b".to_string()]), "a lone word matches anywhere inside");
+        assert_eq!(ids(&index, "ix the pars"), Some(vec!["a".to_string()]));
+        assert_eq!(ids(&index, "the parser"), Some(vec!["a".to_string(), "b".to_string()]), "the last word may continue");
+        assert_eq!(ids(&index, "fix a parser"), Some(vec![]), "middle words match whole");
+        assert_eq!(ids(&index, "\"!"), None);
+
+        assert!(index.is_fresh("a", 10, t) && !index.is_fresh("a", 11, t) && !index.is_fresh("c", 10, t));
+        index.update("a", 11, t, set(&["rewrite"]));
+        assert_eq!(ids(&index, "fix"), Some(vec![]));
+        assert_eq!(index.retain(|id| id == "a"), 1);
+        assert_eq!(index.len(), 1);
+        assert!(!index.words.contains_key("parsers"), "words no session uses are dropped");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.json");
+        index.save(&path).unwrap();
+        let loaded = SearchIndex::load(&path);
+        assert!(loaded.is_fresh("a", 11, t) && !loaded.is_dirty());
+        assert_eq!(loaded.candidates("rewrite").unwrap(), set(&["a"]));
+    }
+}
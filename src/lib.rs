@@ -0,0 +1,29 @@
+//! Session handling behind `claude-sessions-tui`, usable without the TUI: listing the sessions
+//! under `~/.claude/projects`, deleting them with their related files and `history.jsonl`
+//! entries, exporting transcripts and finding orphaned files.
+//!
+//! ```no_run
+//! use claude_sessions::SessionManager;
+//!
+//! let manager = SessionManager::from_home();
+//! for s in manager.load_sessions()? {
+//!     println!("{}  {:>8}  {}", s.id, s.size_str(), s.display_name());
+//! }
+//! let orphans = manager.find_orphans();
+//! println!("{} orphaned files", orphans.len());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub mod archive;
+pub mod audit;
+pub mod export;
+pub mod health;
+pub mod index;
+pub mod retention;
+pub mod sessions;
+pub mod slim;
+pub mod text;
+#[cfg(test)]
+mod test_support;
+
+pub use sessions::{Config, Session, SessionManager};
@@ -0,0 +1,114 @@
+//! Advisory lock against a second copy of the TUI racing on the metadata cache and on
+//! `history.jsonl` rewrites. The lock file holds the owner's pid and start time; a lock whose
+//! pid is no longer running was left by a crash and may be taken over.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Holder {
+    pub pid: u32,
+    /// RFC 3339, local time.
+    pub started: String,
+}
+
+impl Holder {
+    fn current() -> Self {
+        Holder { pid: std::process::id(), started: chrono::Local::now().to_rfc3339() }
+    }
+
+    /// Whether the owning process still runs. On Linux a pid reused by some other program
+    /// counts as gone.
+    pub fn is_alive(&self) -> bool {
+        if self.pid == 0 { return false; }
+        if cfg!(target_os = "linux") {
+            let comm = |pid: &str| fs::read_to_string(format!("/proc/{}/comm", pid)).ok();
+            return comm(&self.pid.to_string()).is_some_and(|c| Some(c) == comm("self"));
+        }
+        std::process::Command::new("kill").args(["-0", &self.pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+}
+
+/// A lock this process holds; dropping it removes the file.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+pub enum Acquired {
+    Locked(InstanceLock),
+    /// Someone else's lock, live or stale. An unreadable lock file reports pid 0, i.e. stale.
+    Held(Holder),
+}
+
+/// Creates the lock at `path` unless one is already there.
+pub fn acquire(path: &Path) -> io::Result<Acquired> {
+    if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
+    match fs::OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(mut f) => {
+            f.write_all(serde_json::to_string(&Holder::current())?.as_bytes())?;
+            Ok(Acquired::Locked(InstanceLock { path: path.to_path_buf() }))
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(Acquired::Held(read_holder(path))),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces whatever lock is at `path` with one for this process.
+pub fn take_over(path: &Path) -> io::Result<InstanceLock> {
+    fs::write(path, serde_json::to_string(&Holder::current())?)?;
+    Ok(InstanceLock { path: path.to_path_buf() })
+}
+
+fn read_holder(path: &Path) -> Holder {
+    fs::read_to_string(path).ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or(Holder { pid: 0, started: String::new() })
+}
+
+/// Removes the lock at `path` if this process owns it; for the panic hook, which cannot reach
+/// the `InstanceLock`.
+pub fn release(path: &Path) {
+    if read_holder(path).pid == std::process::id() { let _ = fs::remove_file(path); }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        release(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_acquire_sees_the_holder_and_stale_locks_can_be_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/instance.lock");
+        let Acquired::Locked(lock) = acquire(&path).unwrap() else { panic!("first acquire should lock") };
+        let Acquired::Held(holder) = acquire(&path).unwrap() else { panic!("lock should be held") };
+        assert_eq!(holder.pid, std::process::id());
+        assert!(holder.is_alive());
+        drop(lock);
+        assert!(!path.exists());
+
+        fs::write(&path, r#"{"pid":0,"started":"2026-01-01T00:00:00+00:00"}"#).unwrap();
+        let Acquired::Held(stale) = acquire(&path).unwrap() else { panic!("stale lock is still a lock") };
+        assert!(!stale.is_alive());
+        fs::write(&path, "garbage").unwrap();
+        let Acquired::Held(garbled) = acquire(&path).unwrap() else { panic!() };
+        assert!(!garbled.is_alive());
+
+        let lock = take_over(&path).unwrap();
+        assert_eq!(read_holder(&path).pid, std::process::id());
+        fs::write(&path, r#"{"pid":1,"started":""}"#).unwrap();
+        drop(lock);
+        assert!(path.exists(), "a lock taken by someone else is left alone");
+    }
+}
@@ -1,422 +1,3264 @@
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect, Alignment},
-    style::{Color, Modifier, Style},
-    text::Line,
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
-    Frame, Terminal,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::ListState,
+    Terminal,
 };
-use std::{error::Error, io, path::PathBuf};
+use std::{collections::{BTreeMap, HashMap, HashSet, VecDeque}, error::Error, fs, io::{self, Write}, path::{Path, PathBuf}, sync::{mpsc, Arc, Mutex}, time::{Duration, Instant, SystemTime}};
 
-mod sessions;
-use sessions::{Config, Session, SessionManager, SortBy};
+mod cli;
+mod clock;
+mod columns;
+mod diag;
+mod frame;
+mod lock;
+mod notify;
+mod snapshot;
+mod task;
+mod theme;
+mod ui;
+mod watch;
+#[cfg(test)]
+mod test_support;
+#[cfg(test)]
+mod app_tests;
+#[cfg(test)]
+mod ui_snapshots;
+use claude_sessions::{archive, audit, export, index, retention, sessions, text};
+use claude_sessions::index::SearchIndex;
+use archive::ArchivedSession;
+use task::Task;
+use theme::Theme;
+use columns::ColumnSpec;
+use clock::{Clock, SystemClock};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use frame::FrameClock;
+use ui::{ui, LayoutInfo, RowCache};
+use sessions::{format_size, normalize_path, Config, CustomCommand, ScanOverview, HistoryEntry, LogChunk, LogEntry, ProjectStats, Session, SessionManager, SortBy, TriageOrder};
 
-enum Mode { Normal, Filter, Confirm, Message, PruneSelection, Expanded }
-enum Action { Delete, PruneOrphans, PruneBoth }
+enum Mode { Normal, Filter, Rename, Tag, Confirm, Message, PruneSelection, Expanded, Archived, Compare, QuitConfirm, Report, Checklist, Progress, Answer, Peek, Import, ImportConflict, Stats, History, LockConflict, Projects, Triage, Filters, SaveFilter, SortMenu, Note }
+enum Action { Delete, PruneOrphans, PruneBoth, Restore(PathBuf), Slim, Repair, DropHistory(HashSet<(String, Option<i64>)>), Redact(String, HashSet<usize>), DropRelated(String, Vec<PathBuf>), EmptyTrash(Vec<String>), Compact }
+
+/// A line of the session list: a session by its position in `App::filtered`, or in the grouped
+/// view a project heading with the totals of its matching sessions.
+#[derive(Debug, Clone, PartialEq)]
+enum ListRow {
+    Project { name: String, count: usize, size: u64, collapsed: bool },
+    Session(usize),
+}
 
 struct App {
     sessions: Vec<Session>,
     filtered: Vec<usize>,
+    /// What the list shows, built from `filtered` by `build_rows`; `state` indexes it.
+    rows: Vec<ListRow>,
+    /// Sessions grouped under project headings, each group in list order.
+    grouped: bool,
     state: ListState,
-    selected: Vec<usize>,
+    selected: HashSet<usize>,
     manager: SessionManager,
     mode: Mode,
     input: String,
     msg: String,
     action: Action,
     sort: SortBy,
+    /// Ascending instead of the default newest/largest first.
+    sort_reversed: bool,
+    /// Breaks ties in `sort`.
+    sort_then: Option<SortBy>,
+    /// Highlight in the sort popup, an index into `SortBy::ALL`.
+    sort_state: ListState,
     filter: String,
     offset: usize,
     config: Config,
-    to_delete: Vec<String>,
+    to_delete: Vec<ConfirmRow>,
     orphans: Vec<String>,
-    cached_log: Option<Vec<String>>,
+    transcript: Option<Transcript>,
+    archives: Vec<ArchivedSession>,
+    archive_state: ListState,
+    compare_base: Option<String>,
+    compare: Option<Compare>,
+    show_hidden: bool,
+    report: Vec<String>,
+    report_offset: usize,
+    /// Scroll position of the confirm list or message text; `ui` clamps it to the content.
+    popup_scroll: u16,
+    /// Digits typed into a confirm popup that asks for the item count.
+    confirm_typed: String,
+    report_kind: ReportKind,
+    /// Sessions whose health check found a repairable truncated tail.
+    repairable: Vec<usize>,
+    checklist: Option<Checklist>,
+    dangling: Vec<HistoryEntry>,
+    /// Session indices behind the rows of the retention checklist.
+    retention_victims: Vec<usize>,
+    /// The checked rows from before a confirm checked its own targets, put back on cancel.
+    selected_before_confirm: Option<HashSet<usize>>,
+    orphan_scan: Option<OrphanScan>,
+    clock: Box<dyn Clock>,
+    /// The clock's reading for the current tick; every age on screen is measured from it.
+    now: SystemTime,
+    /// Per-project totals, refreshed on every load.
+    projects: Vec<ProjectStats>,
+    project_state: ListState,
+    /// Highlight in the saved filter picker, which lists `config.saved_filters` by name.
+    filter_state: ListState,
+    /// The project picker lists alphabetically instead of by size.
+    projects_by_name: bool,
+    /// Last load time in ms per project directory, kept across runs.
+    scan_times: BTreeMap<String, u64>,
+    /// Slow projects left out of the current load; the project picker lists them.
+    deferred: Vec<String>,
+    /// Deferred projects the user asked to scan, loaded from then on.
+    scan_anyway: HashSet<String>,
+    /// Project the running task is scanning.
+    scanning: Option<String>,
+    /// Session ids queued for deletion from the expanded viewer, confirmed on leaving it.
+    queued: Vec<String>,
+    triage: Option<Triage>,
+    /// Directory and finished files of the running multi-session export, for its `INDEX.md`.
+    export_index: Option<(PathBuf, Arc<Mutex<Vec<export::Exported>>>)>,
+    /// Session id and files behind the rows of the related files checklist.
+    related: (String, Vec<PathBuf>),
+    layout: LayoutInfo,
+    row_cache: RowCache,
+    /// Hidden sessions in `sessions`, counted by `apply_filter` for the list title.
+    hidden_count: usize,
+    /// Batch running on a worker thread; shown in the progress popup until it finishes.
+    task: Option<Task>,
+    answer: Option<Answer>,
+    theme: Theme,
+    peek: Option<Peek>,
+    import: Option<Import>,
+    file_search: Option<FileSearch>,
+    content_search: Option<ContentSearch>,
+    columns: Vec<ColumnSpec>,
+    frames: FrameClock,
+    /// Lines of the stats screen, computed when it opens.
+    stats: Vec<String>,
+    /// Valid `Config.custom_commands` by key.
+    commands: Vec<(char, CustomCommand)>,
+    /// Set when the last load found no sessions at all.
+    overview: Option<ScanOverview>,
+    /// Reports of recent deletes, prunes and exports, newest first.
+    history: VecDeque<ActionReport>,
+    history_state: ListState,
+    /// Owner of the instance lock when it was not ours at startup; shown until the user picks.
+    lock_holder: Option<lock::Holder>,
+    instance: Option<lock::InstanceLock>,
+    /// Transcript bookmarks by session id, kept for the whole run.
+    bookmarks: HashMap<String, BTreeMap<char, usize>>,
+    /// `m` or `'` waiting for its bookmark letter in the expanded view.
+    pending_bookmark: Option<char>,
+    /// One-off note shown in place of the key help until the next key press.
+    status: Option<String>,
+    /// Change in session sizes since the snapshot the previous run left.
+    since_last: Option<snapshot::Delta>,
+    /// Sessions being loaded in the background; the list shows a spinner until they land.
+    loading: Option<Loading>,
+    /// `--project` to open once the first load is in.
+    open_after_load: Option<String>,
+    /// Filesystem events for `projects` and `todos`, when `auto_refresh` is on.
+    watch: Option<watch::Watcher>,
+    /// The watcher saw a change the list doesn't have yet.
+    stale: bool,
+    last_load: Instant,
+}
+
+/// What the scrollable report popup is showing.
+#[derive(Clone, Copy, PartialEq)]
+enum ReportKind { Health, Audit }
+
+impl ReportKind {
+    fn title(self) -> &'static str {
+        match self { ReportKind::Health => "Health Check", ReportKind::Audit => "Audit Log" }
+    }
+
+    fn file_prefix(self) -> &'static str {
+        match self { ReportKind::Health => "health", ReportKind::Audit => "audit" }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ChecklistKind { Orphans, History, Columns, Retention, Related }
+
+/// A reviewable list of items with per-item checkboxes, applied in one go.
+struct Checklist {
+    kind: ChecklistKind,
+    title: String,
+    items: Vec<(String, bool)>,
+    state: ListState,
+}
+
+impl Checklist {
+    fn new(kind: ChecklistKind, title: &str, items: Vec<String>) -> Self {
+        let mut state = ListState::default();
+        state.select((!items.is_empty()).then_some(0));
+        Checklist { kind, title: title.to_string(), items: items.into_iter().map(|i| (i, true)).collect(), state }
+    }
+
+    fn move_sel(&mut self, delta: isize) {
+        if self.items.is_empty() { return; }
+        let i = self.state.selected().unwrap_or(0) as isize + delta;
+        self.state.select(Some(i.rem_euclid(self.items.len() as isize) as usize));
+    }
+
+    fn toggle(&mut self) {
+        if let Some(item) = self.state.selected().and_then(|i| self.items.get_mut(i)) { item.1 = !item.1; }
+    }
+
+    fn toggle_all(&mut self) {
+        let on = !self.items.iter().all(|i| i.1);
+        for item in &mut self.items { item.1 = on; }
+    }
+
+    fn checked(&self) -> Vec<usize> {
+        self.items.iter().enumerate().filter(|(_, i)| i.1).map(|(n, _)| n).collect()
+    }
+}
+
+/// Two transcripts shown side by side, each with its own scroll offset.
+struct Compare {
+    panes: [(usize, Transcript); 2],
+    offsets: [usize; 2],
+    focus: usize,
+    sync: bool,
+}
+
+impl Compare {
+    fn scroll(&mut self, delta: isize) {
+        for p in 0..2 {
+            if self.sync || p == self.focus {
+                self.offsets[p] = self.offsets[p].saturating_add_signed(delta).min(self.panes[p].1.lines.len().saturating_sub(1));
+            }
+        }
+    }
+}
+
+/// The transcript open in the expanded viewer, flattened into display lines.
+struct Transcript {
+    session_id: String,
+    entries: Vec<LogEntry>,
+    lines: Vec<String>,
+    /// Index into `entries` for each display line.
+    line_entry: Vec<usize>,
+    /// Marked entries, used as cut points for duplication.
+    marks: Vec<usize>,
+    /// Named bookmarks: letter to the `record` of the bookmarked entry, which stays valid as
+    /// the session grows.
+    bookmarks: BTreeMap<char, usize>,
+    /// Entry under the cursor in message mode; `None` scrolls by line.
+    focus: Option<usize>,
+    /// Result of the last copy, shown in the title.
+    note: Option<String>,
+    /// Set when this shows the session's debug log rather than its messages.
+    debug: Option<DebugLog>,
+    /// Set when only the end of a large session log is loaded.
+    window: Option<LogWindow>,
+}
+
+/// Where the loaded part of a large session log begins. Scrolling to the top reads the chunk
+/// before it.
+struct LogWindow {
+    path: PathBuf,
+    start: u64,
+    first_record: usize,
+    size: u64,
+}
+
+/// How much of a debug log the viewer holds.
+struct DebugLog {
+    shown: u64,
+    size: u64,
+}
+
+impl Transcript {
+    fn new(session_id: String, entries: Vec<LogEntry>) -> Self {
+        let mut lines = Vec::new();
+        let mut line_entry = Vec::new();
+        for (i, e) in entries.iter().enumerate() {
+            for l in e.render().lines() {
+                lines.push(l.to_string());
+                line_entry.push(i);
+            }
+        }
+        Transcript { session_id, entries, lines, line_entry, marks: Vec::new(), bookmarks: BTreeMap::new(), focus: None, note: None, debug: None, window: None }
+    }
+
+    /// Puts an earlier chunk of the log in front, keeping marks and focus on their entries.
+    /// Returns how many display lines were added.
+    fn prepend(&mut self, chunk: LogChunk) -> usize {
+        let (added, before) = (chunk.entries.len(), self.lines.len());
+        let mut entries = chunk.entries;
+        entries.append(&mut self.entries);
+        let mut t = Transcript::new(std::mem::take(&mut self.session_id), entries);
+        t.marks = self.marks.iter().map(|m| m + added).collect();
+        t.focus = self.focus.map(|f| f + added);
+        t.bookmarks = std::mem::take(&mut self.bookmarks);
+        t.note = self.note.take();
+        t.window = self.window.take().map(|w| LogWindow { start: chunk.start, first_record: chunk.first_record, ..w });
+        *self = t;
+        self.lines.len() - before
+    }
+
+    /// A plain-text debug log in the transcript viewer, as one entry.
+    fn debug_log(session_id: String, text: String, size: u64) -> Self {
+        let shown = text.len() as u64;
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        let mut t = Transcript::new(session_id, Vec::new());
+        t.line_entry = vec![0; lines.len()];
+        t.lines = lines;
+        t.entries = vec![LogEntry { role: "debug".into(), text, record: 0, model: None }];
+        t.debug = Some(DebugLog { shown, size });
+        t
+    }
+
+    fn entry_at(&self, line: usize) -> Option<usize> {
+        self.line_entry.get(line.min(self.line_entry.len().saturating_sub(1))).copied()
+    }
+
+    /// First display line of the bookmarked record, or of the next entry that has one when it
+    /// renders nothing; the last line when the record is past the end.
+    fn bookmark_line(&self, record: usize) -> usize {
+        self.line_entry.iter().position(|&e| self.entries[e].record >= record)
+            .unwrap_or(self.lines.len().saturating_sub(1))
+    }
+
+    fn first_line(&self, entry: usize) -> Option<usize> {
+        self.line_entry.iter().position(|&e| e == entry)
+    }
+
+    /// The next (`delta` 1) or previous (-1) entry with display lines, staying put at the ends.
+    fn step_focus(&mut self, delta: isize) {
+        let Some(cur) = self.focus else { return };
+        let mut next = cur as isize + delta;
+        while next >= 0 && (next as usize) < self.entries.len() {
+            if self.first_line(next as usize).is_some() { self.focus = Some(next as usize); return; }
+            next += delta;
+        }
+    }
+
+    fn toggle_mark(&mut self, entry: usize) {
+        if let Some(pos) = self.marks.iter().position(|&m| m == entry) { self.marks.remove(pos); } else { self.marks.push(entry); }
+    }
+
+    fn models(&self) -> Vec<&str> {
+        let mut models: Vec<&str> = self.entries.iter().filter_map(|e| e.model.as_deref()).collect();
+        models.sort_unstable();
+        models.dedup();
+        models
+    }
+
+    /// Styled display lines for a window of the transcript.
+    fn view(&self, offset: usize, height: usize, theme: &Theme) -> Vec<Line<'_>> {
+        self.lines.iter().enumerate()
+            .skip(offset)
+            .take(height)
+            .map(|(n, l)| {
+                let marked = self.marks.contains(&self.line_entry[n]);
+                let named: String = self.bookmarks.iter()
+                    .filter(|(_, &r)| self.bookmark_line(r) == n)
+                    .map(|(c, _)| format!("'{} ", c)).collect();
+                if self.focus == Some(self.line_entry[n]) {
+                    Line::styled(format!("{}{}", named, l), theme.highlight)
+                } else if marked && l.starts_with('[') {
+                    Line::styled(format!("{}● {}", named, l), theme.warning.add_modifier(Modifier::BOLD))
+                } else if !named.is_empty() {
+                    Line::from(vec![Span::styled(named, theme.accent.add_modifier(Modifier::BOLD)), Span::raw(l.as_str())])
+                } else {
+                    Line::from(l.as_str())
+                }
+            })
+            .collect()
+    }
+}
+
+/// Result of a `file:` query: matching paths by session id. The worker streaming transcripts
+/// adds to `hits` while it runs.
+struct FileSearch {
+    query: String,
+    hits: Arc<Mutex<HashMap<String, Vec<String>>>>,
+}
+
+/// Result of a `text:` query: a snippet of the first matching line by session id, filled in
+/// by the worker like `FileSearch`. The worker also reindexes the sessions that changed; the
+/// index is saved once it finishes.
+struct ContentSearch {
+    query: String,
+    hits: Arc<Mutex<HashMap<String, String>>>,
+    index: Arc<Mutex<SearchIndex>>,
+}
+
+/// What `load_projects` returns: the sessions and each project's scan time.
+type Loaded = (Vec<Session>, BTreeMap<String, u64>);
+
+/// A `load_projects` running on a worker thread; `App::poll_loading` installs the result.
+struct Loading {
+    rx: mpsc::Receiver<io::Result<Loaded>>,
+    /// Projects left out as slow, reported as deferred once the load lands.
+    slow: HashSet<String>,
+    started: Instant,
+    /// The load at startup, after which the startup notes and offers are made.
+    first: bool,
+}
+
+/// The last assistant reply of a session, shown on its own in the answer popup.
+struct Answer {
+    session: usize,
+    text: String,
+    offset: u16,
+}
+
+/// An import working through its archives; it pauses on `conflict` until the user picks
+/// skip or rename.
+#[derive(Default)]
+struct Import {
+    queue: VecDeque<PathBuf>,
+    conflict: Option<PathBuf>,
+    sessions: usize,
+    bytes: u64,
+    skipped: usize,
+    errors: Vec<String>,
+}
+
+impl Import {
+    fn record(&mut self, path: &Path, res: io::Result<archive::Manifest>) {
+        match res {
+            Ok(m) => { self.sessions += 1; self.bytes += m.entries.iter().map(|d| d.size).sum::<u64>(); }
+            Err(e) => self.errors.push(format!("{}: {}", path.file_name().unwrap_or_default().to_string_lossy(), e)),
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut out = format!("Imported {} session{} ({})", self.sessions, if self.sessions == 1 { "" } else { "s" }, format_size(self.bytes));
+        if self.skipped > 0 { out.push_str(&format!(", skipped {}", self.skipped)); }
+        out.push('.');
+        for e in &self.errors { out.push_str(&format!("\n- {}", e)); }
+        out
+    }
+}
+
+const PEEK_LINES: usize = 6;
+/// How much of the end of a debug log is read until the full file is asked for.
+const DEBUG_TAIL_BYTES: u64 = 64 * 1024;
+/// Default for `Config::transcript_chunk_bytes`.
+const TRANSCRIPT_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+/// Projects listed by size on the stats screen.
+const STATS_PROJECTS: usize = 8;
+const STATS_SLOW_PROJECTS: usize = 5;
+const SNAPSHOT_FILE: &str = "snapshot.json";
+/// Cache size, as a percentage of session data, at which the stats screen suggests a leaner level.
+const CACHE_WARN_PERCENT: u64 = 5;
+/// How often an idle screen refreshes, and how often while a background task reports progress.
+const IDLE_TICK: Duration = Duration::from_secs(1);
+const TASK_TICK: Duration = Duration::from_millis(100);
+/// Shortest gap between reloads the file watcher triggers.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Action reports kept for the `Ctrl+o` history.
+const HISTORY_REPORTS: usize = 20;
+/// Age after which a reload rescans for orphans and the gauge calls its orphan count stale.
+const ORPHAN_RESCAN: Duration = Duration::from_secs(300);
+
+/// Trash age emptied from the prune menu when `trash_retention_days` is unset.
+const DEFAULT_TRASH_DAYS: u64 = 30;
+const TRASH_WARN_MB: u64 = 500;
+const SECS_PER_DAY: u64 = 86400;
+
+/// Orphaned files found by the last scan, for the reclaimable-space gauge.
+struct OrphanScan {
+    at: Instant,
+    count: usize,
+    bytes: u64,
+}
+
+/// The message popup text of a finished delete, prune or export, kept for later reading.
+struct ActionReport {
+    at: chrono::DateTime<chrono::Local>,
+    text: String,
+}
+
+impl ActionReport {
+    fn title(&self) -> &str {
+        self.text.lines().next().unwrap_or_default().trim_end_matches(':')
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Verdict { Keep, Delete, Archive, Skip }
+
+/// The triage screen's walk through the listed sessions. Verdicts are keyed by session id and
+/// applied together when the walk ends.
+struct Triage {
+    ids: Vec<String>,
+    pos: usize,
+    verdicts: HashMap<String, Verdict>,
+    /// The current session's last prompt and what followed it.
+    exchange: Vec<LogEntry>,
+    offset: u16,
+}
+
+impl Triage {
+    fn count(&self, v: Verdict) -> usize {
+        self.verdicts.values().filter(|&&x| x == v).count()
+    }
+
+    fn tally(&self) -> String {
+        format!("{} kept, {} to delete, {} to archive, {} skipped",
+            self.count(Verdict::Keep), self.count(Verdict::Delete), self.count(Verdict::Archive), self.count(Verdict::Skip))
+    }
+}
+
+/// Untruncated name and opening prompt lines of the highlighted row, shown next to it.
+struct Peek {
+    name: String,
+    prompt: Vec<String>,
+}
+
+/// One line of the confirm popup; `open_todos` is set on rows that delete a session.
+struct ConfirmRow {
+    label: String,
+    open_todos: Option<usize>,
+}
+
+impl From<String> for ConfirmRow {
+    fn from(label: String) -> Self {
+        ConfirmRow { label, open_todos: None }
+    }
+}
+
+/// A parsed filter query: `tag:x`, `is:x`, `project:x`, `id:x` and `name:x` tokens,
+/// comparisons like `size>10mb`, `-word` or `!word` exclusions, and the remaining free text, which is a case-insensitive regex when it starts with `re:`. `text:` takes
+/// the rest of the query, spaces included, as a phrase to find inside the transcripts.
+#[derive(Default)]
+struct FilterSpec {
+    tags: Vec<String>,
+    project: Option<String>,
+    id: Option<String>,
+    name: Option<String>,
+    file: Option<String>,
+    content: Option<String>,
+    is: Vec<String>,
+    text: String,
+    /// The free text as written, from a query starting with `re:`.
+    pattern: Option<String>,
+    /// Lowercase words no field of a listed session may contain.
+    exclude: Vec<String>,
+    /// `size>10mb`, `msgs=0`, `age>30d` and the like.
+    conditions: Vec<Condition>,
+    /// A comparison on a known field whose value could not be read.
+    bad_condition: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field { Size, Msgs, Age, Tokens }
+
+#[derive(Debug, PartialEq)]
+struct Condition {
+    field: Field,
+    op: std::cmp::Ordering,
+    /// `>=` and `<=` also take equality.
+    or_equal: bool,
+    /// Bytes, messages, seconds or tokens.
+    value: u64,
+}
+
+impl Condition {
+    /// `Some(Err(()))` for a known field with an unreadable value, `None` when `word` is no
+    /// comparison at all and counts as free text.
+    fn parse(word: &str) -> Option<Result<Self, ()>> {
+        let at = word.find(['<', '>', '='])?;
+        let field = match word[..at].to_lowercase().as_str() {
+            "size" => Field::Size,
+            "msgs" | "messages" => Field::Msgs,
+            "age" => Field::Age,
+            "tokens" => Field::Tokens,
+            _ => return None,
+        };
+        let rest = &word[at..];
+        let (op, or_equal, value) = [(">=", std::cmp::Ordering::Greater, true), ("<=", std::cmp::Ordering::Less, true),
+            (">", std::cmp::Ordering::Greater, false), ("<", std::cmp::Ordering::Less, false), ("=", std::cmp::Ordering::Equal, true)]
+            .into_iter().find_map(|(sym, op, eq)| rest.strip_prefix(sym).map(|v| (op, eq, v)))?;
+        let value = value.to_lowercase();
+        let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let Ok(number) = number.parse::<f64>() else { return Some(Err(())) };
+        let scale = match (field, unit) {
+            (Field::Size, "" | "b") => 1,
+            (Field::Size, "k" | "kb") => 1024,
+            (Field::Size, "m" | "mb") => 1024 * 1024,
+            (Field::Size, "g" | "gb") => 1024 * 1024 * 1024,
+            (Field::Age, "s") => 1,
+            (Field::Age, "m") => 60,
+            (Field::Age, "h") => 3600,
+            (Field::Age, "" | "d") => SECS_PER_DAY,
+            (Field::Age, "w") => 7 * SECS_PER_DAY,
+            (Field::Msgs | Field::Tokens, "") => 1,
+            (Field::Tokens, "k") => 1000,
+            (Field::Tokens, "m") => 1_000_000,
+            _ => return Some(Err(())),
+        };
+        Some(Ok(Condition { field, op, or_equal, value: (number * scale as f64) as u64 }))
+    }
+
+    fn holds(&self, s: &Session, now: SystemTime) -> bool {
+        let actual = match self.field {
+            Field::Size => s.size,
+            Field::Msgs => s.message_count as u64,
+            Field::Age => now.duration_since(s.modified).unwrap_or_default().as_secs(),
+            Field::Tokens => s.tokens,
+        };
+        let ord = actual.cmp(&self.value);
+        ord == self.op || (self.or_equal && ord.is_eq())
+    }
+}
+
+/// The fields free text is matched against, notes included.
+fn text_fields(s: &Session) -> Vec<&str> {
+    s.names().map(|(_, n)| n)
+        .chain([s.first_message.as_str(), s.history_prompt.as_deref().unwrap_or(""), s.id.as_str(), s.project.as_str(), s.note.as_deref().unwrap_or("")])
+        .collect()
+}
+
+/// Whether any of the free text fields contains one of the excluded words. Unlike the words
+/// that select, these match as plain substrings so an exclusion only hides what it names.
+fn excluded(s: &Session, exclude: &[String]) -> bool {
+    !exclude.is_empty() && text_fields(s).iter().any(|f| {
+        let f = f.to_lowercase();
+        exclude.iter().any(|x| f.contains(x.as_str()))
+    })
+}
+
+/// How well free text fits a session: every word has to fuzzily match one of its names, first
+/// message, history prompt, id or project, and the best match of each word adds up. `fxbug`
+/// finds "fix the bug", and an exact substring outscores scattered letters.
+fn fuzzy_score(matcher: &SkimMatcherV2, s: &Session, query: &str) -> Option<i64> {
+    let fields = text_fields(s);
+    query.split_whitespace()
+        .map(|word| fields.iter().filter_map(|f| matcher.fuzzy_match(f, word)).max())
+        .sum()
+}
+
+impl FilterSpec {
+    fn parse(query: &str) -> Self {
+        let mut spec = FilterSpec::default();
+        let mut words = Vec::new();
+        let mut raw = Vec::new();
+        let (query, content) = match query.match_indices("text:").find(|(i, _)| query[..*i].chars().next_back().is_none_or(char::is_whitespace)) {
+            Some((i, _)) => (&query[..i], Some(query[i + 5..].trim().to_lowercase()).filter(|c| !c.is_empty())),
+            None => (query, None),
+        };
+        spec.content = content;
+        for w in query.split_whitespace() {
+            if let Some(t) = w.strip_prefix("tag:").filter(|t| !t.is_empty()) {
+                spec.tags.push(t.to_lowercase());
+            } else if let Some(p) = w.strip_prefix("project:").filter(|p| !p.is_empty()) {
+                spec.project = Some(p.to_lowercase());
+            } else if let Some(id) = w.strip_prefix("id:").filter(|id| !id.is_empty()) {
+                spec.id = Some(id.to_lowercase());
+            } else if let Some(n) = w.strip_prefix("name:").filter(|n| !n.is_empty()) {
+                spec.name = Some(n.to_lowercase());
+            } else if let Some(f) = w.strip_prefix("file:").filter(|f| !f.is_empty()) {
+                spec.file = Some(normalize_path(f));
+            } else if let Some(t) = w.strip_prefix("is:").filter(|t| !t.is_empty()) {
+                spec.is.push(t.to_lowercase());
+            } else if let Some(x) = w.strip_prefix(['-', '!']).filter(|x| !x.is_empty()) {
+                spec.exclude.push(x.to_lowercase());
+            } else if let Some(c) = Condition::parse(w) {
+                match c {
+                    Ok(c) => spec.conditions.push(c),
+                    Err(()) => spec.bad_condition = Some(w.to_string()),
+                }
+            } else {
+                words.push(w.to_lowercase());
+                raw.push(w);
+            }
+        }
+        spec.text = words.join(" ");
+        if let Some(pattern) = raw.join(" ").strip_prefix("re:") {
+            spec.pattern = Some(pattern.trim().to_string()).filter(|p| !p.is_empty());
+            spec.text.clear();
+        }
+        spec
+    }
+
+    fn has_is(&self, flag: &str) -> bool {
+        self.is.iter().any(|f| f == flag)
+    }
+
+    /// `project:` names a project directory outright or by its trailing path component, so
+    /// `project:api` finds `-home-me-api` but not `-home-me-api-docs`.
+    fn project_matches(&self, project: &str) -> bool {
+        self.project.as_ref().is_none_or(|p| {
+            let lower = project.to_lowercase();
+            lower == *p || lower.strip_suffix(p.as_str()).is_some_and(|rest| rest.ends_with('-'))
+        })
+    }
+
+    /// `id:` matches from the start of the id; `name:` anywhere in a name or the list label.
+    fn session_matches(&self, s: &Session) -> bool {
+        self.id.as_ref().is_none_or(|id| s.id.to_lowercase().starts_with(id.as_str()))
+            && self.name.as_ref().is_none_or(|n| {
+                s.names().any(|(_, name)| name.to_lowercase().contains(n.as_str())) || s.display_name().to_lowercase().contains(n.as_str())
+            })
+    }
 }
 
-impl App {
-    fn new() -> io::Result<Self> {
-        let config = Config::load();
-        let manager = SessionManager::new();
-        let mut app = App {
-            sessions: Vec::new(), filtered: Vec::new(), state: ListState::default(),
-            selected: Vec::new(), manager, mode: Mode::Normal, input: String::new(),
-            msg: String::new(), action: Action::Delete, 
-            sort: config.sort_by.unwrap_or(SortBy::Date),
-            filter: config.filter_query.clone().unwrap_or_default(),
-            offset: 0, config, to_delete: Vec::new(), orphans: Vec::new(),
-            cached_log: None,
+impl App {
+    /// An app whose sessions load on a worker thread, so the first frame draws at once.
+    fn loading(manager: SessionManager, config: Config) -> Self {
+        let mut app = Self::unloaded(manager, config);
+        app.spawn_load(true);
+        app
+    }
+
+    fn unloaded(mut manager: SessionManager, config: Config) -> Self {
+        manager.configure(&config);
+        let theme = Theme::detect(config.color);
+        let (columns, unknown) = columns::parse_columns(config.columns.as_deref());
+        let (commands, rejected) = check_commands(&config.custom_commands);
+        let bookmarks = if config.persist_bookmarks.unwrap_or(false) { manager.load_sidecar().bookmarks } else { HashMap::new() };
+        let mut app = App {
+            sessions: Vec::new(), filtered: Vec::new(), state: ListState::default(),
+            selected: HashSet::new(), manager, mode: Mode::Normal, input: String::new(),
+            msg: String::new(), action: Action::Delete, 
+            sort: config.sort_by.unwrap_or(SortBy::Date), sort_reversed: config.sort_reversed.unwrap_or(false),
+            sort_then: config.sort_then, sort_state: ListState::default(),
+            rows: Vec::new(), grouped: config.group_by_project.unwrap_or(false),
+            filter: config.filter_query.clone().unwrap_or_default(),
+            offset: 0, config, to_delete: Vec::new(), orphans: Vec::new(),
+            transcript: None, archives: Vec::new(), archive_state: ListState::default(),
+            compare_base: None, compare: None, show_hidden: false,
+            report: Vec::new(), report_offset: 0, popup_scroll: 0, confirm_typed: String::new(), report_kind: ReportKind::Health, repairable: Vec::new(),
+            checklist: None, dangling: Vec::new(), retention_victims: Vec::new(), selected_before_confirm: None, orphan_scan: None, clock: Box::new(SystemClock), now: SystemTime::now(), projects: Vec::new(), project_state: ListState::default(), filter_state: ListState::default(), projects_by_name: false, scan_times: BTreeMap::new(), deferred: Vec::new(), scan_anyway: HashSet::new(), scanning: None, queued: Vec::new(), triage: None, export_index: None, related: Default::default(), layout: LayoutInfo::default(),
+            row_cache: RowCache::default(), hidden_count: 0, task: None, answer: None, theme, peek: None, import: None,
+            file_search: None, content_search: None, columns, frames: FrameClock::new(Instant::now()), stats: Vec::new(), commands, overview: None,
+            history: VecDeque::new(), history_state: ListState::default(), lock_holder: None, instance: None,
+            bookmarks, pending_bookmark: None, status: None, since_last: None, loading: None, open_after_load: None,
+            watch: None, stale: false, last_load: Instant::now(),
+        };
+        app.scan_times = app.manager.load_scan_times();
+        let mut problems = Vec::new();
+        if !unknown.is_empty() {
+            problems.push(format!("Ignoring unknown columns in config: {}\nKnown: {}", unknown.join(", "),
+                columns::Column::ALL.map(|c| c.key()).join(", ")));
+        }
+        problems.extend(rejected);
+        if !problems.is_empty() {
+            app.msg = problems.join("\n");
+            app.mode = Mode::Message;
+        }
+        app
+    }
+
+    /// Startup notes and offers that need the session list.
+    fn after_first_load(&mut self) {
+        self.refresh_searches();
+        if let Some(p) = self.open_after_load.take() { self.open_project(&p); }
+        if let Some(prev) = snapshot::Snapshot::load(&self.manager.state_file(SNAPSHOT_FILE)) {
+            let now = snapshot::Snapshot::of(self.manager.root(), &self.sessions, chrono::Utc::now().timestamp());
+            self.since_last = now.since(&prev);
+        }
+        if self.config.size_delta_note.unwrap_or(true) {
+            self.status = self.since_last.as_ref().filter(|d| d.bytes != 0).map(snapshot::Delta::describe);
+        }
+        let deferred = (!self.deferred.is_empty()).then(|| format!("{} slow projects not scanned; P lists them", self.deferred.len()));
+        for note in [self.trash_note(), deferred].into_iter().flatten() {
+            self.status = Some(match self.status.take() { Some(s) => format!("{}; {}", s, note), None => note });
+        }
+        if !matches!(self.mode, Mode::Normal) || self.task.is_some() { return; }
+        if let Some(days) = self.config.trash_retention_days.filter(|_| !self.manager.read_only) {
+            self.offer_empty_trash(days, true);
+        }
+    }
+
+    /// Projects to leave out of a load because they were slow last time.
+    fn slow_projects(&self) -> HashSet<String> {
+        self.config.slow_scan_threshold_ms.map_or_else(HashSet::new, |limit| {
+            self.scan_times.iter().filter(|(p, ms)| **ms > limit && !self.scan_anyway.contains(*p)).map(|(p, _)| p.clone()).collect()
+        })
+    }
+
+    /// Reloads the list after a change on a worker thread, so it never stalls.
+    fn refresh(&mut self) -> io::Result<()> {
+        self.spawn_load(false);
+        Ok(())
+    }
+
+    /// Starts loading the sessions on a worker thread, replacing any load still running.
+    fn spawn_load(&mut self, first: bool) {
+        let slow = self.slow_projects();
+        let (tx, rx) = mpsc::channel();
+        let (manager, skip) = (self.manager.clone(), slow.clone());
+        self.last_load = Instant::now();
+        std::thread::spawn(move || { tx.send(manager.load_projects(|p| !skip.contains(p))).ok(); });
+        let first = first || self.loading.as_ref().is_some_and(|l| l.first);
+        self.loading = Some(Loading { rx, slow, started: Instant::now(), first });
+    }
+
+    /// Starts watching `projects` and `todos` for `poll_watch`. A watcher that can't start is
+    /// logged and the list simply doesn't refresh by itself.
+    fn start_watching(&mut self) {
+        if !self.config.auto_refresh.unwrap_or(true) { return; }
+        let root = self.manager.root();
+        match watch::Watcher::new(&root.join("projects"), &root.join("todos")) {
+            Ok(w) => self.watch = Some(w),
+            Err(e) => log::warn!("not watching {} for changes: {}", root.display(), e),
+        }
+    }
+
+    /// Reloads after the watcher saw a change, at most once per `WATCH_INTERVAL` so a session
+    /// being written doesn't keep the scan busy. Waits while a popup or task might still
+    /// point at rows of the current list.
+    fn poll_watch(&mut self) {
+        if self.watch.as_ref().is_some_and(watch::Watcher::changed) { self.stale = true; }
+        if !self.stale || self.loading.is_some() || self.task.is_some() || !matches!(self.mode, Mode::Normal) { return; }
+        if self.last_load.elapsed() < WATCH_INTERVAL { return; }
+        self.stale = false;
+        self.spawn_load(false);
+    }
+
+    /// `F5`/`Ctrl+r`: rescans now, keeping the filter, checked rows and highlight.
+    fn manual_reload(&mut self) {
+        self.stale = false;
+        if let Err(e) = self.refresh() {
+            self.msg = format!("Reload failed: {}", e);
+            self.mode = Mode::Message;
+            return;
+        }
+        self.status = Some("Reloading sessions".into());
+    }
+
+    /// Installs the background load once it is done. Returns true when the list changed. Like
+    /// `poll_watch` it waits while a popup might still point at rows of the current list, such
+    /// as the health report's repairable sessions or the retention checklist.
+    fn poll_loading(&mut self) -> bool {
+        let Some(loading) = &self.loading else { return false };
+        if !matches!(self.mode, Mode::Normal | Mode::Message) { return false; }
+        let result = match loading.rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => Err(io::Error::other("the loader stopped")),
+        };
+        let Some(loading) = self.loading.take() else { return false };
+        log::info!("background load took {}ms", loading.started.elapsed().as_millis());
+        match result {
+            Ok(loaded) => self.install(loaded, loading.slow),
+            Err(e) => { self.msg = format!("Loading sessions failed: {}", e); self.mode = Mode::Message; }
+        }
+        if loading.first { self.after_first_load(); } else { self.refresh_searches(); }
+        true
+    }
+
+    /// Puts a fresh load in place. Checked and highlighted sessions stay so by id, since the
+    /// indices may have moved while the load ran.
+    fn install(&mut self, (sessions, times): Loaded, slow: HashSet<String>) {
+        let checked: HashSet<String> = self.selected.iter().filter_map(|&i| self.sessions.get(i)).map(|s| s.id.clone()).collect();
+        let highlighted = self.current().map(|i| self.sessions[i].id.clone());
+        self.sessions = sessions;
+        self.selected = self.sessions.iter().enumerate().filter(|(_, s)| checked.contains(&s.id)).map(|(i, _)| i).collect();
+        if !times.is_empty() {
+            self.scan_times.extend(times);
+            self.manager.save_scan_times(&self.scan_times).ok();
+        }
+        self.deferred = if slow.is_empty() { Vec::new() } else { self.manager.project_names().into_iter().filter(|p| slow.contains(p)).collect() };
+        self.overview = self.sessions.is_empty().then(|| self.manager.overview());
+        self.projects = sessions::project_stats(&self.sessions);
+        self.refresh_orphan_scan(false);
+        self.apply_sort();
+        self.apply_filter();
+        let pos = highlighted.and_then(|id| self.filtered.iter().position(|&i| self.sessions[i].id == id));
+        match pos {
+            Some(pos) => self.select_pos(Some(pos)),
+            None => self.state.select((!self.rows.is_empty()).then_some(0)),
+        }
+    }
+
+    /// Narrows the list to one project and highlights where the user left off there, falling
+    /// back to its newest session.
+    fn open_project(&mut self, project: &str) {
+        self.filter = format!("project:{}", project);
+        self.apply_filter();
+        let remembered = self.config.last_selected.get(project);
+        let pos = self.filtered.iter().position(|&i| Some(&self.sessions[i].id) == remembered)
+            .or_else(|| (0..self.filtered.len()).max_by_key(|&p| self.sessions[self.filtered[p]].modified));
+        self.select_pos(pos);
+    }
+
+    /// Leaves totals for the next launch to compare against.
+    fn save_snapshot(&self) {
+        if self.manager.read_only || self.loading.as_ref().is_some_and(|l| l.first) { return; }
+        snapshot::Snapshot::of(self.manager.root(), &self.sessions, chrono::Utc::now().timestamp())
+            .save(&self.manager.state_file(SNAPSHOT_FILE)).ok();
+    }
+
+    /// Records the highlighted session against its project for the next `open_project`.
+    fn remember_position(&mut self) {
+        let Some(s) = self.current().map(|i| &self.sessions[i]) else { return };
+        self.config.last_selected.insert(s.project.clone(), s.id.clone());
+        self.config.save().ok();
+    }
+
+    fn apply_sort(&mut self) {
+        let (sort, reversed) = (self.sort, self.sort_reversed);
+        let then = self.sort_then.filter(|&t| t != sort);
+        self.sessions.sort_by(|a, b| {
+            let first = if reversed { sort.compare(b, a) } else { sort.compare(a, b) };
+            let first = b.pinned.cmp(&a.pinned).then(first);
+            first.then_with(|| then.map_or(std::cmp::Ordering::Equal, |t| t.compare(a, b)))
+        });
+        self.config.sort_by = Some(self.sort);
+        self.config.sort_reversed = Some(self.sort_reversed);
+        self.config.sort_then = self.sort_then;
+        self.config.save().ok();
+    }
+
+    /// `o`: the sort popup, on the current primary key.
+    fn open_sort_menu(&mut self) {
+        self.sort_state.select(SortBy::ALL.iter().position(|&o| o == self.sort));
+        self.mode = Mode::SortMenu;
+    }
+
+    /// Enter in the sort popup makes the highlighted key primary, or flips the direction when
+    /// it already is; `2` makes it the tie-breaker, or drops it as one.
+    fn pick_sort(&mut self, secondary: bool) {
+        let Some(&order) = self.sort_state.selected().and_then(|i| SortBy::ALL.get(i)) else { return };
+        if secondary {
+            self.sort_then = if self.sort_then == Some(order) { None } else { Some(order) };
+        } else if order == self.sort {
+            self.sort_reversed = !self.sort_reversed;
+        } else {
+            self.sort = order;
+            self.sort_reversed = false;
+        }
+        self.apply_sort();
+        self.apply_filter();
+    }
+
+    /// Sorts by `column`, or flips the direction when it already drives the sort.
+    fn sort_by_column(&mut self, column: columns::Column) {
+        let Some(order) = column.sort() else { return };
+        if order == self.sort { self.sort_reversed = !self.sort_reversed; } else { self.sort = order; self.sort_reversed = false; }
+        self.apply_sort();
+        self.apply_filter();
+    }
+
+    /// A left click in the session list; only the column header reacts.
+    fn click(&mut self, x: u16, y: u16) {
+        if !matches!(self.mode, Mode::Normal) { return; }
+        let hit = self.layout.header.iter().find(|(r, _)| r.contains(ratatui::layout::Position { x, y })).map(|(_, c)| *c);
+        if let Some(c) = hit { self.sort_by_column(c); }
+    }
+
+    fn apply_filter(&mut self) {
+        let spec = FilterSpec::parse(&self.filter);
+        let query = spec.text.clone();
+        let file_hits = spec.file.as_ref().map(|_| self.file_search.as_ref().map(|f| f.hits.lock().unwrap()));
+        let content_hits = spec.content.as_ref().map(|_| self.content_search.as_ref().map(|c| c.hits.lock().unwrap()));
+        // An invalid pattern matches nothing and says why on the status line.
+        let regex = spec.pattern.as_deref().map(|p| regex::RegexBuilder::new(p).case_insensitive(true).build());
+        if let Some(bad) = &spec.bad_condition {
+            self.status = Some(format!("Can't read `{}`; try size>10mb, msgs=0, age>30d or tokens>50k", bad));
+        }
+        if let Some(Err(e)) = &regex {
+            self.status = Some(format!("Invalid regex: {}", e.to_string().lines().last().unwrap_or_default().trim_start_matches("error: ")));
+        }
+        let matcher = SkimMatcherV2::default();
+        let mut scored = self.sessions.iter().enumerate()
+            .filter(|_| !spec.has_is("archived"))
+            .filter(|(_, s)| if spec.has_is("hidden") { s.hidden } else { self.show_hidden || !s.hidden })
+            .filter(|(_, s)| spec.tags.iter().all(|t| s.has_tag(t)))
+            .filter(|(_, s)| !spec.has_is("missing-project") || s.project_missing)
+            .filter(|(_, s)| spec.project_matches(&s.project) && spec.session_matches(s))
+            .filter(|(_, s)| !excluded(s, &spec.exclude))
+            .filter(|(_, s)| spec.bad_condition.is_none() && spec.conditions.iter().all(|c| c.holds(s, self.now)))
+            .filter(|(_, s)| file_hits.as_ref().is_none_or(|h| h.as_ref().is_some_and(|h| h.contains_key(&s.id))))
+            .filter(|(_, s)| content_hits.as_ref().is_none_or(|h| h.as_ref().is_some_and(|h| h.contains_key(&s.id))))
+            .filter(|(_, s)| regex.as_ref().is_none_or(|r| r.as_ref().is_ok_and(|r| r.is_match(&s.display_name()) || r.is_match(&s.id) || r.is_match(&s.project))))
+            .filter_map(|(i, s)| Some((i, if query.is_empty() { 0 } else { fuzzy_score(&matcher, s, &query)? })))
+            .collect::<Vec<_>>();
+        drop((file_hits, content_hits));
+        // Best matches first; the stable sort keeps the chosen order among equal scores.
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        self.filtered = scored.into_iter().map(|(i, _)| i).collect();
+        if self.grouped {
+            // Groups come in the order of their first session, which keeps the sort between them.
+            let mut rank: HashMap<&str, usize> = HashMap::new();
+            for &i in &self.filtered { let n = rank.len(); rank.entry(&self.sessions[i].project).or_insert(n); }
+            let rank: HashMap<String, usize> = rank.into_iter().map(|(p, r)| (p.to_string(), r)).collect();
+            self.filtered.sort_by_key(|&i| rank[&self.sessions[i].project]);
+        }
+        self.hidden_count = self.sessions.iter().filter(|s| s.hidden).count();
+        self.row_cache.clear();
+        self.config.filter_query = Some(self.filter.clone());
+        self.config.save().ok();
+        self.build_rows();
+        match self.state.selected() {
+            None if !self.rows.is_empty() => self.state.select(Some(0)),
+            Some(i) if i >= self.rows.len() => self.state.select(self.rows.len().checked_sub(1)),
+            _ => {}
+        }
+    }
+
+    /// Lays out `filtered` as list rows: one per session, or in the grouped view a heading per
+    /// project followed by its sessions unless it is folded.
+    fn build_rows(&mut self) {
+        if !self.grouped {
+            self.rows = (0..self.filtered.len()).map(ListRow::Session).collect();
+            return;
+        }
+        self.rows.clear();
+        let mut start = 0;
+        while start < self.filtered.len() {
+            let name = &self.sessions[self.filtered[start]].project;
+            let end = start + self.filtered[start..].iter().take_while(|&&i| &self.sessions[i].project == name).count();
+            let collapsed = self.config.collapsed_projects.contains(name);
+            let size = self.filtered[start..end].iter().map(|&i| self.sessions[i].size).sum();
+            self.rows.push(ListRow::Project { name: name.clone(), count: end - start, size, collapsed });
+            if !collapsed { self.rows.extend((start..end).map(ListRow::Session)); }
+            start = end;
+        }
+    }
+
+    /// Position in `filtered` of the highlighted row; `None` on a project heading.
+    fn cursor(&self) -> Option<usize> {
+        match self.rows.get(self.state.selected()?)? {
+            ListRow::Session(pos) => Some(*pos),
+            ListRow::Project { .. } => None,
+        }
+    }
+
+    /// The highlighted session.
+    fn current(&self) -> Option<usize> {
+        self.cursor().map(|pos| self.filtered[pos])
+    }
+
+    /// The project whose heading is highlighted.
+    fn current_heading(&self) -> Option<&str> {
+        match self.rows.get(self.state.selected()?)? {
+            ListRow::Project { name, .. } => Some(name),
+            ListRow::Session(_) => None,
+        }
+    }
+
+    /// Highlights `filtered[pos]`, unfolding its project first if needed.
+    fn select_pos(&mut self, pos: Option<usize>) {
+        let Some(pos) = pos else { self.state.select(None); return };
+        if self.grouped && self.config.collapsed_projects.remove(&self.sessions[self.filtered[pos]].project) {
+            self.config.save().ok();
+            self.build_rows();
+        }
+        self.state.select(self.rows.iter().position(|r| *r == ListRow::Session(pos)));
+    }
+
+    /// `-` folds every project in the grouped view and `+` unfolds them all. The cursor stays on
+    /// the highlighted session, or its project's heading when that is folded.
+    fn fold_all(&mut self, fold: bool) {
+        if !self.grouped { return; }
+        let current = self.cursor();
+        let name = self.current_heading().map(String::from).or_else(|| self.current().map(|i| self.sessions[i].project.clone()));
+        if fold {
+            self.config.collapsed_projects.extend(self.sessions.iter().map(|s| s.project.clone()));
+        } else {
+            self.config.collapsed_projects.clear();
+        }
+        self.config.save().ok();
+        self.build_rows();
+        match (fold, current) {
+            (false, Some(pos)) => self.select_pos(Some(pos)),
+            _ => self.state.select(self.rows.iter().position(|r| matches!(r, ListRow::Project { name: n, .. } if Some(n) == name.as_ref()))),
+        }
+    }
+
+    /// `t`: switches between the flat list and sessions grouped by project, keeping the
+    /// highlighted session, or on a heading the project's first one.
+    fn toggle_grouped(&mut self) {
+        let current = self.current()
+            .or_else(|| self.current_heading().and_then(|name| self.filtered.iter().copied().find(|&i| self.sessions[i].project == name)))
+            .map(|i| self.sessions[i].id.clone());
+        self.grouped = !self.grouped;
+        self.config.group_by_project = Some(self.grouped);
+        self.apply_filter();
+        let pos = current.and_then(|id| self.filtered.iter().position(|&i| self.sessions[i].id == id));
+        self.select_pos(pos.or((!self.filtered.is_empty()).then_some(0)));
+    }
+
+    /// Folds (`Left`) or unfolds (`Right`) the highlighted heading or the highlighted
+    /// session's project, leaving the cursor on the heading; `None` flips it.
+    fn fold(&mut self, fold: Option<bool>) {
+        if !self.grouped { return; }
+        let Some(name) = self.current_heading().map(String::from).or_else(|| self.current().map(|i| self.sessions[i].project.clone())) else { return };
+        let fold = fold.unwrap_or(!self.config.collapsed_projects.contains(&name));
+        if fold { self.config.collapsed_projects.insert(name.clone()); } else { self.config.collapsed_projects.remove(&name); }
+        self.config.save().ok();
+        self.build_rows();
+        self.state.select(self.rows.iter().position(|r| matches!(r, ListRow::Project { name: n, .. } if *n == name)));
+    }
+
+    /// Starts a search when the filter has a `file:` query that hasn't run yet. Sessions whose
+    /// cached edit list matches are answered at once; the rest are streamed on a background task.
+    fn refresh_file_search(&mut self) {
+        let Some(query) = FilterSpec::parse(&self.filter).file else { self.file_search = None; return };
+        if self.task.is_some() || self.file_search.as_ref().is_some_and(|f| f.query == query) { return; }
+        let mut hits = HashMap::new();
+        let mut items = Vec::new();
+        for s in &self.sessions {
+            let cached: Vec<String> = s.summary.iter().flat_map(|s| &s.files).filter(|f| normalize_path(f).contains(&query)).cloned().collect();
+            if cached.is_empty() { items.push((s.display_name(), (s.path.clone(), s.id.clone()))); }
+            else { hits.insert(s.id.clone(), cached); }
+        }
+        let hits = Arc::new(Mutex::new(hits));
+        let (shared, q) = (hits.clone(), query.clone());
+        self.file_search = Some(FileSearch { query: query.clone(), hits });
+        // Filter on the cached hits before the worker starts adding its own.
+        self.apply_filter();
+        let task = Task::spawn(format!("Searching sessions for {}", query), "Searched", items, move |(path, id): (PathBuf, String)| {
+            let found = sessions::referenced_files(&path, &q)?;
+            if !found.is_empty() { shared.lock().unwrap().insert(id, found); }
+            Ok(fs::metadata(&path).map(|m| m.len()).unwrap_or(0))
+        });
+        self.run_task(task);
+    }
+
+    /// Starts a search when the filter has a `text:` query that hasn't run yet. The search
+    /// index rules out most sessions; the background task streams the rest, reindexing those
+    /// that changed since they were indexed.
+    fn refresh_content_search(&mut self) {
+        let Some(query) = FilterSpec::parse(&self.filter).content else { self.content_search = None; return };
+        if self.task.is_some() || self.content_search.as_ref().is_some_and(|c| c.query == query) { return; }
+        let index = self.manager.load_search_index();
+        let candidates = index.candidates(&query);
+        let items = self.sessions.iter()
+            .filter_map(|s| {
+                let stale = !index.is_fresh(&s.id, s.size, s.modified);
+                (stale || candidates.as_ref().is_none_or(|c| c.contains(&s.id)))
+                    .then(|| (s.display_name(), (s.path.clone(), s.id.clone(), stale.then_some((s.size, s.modified)))))
+            })
+            .collect();
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+        let index = Arc::new(Mutex::new(index));
+        let (shared, q, idx) = (hits.clone(), query.clone(), index.clone());
+        let task = Task::spawn(format!("Searching sessions for \"{}\"", query), "Searched", items, move |(path, id, stale): (PathBuf, String, Option<(u64, SystemTime)>)| {
+            if let Some((size, modified)) = stale {
+                let words = index::words_of(&path)?;
+                idx.lock().unwrap().update(&id, size, modified, words);
+            }
+            if let Some(snippet) = sessions::content_match(&path, &q)? { shared.lock().unwrap().insert(id, snippet); }
+            Ok(fs::metadata(&path).map(|m| m.len()).unwrap_or(0))
+        });
+        self.content_search = Some(ContentSearch { query, hits, index });
+        self.apply_filter();
+        self.run_task(task);
+    }
+
+    /// Runs whichever of the `file:` and `text:` searches the filter still needs, one at a time.
+    fn refresh_searches(&mut self) {
+        self.refresh_file_search();
+        self.refresh_content_search();
+    }
+
+    /// Hides the targets, or un-hides them when all are already hidden.
+    fn toggle_hidden(&mut self) -> io::Result<()> {
+        let targets = self.targets();
+        if targets.is_empty() { return Ok(()); }
+        let hide = !targets.iter().all(|&i| self.sessions[i].hidden);
+        let mut sidecar = self.manager.load_sidecar();
+        for &i in &targets {
+            let s = &mut self.sessions[i];
+            if hide { sidecar.hidden.insert(s.id.clone()); } else { sidecar.hidden.remove(&s.id); }
+            s.hidden = hide;
+        }
+        self.manager.save_sidecar(&sidecar)?;
+        self.selected.clear();
+        self.apply_filter();
+        Ok(())
+    }
+
+    /// `*`: pins the targeted sessions to the top of the list, or unpins them when all already are.
+    fn toggle_pinned(&mut self) -> io::Result<()> {
+        let targets = self.targets();
+        if targets.is_empty() { return Ok(()); }
+        let pin = !targets.iter().all(|&i| self.sessions[i].pinned);
+        let mut sidecar = self.manager.load_sidecar();
+        for &i in &targets {
+            let s = &mut self.sessions[i];
+            if pin { sidecar.pinned.insert(s.id.clone()); } else { sidecar.pinned.remove(&s.id); }
+            s.pinned = pin;
+        }
+        self.manager.save_sidecar(&sidecar)?;
+        // Sorting moves sessions around, so the highlight follows by id and the checks go.
+        let current = self.current().map(|i| self.sessions[i].id.clone());
+        self.selected.clear();
+        self.apply_sort();
+        self.apply_filter();
+        let pos = current.and_then(|id| self.filtered.iter().position(|&i| self.sessions[i].id == id));
+        if pos.is_some() { self.select_pos(pos); }
+        Ok(())
+    }
+
+    /// `L`: protects the targeted sessions from deletes and prunes, or lifts the protection when
+    /// all of them already have it.
+    fn toggle_protected(&mut self) -> io::Result<()> {
+        let targets = self.targets();
+        if targets.is_empty() { return Ok(()); }
+        let protect = !targets.iter().all(|&i| self.sessions[i].protected);
+        let mut sidecar = self.manager.load_sidecar();
+        for &i in &targets {
+            let s = &mut self.sessions[i];
+            if protect { sidecar.protected.insert(s.id.clone()); } else { sidecar.protected.remove(&s.id); }
+            s.protected = protect;
+        }
+        self.manager.save_sidecar(&sidecar)?;
+        self.status = Some(format!("{} {} session{}", if protect { "Protected" } else { "Unprotected" }, targets.len(), if targets.len() == 1 { "" } else { "s" }));
+        self.selected.clear();
+        self.apply_filter();
+        Ok(())
+    }
+
+    /// Whether a session may be offered by prune flows; hidden ones are exempt unless revealed.
+    fn prune_candidate(&self, s: &Session) -> bool {
+        self.show_hidden || !s.hidden
+    }
+
+    /// Runs the jsonl health check over `targets` and opens the report popup.
+    fn check_health(&mut self, targets: Vec<usize>) {
+        let mut lines = Vec::new();
+        let mut healthy = 0;
+        self.repairable.clear();
+        for idx in targets {
+            let s = &self.sessions[idx];
+            match self.manager.check_session(s) {
+                Ok(r) if r.is_healthy() => healthy += 1,
+                Ok(r) => {
+                    lines.push(format!("{} ({})", s.display_name(), s.id));
+                    lines.extend(r.describe().into_iter().map(|d| format!("  - {}", d)));
+                    if r.truncated_tail { self.repairable.push(idx); }
+                }
+                Err(e) => lines.push(format!("{} ({}): unreadable: {}", s.display_name(), s.id, e)),
+            }
+        }
+        let mut header = vec![format!("{} healthy, {} with problems, {} repairable", healthy, lines.iter().filter(|l| !l.starts_with(' ')).count(), self.repairable.len()), String::new()];
+        header.append(&mut lines);
+        self.report = header;
+        self.report_offset = 0;
+        self.report_kind = ReportKind::Health;
+        self.mode = Mode::Report;
+    }
+
+    /// Opens the most recent audit log entries, newest first, in the report popup.
+    fn show_audit(&mut self) {
+        let entries = self.manager.audit.recent(200);
+        self.report = if entries.is_empty() {
+            vec!["Nothing has been deleted yet.".into()]
+        } else {
+            entries.iter().flat_map(|e| std::iter::once(e.describe()).chain(e.paths.iter().map(|p| format!("    {}", p)))).collect()
+        };
+        if !self.config.audit_log.unwrap_or(true) { self.report.insert(0, "(audit logging is off: audit_log is false in the config)".into()); }
+        self.report_offset = 0;
+        self.report_kind = ReportKind::Audit;
+        self.mode = Mode::Report;
+    }
+
+    fn export_report(&mut self) -> io::Result<()> {
+        let dir = std::env::current_dir()?.join("exports");
+        std::fs::create_dir_all(&dir)?;
+        let name = format!("{}-{}.txt", self.report_kind.file_prefix(), archive::now_ts());
+        std::fs::write(dir.join(&name), self.report.join("\n"))?;
+        self.msg = format!("Report written to ./exports/{}", name);
+        self.mode = Mode::Message;
+        Ok(())
+    }
+
+    fn review_orphans(&mut self) {
+        self.orphans = self.manager.find_orphans().iter().map(|p| p.to_string_lossy().into()).collect();
+        if self.orphans.is_empty() { self.msg = "No orphans.".into(); self.mode = Mode::Message; return; }
+        self.checklist = Some(Checklist::new(ChecklistKind::Orphans, "Orphaned Files", self.orphans.clone()));
+        self.mode = Mode::Checklist;
+    }
+
+    fn review_history(&mut self) {
+        self.dangling = self.manager.dangling_history();
+        if self.dangling.is_empty() { self.msg = "No dangling history entries.".into(); self.mode = Mode::Message; return; }
+        let items = self.dangling.iter()
+            .map(|h| format!("{} [{:.8}] {}: {}", h.time_str(), h.session_id, h.project, h.display.replace('\n', " ")))
+            .collect();
+        self.checklist = Some(Checklist::new(ChecklistKind::History, "Dangling History Entries", items));
+        self.mode = Mode::Checklist;
+    }
+
+    /// Lists what the configured retention rules would delete, grouped by rule, for review.
+    /// Sessions tagged `keep`, pinned and protected ones are always exempt, whatever `prune_skip_keep` says.
+    fn review_retention(&mut self) {
+        if self.config.retention.is_empty() { self.msg = "No retention rules; add \"retention\" to the config.".into(); self.mode = Mode::Message; return; }
+        let found = retention::victims(&self.config.retention, &self.sessions, self.now(), |s| s.has_tag("keep") || s.pinned || s.protected || !self.prune_candidate(s));
+        if found.is_empty() { self.msg = "The retention policy is already met.".into(); self.mode = Mode::Message; return; }
+        self.retention_victims.clear();
+        let mut items = Vec::new();
+        for (r, victims) in found {
+            let rule = self.config.retention[r].describe();
+            for i in victims {
+                let s = &self.sessions[i];
+                items.push(format!("[{}] {} ({}, {})", rule, s.display_name(), s.project, s.id));
+                self.retention_victims.push(i);
+            }
+        }
+        self.checklist = Some(Checklist::new(ChecklistKind::Retention, "Retention Policy", items));
+        self.mode = Mode::Checklist;
+    }
+
+    /// Lists the files inside the highlighted session's `file-history` and `session-env`
+    /// directories, oldest first and unchecked, so single snapshots can be dropped.
+    fn browse_related(&mut self) {
+        let Some(s) = self.current().map(|i| &self.sessions[i]) else { return };
+        let entries = self.manager.related_entries(s);
+        if entries.is_empty() { self.msg = format!("{} has no file-history or session-env files.", s.display_name()); self.mode = Mode::Message; return; }
+        let root = self.manager.root();
+        let items = entries.iter().map(|e| {
+            let when: chrono::DateTime<chrono::Local> = e.modified.into();
+            format!("{:>8}  {}  {}", format_size(e.size), when.format("%Y-%m-%d %H:%M"), e.path.strip_prefix(root).unwrap_or(&e.path).display())
+        }).collect();
+        let title = format!("Related Files of {} (footprint {})", s.display_name(), format_size(s.footprint()));
+        let mut c = Checklist::new(ChecklistKind::Related, &title, items);
+        for item in &mut c.items { item.1 = false; }
+        self.related = (s.id.clone(), entries.into_iter().map(|e| e.path).collect());
+        self.checklist = Some(c);
+        self.mode = Mode::Checklist;
+    }
+
+    /// `C`: lists every column, the shown ones checked and first, for toggling.
+    fn open_column_picker(&mut self) {
+        let shown: Vec<columns::Column> = self.columns.iter().map(|s| s.column).collect();
+        let hidden = columns::Column::ALL.into_iter().filter(|c| !shown.contains(c));
+        let order: Vec<columns::Column> = shown.iter().copied().chain(hidden).collect();
+        let mut c = Checklist::new(ChecklistKind::Columns, "Columns", order.iter().map(|c| c.key().to_string()).collect());
+        for (item, col) in c.items.iter_mut().zip(&order) { item.1 = shown.contains(col); }
+        self.checklist = Some(c);
+        self.mode = Mode::Checklist;
+    }
+
+    /// Shows the checked columns in picker order, keeping configured widths, and saves them.
+    fn set_columns(&mut self, c: &Checklist) {
+        self.columns = c.checked().iter()
+            .filter_map(|&i| ColumnSpec::parse(&c.items[i].0))
+            .map(|spec| self.columns.iter().find(|s| s.column == spec.column).copied().unwrap_or(spec))
+            .collect();
+        self.config.columns = Some(self.columns.iter().map(|s| s.to_config()).collect());
+        self.config.save().ok();
+        self.row_cache.clear();
+    }
+
+    /// Turns the checked items of the open checklist into a confirm prompt.
+    fn apply_checklist(&mut self) {
+        let Some(c) = self.checklist.take() else { return };
+        let checked = c.checked();
+        if checked.is_empty() { self.mode = Mode::Normal; return; }
+        self.to_delete = checked.iter().map(|&i| c.items[i].0.clone().into()).collect();
+        match c.kind {
+            ChecklistKind::Columns => {
+                self.set_columns(&c);
+                self.mode = Mode::Normal;
+                return;
+            }
+            ChecklistKind::Orphans => {
+                self.orphans = checked.iter().map(|&i| c.items[i].0.clone()).collect();
+                self.msg = format!("Delete {} orphans?", self.orphans.len());
+                self.action = Action::PruneOrphans;
+            }
+            ChecklistKind::Related => {
+                if self.manager.read_only { self.refuse_read_only(); return; }
+                let paths: Vec<PathBuf> = checked.iter().map(|&i| self.related.1[i].clone()).collect();
+                self.msg = format!("Delete {} related files?", paths.len());
+                self.action = Action::DropRelated(self.related.0.clone(), paths);
+            }
+            ChecklistKind::Retention => {
+                let victims = checked.iter().map(|&i| self.retention_victims[i]).collect();
+                self.selected_before_confirm = Some(std::mem::replace(&mut self.selected, victims));
+                self.to_delete = self.selection().into_iter().map(|i| self.delete_row(i, self.sessions[i].display_name())).collect();
+                self.msg = format!("Delete {} sessions outside the retention policy?", self.selected.len());
+                self.action = Action::Delete;
+            }
+            ChecklistKind::History => {
+                self.msg = format!("Remove {} history entries?", checked.len());
+                self.action = Action::DropHistory(checked.iter().map(|&i| self.dangling[i].key()).collect());
+            }
+        }
+        self.mode = Mode::Confirm;
+    }
+
+    /// Writes the prompt text of the checked dangling history entries to ./exports/.
+    fn export_history(&mut self) -> io::Result<()> {
+        let Some(c) = &self.checklist else { return Ok(()) };
+        let text: Vec<String> = c.checked().iter().map(|&i| {
+            let h = &self.dangling[i];
+            format!("# {} {} {}\n{}\n", h.time_str(), h.session_id, h.project, h.display)
+        }).collect();
+        let dir = std::env::current_dir()?.join("exports");
+        std::fs::create_dir_all(&dir)?;
+        let name = format!("history-{}.txt", archive::now_ts());
+        std::fs::write(dir.join(&name), text.join("\n"))?;
+        self.msg = format!("Exported {} prompts to ./exports/{}", text.len(), name);
+        self.checklist = None;
+        self.mode = Mode::Message;
+        Ok(())
+    }
+
+    /// Reasons quitting now would lose something; empty when it's safe to exit.
+    fn quit_blockers(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if !self.selected.is_empty() && self.config.confirm_quit.unwrap_or(true) {
+            reasons.push(format!("{} sessions still selected", self.selected.len()));
+        }
+        if let Some(t) = &self.task {
+            reasons.push(format!("{} is still running", t.title));
+        }
+        reasons
+    }
+
+    /// Re-clamps scroll positions against the new terminal size so nothing points past the end.
+    fn on_resize(&mut self, _w: u16, h: u16) {
+        let page = h.saturating_sub(2) as usize;
+        if let Some(t) = &self.transcript {
+            if self.offset != usize::MAX { self.offset = self.offset.min(t.lines.len().saturating_sub(page)); }
+        }
+        if let Some(c) = self.compare.as_mut() {
+            for p in 0..2 { c.offsets[p] = c.offsets[p].min(c.panes[p].1.lines.len().saturating_sub(1)); }
+        }
+        if let Some(i) = self.state.selected() {
+            if i >= self.rows.len() { self.state.select(self.rows.len().checked_sub(1)); }
+        }
+    }
+
+    fn move_sel(&mut self, delta: isize) {
+        if self.rows.is_empty() { return; }
+        let len = self.rows.len();
+        let i = match self.state.selected() {
+            Some(i) => (i as isize + delta).rem_euclid(len as isize) as usize,
+            None => 0,
+        };
+        self.state.select(Some(i));
+        self.offset = 0;
+    }
+
+    /// Space: checks or unchecks the highlighted session; on a heading, the whole project.
+    fn toggle(&mut self) {
+        if let Some(name) = self.current_heading() {
+            let group: Vec<usize> = self.filtered.iter().copied().filter(|&i| self.sessions[i].project == name).collect();
+            if group.iter().all(|i| self.selected.contains(i)) {
+                for i in &group { self.selected.remove(i); }
+            } else {
+                self.selected.extend(group);
+            }
+            self.row_cache.clear();
+        } else if let Some(idx) = self.current() {
+            if !self.selected.remove(&idx) { self.selected.insert(idx); }
+        }
+    }
+
+    /// The selected sessions in list order.
+    fn selection(&self) -> Vec<usize> {
+        let mut v: Vec<usize> = self.selected.iter().copied().collect();
+        v.sort_unstable();
+        v
+    }
+
+    /// `D`: checks every session of the highlighted project, hidden ones and those the filter
+    /// leaves out included, and asks to delete them with their related files.
+    fn delete_project(&mut self) {
+        let Some(name) = self.current_heading().map(String::from).or_else(|| self.current().map(|i| self.sessions[i].project.clone())) else { return };
+        let project = (0..self.sessions.len()).filter(|&i| self.sessions[i].project == name).collect();
+        self.selected_before_confirm = Some(std::mem::replace(&mut self.selected, project));
+        self.row_cache.clear();
+        let targets = self.selection();
+        let related: usize = targets.iter().map(|&i| self.sessions[i].related_files.len()).sum();
+        self.to_delete = targets.into_iter().map(|i| self.delete_row(i, self.sessions[i].display_name())).collect();
+        self.msg = format!("Delete all {} sessions of {} ({} related files)?", self.selected.len(), name, related);
+        self.action = Action::Delete;
+        self.mode = Mode::Confirm;
+    }
+
+    /// A confirm row deleting session `idx`, annotated with its open todo count.
+    fn delete_row(&self, idx: usize, label: String) -> ConfirmRow {
+        ConfirmRow { label, open_todos: Some(self.sessions[idx].open_todos()) }
+    }
+
+    /// (rows with open todos, rows deleting a session) for the confirm popup.
+    fn open_todo_summary(&self) -> (usize, usize) {
+        let sessions = self.to_delete.iter().filter_map(|r| r.open_todos);
+        (sessions.clone().filter(|&n| n > 0).count(), sessions.count())
+    }
+
+    /// The count to type when the confirm popup lists more than `confirm_threshold` items.
+    fn typed_confirm(&self) -> Option<usize> {
+        self.config.confirm_threshold.filter(|&t| self.to_delete.len() > t).map(|_| self.to_delete.len())
+    }
+
+    /// Leaves the confirm popup without acting.
+    fn cancel_confirm(&mut self) {
+        self.popup_scroll = 0;
+        self.confirm_typed.clear();
+        if let Some(selected) = self.selected_before_confirm.take() {
+            self.selected = selected;
+            self.row_cache.clear();
+        }
+        self.mode = if matches!(self.action, Action::Redact(..)) { Mode::Expanded } else { Mode::Normal };
+    }
+
+    fn needs_capital_y(&self) -> bool {
+        self.config.block_delete_with_open_todos.unwrap_or(false) && self.open_todo_summary().0 > 0
+    }
+
+    /// Selected sessions, or the highlighted one when nothing is selected.
+    fn targets(&self) -> Vec<usize> {
+        if !self.selected.is_empty() { return self.selection(); }
+        self.current().into_iter().collect()
+    }
+
+    fn tag_targets(&mut self) -> io::Result<()> {
+        let mut sidecar = self.manager.load_sidecar();
+        for idx in self.targets() {
+            let id = self.sessions[idx].id.clone();
+            sidecar.edit_tags(&id, &self.input);
+            self.sessions[idx].tags = sidecar.tags.get(&id).cloned().unwrap_or_default();
+        }
+        self.manager.save_sidecar(&sidecar)?;
+        self.apply_filter();
+        Ok(())
+    }
+
+    /// Sessions matching `pred` that are eligible for pruning, honoring the keep-tag exemption.
+    /// Pinned and protected sessions are never offered.
+    fn prunable(&self, pred: impl Fn(&Session) -> bool) -> Vec<usize> {
+        let skip_keep = self.config.prune_skip_keep.unwrap_or(true);
+        self.sessions.iter().enumerate()
+            .filter(|(_, s)| pred(s) && !(skip_keep && s.has_tag("keep")) && self.prune_candidate(s))
+            .filter(|(_, s)| !s.pinned && !s.protected)
+            .map(|(i, _)| i).collect()
+    }
+
+    fn prunable_empty(&self) -> Vec<usize> {
+        self.prunable(|s| s.message_count == 0)
+    }
+
+    /// Rescans for orphans when forced or when the last scan is older than `ORPHAN_RESCAN`.
+    fn refresh_orphan_scan(&mut self, force: bool) {
+        if !force && self.orphan_scan.as_ref().is_some_and(|o| o.at.elapsed() < ORPHAN_RESCAN) { return; }
+        self.record_orphans(&self.manager.find_orphans());
+    }
+
+    fn record_orphans(&mut self, found: &[PathBuf]) {
+        self.orphan_scan = Some(OrphanScan { at: Instant::now(), count: found.len(), bytes: found.iter().map(|p| audit::disk_size(p)).sum() });
+    }
+
+    /// The bottom bar gauge, `reclaimable: ~310MB (42 empty, 18 orphans)`. An orphan count
+    /// older than `ORPHAN_RESCAN` says how old it is.
+    fn reclaimable(&self) -> String {
+        let empty = self.prunable_empty();
+        let mut bytes: u64 = empty.iter().map(|&i| self.sessions[i].size).sum();
+        let orphans = match &self.orphan_scan {
+            Some(o) => {
+                bytes += o.bytes;
+                let age = o.at.elapsed();
+                let stale = if age >= ORPHAN_RESCAN { format!(" as of {}m ago", age.as_secs() / 60) } else { String::new() };
+                format!("{} orphan{}{}", o.count, if o.count == 1 { "" } else { "s" }, stale)
+            }
+            None => "orphans not scanned".into(),
+        };
+        format!("reclaimable: ~{} ({} empty, {})", format_size(bytes), empty.len(), orphans)
+    }
+
+    /// Status bar warning when the backup trash is bigger than `trash_warn_mb` or holds files
+    /// past the retention age.
+    fn trash_note(&self) -> Option<String> {
+        let trash = self.manager.trash();
+        let size: u64 = trash.iter().map(|e| e.size).sum();
+        let days = self.config.trash_retention_days.unwrap_or(DEFAULT_TRASH_DAYS);
+        let old = trash.iter().filter(|e| self.older_than_days(e.modified, days)).count();
+        if size > self.config.trash_warn_mb.unwrap_or(TRASH_WARN_MB) * 1024 * 1024 {
+            Some(format!("Trash (archives/backups) holds {}; p then t empties files older than {} days", format_size(size), days))
+        } else if old > 0 {
+            Some(format!("{} trash files are older than {} days; p then t empties them", old, days))
+        } else {
+            None
+        }
+    }
+
+    fn older_than_days(&self, t: SystemTime, days: u64) -> bool {
+        self.now().duration_since(t).unwrap_or_default().as_secs() > days * SECS_PER_DAY
+    }
+
+    /// Asks to delete backups older than `days`. At startup an empty result stays silent.
+    fn offer_empty_trash(&mut self, days: u64, startup: bool) {
+        let old: Vec<sessions::RelatedEntry> = self.manager.trash().into_iter()
+            .filter(|e| self.older_than_days(e.modified, days))
+            .collect();
+        if old.is_empty() {
+            if !startup { self.msg = format!("No trash older than {} days.", days); self.mode = Mode::Message; }
+            return;
+        }
+        let size: u64 = old.iter().map(|e| e.size).sum();
+        self.to_delete = old.iter().map(|e| format!("{} ({})", e.path.display(), format_size(e.size)).into()).collect();
+        self.msg = format!("Empty {} trash files older than {} days ({})?", old.len(), days, format_size(size));
+        self.action = Action::EmptyTrash(old.into_iter().map(|e| e.path.to_string_lossy().into_owned()).collect());
+        self.mode = Mode::Confirm;
+    }
+
+    /// Offers empty sessions and orphaned files for deletion in one confirm, after a fresh
+    /// orphan scan.
+    fn prune_both(&mut self) {
+        self.selected = self.prunable_empty().into_iter().collect();
+        let found = self.manager.find_orphans();
+        self.record_orphans(&found);
+        self.orphans = found.iter().map(|p| p.to_string_lossy().into()).collect();
+        if self.selected.is_empty() && self.orphans.is_empty() { self.msg = "Nothing to prune.".into(); self.mode = Mode::Message; return; }
+        self.to_delete = self.selection().into_iter().map(|i| self.delete_row(i, self.sessions[i].display_name()))
+            .chain(self.orphans.iter().map(|p| p.clone().into())).collect();
+        self.msg = format!("Delete {} empty & {} orphans?", self.selected.len(), self.orphans.len());
+        self.action = Action::PruneBoth;
+        self.mode = Mode::Confirm;
+    }
+
+    fn archive_targets(&mut self) {
+        let targets = self.targets();
+        let mut archived = Vec::new();
+        let mut errors = String::new();
+        for idx in targets {
+            let s = &self.sessions[idx];
+            match self.manager.archive_session(s) {
+                Ok(_) => archived.push(idx),
+                Err(e) => errors.push_str(&format!("- {}: {}\n", s.id, e)),
+            }
+        }
+        if archived.is_empty() {
+            self.msg = format!("Archive failed:\n{}", errors);
+            self.mode = Mode::Message;
+            return;
+        }
+        self.to_delete = archived.iter().map(|&i| self.delete_row(i, self.sessions[i].display_name())).collect();
+        self.msg = format!("Archived {} to {}. Delete originals?", archived.len(), self.manager.archive_dir().display());
+        if !errors.is_empty() { self.msg.push_str(&format!(" ({} failed)", errors.lines().count())); }
+        self.selected = archived.into_iter().collect();
+        self.action = Action::Delete;
+        self.mode = Mode::Confirm;
+    }
+
+    fn open_archives(&mut self) {
+        let query = FilterSpec::parse(&self.filter).text;
+        self.archives = self.manager.list_archives().into_iter()
+            .filter(|a| query.is_empty() || [&a.manifest.id, &a.manifest.name, &a.manifest.project]
+                .iter().any(|f| f.to_lowercase().contains(&query)))
+            .collect();
+        self.archive_state.select(if self.archives.is_empty() { None } else { Some(0) });
+        self.mode = Mode::Archived;
+    }
+
+    fn close_archives(&mut self) {
+        if FilterSpec::parse(&self.filter).has_is("archived") {
+            self.filter = self.filter.split_whitespace().filter(|w| *w != "is:archived").collect::<Vec<_>>().join(" ");
+            self.apply_filter();
+        }
+        self.archives.clear();
+        self.mode = Mode::Normal;
+    }
+
+    fn rename_current(&mut self) -> io::Result<()> {
+        let Some(idx) = self.current() else { return Ok(()) };
+        let id = self.sessions[idx].id.clone();
+        let mut sidecar = self.manager.load_sidecar();
+        sidecar.set_name(&id, &self.input);
+        self.manager.save_sidecar(&sidecar)?;
+        self.sessions[idx].user_name = sidecar.names.get(&id).cloned();
+        self.apply_filter();
+        Ok(())
+    }
+
+    /// Saves the typed note on the highlighted session; an empty one removes it.
+    fn save_note(&mut self) -> io::Result<()> {
+        let Some(idx) = self.current() else { return Ok(()) };
+        let id = self.sessions[idx].id.clone();
+        let mut sidecar = self.manager.load_sidecar();
+        sidecar.set_note(&id, &self.input);
+        self.manager.save_sidecar(&sidecar)?;
+        self.sessions[idx].note = sidecar.notes.get(&id).cloned();
+        self.apply_filter();
+        Ok(())
+    }
+
+    /// Restores the highlighted archive, asking for confirmation first if it would overwrite live files.
+    fn restore_selected(&mut self) -> io::Result<()> {
+        let Some(a) = self.archive_state.selected().and_then(|i| self.archives.get(i)) else { return Ok(()) };
+        let path = a.path.clone();
+        match self.manager.restore_archive(&path, false) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                self.to_delete = vec![a.manifest.name.clone().into()];
+                self.msg = format!("Overwrite live session {}?", a.manifest.id);
+                self.action = Action::Restore(path);
+                self.mode = Mode::Confirm;
+            }
+            res => {
+                self.msg = restore_report(res);
+                self.refresh()?;
+                self.mode = Mode::Message;
+            }
+        }
+        Ok(())
+    }
+
+    /// Duplicates a session, optionally cutting it after a transcript entry's record.
+    fn duplicate(&mut self, idx: usize, keep_through: Option<usize>) -> io::Result<()> {
+        let Some(s) = self.sessions.get(idx) else { return Ok(()) };
+        self.msg = match self.manager.duplicate_session(s, keep_through) {
+            Ok(id) => format!("Duplicated {} as {}{}", s.id, id, if keep_through.is_some() { " (cut at mark)" } else { "" }),
+            Err(e) => format!("Duplicate failed: {}", e),
+        };
+        self.refresh()?;
+        self.mode = Mode::Message;
+        Ok(())
+    }
+
+    fn slim_threshold(&self) -> usize {
+        self.config.slim_threshold.unwrap_or(4096)
+    }
+
+    /// Dry-runs the slim action over the targets and asks for confirmation with projected sizes.
+    fn start_slim(&mut self) {
+        let threshold = self.slim_threshold();
+        let mut items = Vec::new();
+        let mut candidates = Vec::new();
+        let mut saved = 0;
+        for idx in self.targets() {
+            let s = &self.sessions[idx];
+            if let Ok((before, after)) = self.manager.slim_session(s, threshold, true) {
+                if after < before {
+                    items.push(format!("{}: {} -> {}", s.display_name(), format_size(before), format_size(after)));
+                    candidates.push(idx);
+                    saved += before - after;
+                }
+            }
+        }
+        if candidates.is_empty() {
+            self.msg = format!("No tool results larger than {} to strip.", format_size(threshold as u64));
+            self.mode = Mode::Message;
+            return;
+        }
+        self.msg = format!("Slim {} sessions, saving ~{}?", candidates.len(), format_size(saved));
+        self.to_delete = items.into_iter().map(Into::into).collect();
+        self.selected = candidates.into_iter().collect();
+        self.action = Action::Slim;
+        self.mode = Mode::Confirm;
+    }
+
+    /// Groups byte-identical sessions (delete all but the newest) and near-duplicates sharing
+    /// their first messages (listed only), then opens the confirm popup.
+    fn find_duplicates(&mut self) {
+        const NEAR_PREFIX: usize = 6;
+        let hashes = self.manager.content_hashes(&self.sessions);
+        let describe = |i: usize| { let s = &self.sessions[i]; format!("{} ({}, {})", s.display_name(), s.project, s.formatted_age(self.now())) };
+        let group_by = |key: &dyn Fn(usize) -> Option<String>| {
+            let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+            for i in (0..self.sessions.len()).filter(|&i| self.prune_candidate(&self.sessions[i])) {
+                if let Some(k) = key(i) { groups.entry(k).or_default().push(i); }
+            }
+            let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+            for g in &mut groups { g.sort_by_key(|&i| std::cmp::Reverse(self.sessions[i].modified)); }
+            groups.sort_by_key(|g| std::cmp::Reverse(self.sessions[g[0]].modified));
+            groups
+        };
+        let exact = group_by(&|i| hashes.get(&self.sessions[i].path).cloned());
+        let near: Vec<Vec<usize>> = group_by(&|i| {
+            let s = &self.sessions[i];
+            if s.message_count < NEAR_PREFIX / 2 { return None; }
+            let entries = self.manager.read_entries(&s.path).ok()?;
+            (entries.len() >= NEAR_PREFIX).then(|| entries[..NEAR_PREFIX].iter().map(|e| e.text.as_str()).collect::<Vec<_>>().join("\u{0}"))
+        }).into_iter()
+            .filter(|g| g.iter().any(|i| hashes.get(&self.sessions[*i].path) != hashes.get(&self.sessions[g[0]].path)))
+            .collect();
+
+        let mut items = Vec::new();
+        let mut victims = Vec::new();
+        for (n, g) in exact.iter().enumerate() {
+            items.push(ConfirmRow::from(format!("[dup {}] keep: {}", n + 1, describe(g[0]))));
+            for &i in &g[1..] {
+                items.push(self.delete_row(i, format!("[dup {}] delete: {}", n + 1, describe(i))));
+                victims.push(i);
+            }
+        }
+        for (n, g) in near.iter().enumerate() {
+            for &i in g { items.push(format!("[near-duplicate {}, not deleted] {}", n + 1, describe(i)).into()); }
+        }
+        self.to_delete = items;
+        if victims.is_empty() {
+            self.msg = if near.is_empty() { "No duplicate sessions.".into() }
+                else { format!("No identical sessions; {} near-duplicate groups:\n{}", near.len(), self.to_delete.iter().map(|r| r.label.as_str()).collect::<Vec<_>>().join("\n")) };
+            self.mode = Mode::Message;
+            return;
+        }
+        self.msg = format!("Delete {} duplicates ({} groups), keeping the newest of each?", victims.len(), exact.len());
+        self.selected = victims.into_iter().collect();
+        self.action = Action::Delete;
+        self.mode = Mode::Confirm;
+    }
+
+    /// First press remembers the highlighted session; a second press on another opens the compare view.
+    fn compare_mark(&mut self) {
+        let Some(idx) = self.current() else { return };
+        let id = self.sessions[idx].id.clone();
+        match self.compare_base.take() {
+            None => { self.compare_base = Some(id); }
+            Some(base) if base == id => {}
+            Some(base) => {
+                let Some(bidx) = self.sessions.iter().position(|s| s.id == base) else { return };
+                let load = |i: usize| {
+                    let s = &self.sessions[i];
+                    (i, Transcript::new(s.id.clone(), self.manager.read_entries(&s.path).unwrap_or_default()))
+                };
+                self.compare = Some(Compare { panes: [load(bidx), load(idx)], offsets: [0, 0], focus: 0, sync: true });
+                self.mode = Mode::Compare;
+            }
+        }
+    }
+
+    /// Hands a finished delete to `notify_command` when `notify_in_tui` is set.
+    fn notify(&self, action: &'static str, deleted: usize, freed: u64, report: &str) {
+        if !self.config.notify_in_tui.unwrap_or(false) { return; }
+        notify::send(&self.config, &notify::Outcome { action, deleted, freed, report: report.to_string() });
+    }
+
+    /// Deletes session `idx`. When another listed transcript has the same id, as in a copied
+    /// project directory, only this file goes and the id's related files and history stay.
+    fn delete_one(&self, idx: usize) -> io::Result<Vec<String>> {
+        let s = &self.sessions[idx];
+        let shared = self.sessions.iter().enumerate().any(|(i, o)| i != idx && o.id == s.id && o.path.exists());
+        if shared { self.manager.delete_copy(s) } else { self.manager.delete_session(s) }
+    }
+
+    fn perform_action(&mut self) -> io::Result<()> {
+        self.selected_before_confirm = None;
+        match std::mem::replace(&mut self.action, Action::Delete) {
+            Action::Delete => {
+                let mut report = String::from("Deleted:\n");
+                let (mut count, mut freed) = (0, 0);
+                for idx in self.selection() {
+                    if let Some(s) = self.sessions.get(idx) {
+                        if s.protected { report.push_str(&format!("- {}: protected, skipped\n", s.id)); continue; }
+                        match self.delete_one(idx) {
+                            Ok(files) => { count += 1; freed += s.size; for f in files { report.push_str(&format!("- {}\n", f)); } }
+                            Err(e) => report.push_str(&format!("- {}: failed: {}\n", s.id, e)),
+                        }
+                    }
+                }
+                self.notify("delete", count, freed, &report);
+                self.msg = report;
+                self.selected.clear();
+            }
+            Action::PruneOrphans => {
+                self.orphan_scan = None;
+                let results = self.manager.remove_orphans(&self.orphans);
+                let count = results.iter().filter(|r| r.is_ok()).count();
+                self.msg = format!("Pruned {} orphans.\n{}", count, orphan_outcomes(&self.orphans, &results));
+            }
+            Action::PruneBoth => {
+                let (mut count, mut freed) = (0, 0);
+                let mut lines = String::new();
+                for idx in self.selection() {
+                     if let Some(s) = self.sessions.get(idx) {
+                         if s.protected { lines.push_str(&format!("- {}: protected, skipped\n", s.id)); continue; }
+                         match self.delete_one(idx) {
+                             Ok(files) => { count += 1; freed += s.size; lines.push_str(&format!("- {}: {} files\n", s.id, files.len())); }
+                             Err(e) => lines.push_str(&format!("- {}: failed: {}\n", s.id, e)),
+                         }
+                     }
+                }
+                self.orphan_scan = None;
+                let results = self.manager.remove_orphans(&self.orphans);
+                let orph = results.iter().filter(|r| r.is_ok()).count();
+                self.msg = format!("Deleted {} sessions, {} orphans.\n{}{}", count, orph, lines, orphan_outcomes(&self.orphans, &results));
+                self.notify("prune", count, freed, &self.msg.clone());
+                self.selected.clear();
+            }
+            Action::Slim => {
+                let mut report = format!("Slimmed (backups in {}):\n", self.manager.backup_dir().display());
+                for idx in self.selection() {
+                    if let Some(s) = self.sessions.get(idx) {
+                        match self.manager.slim_session(s, self.slim_threshold(), false) {
+                            Ok((before, after)) => report.push_str(&format!("- {}: {} -> {}\n", s.id, format_size(before), format_size(after))),
+                            Err(e) => report.push_str(&format!("- {}: failed: {}\n", s.id, e)),
+                        }
+                    }
+                }
+                self.msg = report;
+                self.selected.clear();
+            }
+            Action::Repair => {
+                let mut report = format!("Repaired (backups in {}):\n", self.manager.backup_dir().display());
+                for &idx in &self.repairable {
+                    if let Some(s) = self.sessions.get(idx) {
+                        match self.manager.repair_session(s) {
+                            Ok(true) => report.push_str(&format!("- {}: dropped truncated last line\n", s.id)),
+                            Ok(false) => report.push_str(&format!("- {}: nothing to repair\n", s.id)),
+                            Err(e) => report.push_str(&format!("- {}: failed: {}\n", s.id, e)),
+                        }
+                    }
+                }
+                self.repairable.clear();
+                self.msg = report;
+            }
+            Action::Redact(id, records) => {
+                self.transcript = None;
+                self.msg = match self.sessions.iter().find(|s| s.id == id) {
+                    Some(s) => match self.manager.redact_session(s, &records, false) {
+                        Ok((before, after)) => format!("Redacted {} messages in {}: {} -> {} (backup in {}).",
+                            records.len(), s.display_name(), format_size(before), format_size(after), self.manager.backup_dir().display()),
+                        Err(e) => format!("Redact failed for {}: {}", s.id, e),
+                    },
+                    None => format!("Session {} is gone.", id),
+                };
+            }
+            Action::DropRelated(id, paths) => {
+                self.msg = match self.sessions.iter().find(|s| s.id == id) {
+                    Some(s) => {
+                        let before = s.footprint();
+                        let results = self.manager.remove_related_entries(s, &paths);
+                        let names: Vec<String> = paths.iter().map(|p| p.strip_prefix(self.manager.root()).unwrap_or(p).display().to_string()).collect();
+                        format!("Deleted {} of {} related files of {}; footprint {} -> {}.\n{}", results.iter().filter(|r| r.is_ok()).count(), paths.len(),
+                            s.display_name(), format_size(before), format_size(s.footprint()), orphan_outcomes(&names, &results))
+                    }
+                    None => format!("Session {} is gone.", id),
+                };
+            }
+            Action::Compact => {
+                let live: HashSet<&str> = self.sessions.iter().map(|s| s.id.as_str()).collect();
+                let before = self.config.last_selected.len();
+                self.config.last_selected.retain(|_, id| live.contains(id.as_str()));
+                let highlights = before - self.config.last_selected.len();
+                self.config.save()?;
+                self.msg = match self.manager.compact_artifacts() {
+                    Ok(dropped) => {
+                        let mut report = String::from("Compacted:\n");
+                        for (name, n) in dropped { report.push_str(&format!("- {}: {} stale entries dropped\n", name, n)); }
+                        report.push_str(&format!("- last highlighted per project (config): {} stale entries dropped\n- audit log rotated\n", highlights));
+                        report
+                    }
+                    Err(e) => format!("Compaction failed: {}", e),
+                };
+            }
+            Action::EmptyTrash(paths) => {
+                let results = self.manager.empty_trash(&paths);
+                let count = results.iter().filter(|r| r.is_ok()).count();
+                self.msg = format!("Emptied {} trash files.\n{}", count, orphan_outcomes(&paths, &results));
+            }
+            Action::DropHistory(keys) => {
+                self.msg = format!("Removed {} history entries.", self.manager.drop_history_entries(&keys));
+            }
+            Action::Restore(path) => {
+                self.msg = restore_report(self.manager.restore_archive(&path, true));
+            }
+        }
+        self.remember();
+        self.refresh()?;
+        self.mode = Mode::Message;
+        Ok(())
+    }
+
+    fn start_export(&mut self) -> io::Result<()> {
+        let dir = std::env::current_dir()?.join("exports");
+        std::fs::create_dir_all(&dir)?;
+        let items: Vec<(String, Session)> = self.targets().into_iter()
+            .map(|i| (self.sessions[i].display_name(), self.sessions[i].clone())).collect();
+        let done = Arc::new(Mutex::new(Vec::new()));
+        if items.len() > 1 { self.export_index = Some((dir.clone(), done.clone())); }
+        let manager = self.manager.clone();
+        self.run_task(Task::spawn("Export to ./exports/", "Exported", items, move |s: Session| {
+            let file = export::export_session(&manager, &s, &dir)?;
+            let size = file.size;
+            done.lock().unwrap().push(file);
+            Ok(size)
+        }));
+        Ok(())
+    }
+
+    fn transcript_chunk(&self) -> u64 {
+        self.config.transcript_chunk_bytes.unwrap_or(TRANSCRIPT_CHUNK_BYTES)
+    }
+
+    /// Opens the full viewer on a session, scrolled to the end or to its last assistant reply.
+    fn open_transcript(&mut self, idx: usize, at_last_reply: bool) {
+        let Some(s) = self.sessions.get(idx) else { return };
+        let mut t = match self.manager.read_tail(&s.path, self.transcript_chunk()) {
+            Ok(chunk) if chunk.start > 0 => {
+                let window = LogWindow { path: s.path.clone(), start: chunk.start, first_record: chunk.first_record, size: s.size };
+                let mut t = Transcript::new(s.id.clone(), chunk.entries);
+                t.window = Some(window);
+                t
+            }
+            Ok(chunk) => Transcript::new(s.id.clone(), chunk.entries),
+            Err(_) => Transcript::new(s.id.clone(), Vec::new()),
+        };
+        t.bookmarks = self.bookmarks.get(&s.id).cloned().unwrap_or_default();
+        let reply = t.entries.iter().rposition(|e| e.role == "assistant");
+        self.offset = match reply.filter(|_| at_last_reply) {
+            Some(e) => t.line_entry.iter().position(|&l| l == e).unwrap_or(0),
+            None => usize::MAX, // Will be clamped in render
+        };
+        self.transcript = Some(t);
+        self.mode = Mode::Expanded;
+    }
+
+    /// Bookmarks the message at the top of the expanded view under `letter`.
+    fn set_bookmark(&mut self, letter: char) {
+        let Some(t) = self.transcript.as_mut() else { return };
+        let Some(e) = t.entry_at(self.offset) else { return };
+        t.bookmarks.insert(letter, t.entries[e].record);
+        self.bookmarks.insert(t.session_id.clone(), t.bookmarks.clone());
+        if self.config.persist_bookmarks.unwrap_or(false) {
+            let mut sidecar = self.manager.load_sidecar();
+            sidecar.bookmarks.insert(t.session_id.clone(), t.bookmarks.clone());
+            self.manager.save_sidecar(&sidecar).ok();
+        }
+    }
+
+    /// Asks to redact the marked messages of the open transcript, listing each with a preview.
+    fn confirm_redact(&mut self) {
+        let Some(t) = &self.transcript else { return };
+        let refusal = if self.manager.read_only { Some("read-only") } else if t.marks.is_empty() { Some("mark messages with v first") } else { None };
+        if let Some(r) = refusal {
+            if let Some(t) = self.transcript.as_mut() { t.note = Some(r.into()); }
+            return;
+        }
+        let Some(s) = self.sessions.iter().find(|s| s.id == t.session_id) else { return };
+        let mut marks = t.marks.clone();
+        marks.sort_unstable();
+        let records: HashSet<usize> = marks.iter().map(|&e| t.entries[e].record).collect();
+        self.to_delete = marks.iter().map(|&e| {
+            let entry = &t.entries[e];
+            let preview = text::ellipsize(&entry.text.split_whitespace().collect::<Vec<_>>().join(" "), 60);
+            format!("[{}] line {}: {}", entry.role.to_uppercase(), entry.record + 1, preview).into()
+        }).collect();
+        let saved = match self.manager.redact_session(s, &records, true) {
+            Ok((before, after)) => format!(", saving ~{}", format_size(before.saturating_sub(after))),
+            Err(_) => String::new(),
+        };
+        self.msg = format!("Redact {} messages in {}{}? A backup is kept.", records.len(), s.display_name(), saved);
+        self.action = Action::Redact(s.id.clone(), records);
+        self.mode = Mode::Confirm;
+    }
+
+    /// Switches the expanded view between scrolling by line and a cursor on whole messages,
+    /// starting at the message at the top of the view.
+    fn toggle_message_mode(&mut self) {
+        let Some(t) = self.transcript.as_mut() else { return };
+        t.focus = match t.focus {
+            Some(_) => None,
+            None => t.entry_at(self.offset),
+        };
+        t.note = None;
+    }
+
+    fn move_focus(&mut self, delta: isize) {
+        if delta < 0 && self.transcript.as_ref().is_some_and(|t| t.focus == Some(0)) { self.load_earlier(); }
+        let Some(t) = self.transcript.as_mut() else { return };
+        t.step_focus(delta);
+        if let Some(line) = t.focus.and_then(|e| t.first_line(e)) { self.offset = line; }
+    }
+
+    /// Copies the focused message, or only its fenced code blocks, to the clipboard.
+    fn copy_focused(&mut self, code_only: bool) -> io::Result<()> {
+        let Some(t) = self.transcript.as_mut() else { return Ok(()) };
+        let Some(e) = t.focus.and_then(|i| t.entries.get(i)) else { return Ok(()) };
+        if !code_only {
+            osc52_copy(&e.text)?;
+            t.note = Some(format!("copied {} chars", e.text.chars().count()));
+            return Ok(());
+        }
+        let blocks = e.code_blocks();
+        t.note = Some(match blocks.len() {
+            0 => "no code blocks in this message".into(),
+            n => { osc52_copy(&blocks.join("\n\n"))?; format!("copied {} code block{}", n, if n == 1 { "" } else { "s" }) }
+        });
+        Ok(())
+    }
+
+    fn jump_to_bookmark(&mut self, letter: char) {
+        let Some(&record) = self.transcript.as_ref().and_then(|t| t.bookmarks.get(&letter)) else { return };
+        while self.transcript.as_ref().and_then(|t| t.window.as_ref()).is_some_and(|w| w.first_record > record) {
+            if !self.load_earlier() { break; }
+        }
+        if let Some(t) = &self.transcript { self.offset = t.bookmark_line(record); }
+    }
+
+    /// Reads the chunk before the loaded part of a large log, keeping the view where it was.
+    /// Returns false when there is nothing earlier or it could not be read.
+    fn load_earlier(&mut self) -> bool {
+        let chunk_bytes = self.transcript_chunk();
+        let Some(t) = self.transcript.as_mut() else { return false };
+        let Some(w) = t.window.as_ref().filter(|w| w.start > 0) else { return false };
+        match self.manager.read_before(&w.path, w.start, w.first_record, chunk_bytes) {
+            Ok(chunk) => {
+                self.offset = self.offset.saturating_add(t.prepend(chunk));
+                true
+            }
+            Err(e) => { t.note = Some(format!("could not read earlier messages: {}", e)); false }
+        }
+    }
+
+    /// Scrolls the expanded view up, reading the earlier chunk once the top is reached.
+    fn scroll_up(&mut self, lines: usize) {
+        if self.offset < lines && self.offset != usize::MAX { self.load_earlier(); }
+        self.offset = self.offset.saturating_sub(lines);
+    }
+
+    /// Opens the highlighted session's debug log at its end; `full` reads all of it rather than
+    /// the last `DEBUG_TAIL_BYTES`.
+    fn open_debug_log(&mut self, full: bool) {
+        let id = match &self.transcript {
+            Some(t) if t.debug.is_some() => Some(t.session_id.clone()),
+            _ => self.current().map(|i| self.sessions[i].id.clone()),
         };
-        app.reload()?;
-        Ok(app)
+        let Some(id) = id else { return };
+        match self.manager.read_debug_log(&id, (!full).then_some(DEBUG_TAIL_BYTES)) {
+            Ok((text, size)) => {
+                self.transcript = Some(Transcript::debug_log(id, text, size));
+                self.offset = usize::MAX;
+                self.mode = Mode::Expanded;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => { self.msg = format!("No debug log for {}.", id); self.mode = Mode::Message; }
+            Err(e) => { self.msg = format!("Could not read the debug log: {}", e); self.mode = Mode::Message; }
+        }
     }
 
-    fn reload(&mut self) -> io::Result<()> {
-        self.sessions = self.manager.load_sessions()?;
-        self.apply_sort();
-        self.apply_filter();
-        if !self.filtered.is_empty() { self.state.select(Some(0)); }
-        else { self.state.select(None); }
+    fn start_import(&mut self) -> io::Result<()> {
+        let source = sessions::expand_home(self.input.trim());
+        self.import = Some(Import { queue: archive::archives_in(&source).into(), ..Default::default() });
+        self.step_import()
+    }
+
+    /// Imports queued archives until one clashes with a local session, then asks about it.
+    /// Once the queue is empty, reloads and reports.
+    fn step_import(&mut self) -> io::Result<()> {
+        let Some(imp) = self.import.as_mut() else { return Ok(()) };
+        while let Some(path) = imp.queue.pop_front() {
+            match self.manager.import_archive(&path, false) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    self.msg = format!("{}: {}.", path.file_name().unwrap_or_default().to_string_lossy(), e);
+                    imp.conflict = Some(path);
+                    self.mode = Mode::ImportConflict;
+                    return Ok(());
+                }
+                res => imp.record(&path, res),
+            }
+        }
+        self.msg = self.import.take().map(|imp| imp.summary()).unwrap_or_default();
+        self.remember();
+        self.refresh()?;
+        self.mode = Mode::Message;
         Ok(())
     }
 
-    fn apply_sort(&mut self) {
-        match self.sort {
-            SortBy::Date => self.sessions.sort_by(|a, b| b.modified.cmp(&a.modified)),
-            SortBy::Size => self.sessions.sort_by(|a, b| b.size.cmp(&a.size)),
-            SortBy::Messages => self.sessions.sort_by(|a, b| b.message_count.cmp(&a.message_count)),
+    /// Answers the pending conflict: `Some(true)` imports under a new id, `Some(false)` skips
+    /// it, `None` skips it and everything still queued.
+    fn resolve_conflict(&mut self, rename: Option<bool>) -> io::Result<()> {
+        let Some(imp) = self.import.as_mut() else { return Ok(()) };
+        let Some(path) = imp.conflict.take() else { return Ok(()) };
+        match rename {
+            Some(true) => imp.record(&path, self.manager.import_archive(&path, true)),
+            Some(false) => imp.skipped += 1,
+            None => imp.skipped += 1 + imp.queue.drain(..).count(),
         }
-        self.config.sort_by = Some(self.sort);
+        self.step_import()
+    }
+
+    fn open_stats(&mut self) {
+        let total: u64 = self.sessions.iter().map(|s| s.size).sum();
+        let empty = self.sessions.iter().filter(|s| s.message_count == 0).count();
+        let tokens: u64 = self.sessions.iter().map(|s| s.tokens).sum();
+        let cost: f64 = self.sessions.iter().map(|s| s.usage.cost).sum();
+        let mut lines = vec![
+            format!("Sessions: {} ({} hidden, {} empty)", self.sessions.len(), self.hidden_count, empty),
+            format!("Total size: {}", format_size(total)),
+            format!("Tokens: {} (~{} at API prices)", sessions::format_tokens(tokens), sessions::format_cost(cost)),
+            format!("Projects: {}", self.projects.len()),
+            String::new(),
+            "Largest projects:".to_string(),
+        ];
+        lines.extend(self.projects.iter().take(STATS_PROJECTS)
+            .map(|p| format!("  {:>8}  {:>4} sessions  {}", format_size(p.size), p.count, p.name)));
+        let mut models: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for (m, n) in self.sessions.iter().flat_map(|s| &s.models) {
+            let e = models.entry(m).or_default();
+            e.0 += n;
+            e.1 += 1;
+        }
+        if !models.is_empty() {
+            let mut by_use: Vec<_> = models.into_iter().collect();
+            by_use.sort_by_key(|(_, (n, _))| std::cmp::Reverse(*n));
+            lines.push(String::new());
+            lines.push("Models:".into());
+            lines.extend(by_use.into_iter().map(|(m, (n, s))| format!("  {:>6} msgs  {:>4} sessions  {}", n, s, m)));
+        }
+        let cache = self.manager.cache_size();
+        let share = (cache * 100).checked_div(total).unwrap_or(0);
+        if let Some(d) = &self.since_last {
+            lines.push(String::new());
+            lines.extend(d.lines());
+        }
+        lines.push(String::new());
+        lines.push(format!("Metadata cache: {} ({}% of session data, detail: {:?})", format_size(cache), share, self.manager.cache_detail));
+        if share >= CACHE_WARN_PERCENT && self.manager.cache_detail != sessions::CacheDetail::Minimal {
+            lines.push("  The cache is a sizeable share of what it describes; \"cache_detail\": \"minimal\" shrinks it.".into());
+        }
+        let mut slowest: Vec<(&String, &u64)> = self.scan_times.iter().collect();
+        slowest.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        if slowest.first().is_some_and(|(_, ms)| **ms > 0) {
+            lines.push(String::new());
+            lines.push("Slowest project directories to load:".into());
+            lines.extend(slowest.into_iter().take(STATS_SLOW_PROJECTS).map(|(p, ms)| {
+                format!("  {:>6}ms  {}{}", ms, p, if self.deferred.contains(p) { " (not scanned)" } else { "" })
+            }));
+        }
+        let trash = self.manager.trash();
+        let oldest = trash.first().map_or(String::new(), |e| format!(", oldest {}", sessions::format_age(e.modified, self.now())));
+        let archives = audit::disk_size(&self.manager.archive_dir()).saturating_sub(audit::disk_size(&self.manager.backup_dir()));
+        let exports = std::env::current_dir().map_or(0, |d| audit::disk_size(&d.join("exports")));
+        lines.push(String::new());
+        lines.push("Tool output:".into());
+        lines.push(format!("  {:>8}  archives ({})", format_size(archives), self.manager.archive_dir().display()));
+        lines.push(format!("  {:>8}  trash: {} backups{}", format_size(trash.iter().map(|e| e.size).sum()), trash.len(), oldest));
+        lines.push(format!("  {:>8}  ./exports", format_size(exports)));
+        lines.push(String::new());
+        lines.push("Self maintenance (c compacts):".into());
+        for a in self.artifacts() {
+            let stale = a.stale.map_or(String::new(), |n| format!(", {} stale", n));
+            lines.push(format!("  {:>8}  {}{} ({})", format_size(a.size), a.name, stale, a.path.display()));
+        }
+        self.stats = lines;
+        self.mode = Mode::Stats;
+    }
+
+    /// The manager's artifacts plus the two this file keeps: the startup snapshot and the
+    /// remembered highlight per project in the config.
+    fn artifacts(&self) -> Vec<sessions::Artifact> {
+        let mut out = self.manager.artifacts();
+        let snapshot = self.manager.state_file(SNAPSHOT_FILE);
+        out.push(sessions::Artifact { name: "size snapshot", size: fs::metadata(&snapshot).map_or(0, |m| m.len()), path: snapshot, stale: None });
+        let stale = self.config.last_selected.values().filter(|id| !self.sessions.iter().any(|s| s.id == **id)).count();
+        let config = self.config.file().map(Path::to_path_buf).unwrap_or_default();
+        out.push(sessions::Artifact { name: "last highlighted per project (config)", size: fs::metadata(&config).map_or(0, |m| m.len()), path: config, stale: Some(stale) });
+        out
+    }
+
+    /// Asks before compacting the tool's own files from the stats screen.
+    fn confirm_compact(&mut self) {
+        self.to_delete = self.artifacts().into_iter().filter_map(|a| a.stale.filter(|&n| n > 0).map(|n| format!("{}: {} stale entries", a.name, n).into()))
+            .chain(std::iter::once(format!("audit log: rotate ({})", format_size(self.manager.audit.size())).into()))
+            .collect();
+        self.msg = "Drop stale entries from the tool's own files and rotate the audit log?".into();
+        self.action = Action::Compact;
+        self.mode = Mode::Confirm;
+    }
+
+    /// "Now" for age strings, as of the last `tick`.
+    fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    /// Reads the clock again; called once per drawn frame so ages move while the TUI is idle.
+    fn tick(&mut self) {
+        self.now = self.clock.now();
+    }
+
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+        self.tick();
+    }
+
+    /// Opens the saved filter picker on the preset matching the current filter, if any.
+    fn open_saved_filters(&mut self) {
+        let pos = self.config.saved_filters.values().position(|q| *q == self.filter).unwrap_or(0);
+        self.filter_state.select(Some(pos));
+        self.mode = Mode::Filters;
+    }
+
+    fn picked_filter(&self) -> Option<(&String, &String)> {
+        self.config.saved_filters.iter().nth(self.filter_state.selected()?)
+    }
+
+    /// Enter in the picker: the highlighted preset becomes the filter.
+    fn apply_saved_filter(&mut self) {
+        let Some((_, query)) = self.picked_filter() else { return };
+        self.filter = query.clone();
+        self.apply_filter();
+        self.refresh_searches();
+        self.mode = Mode::Normal;
+    }
+
+    /// `a` in the picker: asks for a name to keep the current filter under.
+    fn start_save_filter(&mut self) {
+        if self.filter.trim().is_empty() {
+            self.status = Some("Nothing to save: the filter is empty".into());
+            return;
+        }
+        self.input.clear();
+        self.mode = Mode::SaveFilter;
+    }
+
+    /// Keeps the current filter under the typed name, replacing a preset of that name.
+    fn save_filter(&mut self) {
+        let name = self.input.trim().to_string();
+        if name.is_empty() { return; }
+        self.config.saved_filters.insert(name.clone(), self.filter.clone());
         self.config.save().ok();
+        self.filter_state.select(self.config.saved_filters.keys().position(|n| *n == name));
+        self.status = Some(format!("Saved filter {}: {}", name, self.filter));
+        self.mode = Mode::Filters;
     }
 
-    fn apply_filter(&mut self) {
-        let query = self.filter.to_lowercase();
-        self.filtered = self.sessions.iter().enumerate()
-            .filter(|(_, s)| query.is_empty() || 
-                s.display_name().to_lowercase().contains(&query) || 
-                s.id.to_lowercase().contains(&query) || 
-                s.project.to_lowercase().contains(&query))
-            .map(|(i, _)| i).collect();
-        self.config.filter_query = Some(self.filter.clone());
+    fn delete_saved_filter(&mut self) {
+        let Some((name, _)) = self.picked_filter() else { return };
+        let name = name.clone();
+        self.config.saved_filters.remove(&name);
         self.config.save().ok();
+        let last = self.config.saved_filters.len().checked_sub(1);
+        self.filter_state.select(self.filter_state.selected().zip(last).map(|(i, last)| i.min(last)));
     }
 
-    fn move_sel(&mut self, delta: isize) {
-        if self.filtered.is_empty() { return; }
-        let len = self.filtered.len();
-        let i = match self.state.selected() {
-            Some(i) => (i as isize + delta).rem_euclid(len as isize) as usize,
-            None => 0,
+    /// `projects` in picker order: largest first, or by name once toggled.
+    fn picker_order(&self) -> Vec<&ProjectStats> {
+        let mut order: Vec<&ProjectStats> = self.projects.iter().collect();
+        if self.projects_by_name { order.sort_by(|a, b| a.name.cmp(&b.name)); }
+        order
+    }
+
+    /// Opens the project picker on the highlighted session's project.
+    fn open_project_picker(&mut self) {
+        if self.projects.is_empty() && self.deferred.is_empty() { return; }
+        let current = self.current().map(|i| self.sessions[i].project.clone());
+        self.select_picked(current.as_deref());
+        self.mode = Mode::Projects;
+    }
+
+    fn select_picked(&mut self, name: Option<&str>) {
+        let pos = self.picker_order().iter().position(|p| Some(p.name.as_str()) == name).unwrap_or(0);
+        self.project_state.select(Some(pos));
+    }
+
+    /// The highlighted picker row's project; deferred projects come after the loaded ones.
+    fn picked_project(&self) -> Option<String> {
+        let i = self.project_state.selected()?;
+        let order = self.picker_order();
+        order.get(i).map(|p| p.name.clone()).or_else(|| self.deferred.get(i - order.len()).cloned())
+    }
+
+    /// Loads a deferred project on a worker thread; the list reloads with it once done.
+    fn scan_deferred(&mut self, project: String) {
+        let manager = self.manager.clone();
+        let wanted = project.clone();
+        self.scanning = Some(project.clone());
+        self.run_task(Task::spawn(format!("Scanning {}", project), "Scanned", vec![(project.clone(), ())], move |()| {
+            manager.load_projects(|p| p == wanted).map(|(sessions, _)| sessions.iter().map(|s| s.size).sum())
+        }));
+    }
+
+    /// Switches the picker between size and name order, keeping the highlighted project.
+    fn toggle_picker_order(&mut self) {
+        let picked = self.picked_project();
+        self.projects_by_name = !self.projects_by_name;
+        self.select_picked(picked.as_deref());
+    }
+
+    /// Moves the highlight to the nearest session of the same project in list order, `dir` 1
+    /// going down and -1 up, wrapping around the list. Returns false when the project has no
+    /// other visible session.
+    fn jump_in_project(&mut self, dir: isize) -> bool {
+        let Some(cur) = self.cursor() else { return false };
+        let project = self.sessions[self.filtered[cur]].project.clone();
+        let n = self.filtered.len() as isize;
+        let next = (1..n).map(|k| (cur as isize + dir * k).rem_euclid(n) as usize)
+            .find(|&p| self.sessions[self.filtered[p]].project == project);
+        let Some(next) = next else {
+            self.status = Some(format!("No other sessions in {}", project));
+            return false;
         };
-        self.state.select(Some(i));
-        self.offset = 0;
+        if (dir > 0) == (next < cur) {
+            self.status = Some(format!("Wrapped to the {} session of {}", if dir > 0 { "first" } else { "last" }, project));
+        }
+        self.select_pos(Some(next));
+        true
     }
 
-    fn toggle(&mut self) {
-        if let Some(i) = self.state.selected() {
-            let idx = self.filtered[i];
-            if let Some(pos) = self.selected.iter().position(|&x| x == idx) {
-                self.selected.remove(pos);
-            } else {
-                self.selected.push(idx);
-            }
+    /// Index of the session open in the viewer.
+    fn viewed(&self) -> Option<usize> {
+        let t = self.transcript.as_ref()?;
+        self.sessions.iter().position(|s| s.id == t.session_id)
+    }
+
+    /// Selects or deselects the viewed session.
+    fn toggle_viewed(&mut self) {
+        let Some(idx) = self.viewed() else { return };
+        if !self.selected.remove(&idx) { self.selected.insert(idx); }
+    }
+
+    /// Queues the viewed session for deletion, or takes it off the queue again.
+    fn queue_viewed(&mut self) {
+        let Some(t) = self.transcript.as_mut() else { return };
+        match self.queued.iter().position(|id| *id == t.session_id) {
+            Some(p) => { self.queued.remove(p); t.note = Some("Unqueued".into()); }
+            None => { self.queued.push(t.session_id.clone()); t.note = Some("Queued for deletion".into()); }
         }
     }
 
-    fn perform_action(&mut self) -> io::Result<()> {
-        match self.action {
-            Action::Delete => {
-                let mut report = String::from("Deleted:\n");
-                for &idx in &self.selected {
-                    if let Some(s) = self.sessions.get(idx) {
-                        for f in self.manager.delete_session(s)? {
-                            report.push_str(&format!("- {}\n", f));
-                        }
-                    }
-                }
-                self.msg = report;
-                self.selected.clear();
-            }
-            Action::PruneOrphans => {
-                let mut count = 0;
-                for p in &self.orphans {
-                    let path = PathBuf::from(p);
-                    if path.is_dir() { std::fs::remove_dir_all(path).ok(); } 
-                    else { std::fs::remove_file(path).ok(); }
-                    count += 1;
-                }
-                self.msg = format!("Pruned {} orphans.", count);
+    /// Closes the viewer; sessions queued there are added to the selection and the delete
+    /// confirm opens on it.
+    fn close_transcript(&mut self) {
+        self.transcript = None;
+        self.mode = Mode::Normal;
+        let queued: Vec<usize> = std::mem::take(&mut self.queued).iter()
+            .filter_map(|id| self.sessions.iter().position(|s| s.id == *id))
+            .collect();
+        if queued.is_empty() { return; }
+        self.selected.extend(queued);
+        self.to_delete = self.selection().into_iter().map(|i| self.delete_row(i, self.sessions[i].display_name())).collect();
+        self.msg = format!("Delete {} sessions?", self.selected.len());
+        self.action = Action::Delete;
+        self.mode = Mode::Confirm;
+    }
+
+    /// Directory and id to resume the highlighted session with, or why it cannot be.
+    fn resume_target(&self) -> Result<(PathBuf, String), String> {
+        let s = self.current().map(|i| &self.sessions[i]).ok_or("No session selected.")?;
+        let cwd = s.cwd.as_deref().ok_or_else(|| format!("{} does not record the directory it ran in.", s.id))?;
+        if !Path::new(cwd).is_dir() { return Err(format!("The session's directory {} no longer exists.", cwd)); }
+        Ok((PathBuf::from(cwd), s.id.clone()))
+    }
+
+    /// Starts the triage screen on the listed sessions, oldest or largest first.
+    fn start_triage(&mut self) {
+        let mut order = self.filtered.clone();
+        match self.config.triage_order.unwrap_or_default() {
+            TriageOrder::Oldest => order.sort_by_key(|&i| self.sessions[i].modified),
+            TriageOrder::Largest => order.sort_by_key(|&i| std::cmp::Reverse(self.sessions[i].size)),
+        }
+        if order.is_empty() { self.status = Some("Nothing to triage".into()); return; }
+        let ids = order.iter().map(|&i| self.sessions[i].id.clone()).collect();
+        self.triage = Some(Triage { ids, pos: 0, verdicts: HashMap::new(), exchange: Vec::new(), offset: 0 });
+        self.load_exchange();
+        self.mode = Mode::Triage;
+    }
+
+    /// Index of the session the triage screen shows.
+    fn triaged(&self) -> Option<usize> {
+        let t = self.triage.as_ref()?;
+        let id = t.ids.get(t.pos)?;
+        self.sessions.iter().position(|s| s.id == *id)
+    }
+
+    fn load_exchange(&mut self) {
+        let entries = self.triaged().and_then(|i| self.manager.read_entries(&self.sessions[i].path).ok()).unwrap_or_default();
+        let from = entries.iter().rposition(|e| e.role == "user").unwrap_or(0);
+        if let Some(t) = self.triage.as_mut() {
+            t.exchange = entries.into_iter().skip(from).collect();
+            t.offset = 0;
+        }
+    }
+
+    /// Records `v` for the current session and moves on, finishing after the last one.
+    fn decide(&mut self, v: Verdict) {
+        let Some(t) = self.triage.as_mut() else { return };
+        if let Some(id) = t.ids.get(t.pos) { t.verdicts.insert(id.clone(), v); }
+        t.pos += 1;
+        if t.pos >= t.ids.len() { self.finish_triage(); } else { self.load_exchange(); }
+    }
+
+    fn triage_back(&mut self) {
+        let Some(t) = self.triage.as_mut() else { return };
+        if t.pos == 0 { return; }
+        t.pos -= 1;
+        self.load_exchange();
+    }
+
+    /// Archives the sessions triaged for archiving, then asks once to delete them together with
+    /// the ones triaged for deletion.
+    fn finish_triage(&mut self) {
+        let Some(t) = self.triage.take() else { return };
+        self.mode = Mode::Normal;
+        let find = |v: Verdict| -> Vec<usize> {
+            t.verdicts.iter().filter(|&(_, &x)| x == v)
+                .filter_map(|(id, _)| self.sessions.iter().position(|s| s.id == *id)).collect()
+        };
+        let (deletes, archives) = (find(Verdict::Delete), find(Verdict::Archive));
+        let mut errors = 0;
+        let mut archived = Vec::new();
+        for idx in archives {
+            match self.manager.archive_session(&self.sessions[idx]) {
+                Ok(_) => archived.push(idx),
+                Err(e) => { log::warn!("{}: archive failed: {}", self.sessions[idx].id, e); errors += 1; }
             }
-            Action::PruneBoth => {
-                let mut count = 0;
-                for idx in &self.selected {
-                     if let Some(s) = self.sessions.get(*idx) {
-                         self.manager.delete_session(s)?;
-                         count += 1;
-                     }
-                }
-                let mut orph = 0;
-                for p in &self.orphans {
-                    let path = PathBuf::from(p);
-                    if path.is_dir() { std::fs::remove_dir_all(path).ok(); } 
-                    else { std::fs::remove_file(path).ok(); }
-                    orph += 1;
+        }
+        if deletes.is_empty() && archived.is_empty() {
+            self.status = Some(format!("Triage done: {}{}", t.tally(), if errors > 0 { format!(", {} archives failed", errors) } else { String::new() }));
+            return;
+        }
+        self.selected = deletes.iter().chain(&archived).copied().collect();
+        self.to_delete = self.selection().into_iter().map(|i| self.delete_row(i, self.sessions[i].display_name())).collect();
+        self.msg = format!("Delete {} sessions", self.selected.len());
+        if !archived.is_empty() { self.msg.push_str(&format!(" ({} archived to {} first)", archived.len(), self.manager.archive_dir().display())); }
+        self.msg.push('?');
+        if errors > 0 { self.msg.push_str(&format!(" {} archives failed and are kept.", errors)); }
+        self.action = Action::Delete;
+        self.mode = Mode::Confirm;
+    }
+
+    /// Opens the transcript of the session `dir` rows away in the list, across projects.
+    fn open_adjacent(&mut self, dir: isize) {
+        let Some(idx) = self.viewed() else { return };
+        let Some(pos) = self.filtered.iter().position(|&i| i == idx) else { return };
+        let Some(&next) = pos.checked_add_signed(dir).and_then(|p| self.filtered.get(p)) else {
+            if let Some(t) = self.transcript.as_mut() { t.note = Some(format!("No {} session", if dir > 0 { "next" } else { "previous" })); }
+            return;
+        };
+        self.select_pos(Some((pos as isize + dir) as usize));
+        self.open_transcript(next, false);
+    }
+
+    /// `jump_in_project` from the open transcript, opening the neighbour's transcript instead.
+    fn open_in_project(&mut self, dir: isize) {
+        let Some(id) = self.transcript.as_ref().map(|t| t.session_id.clone()) else { return };
+        if let Some(pos) = self.filtered.iter().position(|&i| self.sessions[i].id == id) { self.select_pos(Some(pos)); }
+        let moved = self.jump_in_project(dir);
+        let note = self.status.take();
+        if let Some(idx) = self.current().filter(|_| moved) { self.open_transcript(idx, false); }
+        if let Some(t) = self.transcript.as_mut() { t.note = note; }
+    }
+
+    /// Opens the `rank`-th most recently modified session (0 = newest) at its bottom, whatever
+    /// the sort. A filter hiding it is cleared, with a note saying so.
+    fn open_recent(&mut self, rank: usize) {
+        let mut recent: Vec<usize> = (0..self.sessions.len()).filter(|&i| self.show_hidden || !self.sessions[i].hidden).collect();
+        recent.sort_by_key(|&i| std::cmp::Reverse(self.sessions[i].modified));
+        let Some(&idx) = recent.get(rank) else {
+            self.status = Some(format!("There are only {} sessions", recent.len()));
+            return;
+        };
+        let mut note = None;
+        if !self.filtered.contains(&idx) {
+            note = Some(format!("Cleared filter \"{}\" to show it", self.filter));
+            self.filter.clear();
+            self.apply_filter();
+            self.refresh_searches();
+        }
+        if let Some(pos) = self.filtered.iter().position(|&i| i == idx) { self.select_pos(Some(pos)); }
+        self.open_transcript(idx, false);
+        if let Some(t) = self.transcript.as_mut() { t.note = note; }
+    }
+
+    /// Reads the summary of the highlighted session when the cache left it out.
+    fn load_preview_summary(&mut self) {
+        let Some(idx) = self.current() else { return };
+        if self.sessions[idx].summary.is_some() { return; }
+        let summary = self.manager.summary_of(&self.sessions[idx].path);
+        self.sessions[idx].summary = Some(summary);
+    }
+
+    /// Leaves the startup lock popup: `take_over` claims a stale lock and reloads with writes
+    /// enabled, otherwise the session stays read-only.
+    fn resolve_lock(&mut self, take_over: bool) {
+        self.mode = Mode::Normal;
+        if take_over {
+            match lock::take_over(&sessions::config_dir().join("instance.lock")) {
+                Ok(l) => {
+                    self.instance = Some(l);
+                    self.manager.read_only = false;
+                    self.lock_holder = None;
+                    if let Err(e) = self.refresh() { self.msg = format!("Reload failed: {}", e); }
                 }
-                self.msg = format!("Deleted {} sessions, {} orphans.", count, orph);
-                self.selected.clear();
+                Err(e) => self.msg = format!("Could not take over the lock, staying read-only: {}", e),
             }
         }
-        self.reload()?;
+        if !self.msg.is_empty() { self.mode = Mode::Message; }
+    }
+
+    fn refuse_read_only(&mut self) {
+        self.msg = "Read-only: another instance holds the lock. Quit it and restart to make changes.".into();
         self.mode = Mode::Message;
-        Ok(())
     }
 
-    fn start_export(&mut self) -> io::Result<()> {
-        let mut target = Vec::new(); // Use simple vec to avoid ref issues
-        if !self.selected.is_empty() {
-             target = self.selected.clone();
-        } else if let Some(i) = self.state.selected() {
-             target.push(self.filtered[i]);
+    /// Keeps the report in `msg` for the `Ctrl+o` history.
+    fn remember(&mut self) {
+        self.history.push_front(ActionReport { at: chrono::Local::now(), text: self.msg.trim_end().to_string() });
+        self.history.truncate(HISTORY_REPORTS);
+    }
+
+    fn open_history(&mut self) {
+        if self.history.is_empty() { self.msg = "No reports yet.".into(); self.mode = Mode::Message; return; }
+        self.history_state.select(Some(0));
+        self.popup_scroll = 0;
+        self.mode = Mode::History;
+    }
+
+    fn scroll_popup(&mut self, code: KeyCode) {
+        let delta: i32 = match code {
+            KeyCode::Down|KeyCode::Char('j') => 1,
+            KeyCode::Up|KeyCode::Char('k') => -1,
+            KeyCode::PageDown => 10,
+            KeyCode::PageUp => -10,
+            _ => return,
+        };
+        self.popup_scroll = (self.popup_scroll as i32 + delta).clamp(0, u16::MAX as i32) as u16;
+    }
+
+    fn open_peek(&mut self) {
+        let Some(idx) = self.current() else { return };
+        let s = &self.sessions[idx];
+        let prompt = self.manager.first_prompt(&s.path).ok().flatten().unwrap_or_else(|| s.first_message.clone());
+        self.peek = Some(Peek { name: s.full_name().to_string(), prompt: prompt.lines().take(PEEK_LINES).map(String::from).collect() });
+        self.mode = Mode::Peek;
+    }
+
+    /// Shows just the highlighted session's last assistant reply.
+    fn open_answer(&mut self) {
+        let Some(idx) = self.current() else { return };
+        let s = &self.sessions[idx];
+        match self.manager.last_reply(&s.path, s.last_reply_at) {
+            Ok(Some(text)) => { self.answer = Some(Answer { session: idx, text, offset: 0 }); self.mode = Mode::Answer; }
+            Ok(None) => { self.msg = "No assistant reply in this session.".into(); self.mode = Mode::Message; }
+            Err(e) => { self.msg = format!("Could not read session: {}", e); self.mode = Mode::Message; }
         }
-        
-        let dir = std::env::current_dir()?.join("exports");
-        std::fs::create_dir_all(&dir)?;
-        let mut count = 0;
-        for idx in target {
-            if let Some(s) = self.sessions.get(idx) {
-                let content = self.manager.read_log(&s.path);
-                std::fs::write(dir.join(format!("{}.txt", s.id)), content)?;
-                count += 1;
+    }
+
+    fn run_task(&mut self, task: Task) {
+        self.task = Some(task);
+        self.mode = Mode::Progress;
+    }
+
+    /// Once the background task is done, replaces the progress popup with its summary.
+    fn poll_task(&mut self) {
+        if !self.task.as_ref().is_some_and(Task::is_finished) { return; }
+        let Some(task) = self.task.take() else { return };
+        let (title, verb) = (task.title.clone(), task.verb);
+        self.msg = format!("{}\n{}", title, task.join().summary(verb));
+        self.remember();
+        self.mode = Mode::Message;
+        if self.file_search.is_some() || self.content_search.is_some() { self.apply_filter(); }
+        if let Some(c) = &self.content_search {
+            if let Err(e) = self.manager.save_search_index(&mut c.index.lock().unwrap()) { log::warn!("search index: {}", e); }
+        }
+        if let Some((dir, files)) = self.export_index.take() {
+            let files = files.lock().unwrap();
+            if !files.is_empty() {
+                match std::fs::write(dir.join(export::INDEX_FILE), export::index(&files, chrono::Utc::now())) {
+                    Ok(()) => self.msg.push_str(&format!("\nIndex: ./exports/{}", export::INDEX_FILE)),
+                    Err(e) => self.msg.push_str(&format!("\nCould not write {}: {}", export::INDEX_FILE, e)),
+                }
             }
         }
-        self.msg = format!("Exported {} sessions to ./exports/", count);
-        self.mode = Mode::Message;
-        Ok(())
+        if let Some(p) = self.scanning.take() {
+            self.scan_anyway.insert(p);
+            if let Err(e) = self.refresh() { self.msg.push_str(&format!("\nReload failed: {}", e)); }
+        }
+        self.refresh_searches();
+    }
+}
+
+/// Ctrl+R in the filter prompt: switches the free text between substring and regex matching
+/// by adding or removing the `re:` prefix, keeping any tokens in front of it.
+fn toggle_regex(input: &mut String) {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let first_free = words.iter().position(|w| !w.contains(':') || w.starts_with("re:"));
+    *input = match first_free {
+        Some(i) if words[i].starts_with("re:") => {
+            let mut words = words.clone();
+            words[i] = &words[i][3..];
+            words.into_iter().filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" ")
+        }
+        Some(i) => format!("{} re:{}", words[..i].join(" "), words[i..].join(" ")).trim_start().to_string(),
+        None => format!("{} re:", input.trim_end()).trim_start().to_string(),
+    };
+}
+
+/// Normal-mode keys that change files, refused in read-only mode.
+const WRITE_KEYS: &str = "*DILNRTZcdnpwx";
+
+/// Normal-mode keys a custom command may not take.
+const RESERVED_KEYS: &str = " !*+-/123=CDFHILNPRSTZ[]acdefhijknopqrstvwxz";
+
+/// The custom commands that can be bound, and an error for each one that can't.
+fn check_commands(commands: &[CustomCommand]) -> (Vec<(char, CustomCommand)>, Vec<String>) {
+    let mut ok: Vec<(char, CustomCommand)> = Vec::new();
+    let mut errors = Vec::new();
+    for c in commands {
+        let mut chars = c.key.chars();
+        let problem = match (chars.next(), chars.next()) {
+            (Some(k), None) if RESERVED_KEYS.contains(k) => Some(format!("'{}' is a built-in key", k)),
+            (Some(k), None) if ok.iter().any(|(used, _)| *used == k) => Some(format!("'{}' is bound twice", k)),
+            (Some(k), None) => { ok.push((k, c.clone())); None }
+            _ => Some(format!("key {:?} must be a single character", c.key)),
+        };
+        if let Some(p) = problem { errors.push(format!("Custom command \"{}\" rejected: {}", c.label, p)); }
+    }
+    (ok, errors)
+}
+
+/// Runs `f` with the terminal handed back to the shell, then restores the TUI.
+fn suspended<T>(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mouse: bool, f: impl FnOnce() -> T) -> io::Result<T> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
+    let out = f();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    if mouse { execute!(io::stdout(), EnableMouseCapture)?; }
+    terminal.clear()?;
+    Ok(out)
+}
+
+/// Leaves the TUI to run a custom command on the highlighted session, then reports its exit
+/// status and anything it wrote to stderr.
+fn run_custom_command(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, key: char) -> Result<(), Box<dyn Error>> {
+    let Some(idx) = app.current() else { return Ok(()) };
+    let Some((_, cmd)) = app.commands.iter().find(|(k, _)| *k == key) else { return Ok(()) };
+    let line = cmd.expand(&app.sessions[idx]);
+    let res = suspended(terminal, app.config.mouse.unwrap_or(false), || std::process::Command::new("sh").arg("-c").arg(&line)
+        .stdin(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::piped())
+        .output())?;
+    app.msg = match res {
+        Ok(out) => {
+            let mut msg = format!("{}: {}", cmd.label, out.status);
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            if !stderr.trim().is_empty() { msg.push_str(&format!("\n\n{}", stderr.trim_end())); }
+            msg
+        }
+        Err(e) => format!("{}: could not run: {}", cmd.label, e),
+    };
+    app.mode = Mode::Message;
+    Ok(())
+}
+
+/// Hands the terminal to `claude --resume <id>` in the session's directory and reloads the
+/// list when it exits.
+fn resume_session(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<(), Box<dyn Error>> {
+    let (dir, id) = match app.resume_target() {
+        Ok(t) => t,
+        Err(e) => { app.msg = e; app.mode = Mode::Message; return Ok(()); }
+    };
+    let res = suspended(terminal, app.config.mouse.unwrap_or(false), || std::process::Command::new("claude")
+        .arg("--resume").arg(&id).current_dir(&dir).status())?;
+    match res {
+        Ok(status) => {
+            app.refresh()?;
+            app.status = Some(format!("claude exited ({})", status));
+        }
+        Err(e) => { app.msg = format!("Could not run claude: {}", e); app.mode = Mode::Message; }
+    }
+    Ok(())
+}
+
+/// Puts `text` on the clipboard through the terminal (OSC 52), which also works over SSH.
+fn osc52_copy(text: &str) -> io::Result<()> {
+    use base64::Engine;
+    let mut out = io::stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64::engine::general_purpose::STANDARD.encode(text))?;
+    out.flush()
+}
+
+/// One line per orphan path: removed, or why not.
+fn orphan_outcomes(paths: &[String], results: &[io::Result<()>]) -> String {
+    paths.iter().zip(results).map(|(p, r)| match r {
+        Ok(()) => format!("- {}\n", p),
+        Err(e) => format!("- {}: failed: {}\n", p, e),
+    }).collect()
+}
+
+fn restore_report(res: io::Result<archive::Manifest>) -> String {
+    match res {
+        Ok(m) => format!("Restored {} ({} files).", m.id, m.entries.len()),
+        Err(e) => format!("Restore failed: {}", e),
     }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = <cli::Cli as clap::Parser>::parse();
+    let log_file = cli.log_file.clone().or_else(|| Config::load().log_file.map(PathBuf::from));
+    if cli.is_headless() && cli.verbose {
+        diag::to_stderr()?;
+    } else if let Some(path) = &log_file {
+        diag::to_file(path).map_err(|e| format!("cannot open log file {}: {}", path.display(), e))?;
+    }
+    log::info!("claude-sessions-tui {} started with {:?}", env!("CARGO_PKG_VERSION"), cli);
+    if let Some(res) = cli::run(&cli) { return res; }
+
+    let mut manager = SessionManager::from_home();
+    let project = match &cli.project {
+        Some(arg) => match manager.resolve_project(arg) {
+            Ok(p) => Some(p),
+            Err(close) => {
+                eprintln!("{}", cli::no_project(arg, &close));
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    let lock_path = sessions::config_dir().join("instance.lock");
+    let (instance, holder) = match lock::acquire(&lock_path) {
+        Ok(lock::Acquired::Locked(l)) => (Some(l), None),
+        Ok(lock::Acquired::Held(h)) => (None, Some(h)),
+        Err(_) => (None, None),
+    };
+    manager.read_only = holder.is_some();
+    let mut app = App::loading(manager, Config::load());
+    app.open_after_load = project;
+    app.start_watching();
+    app.instance = instance;
+    if holder.is_some() { app.lock_holder = holder; app.mode = Mode::LockConflict; }
+
+    let prev = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen);
+        lock::release(&lock_path);
+        prev(info);
+    }));
+
     enable_raw_mode()?;
     execute!(io::stdout(), EnterAlternateScreen)?;
+    let mouse = app.config.mouse.unwrap_or(false);
+    if mouse { execute!(io::stdout(), EnableMouseCapture)?; }
     let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
-    let mut app = App::new()?;
 
     let res = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
+    if mouse { execute!(io::stdout(), DisableMouseCapture)?; }
     execute!(io::stdout(), LeaveAlternateScreen)?;
+    if let Some(task) = app.task.take() {
+        eprintln!("Stopping {} after the current item...", task.title);
+        task.cancel();
+        task.join();
+    }
+    app.remember_position();
+    app.save_snapshot();
     
     res
 }
 
 fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<(), Box<dyn Error>> {
     loop {
-        terminal.draw(|f| ui(f, app))?;
-        if let Event::Key(key) = event::read()? {
-            match app.mode {
-                Mode::Normal => match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Down | KeyCode::Char('j') => app.move_sel(1),
-                    KeyCode::Up | KeyCode::Char('k') => app.move_sel(-1),
-                    KeyCode::Char(' ') => app.toggle(),
-                    KeyCode::Char('d') => {
-                        if app.selected.is_empty() { if let Some(i) = app.state.selected() { app.selected.push(app.filtered[i]); } }
-                        app.to_delete.clear();
-                        for &i in &app.selected { if let Some(s) = app.sessions.get(i) { app.to_delete.push(s.display_name()); } }
-                        app.msg = format!("Delete {} sessions?", app.selected.len());
-                        app.action = Action::Delete;
-                        app.mode = Mode::Confirm;
-                    },
-                    KeyCode::Char('e') => { app.start_export()?; }
-                    KeyCode::Char('s') => { 
-                        app.sort = match app.sort { SortBy::Date=>SortBy::Size, SortBy::Size=>SortBy::Messages, _=>SortBy::Date };
-                        app.apply_sort(); app.apply_filter();
-                    },
-                    KeyCode::Char('p') => app.mode = Mode::PruneSelection,
-                    KeyCode::Char('/') => { app.input = app.filter.clone(); app.mode = Mode::Filter; }
-                    KeyCode::Enter => { 
-                         if let Some(i) = app.state.selected() {
-                             if let Some(s) = app.sessions.get(app.filtered[i]) {
-                                 let log = app.manager.read_log(&s.path);
-                                 app.cached_log = Some(log.lines().map(String::from).collect());
-                                 app.offset = usize::MAX; // Will be clamped in render
-                                 app.mode = Mode::Expanded;
-                             }
-                         }
-                    },
-                    _ => {}
+        let had_task = app.task.is_some();
+        app.poll_task();
+        if had_task && app.task.is_none() { app.frames.mark(); }
+        app.poll_watch();
+        if app.poll_loading() { app.frames.mark(); }
+        let tick = if app.task.is_some() || app.loading.is_some() { TASK_TICK } else { IDLE_TICK };
+        if app.frames.should_draw(Instant::now(), tick) {
+            app.tick();
+            app.load_preview_summary();
+            terminal.draw(|f| ui(f, app))?;
+        }
+        if !event::poll(app.frames.timeout(Instant::now(), tick))? { continue; }
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            Event::Resize(w, h) => { terminal.autoresize()?; app.on_resize(w, h); app.frames.mark(); continue; }
+            Event::Mouse(m) if m.kind == MouseEventKind::Down(MouseButton::Left) => { app.click(m.column, m.row); app.frames.mark(); continue; }
+            _ => continue,
+        };
+        app.frames.mark();
+        app.status = None;
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) { return Ok(()); }
+        match app.mode {
+            Mode::Peek => { app.peek = None; app.mode = Mode::Normal; }
+            Mode::LockConflict => match key.code {
+                KeyCode::Char('r') => app.resolve_lock(false),
+                KeyCode::Char('t') if app.lock_holder.as_ref().is_some_and(|h| !h.is_alive()) => app.resolve_lock(true),
+                KeyCode::Char('q')|KeyCode::Esc => return Ok(()),
+                _ => {}
+            },
+            Mode::Normal if app.manager.read_only && !key.modifiers.contains(KeyModifiers::CONTROL)
+                && (matches!(key.code, KeyCode::Char(c) if WRITE_KEYS.contains(c)) || key.code == KeyCode::F(2)) => app.refuse_read_only(),
+            Mode::Archived if app.manager.read_only && key.code == KeyCode::Enter => app.refuse_read_only(),
+            Mode::Normal => match key.code {
+                KeyCode::Tab => app.open_peek(),
+                KeyCode::Char('f') => app.browse_related(),
+                KeyCode::Char('R') => app.prune_both(),
+                KeyCode::Char('P') => app.open_project_picker(),
+                KeyCode::Char('v') => app.open_saved_filters(),
+                KeyCode::Char('*') => app.toggle_pinned()?,
+                KeyCode::Char('L') => app.toggle_protected()?,
+                KeyCode::Char('N') => {
+                    if let Some(i) = app.current() {
+                        app.input = app.sessions[i].note.clone().unwrap_or_default();
+                        app.mode = Mode::Note;
+                    }
                 },
-                Mode::Filter => match key.code {
-                    KeyCode::Enter => { app.filter = app.input.clone(); app.apply_filter(); app.mode = Mode::Normal; }
-                    KeyCode::Esc => { app.mode = Mode::Normal; }
-                    KeyCode::Char(c) => app.input.push(c),
-                    KeyCode::Backspace => { app.input.pop(); },
-                    _ => {}
+                KeyCode::Char('o') if !key.modifiers.contains(KeyModifiers::CONTROL) => app.open_sort_menu(),
+                KeyCode::Char('[') => { app.jump_in_project(-1); }
+                KeyCode::Char('1')|KeyCode::Char('!') => app.open_recent(0),
+                KeyCode::Char('2') => app.open_recent(1),
+                KeyCode::Char('3') => app.open_recent(2),
+                KeyCode::Char(']') => { app.jump_in_project(1); }
+                KeyCode::Esc if !app.filter.is_empty() => { app.filter.clear(); app.apply_filter(); app.refresh_searches(); }
+                KeyCode::Char('q') => {
+                    let reasons = app.quit_blockers();
+                    if reasons.is_empty() { return Ok(()); }
+                    app.msg = format!("{} - quit anyway?", reasons.join(", "));
+                    app.mode = Mode::QuitConfirm;
                 },
-                Mode::Confirm => match key.code {
-                    KeyCode::Char('y')|KeyCode::Char('Y') => app.perform_action()?,
-                    KeyCode::Esc|KeyCode::Char('n') => app.mode = Mode::Normal,
-                    _ => {}
+                KeyCode::Down | KeyCode::Char('j') => app.move_sel(1),
+                KeyCode::Up | KeyCode::Char('k') => app.move_sel(-1),
+                KeyCode::Char(' ') => app.toggle(),
+                KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => app.open_debug_log(false),
+                KeyCode::Char('d') => {
+                    if app.selected.is_empty() { if let Some(i) = app.current() { app.selected.insert(i); } }
+                    app.to_delete.clear();
+                    app.to_delete = app.targets().into_iter().map(|i| app.delete_row(i, app.sessions[i].display_name())).collect();
+                    app.msg = format!("Delete {} sessions?", app.selected.len());
+                    app.action = Action::Delete;
+                    app.mode = Mode::Confirm;
                 },
-                Mode::Message => if matches!(key.code, KeyCode::Enter|KeyCode::Esc) { app.mode = Mode::Normal; },
-                Mode::Expanded => match key.code {
-                    KeyCode::Esc|KeyCode::Char('q') => {
-                        app.cached_log = None;
-                        app.mode = Mode::Normal;
-                    },
-                    KeyCode::Down|KeyCode::Char('j') => app.offset += 1,
-                    KeyCode::Up|KeyCode::Char('k') => app.offset = app.offset.saturating_sub(1),
-                    KeyCode::PageUp => app.offset = app.offset.saturating_sub(20),
-                    KeyCode::PageDown => app.offset += 20,
-                    _ => {}
+                KeyCode::Char('D') => app.delete_project(),
+                KeyCode::Char('e') => { app.start_export()?; }
+                KeyCode::Char('s') => { 
+                    app.sort = columns::next_sort(&app.columns, app.sort);
+                    app.apply_sort(); app.apply_filter();
                 },
-                Mode::PruneSelection => match key.code {
-                    KeyCode::Esc => app.mode = Mode::Normal,
-                    KeyCode::Char('1') => { // Empty
-                        app.selected = app.sessions.iter().enumerate().filter(|(_,s)| s.message_count==0).map(|(i,_)| i).collect();
-                        if app.selected.is_empty() { app.msg="No empty sessions.".into(); app.mode=Mode::Message; }
-                        else { app.msg=format!("Delete {} empty sessions?", app.selected.len()); app.action=Action::Delete; app.mode=Mode::Confirm; }
-                    },
-                    KeyCode::Char('2') => { // Orphans
-                        app.orphans = app.manager.find_orphans().iter().map(|p| p.to_string_lossy().into()).collect();
-                        if app.orphans.is_empty() { app.msg="No orphans.".into(); app.mode=Mode::Message; }
-                        else { app.to_delete=app.orphans.clone(); app.msg=format!("Delete {} orphans?", app.orphans.len()); app.action=Action::PruneOrphans; app.mode=Mode::Confirm; }
-                    },
-                    KeyCode::Char('3') => { // Both
-                        app.selected = app.sessions.iter().enumerate().filter(|(_,s)| s.message_count==0).map(|(i,_)| i).collect();
-                        app.orphans = app.manager.find_orphans().iter().map(|p| p.to_string_lossy().into()).collect();
-                        if app.selected.is_empty() && app.orphans.is_empty() { app.msg="Nothing to prune.".into(); app.mode=Mode::Message; }
-                        else { app.msg=format!("Delete {} empty & {} orphans?", app.selected.len(), app.orphans.len()); app.action=Action::PruneBoth; app.mode=Mode::Confirm; }
-                    },
-                    KeyCode::Char('4') => { // History
-                         let c = app.manager.prune_history_orphans();
-                         app.msg = format!("Pruned {} history entries.", c);
-                         app.mode = Mode::Message;
-                    },
-                    _ => {}
+                KeyCode::Char('S') => { app.sort_reversed = !app.sort_reversed; app.apply_sort(); app.apply_filter(); }
+                KeyCode::Char('p') => app.mode = Mode::PruneSelection,
+                KeyCode::Char('C') => app.open_column_picker(),
+                KeyCode::Char('i') => app.open_stats(),
+                KeyCode::Char('/') => { app.input = app.filter.clone(); app.mode = Mode::Filter; }
+                KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.input = format!("{} file:", app.filter).trim_start().to_string();
+                    app.mode = Mode::Filter;
+                }
+                KeyCode::Char('F') => {
+                    app.input = format!("{} text:", app.filter).trim_start().to_string();
+                    app.mode = Mode::Filter;
+                }
+                KeyCode::Char('Z') => app.archive_targets(),
+                KeyCode::F(2) => app.start_triage(),
+                KeyCode::F(5) => app.manual_reload(),
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => app.manual_reload(),
+                KeyCode::Char('r') => resume_session(terminal, app)?,
+                KeyCode::Char('z') => app.open_archives(),
+                KeyCode::Char('I') => { app.input.clear(); app.mode = Mode::Import; }
+                KeyCode::Char('c') => { if let Some(i) = app.current() { app.duplicate(i, None)?; } }
+                KeyCode::Char('=') => app.compare_mark(),
+                KeyCode::Char('w') => app.start_slim(),
+                KeyCode::Char('x') => app.toggle_hidden()?,
+                KeyCode::Char('h') => app.check_health(app.targets()),
+                KeyCode::Char('H') => { app.show_hidden = !app.show_hidden; app.apply_filter(); }
+                KeyCode::Char('T') if !app.targets().is_empty() => { app.input.clear(); app.mode = Mode::Tag; }
+                KeyCode::Char('n') => {
+                    if let Some(i) = app.current() {
+                        app.input = app.sessions[i].display_name();
+                        app.mode = Mode::Rename;
+                    }
+                },
+                KeyCode::Enter if app.current_heading().is_some() => app.fold(None),
+                KeyCode::Enter => { if let Some(i) = app.current() { app.open_transcript(i, false); } },
+                KeyCode::Char('t') => app.toggle_grouped(),
+                KeyCode::Left => app.fold(Some(true)),
+                KeyCode::Char('-') => app.fold_all(true),
+                KeyCode::Char('+') => app.fold_all(false),
+                KeyCode::Right => app.fold(Some(false)),
+                KeyCode::Char('a') => app.open_answer(),
+                KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => app.show_audit(),
+                KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => app.open_history(),
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) && app.commands.iter().any(|(k, _)| *k == c) => run_custom_command(terminal, app, c)?,
+                _ => {}
+            },
+            Mode::Filter => match key.code {
+                KeyCode::Enter => {
+                    app.filter = app.input.clone(); app.apply_filter(); app.mode = Mode::Normal;
+                    app.refresh_searches();
+                    if FilterSpec::parse(&app.filter).has_is("archived") { app.open_archives(); }
+                }
+                KeyCode::Esc => { app.mode = Mode::Normal; }
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => toggle_regex(&mut app.input),
+                KeyCode::Char(c) => app.input.push(c),
+                KeyCode::Backspace => { app.input.pop(); },
+                _ => {}
+            },
+            Mode::Import => match key.code {
+                KeyCode::Enter => app.start_import()?,
+                KeyCode::Esc => { app.mode = Mode::Normal; }
+                KeyCode::Char(c) => app.input.push(c),
+                KeyCode::Backspace => { app.input.pop(); },
+                _ => {}
+            },
+            Mode::ImportConflict => match key.code {
+                KeyCode::Char('r') => app.resolve_conflict(Some(true))?,
+                KeyCode::Char('s') => app.resolve_conflict(Some(false))?,
+                KeyCode::Esc => app.resolve_conflict(None)?,
+                _ => {}
+            },
+            Mode::Filters => match key.code {
+                KeyCode::Esc|KeyCode::Char('q') => app.mode = Mode::Normal,
+                KeyCode::Down|KeyCode::Char('j') => {
+                    let i = app.filter_state.selected().map_or(0, |i| (i + 1).min(app.config.saved_filters.len().saturating_sub(1)));
+                    app.filter_state.select(Some(i));
+                }
+                KeyCode::Up|KeyCode::Char('k') => app.filter_state.select(Some(app.filter_state.selected().map_or(0, |i| i.saturating_sub(1)))),
+                KeyCode::Enter => app.apply_saved_filter(),
+                KeyCode::Char('a') => app.start_save_filter(),
+                KeyCode::Char('d') => app.delete_saved_filter(),
+                _ => {}
+            },
+            Mode::SortMenu => match key.code {
+                KeyCode::Esc|KeyCode::Char('q')|KeyCode::Char('o') => app.mode = Mode::Normal,
+                KeyCode::Down|KeyCode::Char('j') => app.sort_state.select(Some(app.sort_state.selected().map_or(0, |i| (i + 1).min(SortBy::ALL.len() - 1)))),
+                KeyCode::Up|KeyCode::Char('k') => app.sort_state.select(Some(app.sort_state.selected().map_or(0, |i| i.saturating_sub(1)))),
+                KeyCode::Enter|KeyCode::Char('1') => app.pick_sort(false),
+                KeyCode::Char('2') => app.pick_sort(true),
+                _ => {}
+            },
+            Mode::SaveFilter => match key.code {
+                KeyCode::Enter => app.save_filter(),
+                KeyCode::Esc => { app.mode = Mode::Filters; }
+                KeyCode::Char(c) => app.input.push(c),
+                KeyCode::Backspace => { app.input.pop(); },
+                _ => {}
+            },
+            Mode::Rename => match key.code {
+                KeyCode::Enter => { app.rename_current()?; app.mode = Mode::Normal; }
+                KeyCode::Esc => { app.mode = Mode::Normal; }
+                KeyCode::Char(c) => app.input.push(c),
+                KeyCode::Backspace => { app.input.pop(); },
+                _ => {}
+            },
+            Mode::Note => match key.code {
+                KeyCode::Enter => { app.save_note()?; app.mode = Mode::Normal; }
+                KeyCode::Esc => { app.mode = Mode::Normal; }
+                KeyCode::Char(c) => app.input.push(c),
+                KeyCode::Backspace => { app.input.pop(); },
+                _ => {}
+            },
+            Mode::Tag => match key.code {
+                KeyCode::Enter => { app.tag_targets()?; app.mode = Mode::Normal; }
+                KeyCode::Esc => { app.mode = Mode::Normal; }
+                KeyCode::Char(c) => app.input.push(c),
+                KeyCode::Backspace => { app.input.pop(); },
+                _ => {}
+            },
+            Mode::Confirm => match (key.code, app.typed_confirm()) {
+                (KeyCode::Char(c), Some(_)) if c.is_ascii_digit() => app.confirm_typed.push(c),
+                (KeyCode::Backspace, Some(_)) => { app.confirm_typed.pop(); }
+                (KeyCode::Enter, Some(n)) if app.confirm_typed == n.to_string() => {
+                    app.popup_scroll = 0;
+                    app.confirm_typed.clear();
+                    app.perform_action()?;
+                }
+                (KeyCode::Enter, Some(_)) => app.confirm_typed.clear(),
+                (KeyCode::Char('y')|KeyCode::Char('Y'), Some(_)) => {}
+                (KeyCode::Char('y'), None) if app.needs_capital_y() => {}
+                (KeyCode::Char('y')|KeyCode::Char('Y'), None) => { app.popup_scroll = 0; app.perform_action()?; }
+                (KeyCode::Esc|KeyCode::Char('n'), _) => app.cancel_confirm(),
+                (code, _) => app.scroll_popup(code),
+            },
+            Mode::Message => match key.code {
+                KeyCode::Enter|KeyCode::Esc => { app.popup_scroll = 0; app.mode = Mode::Normal; }
+                code => app.scroll_popup(code),
+            },
+            Mode::Report => match key.code {
+                KeyCode::Esc|KeyCode::Char('q') => app.mode = Mode::Normal,
+                KeyCode::Down|KeyCode::Char('j') => app.report_offset = (app.report_offset + 1).min(app.report.len().saturating_sub(1)),
+                KeyCode::Up|KeyCode::Char('k') => app.report_offset = app.report_offset.saturating_sub(1),
+                KeyCode::PageDown => app.report_offset = (app.report_offset + 20).min(app.report.len().saturating_sub(1)),
+                KeyCode::PageUp => app.report_offset = app.report_offset.saturating_sub(20),
+                KeyCode::Char('e') => app.export_report()?,
+                KeyCode::Char('r') if app.report_kind == ReportKind::Health && !app.repairable.is_empty() => {
+                    app.to_delete = app.repairable.iter().map(|&i| app.sessions[i].display_name().into()).collect();
+                    app.msg = format!("Drop the truncated last line of {} sessions?", app.repairable.len());
+                    app.action = Action::Repair;
+                    app.mode = Mode::Confirm;
+                },
+                _ => {}
+            },
+            Mode::Checklist => match (key.code, app.checklist.as_mut()) {
+                (KeyCode::Esc|KeyCode::Char('q'), _) => { app.checklist = None; app.mode = Mode::Normal; },
+                (KeyCode::Down|KeyCode::Char('j'), Some(c)) => c.move_sel(1),
+                (KeyCode::Up|KeyCode::Char('k'), Some(c)) => c.move_sel(-1),
+                (KeyCode::Char(' '), Some(c)) => c.toggle(),
+                (KeyCode::Char('a'), Some(c)) => c.toggle_all(),
+                (KeyCode::Char('e'), Some(c)) if c.kind == ChecklistKind::History => app.export_history()?,
+                (KeyCode::Enter, _) => app.apply_checklist(),
+                _ => {}
+            },
+            Mode::History => match key.code {
+                KeyCode::Esc|KeyCode::Char('q') => { app.popup_scroll = 0; app.mode = Mode::Normal; }
+                KeyCode::Down|KeyCode::Char('j') => {
+                    let i = app.history_state.selected().map_or(0, |i| (i + 1).min(app.history.len() - 1));
+                    app.history_state.select(Some(i));
+                    app.popup_scroll = 0;
                 }
+                KeyCode::Up|KeyCode::Char('k') => {
+                    app.history_state.select(Some(app.history_state.selected().map_or(0, |i| i.saturating_sub(1))));
+                    app.popup_scroll = 0;
+                }
+                KeyCode::PageDown|KeyCode::PageUp => app.scroll_popup(key.code),
+                KeyCode::Char('y') => if let Some(r) = app.history_state.selected().and_then(|i| app.history.get(i)) { osc52_copy(&r.text)?; },
+                _ => {}
+            },
+            Mode::Projects => match key.code {
+                KeyCode::Esc|KeyCode::Char('q') => app.mode = Mode::Normal,
+                KeyCode::Down|KeyCode::Char('j') => {
+                    let i = app.project_state.selected().map_or(0, |i| (i + 1).min(app.projects.len() + app.deferred.len() - 1));
+                    app.project_state.select(Some(i));
+                }
+                KeyCode::Up|KeyCode::Char('k') => app.project_state.select(Some(app.project_state.selected().map_or(0, |i| i.saturating_sub(1)))),
+                KeyCode::Char('s') => app.toggle_picker_order(),
+                KeyCode::Enter => match app.picked_project() {
+                    Some(p) if app.deferred.contains(&p) => app.scan_deferred(p),
+                    Some(p) => { app.mode = Mode::Normal; app.open_project(&p); }
+                    None => {}
+                },
+                _ => {}
+            },
+            Mode::Stats if key.code == KeyCode::Char('c') && app.manager.read_only => app.refuse_read_only(),
+            Mode::Stats if key.code == KeyCode::Char('c') => app.confirm_compact(),
+            Mode::Stats => if matches!(key.code, KeyCode::Esc|KeyCode::Char('q')|KeyCode::Char('i')) { app.mode = Mode::Normal; },
+            Mode::Progress => if key.code == KeyCode::Esc { if let Some(t) = &app.task { t.cancel(); } },
+            Mode::QuitConfirm => match key.code {
+                KeyCode::Char('y')|KeyCode::Char('Y')|KeyCode::Char('q') => return Ok(()),
+                KeyCode::Esc|KeyCode::Char('n')|KeyCode::Char('N') => app.mode = Mode::Normal,
+                _ => {}
+            },
+            Mode::Triage => match key.code {
+                KeyCode::Char('k') => app.decide(Verdict::Keep),
+                KeyCode::Char('d') => app.decide(Verdict::Delete),
+                KeyCode::Char('a') => app.decide(Verdict::Archive),
+                KeyCode::Char('s') => app.decide(Verdict::Skip),
+                KeyCode::Backspace|KeyCode::Char('u') => app.triage_back(),
+                KeyCode::Down => if let Some(t) = app.triage.as_mut() { t.offset = t.offset.saturating_add(1); },
+                KeyCode::Up => if let Some(t) = app.triage.as_mut() { t.offset = t.offset.saturating_sub(1); },
+                KeyCode::PageDown => if let Some(t) = app.triage.as_mut() { t.offset = t.offset.saturating_add(20); },
+                KeyCode::PageUp => if let Some(t) = app.triage.as_mut() { t.offset = t.offset.saturating_sub(20); },
+                KeyCode::Esc|KeyCode::Char('q') => app.finish_triage(),
+                _ => {}
+            },
+            Mode::Answer => match key.code {
+                KeyCode::Esc|KeyCode::Char('q') => { app.answer = None; app.mode = Mode::Normal; },
+                KeyCode::Down|KeyCode::Char('j') => if let Some(a) = app.answer.as_mut() { a.offset = a.offset.saturating_add(1); },
+                KeyCode::Up|KeyCode::Char('k') => if let Some(a) = app.answer.as_mut() { a.offset = a.offset.saturating_sub(1); },
+                KeyCode::PageDown => if let Some(a) = app.answer.as_mut() { a.offset = a.offset.saturating_add(20); },
+                KeyCode::PageUp => if let Some(a) = app.answer.as_mut() { a.offset = a.offset.saturating_sub(20); },
+                KeyCode::Char('y') => if let Some(a) = &app.answer { osc52_copy(&a.text)?; },
+                KeyCode::Enter|KeyCode::Char('e') => if let Some(a) = app.answer.take() { app.open_transcript(a.session, true); },
+                _ => {}
+            },
+            Mode::Expanded => match key.code {
+                code if app.pending_bookmark.is_some() => {
+                    let kind = app.pending_bookmark.take();
+                    match code {
+                        KeyCode::Char(c) if c.is_ascii_alphabetic() && kind == Some('m') => app.set_bookmark(c),
+                        KeyCode::Char(c) if c.is_ascii_alphabetic() => app.jump_to_bookmark(c),
+                        _ => {}
+                    }
+                },
+                KeyCode::Char('f') if app.transcript.as_ref().is_some_and(|t| t.debug.is_some()) => app.open_debug_log(true),
+                // Marks, bookmarks and message actions belong to transcripts.
+                KeyCode::Char(c) if app.transcript.as_ref().is_some_and(|t| t.debug.is_some()) && "vcrmM'y d<>".contains(c) => {}
+                KeyCode::Char(c @ ('m'|'\'')) => app.pending_bookmark = Some(c),
+                KeyCode::Char('M') => app.toggle_message_mode(),
+                KeyCode::Char('[') => app.open_in_project(-1),
+                KeyCode::Char(']') => app.open_in_project(1),
+                KeyCode::Char('<') => app.open_adjacent(-1),
+                KeyCode::Char('>') => app.open_adjacent(1),
+                KeyCode::Char(' ') => app.toggle_viewed(),
+                KeyCode::Char('d') => app.queue_viewed(),
+                KeyCode::Char('r') => app.confirm_redact(),
+                KeyCode::Char('y') if app.transcript.as_ref().is_some_and(|t| t.focus.is_some()) => app.copy_focused(false)?,
+                KeyCode::Char('c') if app.transcript.as_ref().is_some_and(|t| t.focus.is_some()) => app.copy_focused(true)?,
+                KeyCode::Down|KeyCode::Char('j') if app.transcript.as_ref().is_some_and(|t| t.focus.is_some()) => app.move_focus(1),
+                KeyCode::Up|KeyCode::Char('k') if app.transcript.as_ref().is_some_and(|t| t.focus.is_some()) => app.move_focus(-1),
+                KeyCode::Esc|KeyCode::Char('q') => app.close_transcript(),
+                KeyCode::Char('v') => {
+                    let offset = app.offset;
+                    if let Some(t) = app.transcript.as_mut() {
+                        if let Some(e) = t.entry_at(offset) { t.toggle_mark(e); }
+                    }
+                },
+                KeyCode::Char('c') => {
+                    if let Some(t) = app.transcript.take() {
+                        let cut = t.marks.iter().max().map(|&m| t.entries[m].record);
+                        app.mode = Mode::Normal;
+                        if let Some(idx) = app.sessions.iter().position(|s| s.id == t.session_id) { app.duplicate(idx, cut)?; }
+                    }
+                },
+                KeyCode::Down|KeyCode::Char('j') => app.offset += 1,
+                KeyCode::Up|KeyCode::Char('k') => app.scroll_up(1),
+                KeyCode::PageUp => app.scroll_up(20),
+                KeyCode::PageDown => app.offset += 20,
+                _ => {}
+            },
+            Mode::Archived => match key.code {
+                KeyCode::Esc|KeyCode::Char('q')|KeyCode::Char('z') => app.close_archives(),
+                KeyCode::Enter => app.restore_selected()?,
+                KeyCode::Down|KeyCode::Char('j') if !app.archives.is_empty() => {
+                    let i = app.archive_state.selected().map_or(0, |i| (i + 1) % app.archives.len());
+                    app.archive_state.select(Some(i));
+                },
+                KeyCode::Up|KeyCode::Char('k') if !app.archives.is_empty() => {
+                    let i = app.archive_state.selected().map_or(0, |i| (i + app.archives.len() - 1) % app.archives.len());
+                    app.archive_state.select(Some(i));
+                },
+                _ => {}
+            },
+            Mode::Compare => match (key.code, app.compare.as_mut()) {
+                (KeyCode::Esc|KeyCode::Char('q'), _) => { app.compare = None; app.mode = Mode::Normal; },
+                (KeyCode::Tab, Some(c)) => c.focus = 1 - c.focus,
+                (KeyCode::Char('s'), Some(c)) => c.sync = !c.sync,
+                (KeyCode::Down|KeyCode::Char('j'), Some(c)) => c.scroll(1),
+                (KeyCode::Up|KeyCode::Char('k'), Some(c)) => c.scroll(-1),
+                (KeyCode::PageDown, Some(c)) => c.scroll(20),
+                (KeyCode::PageUp, Some(c)) => c.scroll(-20),
+                _ => {}
+            },
+            Mode::PruneSelection => match key.code {
+                KeyCode::Esc => app.mode = Mode::Normal,
+                KeyCode::Char('k') => {
+                    app.config.prune_skip_keep = Some(!app.config.prune_skip_keep.unwrap_or(true));
+                    app.config.save().ok();
+                },
+                KeyCode::Char('1') => { // Empty
+                    app.selected = app.prunable_empty().into_iter().collect();
+                    if app.selected.is_empty() { app.msg="No empty sessions.".into(); app.mode=Mode::Message; }
+                    else {
+                        app.to_delete = app.selection().into_iter().map(|i| app.delete_row(i, app.sessions[i].display_name())).collect();
+                        app.msg=format!("Delete {} empty sessions?", app.selected.len()); app.action=Action::Delete; app.mode=Mode::Confirm;
+                    }
+                },
+                KeyCode::Char('2') => app.review_orphans(),
+                KeyCode::Char('3') => app.prune_both(),
+                KeyCode::Char('8') => { // Deleted projects
+                    app.selected = app.prunable(|s| s.project_missing).into_iter().collect();
+                    if app.selected.is_empty() { app.msg="No sessions for deleted projects.".into(); app.mode=Mode::Message; }
+                    else {
+                        app.to_delete = app.selection().into_iter()
+                            .map(|i| app.delete_row(i, format!("{} ({})", app.sessions[i].display_name(), app.sessions[i].cwd.as_deref().unwrap_or_default())))
+                            .collect();
+                        app.msg=format!("Delete {} sessions whose project directory is gone?", app.selected.len()); app.action=Action::Delete; app.mode=Mode::Confirm;
+                    }
+                },
+                KeyCode::Char('9') => app.review_retention(),
+                KeyCode::Char('t') => app.offer_empty_trash(app.config.trash_retention_days.unwrap_or(DEFAULT_TRASH_DAYS), false),
+                KeyCode::Char('5') => app.find_duplicates(),
+                KeyCode::Char('6') => app.check_health(app.filtered.clone()),
+                KeyCode::Char('7') => app.review_history(),
+                KeyCode::Char('4') => { // History
+                     let c = app.manager.prune_history_orphans();
+                     app.msg = format!("Pruned {} history entries.", c);
+                     app.remember();
+                     app.mode = Mode::Message;
+                },
+                _ => {}
             }
         }
     }
 }
 
-fn ui(f: &mut Frame, app: &mut App) {
-    let main_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(1)])
-        .split(f.area());
-
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
-        .split(main_layout[0]);
-
-    let items: Vec<ListItem> = app.filtered.iter().map(|&i| {
-        let s = &app.sessions[i];
-        let mark = if app.selected.contains(&i) { "[x]" } else { "[ ]" };
-        let msgs = if s.message_count > 0 { format!("{} msgs", s.message_count) } else { "empty".to_string() };
-        ListItem::new(format!("{} {} ({}, {})", mark, s.display_name(), s.size_str(), msgs))
-    }).collect();
-
-    let title = format!(" Sessions ({}/{}) Filter:[{}] Sort:[{:?}] ", 
-        app.filtered.len(), app.sessions.len(), app.filter, app.sort);
-    
-    let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(title).title_alignment(Alignment::Center))
-        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
-    f.render_stateful_widget(list, chunks[0], &mut app.state);
-
-    let preview_text = if let Some(i) = app.state.selected() {
-        if let Some(s) = app.sessions.get(app.filtered[i]) {
-            let todos = s.get_todos();
-            let mut info = format!("ID: {}\nProject: {}\nSize: {}\nModified: {}\n", 
-                s.id, s.project, s.size_str(), s.formatted_age());
-            
-            if s.message_count > 0 {
-                info.push_str(&format!("Messages: {}\n", s.message_count));
-            }
-            if !todos.is_empty() {
-                info.push_str(&format!("\nTODO:\n- {}\n", todos.join("\n- ")));
-            }
-            if !s.first_message.is_empty() {
-                info.push_str(&format!("\nPROMPT:\n{}", s.first_message));
-            }
-            info
-        } else { String::new() }
-    } else { String::new() };
-
-    f.render_widget(Paragraph::new(preview_text).block(Block::default().borders(Borders::ALL).title(" Preview ")).wrap(Wrap{trim:true}), chunks[1]);
-    
-    // Help bar
-    let help_text = "q:Quit j/k:Nav Space:Sel d:Del e:Exp s:Sort p:Prune /:Filt Enter:Open";
-    f.render_widget(Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray).bg(Color::Black)), main_layout[1]);
-
-    // Popup logic
-    let area = f.area();
-    match app.mode {
-        Mode::Filter => {
-             let r = centered(60, 10, area);
-             f.render_widget(Clear, r);
-             let b = Block::default().borders(Borders::ALL).title(" Filter Sessions ");
-             let inner_area = b.inner(r);
-             f.render_widget(b, r);
-             f.render_widget(Paragraph::new(app.input.as_str()).style(Style::default().fg(Color::Yellow)), inner_area);
-        },
-        Mode::Confirm => {
-             let r = centered(60, 60, area);
-             f.render_widget(Clear, r);
-             let b = Block::default().borders(Borders::ALL).title(" Confirm Action ").style(Style::default().bg(Color::Black));
-             let inner_area = b.inner(r);
-             f.render_widget(b, r);
-             
-             let l = Layout::default()
-                 .constraints([Constraint::Length(2), Constraint::Min(0), Constraint::Length(2)])
-                 .split(inner_area);
-             
-             f.render_widget(Paragraph::new(app.msg.as_str()).style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)).alignment(Alignment::Center), l[0]);
-             
-             let del_items: Vec<ListItem> = app.to_delete.iter()
-                 .map(|s| ListItem::new(Line::from(vec![
-                     ratatui::text::Span::styled("- ", Style::default().fg(Color::DarkGray)),
-                     ratatui::text::Span::raw(s)
-                 ])))
-                 .collect();
-             
-             f.render_widget(List::new(del_items).block(Block::default().borders(Borders::TOP).title(" Items to delete ")), l[1]);
-             
-             f.render_widget(Paragraph::new("Press Y to Confirm, N to Cancel").alignment(Alignment::Center).style(Style::default().fg(Color::DarkGray)), l[2]);
-        },
-        Mode::Message => {
-             let r = centered(50, 20, area);
-             f.render_widget(Clear, r);
-             let b = Block::default().borders(Borders::ALL).title(" Information ");
-             f.render_widget(b.clone(), r);
-             f.render_widget(Paragraph::new(app.msg.as_str()).wrap(Wrap{trim:true}).block(Block::default().padding(ratatui::widgets::Padding::new(2,2,1,1))), r);
-        },
-        Mode::PruneSelection => {
-             let r = centered(40, 30, area);
-             f.render_widget(Clear, r);
-             let b = Block::default().title(" Prune Options ").borders(Borders::ALL);
-             let inner_area = b.inner(r);
-             f.render_widget(b, r);
-             let text = vec![
-                 Line::from(" [1] Empty Sessions"),
-                 Line::from(" [2] Orphaned Files"),
-                 Line::from(" [3] Both"),
-                 Line::from(" [4] Prune History"),
-                 Line::from(""),
-                 Line::from(ratatui::text::Span::styled(" Esc to Cancel", Style::default().fg(Color::DarkGray))),
-             ];
-             f.render_widget(Paragraph::new(text).block(Block::default().padding(ratatui::widgets::Padding::new(2,2,2,1))), inner_area);
-        },
-        Mode::Expanded => {
-             if let Some(lines) = &app.cached_log {
-                 let h = area.height as usize - 2;
-                 if app.offset == usize::MAX { app.offset = lines.len().saturating_sub(h); }
-                 app.offset = app.offset.min(lines.len().saturating_sub(h));
-                 
-                 let v: Vec<Line> = lines.iter()
-                     .skip(app.offset)
-                     .take(h)
-                     .map(|l| Line::from(l.as_str()))
-                     .collect();
-                 
-                 f.render_widget(Clear, area);
-                 let b = Block::default().borders(Borders::ALL)
-                     .title(format!(" Full Log (Line {}/{}) ", app.offset, lines.len()));
-                 f.render_widget(Paragraph::new(v).block(b).wrap(Wrap{trim:false}), area);
-             }
-        },
-        _ => {}
-    }
-}
-
-fn centered(px: u16, py: u16, r: Rect) -> Rect {
-    let v = Layout::default().direction(Direction::Vertical).constraints([Constraint::Percentage((100-py)/2), Constraint::Percentage(py), Constraint::Percentage((100-py)/2)]).split(r);
-    Layout::default().direction(Direction::Horizontal).constraints([Constraint::Percentage((100-px)/2), Constraint::Percentage(px), Constraint::Percentage((100-px)/2)]).split(v[1])[1]
-}
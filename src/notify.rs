@@ -0,0 +1,77 @@
+//! Runs the configured `notify_command` after a destructive run, so an unattended prune leaves a
+//! record somewhere it will be seen. The command's failures are logged and otherwise ignored.
+
+use crate::sessions::{shell_quote, Config};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What a destructive run did, as handed to the notify command.
+pub struct Outcome {
+    /// e.g. `apply-retention`; names the summary file.
+    pub action: &'static str,
+    pub deleted: usize,
+    pub freed: u64,
+    /// The full report, written to the file behind `{summary_file}`.
+    pub report: String,
+}
+
+/// `template` with `{summary_file}` shell-quoted and `{freed_bytes}` and `{deleted_count}` filled in.
+pub fn expand(template: &str, summary_file: &Path, freed: u64, deleted: usize) -> String {
+    template
+        .replace("{summary_file}", &shell_quote(&summary_file.to_string_lossy()))
+        .replace("{freed_bytes}", &freed.to_string())
+        .replace("{deleted_count}", &deleted.to_string())
+}
+
+/// Writes the report to a temp file and runs the notify command when one is configured and more
+/// than `notify_threshold` sessions were deleted. Returns the summary file when the command ran.
+pub fn send(config: &Config, outcome: &Outcome) -> Option<PathBuf> {
+    let template = config.notify_command.as_deref()?;
+    if outcome.deleted <= config.notify_threshold.unwrap_or(0) { return None; }
+    let summary = std::env::temp_dir().join(format!("claude-sessions-{}-{}-{}.txt",
+        outcome.action, chrono::Local::now().format("%Y%m%d-%H%M%S"), std::process::id()));
+    if let Err(e) = fs::write(&summary, &outcome.report) {
+        log::warn!("notify: cannot write {}: {}", summary.display(), e);
+        return None;
+    }
+    let line = expand(template, &summary, outcome.freed, outcome.deleted);
+    match run(&line) {
+        Ok(()) => log::info!("notify: ran {}", line),
+        Err(e) => log::warn!("notify: {} failed: {}", line, e),
+    }
+    Some(summary)
+}
+
+fn run(line: &str) -> io::Result<()> {
+    let out = Command::new("sh").arg("-c").arg(line).output()?;
+    if out.status.success() { return Ok(()); }
+    Err(io::Error::other(format!("{}: {}", out.status, String::from_utf8_lossy(&out.stderr).trim())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_above_the_threshold_with_the_report_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let seen = dir.path().join("seen");
+        let mut config = Config::default();
+        config.notify_command = Some(format!("cat {{summary_file}} > '{}'; echo {{deleted_count}} {{freed_bytes}} >> '{}'", seen.display(), seen.display()));
+        config.notify_threshold = Some(2);
+        let outcome = |deleted| Outcome { action: "test", deleted, freed: 4096, report: "Deleted a1\n".into() };
+
+        assert_eq!(send(&config, &outcome(2)), None, "two deletions are not more than the threshold");
+        assert!(!seen.exists());
+
+        let summary = send(&config, &outcome(3)).unwrap();
+        assert_eq!(fs::read_to_string(&seen).unwrap(), "Deleted a1\n3 4096\n");
+        fs::remove_file(summary).unwrap();
+
+        config.notify_command = Some("exit 3".into());
+        let summary = send(&config, &outcome(3)).expect("a failing command is only logged");
+        fs::remove_file(summary).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+//! Per-project retention rules from the config ("keep the 20 newest", "nothing older than 60
+//! days") and which sessions they would delete.
+
+use crate::sessions::Session;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+const SECS_PER_DAY: u64 = 86400;
+
+/// One entry of `retention` in the config. A session is governed by the first rule whose
+/// `project` matches it, so specific rules go before catch-alls.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RetentionRule {
+    /// Glob (`*`, `?`) over the project directory name or the session's working directory.
+    pub project: String,
+    /// Keep at most this many of the newest sessions in each project the rule matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<usize>,
+    /// Delete sessions last modified longer ago than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+}
+
+impl RetentionRule {
+    fn matches(&self, s: &Session) -> bool {
+        glob_match(&self.project, &s.project) || s.cwd.as_deref().is_some_and(|c| glob_match(&self.project, c))
+    }
+
+    /// `*scratch*: 20 newest, 60 days`.
+    pub fn describe(&self) -> String {
+        let limits: Vec<String> = self.max_count.map(|n| format!("{} newest", n)).into_iter()
+            .chain(self.max_age_days.map(|d| format!("{} days", d)))
+            .collect();
+        format!("{}: {}", self.project, if limits.is_empty() { "no limit".into() } else { limits.join(", ") })
+    }
+}
+
+/// Sessions (indices into `sessions`) each rule would delete, as `(rule index, victims)` for
+/// the rules that have any, victims newest first. `max_count` applies to each project a rule
+/// governs on its own. Exempt sessions are never victims and do not take up a rule's `max_count`.
+pub fn victims(rules: &[RetentionRule], sessions: &[Session], now: SystemTime, exempt: impl Fn(&Session) -> bool) -> Vec<(usize, Vec<usize>)> {
+    let mut governed: Vec<Vec<usize>> = vec![Vec::new(); rules.len()];
+    for (i, s) in sessions.iter().enumerate() {
+        if exempt(s) { continue; }
+        if let Some(r) = rules.iter().position(|r| r.matches(s)) { governed[r].push(i); }
+    }
+    let newest_first = |&a: &usize, &b: &usize| sessions[b].modified.cmp(&sessions[a].modified).then_with(|| sessions[a].id.cmp(&sessions[b].id));
+    governed.into_iter().enumerate().filter_map(|(r, own)| {
+        let rule = &rules[r];
+        let cutoff = rule.max_age_days.map(|d| now.checked_sub(Duration::from_secs(d * SECS_PER_DAY)).unwrap_or(SystemTime::UNIX_EPOCH));
+        let mut by_project: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for i in own { by_project.entry(sessions[i].project.as_str()).or_default().push(i); }
+        let mut out: Vec<usize> = by_project.into_values().flat_map(|mut project| {
+            project.sort_by(newest_first);
+            project.into_iter().enumerate()
+                .filter(|&(rank, i)| rule.max_count.is_some_and(|n| rank >= n) || cutoff.is_some_and(|c| sessions[i].modified < c))
+                .map(|(_, i)| i)
+                .collect::<Vec<_>>()
+        }).collect();
+        out.sort_by(newest_first);
+        (!out.is_empty()).then_some((r, out))
+    }).collect()
+}
+
+/// Shell-style match where `*` is any run of characters and `?` any single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        match p.get(pi) {
+            Some('*') => { star = Some((pi, ti)); pi += 1; }
+            Some(&c) if c == '?' || c == t[ti] => { pi += 1; ti += 1; }
+            _ => match star {
+                Some((sp, st)) => { pi = sp + 1; ti = st + 1; star = Some((sp, st + 1)); }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::PromptKind;
+
+    const DAY: u64 = SECS_PER_DAY;
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1000 * DAY)
+    }
+
+    fn session(id: &str, project: &str, age_days: u64) -> Session {
+        Session {
+            id: id.into(), path: Default::default(), project: project.into(), size: 0, message_count: 1,
+            first_message: String::new(), prompt_kind: PromptKind::Other, modified: now() - Duration::from_secs(age_days * DAY),
+            custom_name: None, user_name: None, tags: Vec::new(), hidden: false, pinned: false, protected: false, note: None, related_files: Vec::new(),
+            cwd: None, project_missing: false, last_reply_at: None, summary: None, tokens: 0, usage: Default::default(), models: Default::default(), summary_title: None, history_prompt: None,
+        }
+    }
+
+    fn rule(project: &str, max_count: Option<usize>, max_age_days: Option<u64>) -> RetentionRule {
+        RetentionRule { project: project.into(), max_count, max_age_days }
+    }
+
+    fn ids(sessions: &[Session], found: &[(usize, Vec<usize>)]) -> Vec<(usize, Vec<String>)> {
+        found.iter().map(|(r, v)| (*r, v.iter().map(|&i| sessions[i].id.clone()).collect())).collect()
+    }
+
+    fn run(rules: &[RetentionRule], sessions: &[Session]) -> Vec<(usize, Vec<String>)> {
+        ids(sessions, &victims(rules, sessions, now(), |s| s.has_tag("keep")))
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*scratch*", "-home-me-scratch-1"));
+        assert!(glob_match("-tmp-?", "-tmp-a"));
+        assert!(!glob_match("-tmp-?", "-tmp-ab"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("/home/*/work", "/home/me/work"));
+        assert!(!glob_match("scratch", "-scratch"), "no implicit wildcards");
+    }
+
+    #[test]
+    fn max_count_keeps_the_newest_per_rule() {
+        let sessions: Vec<Session> = (0..5).map(|d| session(&format!("s{}", d), "-p", d)).rev().collect();
+        assert_eq!(run(&[rule("-p", Some(2), None)], &sessions), [(0, vec!["s2".into(), "s3".into(), "s4".into()])]);
+        assert!(run(&[rule("-p", Some(5), None)], &sessions).is_empty());
+        assert_eq!(run(&[rule("-p", Some(0), None)], &sessions)[0].1.len(), 5);
+    }
+
+    #[test]
+    fn max_age_and_both_limits() {
+        let sessions = vec![session("new", "-p", 1), session("mid", "-p", 30), session("old", "-p", 90)];
+        assert_eq!(run(&[rule("-p", None, Some(60))], &sessions), [(0, vec!["old".into()])]);
+        assert_eq!(run(&[rule("-p", None, Some(30))], &sessions), [(0, vec!["old".into()])], "exactly the limit is kept");
+        assert_eq!(run(&[rule("-p", Some(1), Some(60))], &sessions), [(0, vec!["mid".into(), "old".into()])]);
+        assert!(run(&[rule("-p", None, None)], &sessions).is_empty(), "a rule without limits deletes nothing");
+        assert!(run(&[rule("-p", None, Some(100_000))], &sessions).is_empty(), "a cutoff before the epoch keeps everything");
+    }
+
+    #[test]
+    fn first_matching_rule_governs_a_session() {
+        let sessions = vec![
+            session("s-new", "-scratch", 1), session("s-old", "-scratch", 70),
+            session("w1", "-work", 1), session("w2", "-work", 2), session("w3", "-work", 3),
+        ];
+        let rules = [rule("*scratch*", None, Some(60)), rule("*", Some(1), None)];
+        assert_eq!(run(&rules, &sessions), [(0, vec!["s-old".into()]), (1, vec!["w2".into(), "w3".into()])],
+            "the catch-all does not count the scratch sessions");
+        let reversed = [rule("*", Some(1), None), rule("*scratch*", None, Some(60))];
+        assert_eq!(run(&reversed, &sessions), [(0, vec!["w2".into(), "w3".into(), "s-old".into()])],
+            "the catch-all keeps the newest of each project, and the scratch rule never applies");
+    }
+
+    #[test]
+    fn max_count_applies_to_each_project_on_its_own() {
+        let sessions: Vec<Session> = ["-a", "-b", "-c"].iter()
+            .flat_map(|p| (1..=3).map(move |d| session(&format!("{}{}", &p[1..], d), p, d)))
+            .collect();
+        assert_eq!(run(&[rule("*", Some(2), None)], &sessions), [(0, vec!["a3".into(), "b3".into(), "c3".into()])]);
+        assert!(run(&[rule("*", Some(3), None)], &sessions).is_empty(), "nine sessions, but only three per project");
+        assert_eq!(run(&[rule("-b", Some(0), None), rule("*", Some(1), None)], &sessions),
+            [(0, vec!["b1".into(), "b2".into(), "b3".into()]), (1, vec!["a2".into(), "c2".into(), "a3".into(), "c3".into()])]);
+    }
+
+    #[test]
+    fn exempt_sessions_neither_go_nor_count() {
+        let mut sessions = vec![session("a", "-p", 1), session("b", "-p", 2), session("c", "-p", 3)];
+        sessions[0].tags.push("keep".into());
+        sessions[2].tags.push("keep".into());
+        assert_eq!(run(&[rule("-p", Some(1), Some(0))], &sessions), [(0, vec!["b".into()])]);
+        assert!(run(&[rule("-p", Some(1), None)], &sessions).is_empty(), "b is the only non-exempt session");
+    }
+
+    #[test]
+    fn rules_match_the_working_directory_and_ties_break_by_id() {
+        let mut sessions = vec![session("z", "-h-me-tmp", 5), session("y", "-h-me-tmp", 5), session("x", "-other", 5)];
+        sessions[0].cwd = Some("/h/me/tmp".into());
+        sessions[1].cwd = Some("/h/me/tmp".into());
+        assert_eq!(run(&[rule("/h/*/tmp", Some(1), None)], &sessions), [(0, vec!["z".into()])]);
+        assert!(run(&[rule("/nowhere/*", Some(0), None)], &sessions).is_empty());
+        assert_eq!(rule("/h/*/tmp", Some(20), Some(60)).describe(), "/h/*/tmp: 20 newest, 60 days");
+        assert_eq!(rule("*", None, None).describe(), "*: no limit");
+    }
+}
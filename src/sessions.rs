@@ -1,43 +1,244 @@
+use crate::archive::{self, ArchivedSession, Manifest};
+use crate::audit::{self, AuditEntry, AuditLog};
+use crate::health::{self, HealthReport};
+use crate::index::SearchIndex;
+use crate::retention::RetentionRule;
+use crate::slim;
+use crate::text;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 
 const DISPLAY_NAME_MAX_LEN: usize = 60;
 const BYTES_PER_MB: u64 = 1024 * 1024;
+const SCAN_TIMES_FILE: &str = "scan_times.json";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SortBy {
     Date,
     Size,
     Messages,
+    Tokens,
+    Cost,
+    Name,
+    Project,
+}
+
+impl SortBy {
+    pub const ALL: [SortBy; 7] = [SortBy::Date, SortBy::Size, SortBy::Messages, SortBy::Tokens, SortBy::Cost, SortBy::Name, SortBy::Project];
+
+    /// Newest, largest, longest or costliest first; names and projects from A to Z.
+    pub fn compare(self, a: &Session, b: &Session) -> std::cmp::Ordering {
+        match self {
+            SortBy::Date => b.modified.cmp(&a.modified),
+            SortBy::Size => b.size.cmp(&a.size),
+            SortBy::Messages => b.message_count.cmp(&a.message_count),
+            SortBy::Tokens => b.tokens.cmp(&a.tokens),
+            SortBy::Cost => b.usage.cost.total_cmp(&a.usage.cost),
+            SortBy::Name => a.display_name().to_lowercase().cmp(&b.display_name().to_lowercase()),
+            SortBy::Project => a.project.to_lowercase().cmp(&b.project.to_lowercase()),
+        }
+    }
+}
+
+/// `color` in the config: `auto` follows `NO_COLOR` and `TERM=dumb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     pub sort_by: Option<SortBy>,
+    /// Sort oldest, smallest or shortest first instead.
+    pub sort_reversed: Option<bool>,
+    /// Orders sessions that tie on `sort_by`, always in its own default direction.
+    pub sort_then: Option<SortBy>,
+    /// Show the list grouped under project headings (default off).
+    pub group_by_project: Option<bool>,
     pub filter_query: Option<String>,
+    pub prune_skip_keep: Option<bool>,
+    /// Tool results above this many bytes are truncated by the slim action (default 4096).
+    pub slim_threshold: Option<usize>,
+    /// Ask before quitting with a pending selection (default on).
+    pub confirm_quit: Option<bool>,
+    /// Log deletions and history rewrites to `audit.log` (default on).
+    pub audit_log: Option<bool>,
+    pub color: Option<ColorMode>,
+    /// Require a capital `Y` to delete sessions that still have open todos (default off).
+    pub block_delete_with_open_todos: Option<bool>,
+    /// Confirm popups listing more than this many items ask for the count to be typed instead
+    /// of `y` (default off).
+    pub confirm_threshold: Option<usize>,
+    /// Note how much the sessions grew since the last launch in the status bar (default on).
+    pub size_delta_note: Option<bool>,
+    /// Keep transcript bookmarks in the sidecar file across runs (default off).
+    pub persist_bookmarks: Option<bool>,
+    /// Transcripts larger than this many bytes open on their last chunk of this size, reading
+    /// earlier chunks as the viewer scrolls up (default 4 MiB).
+    pub transcript_chunk_bytes: Option<u64>,
+    /// How much of each session the metadata cache keeps (default `normal`).
+    pub cache_detail: Option<CacheDetail>,
+    /// Remove deleted sessions and pruned orphans for good instead of moving them to the OS
+    /// trash (default off).
+    pub hard_delete: Option<bool>,
+    /// Where `Z` writes archives and the backups of slim, redact and repair go; `~/` is expanded
+    /// (default `~/.claude/archives`).
+    pub archive_dir: Option<String>,
+    /// Order the triage screen walks the listed sessions in (default `oldest`).
+    pub triage_order: Option<TriageOrder>,
+    /// List columns in order, each optionally `name:width`; see `columns::parse_columns`.
+    pub columns: Option<Vec<String>>,
+    /// Per-project retention rules applied from the prune menu or `--apply-retention`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retention: Vec<RetentionRule>,
+    /// Backups older than this many days are offered for deletion at startup (default off). The
+    /// prune menu's empty-trash entry uses it too, or 30 days when unset.
+    pub trash_retention_days: Option<u64>,
+    /// Warn in the status bar once the backup trash passes this many MB (default 500).
+    pub trash_warn_mb: Option<u64>,
+    /// Projects whose directory took longer than this many ms to load last time are left out
+    /// of the initial load and scanned on request from the project picker (default off).
+    pub slow_scan_threshold_ms: Option<u64>,
+    /// Append a diagnostic log of scans, cache use, deletes and history rewrites here; the
+    /// `--log-file` flag overrides it.
+    pub log_file: Option<String>,
+    /// Capture the mouse so clicking a column header sorts by it (default off, since it takes
+    /// over the terminal's own text selection).
+    pub mouse: Option<bool>,
+    /// Watch `projects` and `todos` and reload when sessions change on disk (default on).
+    pub auto_refresh: Option<bool>,
+    /// Run through `sh -c` after a headless run (`--apply-retention`, `prune`, `delete`) deletes
+    /// more than `notify_threshold` sessions; `{summary_file}` (the full report), `{freed_bytes}`
+    /// and `{deleted_count}` are filled in.
+    pub notify_command: Option<String>,
+    /// Sessions a run must delete more than before `notify_command` runs (default 0).
+    pub notify_threshold: Option<usize>,
+    /// Run `notify_command` after deletes and prunes in the TUI too (default off).
+    pub notify_in_tui: Option<bool>,
+    /// USD per million tokens by model name fragment (`"opus-4-5"`, `"sonnet"`), overriding the
+    /// built-in prices for the cost column.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pricing: BTreeMap<String, Price>,
+    /// Shell commands bound to keys in the session list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_commands: Vec<CustomCommand>,
+    /// Highlighted session id per project, restored when the TUI is opened on that project.
+    #[serde(default)]
+    pub last_selected: HashMap<String, String>,
+    /// Projects folded to their heading in the grouped view.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub collapsed_projects: BTreeSet<String>,
+    /// Filter queries by name, recalled from the saved filter picker.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub saved_filters: BTreeMap<String, String>,
+    /// Where `save` writes; `None` (as in `Config::default()`) keeps the config in memory only.
+    #[serde(skip)]
+    path: Option<PathBuf>,
 }
 
-impl Config {
-    fn path() -> PathBuf {
-        dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".config/claude-sessions-tui/config.json")
+/// What `sessions_tui_cache.json` keeps per session. Leaner levels make the cache smaller at
+/// the price of work redone later: a clipped first prompt, and summaries read when a session
+/// is previewed rather than at scan time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheDetail {
+    /// First prompt clipped to 200 characters, no summaries.
+    Minimal,
+    /// First prompt clipped to 2000 characters.
+    #[default]
+    Normal,
+    /// Everything, unclipped.
+    Rich,
+}
+
+impl CacheDetail {
+    fn first_message_limit(self) -> Option<usize> {
+        match self {
+            CacheDetail::Minimal => Some(200),
+            CacheDetail::Normal => Some(2000),
+            CacheDetail::Rich => None,
+        }
     }
+}
 
+/// `triage_order` in the config: which sessions the triage screen (`F2`) offers first.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TriageOrder {
+    #[default]
+    Oldest,
+    Largest,
+}
+
+/// A user command run on the highlighted session.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CustomCommand {
+    /// A single character, which must not be a built-in key.
+    pub key: String,
+    pub label: String,
+    /// Run through `sh -c`; `{path}`, `{id}` and `{project}` are replaced with shell-quoted values.
+    pub command: String,
+}
+
+impl CustomCommand {
+    pub fn expand(&self, s: &Session) -> String {
+        self.command
+            .replace("{path}", &shell_quote(&s.path.to_string_lossy()))
+            .replace("{id}", &shell_quote(&s.id))
+            .replace("{project}", &shell_quote(&s.project))
+    }
+}
+
+/// `s` in single quotes, safe to paste into an `sh -c` line.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// `path` with a leading `~/` resolved against the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config/claude-sessions-tui")
+}
+
+impl Config {
     pub fn load() -> Self {
-        fs::read_to_string(Self::path())
+        Self::load_from(config_dir().join("config.json"))
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let mut config: Self = fs::read_to_string(&path)
             .ok()
             .and_then(|c| serde_json::from_str(&c).ok())
-            .unwrap_or_default()
+            .unwrap_or_default();
+        config.path = Some(path);
+        config
+    }
+
+    /// Where `save` writes, if anywhere.
+    pub fn file(&self) -> Option<&Path> {
+        self.path.as_deref()
     }
 
     pub fn save(&self) -> io::Result<()> {
-        let p = Self::path();
+        let Some(p) = &self.path else { return Ok(()) };
         if let Some(parent) = p.parent() {
             fs::create_dir_all(parent)?;
         }
@@ -45,6 +246,135 @@ impl Config {
     }
 }
 
+/// The directory name Claude uses for a project: its path with every non-alphanumeric
+/// character replaced by `-`.
+pub fn encode_project_path(path: &str) -> String {
+    path.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect()
+}
+
+/// Levenshtein distance over chars, for suggesting near misses.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + (ca != *cb) as usize).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// One user or assistant message of a transcript, with the jsonl line it came from.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub role: String,
+    pub text: String,
+    pub record: usize,
+    pub model: Option<String>,
+}
+
+impl LogEntry {
+    pub fn render(&self) -> String {
+        format!("\n[{}]\n{}\n", self.role.to_uppercase(), self.text)
+    }
+
+    /// Contents of the fenced (``` or ~~~) code blocks in the text, without the fences. A block
+    /// left open runs to the end.
+    pub fn code_blocks(&self) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut open: Option<(&str, Vec<&str>)> = None;
+        for line in self.text.lines() {
+            let t = line.trim_start();
+            let fence = ["```", "~~~"].into_iter().find(|f| t.starts_with(f));
+            match (&mut open, fence) {
+                (Some((f, body)), Some(g)) if *f == g => { blocks.push(body.join("\n")); open = None; }
+                (Some((_, body)), _) => body.push(line),
+                (None, Some(g)) => open = Some((g, Vec::new())),
+                (None, None) => {}
+            }
+        }
+        if let Some((_, body)) = open { blocks.push(body.join("\n")); }
+        blocks
+    }
+}
+
+/// User-owned per-session metadata, kept apart from the scan cache so a rescan never erases it.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Sidecar {
+    #[serde(default)]
+    pub names: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub hidden: HashSet<String>,
+    /// Transcript bookmarks per session: letter to record number. Only written when
+    /// `persist_bookmarks` is on.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub bookmarks: HashMap<String, BTreeMap<char, usize>>,
+    /// Sessions listed first whatever the sort.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub pinned: HashSet<String>,
+    /// Sessions deletes and prunes skip.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub protected: HashSet<String>,
+    /// Free-form notes, such as why a session is kept.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub notes: HashMap<String, String>,
+}
+
+impl Sidecar {
+    pub fn set_name(&mut self, id: &str, name: &str) {
+        let name = name.trim();
+        if name.is_empty() { self.names.remove(id); } else { self.names.insert(id.to_string(), name.to_string()); }
+    }
+
+    pub fn set_note(&mut self, id: &str, note: &str) {
+        let note = note.trim();
+        if note.is_empty() { self.notes.remove(id); } else { self.notes.insert(id.to_string(), note.to_string()); }
+    }
+
+    /// Applies a comma-separated edit like `keep, -bug`: plain tags are added, `-tag` removes.
+    pub fn edit_tags(&mut self, id: &str, edit: &str) {
+        let tags = self.tags.entry(id.to_string()).or_default();
+        for tok in edit.split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()) {
+            if let Some(t) = tok.strip_prefix('-') {
+                tags.retain(|x| x != t);
+            } else {
+                let t = tok.strip_prefix('+').unwrap_or(&tok).to_string();
+                if !tags.contains(&t) { tags.push(t); }
+            }
+        }
+        if tags.is_empty() { self.tags.remove(id); }
+    }
+
+    /// Drops every entry for a session that no longer exists.
+    pub fn forget(&mut self, id: &str) -> bool {
+        let mut hit = self.names.remove(id).is_some();
+        hit |= self.tags.remove(id).is_some();
+        hit |= self.hidden.remove(id);
+        hit |= self.bookmarks.remove(id).is_some();
+        hit |= self.pinned.remove(id);
+        hit |= self.protected.remove(id);
+        hit |= self.notes.remove(id).is_some();
+        hit
+    }
+}
+
+/// One of the files this tool writes for itself, as listed under self maintenance in the stats.
+#[derive(Debug, PartialEq)]
+pub struct Artifact {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Entries keyed by a session or project that no longer exists; `None` for files that
+    /// are not keyed that way.
+    pub stale: Option<usize>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Session {
     pub id: String,
@@ -53,172 +383,1086 @@ pub struct Session {
     pub size: u64,
     pub message_count: usize,
     pub first_message: String,
+    pub prompt_kind: PromptKind,
     pub modified: SystemTime,
+    /// `customTitle` from the transcript.
     pub custom_name: Option<String>,
+    /// Title from the latest `type: "summary"` record Claude Code wrote.
+    pub summary_title: Option<String>,
+    /// Name given in this tool, kept in the sidecar.
+    pub user_name: Option<String>,
+    /// Text of the session's first `history.jsonl` entry, joined by session id at load.
+    pub history_prompt: Option<String>,
+    pub tags: Vec<String>,
+    pub hidden: bool,
+    /// Listed first and spared by retention and prunes.
+    pub pinned: bool,
+    /// Skipped by deletes, prunes and retention until unprotected.
+    pub protected: bool,
+    /// Note written in this tool, kept in the sidecar.
+    pub note: Option<String>,
     pub related_files: Vec<PathBuf>,
+    /// Working directory the session ran in, from its transcript.
+    pub cwd: Option<String>,
+    /// `cwd` no longer exists on disk.
+    pub project_missing: bool,
+    /// Byte offset of the last assistant reply, when the scan found one.
+    pub last_reply_at: Option<u64>,
+    /// `None` when the cache leaves summaries out (`CacheDetail::Minimal`) and it has not been
+    /// loaded yet; see `SessionManager::summary_of`.
+    pub summary: Option<Summary>,
+    /// Input, cache-write and output tokens over all assistant turns; cache reads are left out
+    /// since they re-count context already paid for.
+    pub tokens: u64,
+    pub usage: Usage,
+    /// Assistant replies per model, by `short_model` name.
+    pub models: BTreeMap<String, usize>,
+}
+
+/// Writes to a sibling temp file and renames it over `path`, so readers never see a partial file.
+pub fn atomic_write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents).and_then(|_| fs::rename(&tmp, path)).inspect_err(|_| { fs::remove_file(&tmp).ok(); })
+}
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes > BYTES_PER_MB {
+        format!("{:.1}MB", bytes as f64 / BYTES_PER_MB as f64)
+    } else {
+        format!("{}KB", bytes / 1024)
+    }
+}
+
+/// `42s`, `5m`, `3h`, then the date: how long before `now` `t` was.
+pub fn format_age(t: SystemTime, now: SystemTime) -> String {
+    let elapsed = now.duration_since(t).unwrap_or_default().as_secs();
+    if elapsed < 60 { format!("{}s", elapsed) }
+    else if elapsed < 3600 { format!("{}m", elapsed / 60) }
+    else if elapsed < 86400 { format!("{}h", elapsed / 3600) }
+    else {
+        let dt: chrono::DateTime<chrono::Local> = t.into();
+        dt.format("%d %b %y").to_string()
+    }
+}
+
+/// Token counts in the list: `950`, `12.3k`, `4.1M`.
+pub fn format_tokens(n: u64) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", n as f64 / 1e3),
+        _ => format!("{:.1}M", n as f64 / 1e6),
+    }
 }
 
 impl Session {
     pub fn size_str(&self) -> String {
-        if self.size > BYTES_PER_MB {
-            format!("{:.1}MB", self.size as f64 / BYTES_PER_MB as f64)
-        } else {
-            format!("{}KB", self.size / 1024)
-        }
+        format_size(self.size)
+    }
+
+    /// The names a session could go by, best first; the first prompt is the fallback.
+    pub fn names(&self) -> impl Iterator<Item = (NameSource, &str)> {
+        [(NameSource::Renamed, &self.user_name), (NameSource::CustomTitle, &self.custom_name), (NameSource::Summary, &self.summary_title)]
+            .into_iter()
+            .filter_map(|(src, n)| n.as_deref().filter(|n| !n.trim().is_empty()).map(|n| (src, n)))
+    }
+
+    pub fn name_source(&self) -> NameSource {
+        self.names().next().map_or(NameSource::FirstPrompt, |(src, _)| src)
     }
 
     pub fn display_name(&self) -> String {
-        if let Some(ref name) = self.custom_name {
-            if !name.trim().is_empty() { return name.clone(); }
-        }
+        if let Some((_, name)) = self.names().next() { return name.to_string(); }
         let clean = self.first_message.replace('\n', " ");
-        if clean.len() > DISPLAY_NAME_MAX_LEN {
-            format!("{}...", &clean[..DISPLAY_NAME_MAX_LEN])
-        } else {
-            clean
-        }
+        text::ellipsize(&clean, DISPLAY_NAME_MAX_LEN)
     }
 
-    pub fn formatted_age(&self) -> String {
-        let elapsed = SystemTime::now().duration_since(self.modified).unwrap_or_default().as_secs();
-        if elapsed < 60 { format!("{}s", elapsed) }
-        else if elapsed < 3600 { format!("{}m", elapsed / 60) }
-        else if elapsed < 86400 { format!("{}h", elapsed / 3600) }
-        else {
-            let dt: chrono::DateTime<chrono::Local> = self.modified.into();
-            dt.format("%d %b %y").to_string()
-        }
+    /// The display name without truncation.
+    pub fn full_name(&self) -> &str {
+        self.names().next().map_or(&self.first_message, |(_, n)| n)
     }
 
-    pub fn get_todos(&self) -> Vec<String> {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Bytes on disk for the transcript and everything related to it.
+    pub fn footprint(&self) -> u64 {
+        self.size + self.related_files.iter().map(|p| audit::disk_size(p)).sum::<u64>()
+    }
+
+    pub fn formatted_age(&self, now: SystemTime) -> String {
+        format_age(self.modified, now)
+    }
+
+    /// Items from the session's todo files with their status (`pending`, `in_progress`,
+    /// `completed`; empty when the file has none).
+    pub fn todos(&self) -> Vec<Todo> {
         self.related_files.iter()
-            .filter(|p| p.parent().map_or(false, |par| par.ends_with("todos")))
+            .filter(|p| p.parent().is_some_and(|par| par.ends_with("todos")))
             .filter_map(|p| fs::read_to_string(p).ok())
             .filter_map(|c| serde_json::from_str::<Vec<Value>>(&c).ok())
-            .flat_map(|arr| arr)
+            .flatten()
             .filter_map(|item| {
-                item.get("title").or_else(|| item.get("content"))
-                    .and_then(|v| v.as_str().map(String::from))
+                let text = item.get("title").or_else(|| item.get("content")).and_then(Value::as_str)?;
+                let status = item.get("status").and_then(Value::as_str).unwrap_or_default();
+                Some(Todo { text: text.to_string(), status: status.to_string() })
             })
             .collect()
     }
+
+    pub fn get_todos(&self) -> Vec<String> {
+        self.todos().into_iter().map(|t| t.text).collect()
+    }
+
+    /// Todos still pending or in progress.
+    pub fn open_todos(&self) -> usize {
+        self.todos().iter().filter(|t| t.is_open()).count()
+    }
+}
+
+/// Related directories whose contents may be deleted one file at a time.
+const BROWSABLE_DIRS: [&str; 2] = ["file-history", "session-env"];
+
+/// A file inside one of a session's `BROWSABLE_DIRS`, or in the trash.
+#[derive(Debug, Clone)]
+pub struct RelatedEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+pub struct Todo {
+    pub text: String,
+    pub status: String,
+}
+
+impl Todo {
+    pub fn is_open(&self) -> bool {
+        matches!(self.status.as_str(), "pending" | "in_progress")
+    }
+}
+
+/// Where a session's display name comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameSource {
+    Renamed,
+    CustomTitle,
+    Summary,
+    FirstPrompt,
+}
+
+impl NameSource {
+    pub fn describe(self) -> &'static str {
+        match self {
+            NameSource::Renamed => "renamed here",
+            NameSource::CustomTitle => "custom title",
+            NameSource::Summary => "summary record",
+            NameSource::FirstPrompt => "first prompt",
+        }
+    }
+}
+
+/// Rough category of a session's first prompt, shown as a glyph in the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum PromptKind {
+    #[default]
+    Other = 0,
+    Code = 1,
+    Question = 2,
+    Task = 3,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+const QUESTION_WORDS: [&str; 14] = ["how", "what", "why", "where", "when", "which", "who", "can", "could", "is", "are", "does", "do", "should"];
+const TASK_MAX_WORDS: usize = 12;
+
+impl PromptKind {
+    pub fn from_u8(b: u8) -> Self {
+        match b { 1 => Self::Code, 2 => Self::Question, 3 => Self::Task, _ => Self::Other }
+    }
+
+    /// Cheap, deterministic guess: code fences and diffs are code, trailing `?` or a leading
+    /// question word is a question, a slash command or a short one-liner is a task.
+    pub fn classify(prompt: &str) -> Self {
+        let p = prompt.trim();
+        if p.is_empty() { return Self::Other; }
+        let lines: Vec<&str> = p.lines().collect();
+        let diff_like = lines.iter().any(|l| l.starts_with("diff --git") || l.starts_with("@@ ") || l.starts_with("+++ "));
+        let code_lines = lines.iter().filter(|l| { let l = l.trim_end(); l.ends_with(';') || l.ends_with('{') || l.ends_with('}') }).count();
+        if p.starts_with("```") || diff_like || (lines.len() >= 3 && code_lines * 2 >= lines.len()) {
+            return Self::Code;
+        }
+        let first_word = p.split_whitespace().next().unwrap_or("").to_lowercase();
+        if p.ends_with('?') || (QUESTION_WORDS.contains(&first_word.as_str()) && lines.len() == 1) {
+            return Self::Question;
+        }
+        if p.starts_with('/') || (lines.len() == 1 && p.split_whitespace().count() <= TASK_MAX_WORDS) {
+            return Self::Task;
+        }
+        Self::Other
+    }
+}
+
+/// USD per million tokens for one model. The cache rates default to 1.25x (writes) and 0.1x
+/// (reads) the input rate, which is how Anthropic prices them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+}
+
+impl Price {
+    const fn new(input: f64, output: f64) -> Self {
+        Price { input, output, cache_write: None, cache_read: None }
+    }
+
+    pub fn cost(&self, u: &Usage) -> f64 {
+        let write = self.cache_write.unwrap_or(self.input * 1.25);
+        let read = self.cache_read.unwrap_or(self.input * 0.1);
+        (u.input as f64 * self.input + u.output as f64 * self.output + u.cache_write as f64 * write + u.cache_read as f64 * read) / 1e6
+    }
+}
+
+/// API prices by model name fragment, used where `Config.pricing` has no match. Opus before
+/// 4.5 kept the old prices.
+const DEFAULT_PRICES: [(&str, Price); 9] = [
+    ("opus-4-1", Price::new(15.0, 75.0)),
+    ("opus-4-2", Price::new(15.0, 75.0)),
+    ("3-opus", Price::new(15.0, 75.0)),
+    ("opus", Price::new(5.0, 25.0)),
+    ("sonnet", Price::new(3.0, 15.0)),
+    ("haiku-4", Price::new(1.0, 5.0)),
+    ("3-5-haiku", Price::new(0.8, 4.0)),
+    ("3-haiku", Price::new(0.25, 1.25)),
+    ("haiku", Price::new(1.0, 5.0)),
+];
+
+/// The price for `model`: the configured entry with the longest name the model contains,
+/// else the longest matching default.
+pub fn price_for(pricing: &BTreeMap<String, Price>, model: &str) -> Option<Price> {
+    let longest = |found: Option<(usize, Price)>, (frag, p): (&str, Price)| match found {
+        Some((len, _)) if len >= frag.len() => found,
+        _ if model.contains(frag) => Some((frag.len(), p)),
+        _ => found,
+    };
+    pricing.iter().map(|(f, p)| (f.as_str(), *p)).fold(None, longest)
+        .or_else(|| DEFAULT_PRICES.into_iter().fold(None, longest))
+        .map(|(_, p)| p)
+}
+
+/// Token counts from the `usage` of a session's assistant turns, and what they would cost at
+/// API prices.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    /// Assistant replies the counts cover.
+    #[serde(default)]
+    pub replies: usize,
+    pub input: u64,
+    pub output: u64,
+    pub cache_write: u64,
+    pub cache_read: u64,
+    /// Estimated USD, worked out at load from the pricing; models without a price count
+    /// nothing.
+    #[serde(skip)]
+    pub cost: f64,
+}
+
+impl Usage {
+    /// One reply's `message.usage`.
+    fn of(usage: &Value) -> Self {
+        let n = |k: &str| usage.get(k).and_then(Value::as_u64).unwrap_or(0);
+        Usage {
+            replies: 1,
+            input: n("input_tokens"),
+            output: n("output_tokens"),
+            cache_write: n("cache_creation_input_tokens"),
+            cache_read: n("cache_read_input_tokens"),
+            cost: 0.0,
+        }
+    }
+
+    /// See `Session::tokens`.
+    pub fn tokens(&self) -> u64 {
+        self.input + self.cache_write + self.output
+    }
+
+    pub fn add(&mut self, other: &Usage) {
+        self.replies += other.replies;
+        self.input += other.input;
+        self.output += other.output;
+        self.cache_write += other.cache_write;
+        self.cache_read += other.cache_read;
+        self.cost += other.cost;
+    }
+
+    /// `12.3k in, 4.1k out, 300.0k cache write, 1.2M cache read`.
+    pub fn describe(&self) -> String {
+        format!("{} in, {} out, {} cache write, {} cache read", format_tokens(self.input), format_tokens(self.output),
+            format_tokens(self.cache_write), format_tokens(self.cache_read))
+    }
+}
+
+/// `claude-opus-4-5-20251101` as `opus-4-5`: without the vendor prefix and the date.
+pub fn short_model(model: &str) -> &str {
+    let m = model.strip_prefix("claude-").unwrap_or(model);
+    match m.rsplit_once('-') {
+        Some((rest, date)) if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) => rest,
+        _ => m,
+    }
+}
+
+/// `opus-4-5: 40 msgs, sonnet-4-5: 12 msgs`, most used first.
+pub fn describe_models(models: &BTreeMap<String, usize>) -> String {
+    let mut by_use: Vec<_> = models.iter().collect();
+    by_use.sort_by_key(|(_, n)| std::cmp::Reverse(**n));
+    by_use.iter().map(|(m, n)| format!("{}: {} msg{}", m, n, if **n == 1 { "" } else { "s" })).collect::<Vec<_>>().join(", ")
+}
+
+/// An estimated cost: `$1.23`, or `<$0.01` for a little above nothing.
+pub fn format_cost(usd: f64) -> String {
+    if usd > 0.0 && usd < 0.005 { "<$0.01".into() } else { format!("${:.2}", usd) }
+}
+
+/// What a session was about, pieced together from its records when the first prompt says
+/// little ("continue where we left off").
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// Longest of the first `SUMMARY_PROMPTS` human prompts.
+    pub focus: Option<String>,
+    /// Files passed to Edit, MultiEdit, Write and NotebookEdit, in first-touched order.
+    pub files: Vec<String>,
+    /// Last sentence of the last assistant reply.
+    pub outcome: Option<String>,
+}
+
+const SUMMARY_PROMPTS: usize = 10;
+const SUMMARY_TEXT_MAX: usize = 300;
+const EDIT_TOOLS: [&str; 4] = ["Edit", "MultiEdit", "Write", "NotebookEdit"];
+
+impl Summary {
+    /// Touched files shown relative to `cwd` where they fall under it.
+    pub fn files_relative_to(&self, cwd: Option<&str>) -> Vec<&str> {
+        self.files.iter()
+            .map(|f| cwd.and_then(|c| f.strip_prefix(c)).and_then(|r| r.strip_prefix('/')).unwrap_or(f))
+            .collect()
+    }
+
+    fn clip(text: &str) -> String {
+        let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        text::ellipsize(&flat, SUMMARY_TEXT_MAX)
+    }
+
+    /// The last sentence of `text` that has any letters in it.
+    fn last_sentence(text: &str) -> Option<String> {
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            let next = text[i + c.len_utf8()..].chars().next();
+            if c == '\n' || (matches!(c, '.' | '!' | '?') && next.is_none_or(char::is_whitespace)) {
+                sentences.push(&text[start..i + c.len_utf8()]);
+                start = i + c.len_utf8();
+            }
+        }
+        sentences.push(&text[start..]);
+        sentences.into_iter().rev()
+            .map(|s| s.trim().trim_start_matches(['#', '-', '*', ' ']))
+            .find(|s| s.chars().any(char::is_alphabetic))
+            .map(Self::clip)
+    }
+
+    /// File paths from the edit-style `tool_use` blocks in an assistant message.
+    fn touched(content: &Value) -> impl Iterator<Item = &str> {
+        tool_paths(content, &EDIT_TOOLS)
+    }
+}
+
+/// File paths passed to any of `tools` in the `tool_use` blocks of a message's content.
+fn tool_paths<'a>(content: &'a Value, tools: &'a [&str]) -> impl Iterator<Item = &'a str> {
+    content.as_array().into_iter().flatten()
+        .filter(|b| b.get("type").and_then(Value::as_str) == Some("tool_use")
+            && b.get("name").and_then(Value::as_str).is_some_and(|n| tools.contains(&n)))
+        .filter_map(|b| b.pointer("/input/file_path").or_else(|| b.pointer("/input/notebook_path")).and_then(Value::as_str))
+}
+
+/// Tools whose file argument counts as the session referencing that file.
+const REFERENCE_TOOLS: [&str; 5] = ["Read", "Edit", "MultiEdit", "Write", "NotebookEdit"];
+
+/// Forward slashes, no empty or `.` segments, lowercase: the form file queries are matched in.
+pub fn normalize_path(p: &str) -> String {
+    let p = p.trim().replace('\\', "/").to_lowercase();
+    let mut out = p.split('/').filter(|seg| !seg.is_empty() && *seg != ".").collect::<Vec<_>>().join("/");
+    if p.starts_with('/') { out.insert(0, '/'); }
+    out
+}
+
+/// Entries parsed from the lines of a session log that start in `start..`, the part the
+/// expanded viewer holds of a large log.
+pub struct LogChunk {
+    pub start: u64,
+    /// Record number of the line at `start`.
+    pub first_record: usize,
+    pub entries: Vec<LogEntry>,
+}
+
+/// The first line start at or after `pos`.
+fn line_start(f: &mut fs::File, pos: u64) -> io::Result<u64> {
+    if pos == 0 { return Ok(0); }
+    f.seek(SeekFrom::Start(pos - 1))?;
+    let mut skipped = Vec::new();
+    let n = io::BufReader::new(f).read_until(b'\n', &mut skipped)?;
+    Ok(pos - 1 + n as u64)
+}
+
+/// Newlines in the first `len` bytes `r` yields.
+fn count_lines(r: &mut impl Read, len: u64) -> io::Result<usize> {
+    let mut r = io::BufReader::with_capacity(1 << 16, r.take(len));
+    let mut count = 0;
+    loop {
+        let buf = r.fill_buf()?;
+        if buf.is_empty() { return Ok(count); }
+        count += buf.iter().filter(|&&b| b == b'\n').count();
+        let n = buf.len();
+        r.consume(n);
+    }
+}
+
+/// The lines of a jsonl file with the byte offset each starts at, read one at a time so memory
+/// stays bounded by the longest line. Invalid UTF-8 is replaced rather than ending the read.
+pub(crate) fn jsonl_lines(path: &Path) -> io::Result<impl Iterator<Item = (u64, String)>> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let mut offset = 0;
+    let mut buf = Vec::new();
+    Ok(std::iter::from_fn(move || {
+        buf.clear();
+        let n = reader.read_until(b'\n', &mut buf).ok().filter(|&n| n > 0)?;
+        let at = offset;
+        offset += n as u64;
+        let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        Some((at, String::from_utf8_lossy(line).into_owned()))
+    }))
+}
+
+/// Every file the session read or wrote whose normalized path contains `query` (already
+/// normalized), in first-seen order. Streams the transcript rather than loading it.
+pub fn referenced_files(path: &Path, query: &str) -> io::Result<Vec<String>> {
+    let mut found: Vec<String> = Vec::new();
+    for (_, line) in jsonl_lines(path)? {
+        if !line.contains("tool_use") { continue; }
+        let Ok(v) = serde_json::from_str::<Value>(&line) else { continue };
+        let Some(content) = v.pointer("/message/content") else { continue };
+        for p in tool_paths(content, &REFERENCE_TOOLS) {
+            if normalize_path(p).contains(query) && !found.iter().any(|f| f == p) { found.push(p.to_string()); }
+        }
+    }
+    Ok(found)
+}
+
+/// The text of a user or assistant record; what content search looks through.
+pub(crate) fn message_text(record: &Value) -> Option<String> {
+    if !matches!(record.get("type").and_then(Value::as_str), Some("user" | "assistant")) { return None; }
+    record.pointer("/message/content").map(SessionManager::extract_text)
+}
+
+/// Longest snippet `content_match` returns.
+const SNIPPET_WIDTH: usize = 100;
+
+/// The first line of user or assistant message text containing `query` (already lowercase),
+/// trimmed to a snippet that starts a little before the match. Streams the transcript and only
+/// parses records whose raw line could contain the query.
+pub fn content_match(path: &Path, query: &str) -> io::Result<Option<String>> {
+    // JSON escapes quotes and backslashes, so lines can only be prefiltered without them.
+    let prefilter = !query.contains(['"', '\\']);
+    for (_, line) in jsonl_lines(path)? {
+        if prefilter && !line.to_lowercase().contains(query) { continue; }
+        let Ok(v) = serde_json::from_str::<Value>(&line) else { continue };
+        let Some(text) = message_text(&v) else { continue };
+        let Some(hit) = text.lines().find(|l| l.to_lowercase().contains(query)) else { continue };
+        let hit = hit.trim();
+        let at = hit.to_lowercase().find(query).unwrap_or(0);
+        let before = hit.to_lowercase()[..at].chars().count();
+        let skip = before.saturating_sub(SNIPPET_WIDTH / 3);
+        let rest: String = hit.chars().skip(skip).collect();
+        let snippet = if skip > 0 { format!("...{}", rest) } else { rest };
+        return Ok(Some(text::ellipsize(&snippet, SNIPPET_WIDTH)));
+    }
+    Ok(None)
+}
+
+/// One line of history.jsonl.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub session_id: String,
+    pub display: String,
+    pub project: String,
+    /// Milliseconds since the epoch, as written by Claude Code.
+    pub timestamp: Option<i64>,
+}
+
+impl HistoryEntry {
+    /// What `drop_history_entries` matches on; line numbers shift whenever Claude Code appends.
+    pub fn key(&self) -> (String, Option<i64>) {
+        (self.session_id.clone(), self.timestamp)
+    }
+
+    pub fn time_str(&self) -> String {
+        self.timestamp
+            .and_then(chrono::DateTime::from_timestamp_millis)
+            .map(|d| d.with_timezone(&chrono::Local).format("%d %b %y %H:%M").to_string())
+            .unwrap_or_else(|| "?".into())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct CachedMetadata {
     custom_name: Option<String>,
+    /// Latest summary record's title; empty when there is none and `None` for entries written
+    /// before it was tracked, which are rescanned.
+    #[serde(default)]
+    summary_title: Option<String>,
     message_count: usize,
     first_message: String,
     modified_ts: u64,
+    /// Lazily computed content hash, valid while `modified_ts` and `hashed_size` still match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+    #[serde(default)]
+    hashed_size: u64,
+    /// `PromptKind` as a byte; entries written before it existed are rescanned.
+    #[serde(default)]
+    prompt_kind: Option<u8>,
+    /// Working directory recorded in the transcript; empty when it has none and `None` for
+    /// entries written before it was tracked, which are rescanned.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Byte offset of the last assistant record with text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_reply_at: Option<u64>,
+    /// `None` for entries written before summaries existed, which are rescanned.
+    #[serde(default)]
+    summary: Option<Summary>,
+    /// Usage by `short_model` name, `""` for replies that name no model; `None` for entries
+    /// written before it was tracked, which are rescanned.
+    #[serde(default)]
+    usage_by_model: Option<BTreeMap<String, Usage>>,
+    /// Level the entry was stored at; `None` for entries written before levels, which hold
+    /// everything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<CacheDetail>,
 }
 
+impl CachedMetadata {
+    fn detail(&self) -> CacheDetail {
+        self.detail.unwrap_or(CacheDetail::Rich)
+    }
+
+    /// Drops what `level` does not keep; a richer entry is trimmed down, a leaner one untouched.
+    fn reduce(mut self, level: CacheDetail) -> Self {
+        if self.detail() <= level { return self; }
+        if let Some(limit) = level.first_message_limit() {
+            if let Some((at, _)) = self.first_message.char_indices().nth(limit) { self.first_message.truncate(at); }
+        }
+        if level == CacheDetail::Minimal { self.summary = None; }
+        self.detail = Some(level);
+        self
+    }
+}
+
+/// Totals for one project over the loaded sessions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectStats {
+    pub name: String,
+    pub count: usize,
+    pub size: u64,
+    pub empty: usize,
+    pub oldest: SystemTime,
+    pub newest: SystemTime,
+}
+
+impl ProjectStats {
+    /// `4 sessions (1 empty), 12.3MB, newest 2h, oldest 14 Mar 25`.
+    pub fn describe(&self, now: SystemTime) -> String {
+        let empty = if self.empty > 0 { format!(" ({} empty)", self.empty) } else { String::new() };
+        format!("{} session{}{}, {}, newest {}, oldest {}", self.count, if self.count == 1 { "" } else { "s" }, empty,
+            format_size(self.size), format_age(self.newest, now), format_age(self.oldest, now))
+    }
+}
+
+/// One `ProjectStats` per project, largest first (ties by name).
+pub fn project_stats<'a>(sessions: impl IntoIterator<Item = &'a Session>) -> Vec<ProjectStats> {
+    let mut by_name: BTreeMap<&str, ProjectStats> = BTreeMap::new();
+    for s in sessions {
+        let p = by_name.entry(&s.project).or_insert_with(|| ProjectStats {
+            name: s.project.clone(), count: 0, size: 0, empty: 0, oldest: s.modified, newest: s.modified,
+        });
+        p.count += 1;
+        p.size += s.size;
+        if s.message_count == 0 { p.empty += 1; }
+        p.oldest = p.oldest.min(s.modified);
+        p.newest = p.newest.max(s.modified);
+    }
+    let mut out: Vec<ProjectStats> = by_name.into_values().collect();
+    out.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    out
+}
+
+pub struct ScanOverview {
+    pub projects: PathBuf,
+    pub exists: bool,
+    pub project_dirs: usize,
+    /// Sub-agent transcripts, which are not listed as sessions.
+    pub agent_files: usize,
+}
+
+#[derive(Clone)]
 pub struct SessionManager {
     claude_root: PathBuf,
     cache_file: PathBuf,
     history_file: PathBuf,
+    index_file: PathBuf,
+    sidecar_file: PathBuf,
+    state_dir: PathBuf,
+    pub audit: AuditLog,
+    /// Set while another instance holds the lock: deletes, history rewrites, sidecar saves and
+    /// cache writes are refused.
+    pub read_only: bool,
+    pub cache_detail: CacheDetail,
+    /// Deleted sessions and pruned orphans go to the OS trash instead of being removed. Off
+    /// for a bare `new`; `configure` turns it on unless the config asks for `hard_delete`.
+    pub use_os_trash: bool,
+    /// Overrides `archives/` under the claude root; set by `configure` from `archive_dir`.
+    archive_root: Option<PathBuf>,
+    /// `Config.pricing`, checked before the built-in prices.
+    pub pricing: BTreeMap<String, Price>,
 }
 
 impl SessionManager {
-    pub fn new() -> Self {
-        let home = dirs::home_dir().expect("Home dir not found");
-        let claude_root = home.join(".claude");
+    /// Manager over `claude_root` (normally `~/.claude`), keeping its own sidecar state in `state_dir`.
+    pub fn new(claude_root: PathBuf, state_dir: PathBuf) -> Self {
         Self {
             history_file: claude_root.join("history.jsonl"),
             cache_file: claude_root.join("sessions_tui_cache.json"),
+            index_file: claude_root.join("sessions_tui_index.json"),
+            sidecar_file: state_dir.join("sessions.json"),
+            audit: AuditLog::new(state_dir.join("audit.log")),
+            state_dir,
             claude_root,
+            read_only: false,
+            cache_detail: CacheDetail::default(),
+            use_os_trash: false,
+            archive_root: None,
+            pricing: BTreeMap::new(),
         }
     }
 
-    fn load_cache(&self) -> HashMap<String, CachedMetadata> {
-        fs::File::open(&self.cache_file)
+    /// Applies the config's settings for auditing, cache detail, deleting, archiving and pricing.
+    pub fn configure(&mut self, config: &Config) {
+        self.audit.set_enabled(config.audit_log.unwrap_or(true));
+        self.cache_detail = config.cache_detail.unwrap_or_default();
+        self.use_os_trash = !config.hard_delete.unwrap_or(false);
+        self.archive_root = config.archive_dir.as_deref().map(expand_home);
+        self.pricing = config.pricing.clone();
+    }
+
+    /// Removes a file or directory, or moves it to the OS trash when `to_trash` is set.
+    fn remove(path: &Path, to_trash: bool) -> io::Result<()> {
+        if to_trash { return trash::delete(path).map_err(|e| io::Error::other(e.to_string())); }
+        if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) }
+    }
+
+    pub fn from_home() -> Self {
+        let home = dirs::home_dir().expect("Home dir not found");
+        Self::new(home.join(".claude"), config_dir())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.claude_root
+    }
+
+    /// Where this tool keeps a state file of its own, next to the sidecar.
+    pub fn state_file(&self, name: &str) -> PathBuf {
+        self.state_dir.join(name)
+    }
+
+    /// What is on disk under the projects directory, to explain an empty list.
+    pub fn overview(&self) -> ScanOverview {
+        let projects = self.claude_root.join("projects");
+        let dirs: Vec<PathBuf> = fs::read_dir(&projects).into_iter().flatten().flatten()
+            .map(|e| e.path()).filter(|p| p.is_dir()).collect();
+        let agent_files = dirs.iter()
+            .flat_map(|d| fs::read_dir(d).into_iter().flatten().flatten())
+            .filter(|e| { let n = e.file_name().to_string_lossy().into_owned(); n.starts_with("agent-") && n.ends_with(".jsonl") })
+            .count();
+        ScanOverview { exists: projects.is_dir(), projects, project_dirs: dirs.len(), agent_files }
+    }
+
+    pub fn project_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(self.claude_root.join("projects")).into_iter().flatten().flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Resolves a filesystem path, project directory name or trailing path component to a
+    /// project directory name. On failure returns the closest candidates, best first.
+    pub fn resolve_project(&self, arg: &str) -> Result<String, Vec<String>> {
+        let projects = self.project_names();
+        let path = fs::canonicalize(arg).map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|_| arg.to_string());
+        let encoded = encode_project_path(path.trim_end_matches('/')).to_lowercase();
+        if let Some(p) = projects.iter().find(|p| p.to_lowercase() == encoded) { return Ok(p.clone()); }
+
+        let suffix = format!("-{}", encode_project_path(arg.trim_matches('/')).to_lowercase());
+        let tails: Vec<&String> = projects.iter().filter(|p| p.to_lowercase().ends_with(&suffix)).collect();
+        if let [one] = tails[..] { return Ok(one.clone()); }
+        if !tails.is_empty() { return Err(tails.into_iter().cloned().collect()); }
+
+        let needle = suffix.trim_start_matches('-');
+        let mut close: Vec<(usize, &String)> = projects.iter().filter_map(|p| {
+            let lower = p.to_lowercase();
+            if lower.contains(needle) { return Some((0, p)); }
+            let tail = lower.get(lower.len().saturating_sub(needle.len())..).unwrap_or(&lower);
+            let d = edit_distance(needle, tail);
+            (d <= needle.len() / 3 + 1).then_some((d, p))
+        }).collect();
+        close.sort();
+        Err(close.into_iter().take(5).map(|(_, p)| p.clone()).collect())
+    }
+
+    pub fn load_sidecar(&self) -> Sidecar {
+        fs::read_to_string(&self.sidecar_file)
             .ok()
-            .and_then(|f| serde_json::from_reader(f).ok())
+            .and_then(|c| serde_json::from_str(&c).ok())
             .unwrap_or_default()
     }
 
+    pub fn save_sidecar(&self, sidecar: &Sidecar) -> io::Result<()> {
+        self.check_writable()?;
+        if let Some(parent) = self.sidecar_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic_write(&self.sidecar_file, serde_json::to_string_pretty(sidecar)?.as_bytes())
+    }
+
+    /// Session ids the sidecar holds something for whose transcript is gone.
+    fn stale_sidecar_ids(sidecar: &Sidecar, live: &HashSet<String>) -> BTreeSet<String> {
+        sidecar.names.keys().chain(sidecar.tags.keys()).chain(&sidecar.hidden).chain(sidecar.bookmarks.keys()).chain(&sidecar.pinned).chain(&sidecar.protected).chain(sidecar.notes.keys())
+            .filter(|id| !live.contains(*id)).cloned().collect()
+    }
+
+    /// This tool's own files with their size and how many of their entries are stale.
+    pub fn artifacts(&self) -> Vec<Artifact> {
+        let live = self.get_phys_ids();
+        let projects: HashSet<String> = self.project_names().into_iter().collect();
+        let size = |p: &Path| fs::metadata(p).map_or(0, |m| m.len());
+        vec![
+            Artifact { name: "metadata cache", path: self.cache_file.clone(), size: size(&self.cache_file),
+                stale: Some(self.load_cache().keys().filter(|id| !live.contains(*id)).count()) },
+            Artifact { name: "names, tags, hidden, bookmarks", path: self.sidecar_file.clone(), size: size(&self.sidecar_file),
+                stale: Some(Self::stale_sidecar_ids(&self.load_sidecar(), &live).len()) },
+            Artifact { name: "scan times", path: self.state_file(SCAN_TIMES_FILE), size: size(&self.state_file(SCAN_TIMES_FILE)),
+                stale: Some(self.load_scan_times().keys().filter(|p| !projects.contains(*p)).count()) },
+            Artifact { name: "search index", path: self.index_file.clone(), size: size(&self.index_file),
+                stale: Some(SearchIndex::load(&self.index_file).retain(|id| live.contains(id))) },
+            Artifact { name: "audit log", path: self.audit.path().to_path_buf(), size: self.audit.size(), stale: None },
+        ]
+    }
+
+    /// Drops stale entries from the cache, sidecar and scan times and rotates the audit log.
+    /// Returns how many entries each file lost. Refused when the projects directory is missing,
+    /// since every entry would then look stale.
+    pub fn compact_artifacts(&self) -> io::Result<Vec<(&'static str, usize)>> {
+        self.check_writable()?;
+        if !self.claude_root.join("projects").is_dir() { return Err(io::Error::new(io::ErrorKind::NotFound, "no projects directory")); }
+        let live = self.get_phys_ids();
+        let mut cache = self.load_cache();
+        let before = cache.len();
+        cache.retain(|id, _| live.contains(id));
+        let cache_dropped = before - cache.len();
+        if cache_dropped > 0 { atomic_write(&self.cache_file, &serde_json::to_vec(&cache)?)?; }
+
+        let mut sidecar = self.load_sidecar();
+        let stale = Self::stale_sidecar_ids(&sidecar, &live);
+        for id in &stale { sidecar.forget(id); }
+        if !stale.is_empty() { self.save_sidecar(&sidecar)?; }
+
+        let projects: HashSet<String> = self.project_names().into_iter().collect();
+        let mut times = self.load_scan_times();
+        let before = times.len();
+        times.retain(|p, _| projects.contains(p));
+        let times_dropped = before - times.len();
+        if times_dropped > 0 { self.save_scan_times(&times)?; }
+
+        let mut index = SearchIndex::load(&self.index_file);
+        let index_dropped = index.retain(|id| live.contains(id));
+        if index_dropped > 0 { index.save(&self.index_file)?; }
+
+        self.audit.rotate()?;
+        log::info!("compacted own files: {} cache entries, {} sidecar sessions, {} scan times, {} indexed sessions", cache_dropped, stale.len(), times_dropped, index_dropped);
+        Ok(vec![("metadata cache", cache_dropped), ("names, tags, hidden, bookmarks", stale.len()), ("scan times", times_dropped), ("search index", index_dropped)])
+    }
+
+    /// Errors out in read-only mode.
+    fn check_writable(&self) -> io::Result<()> {
+        if self.read_only { return Err(io::Error::new(io::ErrorKind::PermissionDenied, "read-only: another instance holds the lock")); }
+        Ok(())
+    }
+
+    /// Best effort; skipped in read-only mode, where the next scan simply redoes the work.
+    fn save_cache(&self, cache: &HashMap<String, CachedMetadata>) {
+        if self.read_only { return; }
+        if let Ok(f) = fs::File::create(&self.cache_file) {
+            let _ = serde_json::to_writer(f, cache);
+        }
+    }
+
+    /// The content search index, without the sessions that no longer exist.
+    pub fn load_search_index(&self) -> SearchIndex {
+        let live = self.get_phys_ids();
+        let mut index = SearchIndex::load(&self.index_file);
+        index.retain(|id| live.contains(id));
+        index
+    }
+
+    /// Saves the index if it changed. Skipped in read-only mode, like the metadata cache.
+    pub fn save_search_index(&self, index: &mut SearchIndex) -> io::Result<()> {
+        if self.read_only || !index.is_dirty() { return Ok(()); }
+        index.save(&self.index_file)
+    }
+
+    /// Size of the metadata cache file as last saved.
+    pub fn cache_size(&self) -> u64 {
+        fs::metadata(&self.cache_file).map_or(0, |m| m.len())
+    }
+
+    /// A session's summary, read from its transcript; for caches that leave summaries out.
+    pub fn summary_of(&self, path: &Path) -> Summary {
+        Self::scan_file(path).and_then(|c| c.summary).unwrap_or_default()
+    }
+
+    fn forget_cached(&self, id: &str) {
+        let mut cache = self.load_cache();
+        if cache.remove(id).is_some() { self.save_cache(&cache); }
+    }
+
+    fn load_cache(&self) -> HashMap<String, CachedMetadata> {
+        let Ok(f) = fs::File::open(&self.cache_file) else { return HashMap::new() };
+        serde_json::from_reader(f).unwrap_or_else(|e| {
+            log::warn!("{}: unreadable, rescanning everything: {}", self.cache_file.display(), e);
+            HashMap::new()
+        })
+    }
+
+    /// Every session under the projects directory, using and refreshing the metadata cache.
     pub fn load_sessions(&self) -> io::Result<Vec<Session>> {
+        Ok(self.load_projects(|_| true)?.0)
+    }
+
+    /// Sessions of the projects `wanted` accepts, and how many ms each of those project
+    /// directories took to load. Cache entries of the other projects are kept for their turn.
+    pub fn load_projects(&self, wanted: impl Fn(&str) -> bool) -> io::Result<(Vec<Session>, BTreeMap<String, u64>)> {
         let projects_dir = self.claude_root.join("projects");
-        if !projects_dir.exists() { return Ok(Vec::new()); }
+        if !projects_dir.exists() {
+            log::warn!("no projects directory at {}", projects_dir.display());
+            return Ok((Vec::new(), BTreeMap::new()));
+        }
 
         let cache = self.load_cache();
+        let sidecar = self.load_sidecar();
+        let mut history: HashMap<String, String> = HashMap::new();
+        for h in self.read_history() {
+            if !h.display.trim().is_empty() { history.entry(h.session_id).or_insert(h.display); }
+        }
         let mut new_cache = HashMap::new();
         let mut sessions = Vec::new();
+        let mut dir_exists: HashMap<String, bool> = HashMap::new();
+        let mut times = BTreeMap::new();
+        let mut partial = false;
 
         for entry in fs::read_dir(projects_dir)?.flatten() {
             if !entry.path().is_dir() { continue; }
             let proj_name = entry.file_name().to_string_lossy().into_owned();
+            if !wanted(&proj_name) {
+                log::info!("{}: deferred, not scanned", entry.path().display());
+                partial = true;
+                continue;
+            }
+            let started = Instant::now();
+            let (found, mut hits) = (sessions.len(), 0);
 
+            let mut files = Vec::new();
             for file in fs::read_dir(entry.path())?.flatten() {
                 let path = file.path();
-                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") { continue; }
+                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                    log::debug!("{}: skipped, not a .jsonl file", path.display());
+                    continue;
+                }
                 
                 let fname = path.file_stem().unwrap().to_string_lossy();
-                if fname.starts_with("agent-") { continue; }
+                if fname.starts_with("agent-") {
+                    log::debug!("{}: skipped, sub-agent transcript", path.display());
+                    continue;
+                }
                 let id = fname.into_owned();
-
                 let meta = fs::metadata(&path)?;
+                files.push((path, id, meta));
+            }
+
+            // Parsing changed transcripts and looking up related files dominate the load, so
+            // they run in parallel; the cache and the session list are filled in afterwards.
+            let level = self.cache_detail;
+            let scans: Vec<_> = files.into_par_iter().map(|(path, id, meta)| {
                 let mod_time = meta.modified().unwrap_or(SystemTime::now());
                 let mod_ts = mod_time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
-
-                let (custom_name, msg_count, first_msg) = if let Some(c) = cache.get(&id) {
-                    if c.modified_ts == mod_ts {
-                        new_cache.insert(id.clone(), c.clone());
-                        (c.custom_name.clone(), c.message_count, c.first_message.clone())
-                    } else {
-                        Self::scan_and_cache(&path, &id, mod_ts, &mut new_cache)
+                let (hit, scanned) = match cache.get(&id) {
+                    Some(c) if c.modified_ts == mod_ts && c.prompt_kind.is_some() && c.cwd.is_some() && c.usage_by_model.is_some() && c.summary_title.is_some()
+                        && c.detail() >= level && (c.summary.is_some() || c.detail() == CacheDetail::Minimal) => {
+                        log::debug!("{}: cache hit", path.display());
+                        (true, c.clone())
+                    }
+                    c => {
+                        log::debug!("{}: cache miss ({})", path.display(), if c.is_some() { "stale" } else { "not cached" });
+                        (false, Self::scan(&path, mod_ts))
                     }
-                } else {
-                    Self::scan_and_cache(&path, &id, mod_ts, &mut new_cache)
                 };
+                let related = self.find_related(&id, &entry.path());
+                (path, id, meta, mod_time, hit, scanned, related)
+            }).collect();
 
+            for (path, id, meta, mod_time, hit, scanned, related) in scans {
+                if hit { hits += 1; }
+                new_cache.insert(id.clone(), scanned.clone().reduce(level));
+                let cwd = scanned.cwd.filter(|c| !c.is_empty());
+                let project_missing = cwd.as_ref()
+                    .is_some_and(|c| !*dir_exists.entry(c.clone()).or_insert_with(|| Path::new(c).is_dir()));
+                let by_model = scanned.usage_by_model.unwrap_or_default();
+                let mut usage = Usage::default();
+                for (m, u) in &by_model {
+                    usage.add(&Usage { cost: price_for(&self.pricing, m).map_or(0.0, |p| p.cost(u)), ..*u });
+                }
+                let models = by_model.into_iter().filter(|(m, _)| !m.is_empty()).map(|(m, u)| (m, u.replies)).collect();
                 sessions.push(Session {
                     id: id.clone(),
                     path,
                     project: proj_name.clone(),
                     size: meta.len(),
-                    message_count: msg_count,
-                    first_message: first_msg,
+                    message_count: scanned.message_count,
+                    first_message: scanned.first_message,
+                    prompt_kind: PromptKind::from_u8(scanned.prompt_kind.unwrap_or_default()),
                     modified: mod_time,
-                    custom_name,
-                    related_files: self.find_related(&id, &entry.path()),
+                    custom_name: scanned.custom_name,
+                    summary_title: scanned.summary_title.filter(|t| !t.is_empty()),
+                    user_name: sidecar.names.get(&id).cloned(),
+                    history_prompt: history.remove(&id),
+                    tags: sidecar.tags.get(&id).cloned().unwrap_or_default(),
+                    hidden: sidecar.hidden.contains(&id),
+                    pinned: sidecar.pinned.contains(&id),
+                    protected: sidecar.protected.contains(&id),
+                    note: sidecar.notes.get(&id).cloned(),
+                    related_files: related,
+                    cwd,
+                    project_missing,
+                    last_reply_at: scanned.last_reply_at,
+                    summary: scanned.summary,
+                    tokens: usage.tokens(),
+                    usage,
+                    models,
                 });
             }
+            let ms = started.elapsed().as_millis() as u64;
+            let count = sessions.len() - found;
+            log::info!("{}: {} sessions, {} cache hits, {} misses, {}ms", entry.path().display(), count, hits, count - hits, ms);
+            times.insert(proj_name, ms);
         }
-        
-        if let Ok(f) = fs::File::create(&self.cache_file) {
-            let _ = serde_json::to_writer(f, &new_cache);
+
+        if partial {
+            for (id, c) in cache { new_cache.entry(id).or_insert(c); }
         }
+        self.save_cache(&new_cache);
         
-        sessions.sort_by(|a, b| b.modified.cmp(&a.modified));
-        Ok(sessions)
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.modified));
+        Ok((sessions, times))
     }
 
-    fn scan_and_cache(path: &Path, id: &str, ts: u64, cache: &mut HashMap<String, CachedMetadata>) -> (Option<String>, usize, String) {
-        let (title, count, first) = Self::scan_file(path).unwrap_or((None, 0, String::new()));
-        cache.insert(id.to_string(), CachedMetadata {
-            custom_name: title.clone(),
-            message_count: count,
-            first_message: first.clone(),
-            modified_ts: ts,
-        });
-        (title, count, first)
+    /// Load time in ms per project directory as of its last scan.
+    pub fn load_scan_times(&self) -> BTreeMap<String, u64> {
+        fs::read_to_string(self.state_file(SCAN_TIMES_FILE)).ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_scan_times(&self, times: &BTreeMap<String, u64>) -> io::Result<()> {
+        self.check_writable()?;
+        fs::create_dir_all(&self.state_dir)?;
+        atomic_write(&self.state_file(SCAN_TIMES_FILE), serde_json::to_string(times)?.as_bytes())
     }
 
-    fn scan_file(path: &Path) -> Option<(Option<String>, usize, String)> {
-        let content = fs::read_to_string(path).ok()?;
+    /// Scans a session with every field a cache entry needs filled in.
+    fn scan(path: &Path, ts: u64) -> CachedMetadata {
+        let mut scanned = Self::scan_file(path).unwrap_or_default();
+        scanned.modified_ts = ts;
+        scanned.prompt_kind.get_or_insert(PromptKind::Other as u8);
+        scanned.cwd.get_or_insert_with(String::new);
+        scanned.summary.get_or_insert_with(Summary::default);
+        scanned.usage_by_model.get_or_insert_with(BTreeMap::new);
+        scanned.summary_title.get_or_insert_with(String::new);
+        scanned
+    }
+
+    fn scan_file(path: &Path) -> Option<CachedMetadata> {
+        let lines = jsonl_lines(path).ok()?;
         let mut count = 0;
         let mut first = None;
+        let mut kind = PromptKind::Other;
         let mut title = None;
+        let mut summary_title = None;
+        let mut cwd = None;
+        let mut last_reply_at = None;
+        let mut summary = Summary::default();
+        let mut last_reply = String::new();
+        let mut prompts = 0;
+        // A reply split over several records repeats its usage on each, so count it per message id.
+        let mut usage: HashMap<String, (String, Usage)> = HashMap::new();
 
-        for line in content.lines() {
-            if let Ok(val) = serde_json::from_str::<Value>(line) {
+        for (offset, line) in lines {
+            // Records that are neither messages nor titles only matter until the cwd is known.
+            if cwd.is_some() && !["\"user\"", "\"assistant\"", "\"summary\"", "customTitle"].iter().any(|k| line.contains(k)) { continue; }
+            if let Ok(val) = serde_json::from_str::<Value>(&line) {
+                if let Some(u) = val.pointer("/message/usage").filter(|_| val.get("type").and_then(Value::as_str) == Some("assistant")) {
+                    let key = val.pointer("/message/id").and_then(Value::as_str).map_or_else(|| format!("#{}", usage.len()), String::from);
+                    let model = val.pointer("/message/model").and_then(Value::as_str).filter(|m| !m.starts_with('<')).map_or("", short_model);
+                    usage.insert(key, (model.to_string(), Usage::of(u)));
+                }
+                if let Some(c) = val.pointer("/message/content").filter(|_| val.get("type").and_then(Value::as_str) == Some("assistant")) {
+                    for f in Summary::touched(c) {
+                        if !summary.files.iter().any(|s| s == f) { summary.files.push(f.to_string()); }
+                    }
+                    let text = Self::extract_text(c);
+                    if !text.trim().is_empty() {
+                        last_reply_at = Some(offset);
+                        last_reply = text;
+                    }
+                }
+                if cwd.is_none() { cwd = val.get("cwd").and_then(Value::as_str).map(String::from); }
                 if let Some(t) = val.get("type").and_then(|s| s.as_str()) {
                     if t == "user" {
                         if val.get("isMeta").and_then(|b| b.as_bool()).unwrap_or(false) { continue; }
                         let text = Self::extract_text(val.get("message")?.get("content")?);
                         if text.starts_with("Caveat:") || text.starts_with("<command") || text.starts_with("<local-command") { continue; }
                         count += 1;
+                        if !text.trim().is_empty() && prompts < SUMMARY_PROMPTS {
+                            prompts += 1;
+                            if summary.focus.as_ref().is_none_or(|f| text.trim().len() > f.len()) { summary.focus = Some(text.trim().to_string()); }
+                        }
                         if first.is_none() && !text.trim().is_empty() {
+                            kind = PromptKind::classify(&text);
                             first = Some(text.replace('\n', " "));
                         }
                     }
@@ -226,9 +1470,30 @@ impl SessionManager {
                 if let Some(t) = val.get("customTitle").and_then(|s| s.as_str()) {
                     if !t.is_empty() { title = Some(t.to_string()); }
                 }
+                if val.get("type").and_then(Value::as_str) == Some("summary") {
+                    if let Some(t) = val.get("summary").and_then(Value::as_str).filter(|t| !t.trim().is_empty()) { summary_title = Some(t.trim().to_string()); }
+                }
             }
         }
-        Some((title, count, first.unwrap_or_else(|| "(empty)".into())))
+        Some(CachedMetadata {
+            custom_name: title,
+            summary_title,
+            message_count: count,
+            first_message: first.unwrap_or_else(|| "(empty)".into()),
+            prompt_kind: Some(kind as u8),
+            cwd,
+            last_reply_at,
+            summary: Some(Summary {
+                focus: summary.focus.map(|f| Summary::clip(&f)),
+                outcome: Summary::last_sentence(&last_reply),
+                ..summary
+            }),
+            usage_by_model: Some(usage.into_values().fold(BTreeMap::new(), |mut by_model, (m, u)| {
+                by_model.entry(m).or_insert_with(Usage::default).add(&u);
+                by_model
+            })),
+            ..Default::default()
+        })
     }
 
     fn extract_text(v: &Value) -> String {
@@ -263,25 +1528,54 @@ impl SessionManager {
         paths.into_iter().filter(|p| p.exists()).collect()
     }
 
+    /// The end of a session's `debug/<id>.txt`: at most `limit` bytes (the whole file with
+    /// `None`), starting on a line boundary, plus the file's full size.
+    pub fn read_debug_log(&self, id: &str, limit: Option<u64>) -> io::Result<(String, u64)> {
+        let mut f = fs::File::open(self.claude_root.join(format!("debug/{}.txt", id)))?;
+        let len = f.metadata()?.len();
+        let start = limit.map_or(0, |l| len.saturating_sub(l));
+        f.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf)?;
+        let text = String::from_utf8_lossy(&buf);
+        let text = match text.split_once('\n') {
+            Some((_, rest)) if start > 0 => rest.to_string(),
+            _ => text.into_owned(),
+        };
+        Ok((text, len))
+    }
+
+    /// Deletes the transcript and its related files and drops its `history.jsonl` entries and
+    /// sidecar metadata. Returns the paths removed; refused in read-only mode.
     pub fn delete_session(&self, session: &Session) -> io::Result<Vec<String>> {
+        self.check_writable()?;
         let mut files = session.related_files.clone();
         if session.path.exists() { files.push(session.path.clone()); }
 
-        let mut deleted = Vec::new();
+        let mut entry = AuditEntry::new("delete_session");
+        entry.session_id = Some(session.id.clone());
+        entry.trashed = self.use_os_trash;
         for p in files {
             let name = p.strip_prefix(&self.claude_root).unwrap_or(&p).to_string_lossy().into_owned();
-            if p.is_dir() { fs::remove_dir_all(&p)?; } else { fs::remove_file(&p)?; }
-            deleted.push(name);
-        }
-
-        let mut cache = self.load_cache();
-        if cache.remove(&session.id).is_some() {
-            if let Ok(f) = fs::File::create(&self.cache_file) {
-                 let _ = serde_json::to_writer(f, &cache);
+            let size = audit::disk_size(&p);
+            if let Err(e) = Self::remove(&p, self.use_os_trash) {
+                log::warn!("delete {}: {} failed: {}", session.id, p.display(), e);
+                entry.error = Some(format!("{}: {}", name, e));
+                self.audit.record(entry);
+                return Err(io::Error::new(e.kind(), format!("{}: {}", name, e)));
             }
+            entry.bytes += size;
+            entry.paths.push(name);
         }
+        let deleted = entry.paths.clone();
+        log::info!("delete {}: {} {}", session.id, if self.use_os_trash { "moved to trash" } else { "removed" }, deleted.join(", "));
+        self.audit.record(entry);
+
+        self.forget_cached(&session.id);
+        let mut sidecar = self.load_sidecar();
+        if sidecar.forget(&session.id) { self.save_sidecar(&sidecar)?; }
         // Remove from history
-        self.rewrite_history(|line| {
+        self.rewrite_history("history_drop_session", |_, line| {
             serde_json::from_str::<Value>(line).ok()
                 .and_then(|v| v.get("sessionId").and_then(|s| s.as_str()).map(|s| s == session.id))
                 .unwrap_or(false)
@@ -290,27 +1584,173 @@ impl SessionManager {
         Ok(deleted)
     }
 
+    /// Deletes only the transcript of a session whose id another live transcript shares. The
+    /// related files, sidecar entries and `history.jsonl` lines are found by id, so they belong
+    /// to the copy that stays. Refused in read-only mode.
+    pub fn delete_copy(&self, session: &Session) -> io::Result<Vec<String>> {
+        self.check_writable()?;
+        let name = session.path.strip_prefix(&self.claude_root).unwrap_or(&session.path).to_string_lossy().into_owned();
+        let mut entry = AuditEntry::new("delete_copy");
+        entry.session_id = Some(session.id.clone());
+        entry.trashed = self.use_os_trash;
+        entry.bytes = audit::disk_size(&session.path);
+        if let Err(e) = Self::remove(&session.path, self.use_os_trash) {
+            entry.error = Some(format!("{}: {}", name, e));
+            self.audit.record(entry);
+            return Err(io::Error::new(e.kind(), format!("{}: {}", name, e)));
+        }
+        log::info!("delete copy of {}: {}", session.id, name);
+        entry.paths.push(name.clone());
+        self.audit.record(entry);
+        Ok(vec![name])
+    }
+
     pub fn prune_history_orphans(&self) -> usize {
         let valid = self.get_phys_ids();
-        self.rewrite_history(|line| {
+        self.rewrite_history("history_prune_orphans", |_, line| {
             serde_json::from_str::<Value>(line).ok()
                 .and_then(|v| v.get("sessionId").and_then(|s| s.as_str()).map(|s| !valid.contains(s)))
                 .unwrap_or(false) // Drop if not valid
         })
     }
 
-    fn rewrite_history<F>(&self, should_drop: F) -> usize where F: Fn(&str) -> bool {
-        if !self.history_file.exists() { return 0; }
+    /// History entries whose session file no longer exists.
+    pub fn dangling_history(&self) -> Vec<HistoryEntry> {
+        let valid = self.get_phys_ids();
+        self.read_history().into_iter().filter(|h| !valid.contains(&h.session_id)).collect()
+    }
+
+    /// Every entry of `history.jsonl`; malformed lines and lines without a session id are skipped.
+    pub fn read_history(&self) -> Vec<HistoryEntry> {
+        let content = fs::read_to_string(&self.history_file).unwrap_or_default();
+        content.lines().enumerate()
+            .filter_map(|(line, l)| {
+                let Ok(v) = serde_json::from_str::<Value>(l) else {
+                    log::debug!("{}:{}: skipped, not JSON", self.history_file.display(), line + 1);
+                    return None;
+                };
+                let session_id = v.get("sessionId")?.as_str()?.to_string();
+                let text = |k: &str| v.get(k).and_then(|s| s.as_str()).unwrap_or_default().to_string();
+                Some(HistoryEntry {
+                    display: text("display"),
+                    project: text("project"),
+                    timestamp: v.get("timestamp").and_then(|t| t.as_i64()),
+                    session_id,
+                })
+            })
+            .collect()
+    }
+
+    /// Removes the entries with these `HistoryEntry::key`s from history.jsonl as it reads now,
+    /// keeping any whose session has come back since they were listed.
+    pub fn drop_history_entries(&self, keys: &HashSet<(String, Option<i64>)>) -> usize {
+        let valid = self.get_phys_ids();
+        self.rewrite_history("history_drop_entries", |_, line| {
+            let Ok(v) = serde_json::from_str::<Value>(line) else { return false };
+            let Some(id) = v.get("sessionId").and_then(|s| s.as_str()) else { return false };
+            !valid.contains(id) && keys.contains(&(id.to_string(), v.get("timestamp").and_then(|t| t.as_i64())))
+        })
+    }
+
+    fn rewrite_history<F>(&self, action: &str, should_drop: F) -> usize where F: Fn(usize, &str) -> bool {
+        if self.read_only || !self.history_file.exists() { return 0; }
         let content = fs::read_to_string(&self.history_file).unwrap_or_default();
         let mut lines = Vec::new();
         let mut dropped = 0;
-        for line in content.lines() {
-            if should_drop(line) { dropped += 1; } else { lines.push(line); }
+        for (n, line) in content.lines().enumerate() {
+            if should_drop(n, line) { dropped += 1; } else { lines.push(line); }
+        }
+        if dropped > 0 {
+            let kept = lines.join("\n");
+            let mut entry = AuditEntry::new(action);
+            entry.paths = vec![format!("history.jsonl ({} lines)", dropped)];
+            entry.bytes = (content.len() - kept.len()) as u64;
+            entry.error = fs::write(&self.history_file, kept).err().map(|e| e.to_string());
+            match &entry.error {
+                Some(e) => log::warn!("{}: {} failed: {}", self.history_file.display(), action, e),
+                None => log::info!("{}: {} dropped {} of {} lines", self.history_file.display(), action, dropped, dropped + lines.len()),
+            }
+            self.audit.record(entry);
         }
-        if dropped > 0 { fs::write(&self.history_file, lines.join("\n")).ok(); }
         dropped
     }
 
+    /// Files under the session's `file-history/<id>/` and `session-env/<id>/`, oldest first.
+    pub fn related_entries(&self, session: &Session) -> Vec<RelatedEntry> {
+        fn walk(dir: &Path, out: &mut Vec<RelatedEntry>) {
+            for e in fs::read_dir(dir).into_iter().flatten().flatten() {
+                let Ok(meta) = e.metadata() else { continue };
+                if meta.is_dir() { walk(&e.path(), out); continue; }
+                out.push(RelatedEntry { path: e.path(), size: meta.len(), modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH) });
+            }
+        }
+        let mut out = Vec::new();
+        for d in BROWSABLE_DIRS { walk(&self.claude_root.join(d).join(&session.id), &mut out); }
+        out.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+        out
+    }
+
+    /// Deletes single files found by `related_entries`; anything outside the session's
+    /// browsable directories (its transcript above all) is refused. Logged as one audit entry.
+    pub fn remove_related_entries(&self, session: &Session, paths: &[PathBuf]) -> Vec<io::Result<()>> {
+        let mut entry = AuditEntry::new("delete_related_files");
+        entry.session_id = Some(session.id.clone());
+        let results = paths.iter().map(|p| {
+            self.check_writable()?;
+            let inside = BROWSABLE_DIRS.iter().any(|d| p.starts_with(self.claude_root.join(d).join(&session.id)));
+            if !inside || p.is_dir() || p.components().any(|c| c == std::path::Component::ParentDir) {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "not a file-history or session-env entry of this session"));
+            }
+            let size = audit::disk_size(p);
+            let name = p.strip_prefix(&self.claude_root).unwrap_or(p).to_string_lossy().into_owned();
+            fs::remove_file(p).inspect_err(|e| { entry.error.get_or_insert(format!("{}: {}", name, e)); })?;
+            entry.bytes += size;
+            entry.paths.push(name);
+            Ok(())
+        }).collect();
+        if !entry.paths.is_empty() || entry.error.is_some() { self.audit.record(entry); }
+        results
+    }
+
+    /// Deletes the given orphaned files and directories, logging each one. Returns one result
+    /// per path.
+    pub fn remove_orphans(&self, paths: &[String]) -> Vec<io::Result<()>> {
+        self.remove_paths(paths, "prune_orphan", self.use_os_trash)
+    }
+
+    /// Files in the backup dir, which serves as the tool's trash: copies of sessions taken
+    /// before a slim, redact or repair rewrote them. Oldest first.
+    pub fn trash(&self) -> Vec<RelatedEntry> {
+        let mut out: Vec<RelatedEntry> = fs::read_dir(self.backup_dir()).into_iter().flatten().flatten()
+            .filter_map(|e| {
+                let meta = e.metadata().ok().filter(|m| m.is_file())?;
+                Some(RelatedEntry { path: e.path(), size: meta.len(), modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH) })
+            })
+            .collect();
+        out.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+        out
+    }
+
+    pub fn empty_trash(&self, paths: &[String]) -> Vec<io::Result<()>> {
+        self.remove_paths(paths, "empty_trash", false)
+    }
+
+    /// Removes each path, file or directory, with one audit entry per path under `action`.
+    fn remove_paths(&self, paths: &[String], action: &str, to_trash: bool) -> Vec<io::Result<()>> {
+        paths.iter().map(|p| {
+            self.check_writable()?;
+            let path = Path::new(p.as_str());
+            let mut entry = AuditEntry::new(action);
+            entry.bytes = audit::disk_size(path);
+            entry.paths = vec![path.strip_prefix(&self.claude_root).unwrap_or(path).to_string_lossy().into_owned()];
+            entry.trashed = to_trash;
+            let res = Self::remove(path, to_trash);
+            entry.error = res.as_ref().err().map(|e| e.to_string());
+            self.audit.record(entry);
+            res
+        }).collect()
+    }
+
     fn get_phys_ids(&self) -> HashSet<String> {
         let mut ids = HashSet::new();
         if let Ok(projs) = fs::read_dir(self.claude_root.join("projects")) {
@@ -328,6 +1768,7 @@ impl SessionManager {
         ids
     }
 
+    /// Debug logs, environments, file histories and todos whose session no longer exists.
     pub fn find_orphans(&self) -> Vec<PathBuf> {
         let valid = self.get_phys_ids();
         let mut orphans = Vec::new();
@@ -351,17 +1792,584 @@ impl SessionManager {
         orphans
     }
 
+    pub fn archive_dir(&self) -> PathBuf {
+        self.archive_root.clone().unwrap_or_else(|| self.claude_root.join("archives"))
+    }
+
+    /// Packs the session jsonl and its related files into `<archive_dir>/<id>.tar.zst`, leaving the originals in place.
+    pub fn archive_session(&self, session: &Session) -> io::Result<PathBuf> {
+        let dir = self.archive_dir();
+        fs::create_dir_all(&dir)?;
+        let mut files = vec![session.path.clone()];
+        files.extend(session.related_files.iter().cloned());
+        let manifest = Manifest {
+            id: session.id.clone(),
+            project: session.project.clone(),
+            name: session.display_name(),
+            message_count: session.message_count,
+            archived_at: archive::now_ts(),
+            entries: Vec::new(),
+            files: files.iter()
+                .filter_map(|p| p.strip_prefix(&self.claude_root).ok())
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect(),
+        };
+        let dest = dir.join(format!("{}{}", session.id, archive::ARCHIVE_EXT));
+        archive::write_archive(&self.claude_root, &dest, &manifest, &files)?;
+        Ok(dest)
+    }
+
+    pub fn list_archives(&self) -> Vec<ArchivedSession> {
+        archive::list_archives(&self.archive_dir())
+    }
+
+    pub fn find_archive(&self, id: &str) -> Option<PathBuf> {
+        let p = self.archive_dir().join(format!("{}{}", id, archive::ARCHIVE_EXT));
+        p.exists().then_some(p)
+    }
+
+    /// Unpacks an archive back into place. Fails with `AlreadyExists` when files would be
+    /// overwritten and `overwrite` is false, and with `InvalidData` when the archive is damaged.
+    /// Refused in read-only mode.
+    pub fn restore_archive(&self, path: &Path, overwrite: bool) -> io::Result<Manifest> {
+        self.check_writable()?;
+        archive::restore_archive(&self.claude_root, path, overwrite)
+    }
+
+    /// sha256 of each session file by path, reusing hashes cached against the file's mtime and
+    /// size. The cache is keyed by id, so an id shared by several files (a copied project) is
+    /// always hashed afresh and never cached.
+    pub fn content_hashes(&self, sessions: &[Session]) -> HashMap<PathBuf, String> {
+        let mut cache = self.load_cache();
+        let mut hashes = HashMap::new();
+        let mut dirty = false;
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for s in sessions { *seen.entry(s.id.as_str()).or_default() += 1; }
+        for s in sessions {
+            let ts = s.modified.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+            let unique = seen[s.id.as_str()] == 1;
+            let cached = cache.get(&s.id)
+                .filter(|c| unique && c.modified_ts == ts && c.hashed_size == s.size)
+                .and_then(|c| c.content_hash.clone());
+            let hash = match cached {
+                Some(h) => h,
+                None => {
+                    let Ok(f) = fs::File::open(&s.path) else { continue };
+                    let Ok((_, h)) = archive::sha256_hex(f) else { continue };
+                    if let Some(c) = cache.get_mut(&s.id).filter(|c| unique && c.modified_ts == ts) {
+                        c.content_hash = Some(h.clone());
+                        c.hashed_size = s.size;
+                        dirty = true;
+                    }
+                    h
+                }
+            };
+            hashes.insert(s.path.clone(), hash);
+        }
+        if dirty { self.save_cache(&cache); }
+        hashes
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        self.archive_dir().join("backups")
+    }
+
+    /// Shrinks oversized tool results in a session file, returning (before, after) sizes.
+    /// Unless `dry_run`, the original is first copied to the backup dir and then replaced atomically.
+    pub fn slim_session(&self, session: &Session, threshold: usize, dry_run: bool) -> io::Result<(u64, u64)> {
+        let content = fs::read_to_string(&session.path)?;
+        let slimmed = slim::slim_jsonl(&content, threshold);
+        let sizes = (content.len() as u64, slimmed.content.len() as u64);
+        if dry_run || slimmed.records_changed == 0 { return Ok(sizes); }
+        self.check_writable()?;
+        self.backup(session)?;
+        atomic_write(&session.path, slimmed.content.as_bytes())?;
+        Ok(sizes)
+    }
+
+    /// Replaces the messages on the given jsonl lines with a placeholder, returning (before,
+    /// after) sizes. Unless `dry_run`, the original goes to the backup dir first, the file is
+    /// replaced atomically and the session's cache entry is dropped.
+    pub fn redact_session(&self, session: &Session, records: &HashSet<usize>, dry_run: bool) -> io::Result<(u64, u64)> {
+        let content = fs::read_to_string(&session.path)?;
+        let redacted = slim::redact_jsonl(&content, records);
+        let sizes = (content.len() as u64, redacted.content.len() as u64);
+        if dry_run || redacted.records_changed == 0 { return Ok(sizes); }
+        self.check_writable()?;
+        self.backup(session)?;
+        atomic_write(&session.path, redacted.content.as_bytes())?;
+        self.forget_cached(&session.id);
+        let mut entry = AuditEntry::new("redact_messages");
+        entry.session_id = Some(session.id.clone());
+        entry.paths = vec![format!("{} ({} records)", session.path.strip_prefix(&self.claude_root).unwrap_or(&session.path).display(), redacted.records_changed)];
+        entry.bytes = sizes.0.saturating_sub(sizes.1);
+        self.audit.record(entry);
+        Ok(sizes)
+    }
+
+    pub fn check_session(&self, session: &Session) -> io::Result<HealthReport> {
+        health::check(io::BufReader::new(fs::File::open(&session.path)?))
+    }
+
+    /// Drops a truncated final record, backing the original up first. Returns false when the
+    /// last record is intact and nothing was changed.
+    pub fn repair_session(&self, session: &Session) -> io::Result<bool> {
+        self.check_writable()?;
+        let content = fs::read_to_string(&session.path)?;
+        let Some(repaired) = health::strip_truncated_tail(&content) else { return Ok(false) };
+        self.backup(session)?;
+        atomic_write(&session.path, repaired.as_bytes())?;
+        Ok(true)
+    }
+
+    fn backup(&self, session: &Session) -> io::Result<()> {
+        let dir = self.backup_dir();
+        fs::create_dir_all(&dir)?;
+        fs::copy(&session.path, dir.join(format!("{}-{}.jsonl", session.id, archive::now_ts())))?;
+        Ok(())
+    }
+
+    /// Parses one jsonl record into a transcript entry, skipping meta and empty messages.
+    fn parse_entry(record: usize, line: &str) -> Option<LogEntry> {
+        let v = serde_json::from_str::<Value>(line).ok()?;
+        let t = v.get("type")?.as_str()?;
+        if t != "user" && t != "assistant" { return None; }
+        let text = Self::extract_text(v.get("message")?.get("content")?);
+        if text.starts_with("Caveat:") || text.starts_with("<command") || text.starts_with("<local-command") { return None; }
+        if text.trim().is_empty() { return None; }
+        let model = v.get("message")?.get("model").and_then(|m| m.as_str()).map(String::from);
+        Some(LogEntry { role: t.to_string(), text, record, model })
+    }
+
+    /// The first user prompt with its line breaks, reading only as far as needed.
+    pub fn first_prompt(&self, path: &Path) -> io::Result<Option<String>> {
+        for (n, (_, line)) in jsonl_lines(path)?.enumerate() {
+            if let Some(e) = Self::parse_entry(n, &line).filter(|e| e.role == "user") { return Ok(Some(e.text)); }
+        }
+        Ok(None)
+    }
+
+    /// Entries from the end of a session log: the lines that start in its last `chunk` bytes
+    /// (more when the last line alone is longer), numbered as in the whole file.
+    pub fn read_tail(&self, path: &Path, chunk: u64) -> io::Result<LogChunk> {
+        let mut f = fs::File::open(path)?;
+        let len = f.metadata()?.len();
+        let mut start = len;
+        let mut want = chunk.max(1);
+        while start >= len && start > 0 {
+            start = line_start(&mut f, len.saturating_sub(want))?;
+            want = want.saturating_mul(4);
+        }
+        f.seek(SeekFrom::Start(0))?;
+        let first_record = count_lines(&mut f, start)?;
+        let entries = Self::read_span(&mut f, start, len, first_record)?;
+        Ok(LogChunk { start, first_record, entries })
+    }
+
+    /// The chunk before one that starts at byte `end` with record `end_record`; `end` must be
+    /// a line start.
+    pub fn read_before(&self, path: &Path, end: u64, end_record: usize, chunk: u64) -> io::Result<LogChunk> {
+        let mut f = fs::File::open(path)?;
+        let mut start = end;
+        let mut want = chunk.max(1);
+        while start >= end && start > 0 {
+            start = line_start(&mut f, end.saturating_sub(want))?;
+            want = want.saturating_mul(4);
+        }
+        f.seek(SeekFrom::Start(start))?;
+        let lines = count_lines(&mut f, end - start)?;
+        let first_record = end_record.saturating_sub(lines);
+        let entries = Self::read_span(&mut f, start, end, first_record)?;
+        Ok(LogChunk { start, first_record, entries })
+    }
+
+    fn read_span(f: &mut fs::File, start: u64, end: u64, first_record: usize) -> io::Result<Vec<LogEntry>> {
+        f.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::with_capacity((end - start) as usize);
+        f.take(end - start).read_to_end(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).lines().enumerate()
+            .filter_map(|(i, l)| Self::parse_entry(first_record + i, l))
+            .collect())
+    }
+
+    pub fn read_entries(&self, path: &Path) -> io::Result<Vec<LogEntry>> {
+        Ok(jsonl_lines(path)?.enumerate()
+            .filter_map(|(record, (_, l))| Self::parse_entry(record, &l))
+            .collect())
+    }
+
+    /// Text of the last assistant message. `hint` is the byte offset of that record from the
+    /// scan cache; without it the file is read backwards from the end in growing chunks.
+    pub fn last_reply(&self, path: &Path, hint: Option<u64>) -> io::Result<Option<String>> {
+        let mut f = fs::File::open(path)?;
+        let is_reply = |l: &str| Self::parse_entry(0, l).filter(|e| e.role == "assistant").map(|e| e.text);
+        if let Some(at) = hint {
+            f.seek(SeekFrom::Start(at))?;
+            let mut line = String::new();
+            io::BufReader::new(&f).read_line(&mut line)?;
+            if let Some(text) = is_reply(&line) { return Ok(Some(text)); }
+        }
+        let len = f.metadata()?.len();
+        let mut chunk = 64 * 1024;
+        loop {
+            let start = len.saturating_sub(chunk);
+            f.seek(SeekFrom::Start(start))?;
+            let mut buf = Vec::with_capacity((len - start) as usize);
+            (&mut f).take(len - start).read_to_end(&mut buf)?;
+            let tail = String::from_utf8_lossy(&buf);
+            // The first line is partial unless the chunk reaches the start of the file.
+            let lines = tail.lines().skip(usize::from(start > 0));
+            if let Some(text) = lines.collect::<Vec<_>>().into_iter().rev().find_map(is_reply) { return Ok(Some(text)); }
+            if start == 0 { return Ok(None); }
+            chunk *= 4;
+        }
+    }
+
+    /// Timestamps of the first and last record that has one.
+    pub fn record_span(&self, path: &Path) -> Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> {
+        let stamp = |l: &str| serde_json::from_str::<Value>(l).ok()
+            .and_then(|v| chrono::DateTime::parse_from_rfc3339(v.get("timestamp")?.as_str()?).ok())
+            .map(|t| t.with_timezone(&chrono::Utc));
+        let mut stamps = jsonl_lines(path).ok()?.filter_map(|(_, l)| stamp(&l));
+        let first = stamps.next()?;
+        Some((first, stamps.last().unwrap_or(first)))
+    }
+
+    /// The transcript as plain text, one block per message, as written by an export.
     pub fn read_log(&self, path: &Path) -> String {
-        fs::read_to_string(path).ok()
-             .map(|c| c.lines().filter_map(|l| serde_json::from_str::<Value>(l).ok())
-                .filter_map(|v| {
-                    let t = v.get("type")?.as_str()?;
-                    if t != "user" && t != "assistant" { return None; }
-                    let txt = Self::extract_text(v.get("message")?.get("content")?);
-                    if txt.starts_with("Caveat:") || txt.starts_with("<command") || txt.starts_with("<local-command") { return None; }
-                    if txt.trim().is_empty() { return None; }
-                    Some(format!("\n[{}]\n{}\n", t.to_uppercase(), txt))
-                }).collect::<String>())
-             .unwrap_or_else(|| "Error reading log".into())
+        self.read_entries(path)
+            .map(|entries| entries.iter().map(LogEntry::render).collect())
+            .unwrap_or_else(|_| "Error reading log".into())
+    }
+
+    /// Copies a session under a fresh id in the same project, rewriting its `sessionId` fields.
+    /// With `keep_through`, records after that jsonl line are dropped. Related files are not copied.
+    /// Refused in read-only mode.
+    pub fn duplicate_session(&self, session: &Session, keep_through: Option<usize>) -> io::Result<String> {
+        self.check_writable()?;
+        let content = fs::read_to_string(&session.path)?;
+        let new_id = uuid::Uuid::new_v4().to_string();
+        let dest = session.path.with_file_name(format!("{}.jsonl", new_id));
+        fs::write(dest, reassign_session_id(&content, &new_id, keep_through))?;
+        Ok(new_id)
+    }
+
+    /// Whether any project already has a session log with this id.
+    pub fn session_exists(&self, id: &str) -> bool {
+        fs::read_dir(self.claude_root.join("projects")).into_iter().flatten().flatten()
+            .any(|e| e.path().join(format!("{}.jsonl", id)).exists())
+    }
+
+    /// Imports an archive made on another machine. Fails with `AlreadyExists` when the session id
+    /// is taken here, unless `rename` is set, in which case it is imported under a fresh id.
+    /// Refused in read-only mode.
+    pub fn import_archive(&self, path: &Path, rename: bool) -> io::Result<Manifest> {
+        self.check_writable()?;
+        let manifest = archive::read_manifest(path)?;
+        let new_id = rename.then(|| uuid::Uuid::new_v4().to_string());
+        if new_id.is_none() && self.session_exists(&manifest.id) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("session {} already exists", manifest.id)));
+        }
+        archive::import_archive(&self.claude_root, path, new_id.as_deref())
+    }
+}
+
+/// Rewrites every record's `sessionId` to `new_id`, dropping records after line `keep_through`.
+pub fn reassign_session_id(content: &str, new_id: &str, keep_through: Option<usize>) -> String {
+    let mut out = String::with_capacity(content.len());
+    for (i, line) in content.lines().enumerate() {
+        if keep_through.is_some_and(|k| i > k) { break; }
+        match serde_json::from_str::<Value>(line) {
+            Ok(mut v) if v.get("sessionId").is_some() => {
+                v["sessionId"] = Value::String(new_id.to_string());
+                out.push_str(&v.to_string());
+            }
+            _ => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn cache_detail_levels_trim_or_rescan_and_drop_deleted_sessions() {
+        let fx = crate::test_support::Fixture::new();
+        let long = "word ".repeat(1000);
+        fx.session("-repo", "s1", &[&long, "Now something much longer than the opening prompt"], 100);
+        let gone = fx.session("-repo", "s2", &["short"], 200);
+        let mut m = fx.manager();
+        m.cache_detail = CacheDetail::Rich;
+        m.load_sessions().unwrap();
+        let rich = m.cache_size();
+
+        m.cache_detail = CacheDetail::Minimal;
+        fs::remove_file(gone).unwrap();
+        assert!(m.load_sessions().unwrap()[0].summary.is_some(), "this run still has what it read");
+        assert!(m.cache_size() < rich / 4, "{} vs {}", m.cache_size(), rich);
+        assert_eq!(m.load_cache().keys().collect::<Vec<_>>(), ["s1"]);
+        let s = m.load_sessions().unwrap().remove(0);
+        assert_eq!(s.first_message.chars().count(), 200);
+        assert!(s.summary.is_none());
+        assert!(m.summary_of(&s.path).focus.is_some());
+
+        m.cache_detail = CacheDetail::Rich;
+        m.load_sessions().unwrap();
+        let s = m.load_sessions().unwrap().remove(0);
+        assert!(s.summary.is_some() && s.first_message.len() > 4000, "a richer level rescans");
+    }
+
+    #[test]
+    fn hard_delete_turns_off_the_os_trash_and_the_audit_log_says_which() {
+        let fx = crate::test_support::Fixture::new();
+        fx.session("-repo", "s1", &["Removed for good"], 100);
+        let gone = fx.session("-repo", "s2", &["Already gone"], 200);
+        let mut m = fx.manager();
+        m.configure(&Config::default());
+        assert!(m.use_os_trash, "deletes go to the OS trash by default");
+        m.configure(&crate::test_support::config());
+        assert!(!m.use_os_trash);
+
+        let sessions = m.load_sessions().unwrap();
+        m.delete_session(sessions.iter().find(|s| s.id == "s1").unwrap()).unwrap();
+        assert!(!fx.exists("projects/-repo/s1.jsonl"));
+        let entry = m.audit.recent(1).remove(0);
+        assert_eq!((entry.action.as_str(), entry.trashed), ("delete_session", false));
+
+        // A transcript already gone leaves nothing to trash, so the real trash stays untouched.
+        m.configure(&Config::default());
+        fs::remove_file(gone).unwrap();
+        m.delete_session(sessions.iter().find(|s| s.id == "s2").unwrap()).unwrap();
+        assert!(m.audit.recent(1)[0].trashed);
+    }
+
+    #[test]
+    fn summary_records_name_sessions_below_custom_titles() {
+        let fx = crate::test_support::Fixture::new();
+        let path = fx.session("-p", "s1", &["fix the build"], 0);
+        let summary = |t: &str| format!("{{\"type\":\"summary\",\"summary\":\"{}\",\"leafUuid\":\"x\"}}\n", t);
+        let mut text = fs::read_to_string(&path).unwrap();
+        text.push_str(&summary("Old title"));
+        text.push_str(&summary("Build fix for CI"));
+        fs::write(&path, &text).unwrap();
+        let m = fx.manager();
+        let s = m.load_sessions().unwrap().remove(0);
+        assert_eq!((s.display_name().as_str(), s.name_source()), ("Build fix for CI", NameSource::Summary), "the latest summary wins");
+        assert_eq!(m.load_sessions().unwrap()[0].summary_title.as_deref(), Some("Build fix for CI"), "cached");
+
+        text.push_str("{\"type\":\"custom-title\",\"customTitle\":\"Named in Claude\"}\n");
+        fs::write(&path, &text).unwrap();
+        crate::test_support::set_age(&path, 60);
+        let mut s = m.load_sessions().unwrap().remove(0);
+        assert_eq!((s.full_name(), s.name_source()), ("Named in Claude", NameSource::CustomTitle));
+        s.user_name = Some("Mine".into());
+        assert_eq!(s.names().map(|(src, _)| src).collect::<Vec<_>>(), [NameSource::Renamed, NameSource::CustomTitle, NameSource::Summary]);
+        s.user_name = None;
+        s.custom_name = None;
+        s.summary_title = None;
+        assert_eq!((s.display_name().as_str(), s.name_source()), ("fix the build", NameSource::FirstPrompt));
+    }
+
+    #[test]
+    fn record_span_reads_the_first_and_last_timestamps() {
+        let fx = crate::test_support::Fixture::new();
+        let p = fx.write("projects/-p/s.jsonl", concat!(
+            "{\"type\":\"summary\"}\n",
+            "{\"type\":\"user\",\"timestamp\":\"2026-01-02T10:00:00.000Z\"}\n",
+            "{\"type\":\"assistant\",\"timestamp\":\"2026-01-03T11:30:00.000Z\"}\n",
+            "{\"type\":\"summary\"}\n"));
+        let (first, last) = fx.manager().record_span(&p).unwrap();
+        assert_eq!((first.to_rfc3339(), last.to_rfc3339()), ("2026-01-02T10:00:00+00:00".into(), "2026-01-03T11:30:00+00:00".into()));
+        assert!(fx.manager().record_span(&fx.session("-p", "bare", &["hi"], 0)).is_none());
+    }
+
+    #[test]
+    fn extracts_fenced_code_blocks() {
+        let e = LogEntry { role: "assistant".into(), text: "Try:\n```rust\nfn a() {}\n```\ntext\n  ~~~\nls\n```\n~~~\n```sh\nopen".into(), record: 0, model: None };
+        assert_eq!(e.code_blocks(), ["fn a() {}", "ls\n```", "open"]);
+    }
+
+    #[test]
+    fn resolves_projects_by_path_name_or_suggestion() {
+        let fx = crate::test_support::Fixture::new();
+        for p in ["-home-me-work-api", "-home-me-work-web", "-home-me-old-web", "-srv-my-app"] {
+            fs::create_dir_all(fx.path(&format!("projects/{}", p))).unwrap();
+        }
+        let m = fx.manager();
+        assert_eq!(m.resolve_project("/home/me/work/api"), Ok("-home-me-work-api".into()));
+        assert_eq!(m.resolve_project("-srv-my-app"), Ok("-srv-my-app".into()));
+        assert_eq!(m.resolve_project("api"), Ok("-home-me-work-api".into()));
+        assert_eq!(m.resolve_project("my.app"), Ok("-srv-my-app".into()));
+        assert_eq!(m.resolve_project("web"), Err(vec!["-home-me-old-web".into(), "-home-me-work-web".into()]));
+        assert_eq!(m.resolve_project("ap"), Err(vec!["-home-me-work-api".into(), "-srv-my-app".into()]));
+        assert_eq!(m.resolve_project("wbe"), Err(vec!["-home-me-old-web".into(), "-home-me-work-web".into()]));
+        assert_eq!(m.resolve_project("zzzzzzzz"), Err(vec![]));
+    }
+
+    #[test]
+    fn streams_lines_with_offsets_past_bad_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("s.jsonl");
+        let reply = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"done"}]}}"#;
+        let mut bytes = b"{\"type\":\"user\",\"cwd\":\"/r\",\"message\":{\"content\":\"hi\"}}\r\n\xff\xfe\n".to_vec();
+        let at = bytes.len() as u64;
+        bytes.extend_from_slice(reply.as_bytes());
+        fs::write(&path, &bytes).unwrap();
+
+        let lines: Vec<_> = jsonl_lines(&path).unwrap().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].1.ends_with("}}"), "the CR is dropped");
+        assert_eq!(lines[1], (lines[0].1.len() as u64 + 2, "\u{fffd}\u{fffd}".to_string()));
+        assert_eq!(lines[2], (at, reply.to_string()));
+
+        let scanned = SessionManager::scan_file(&path).unwrap();
+        assert_eq!((scanned.message_count, scanned.last_reply_at), (1, Some(at)));
+        assert_eq!(scanned.cwd.as_deref(), Some("/r"));
+    }
+
+    #[test]
+    fn compaction_drops_exactly_the_stale_entries() {
+        let fx = crate::test_support::Fixture::new();
+        fx.session("-p", "live", &["hi"], 10);
+        fx.session("-p", "gone", &["bye"], 10);
+        let m = fx.manager();
+        m.load_sessions().unwrap();
+        let mut sidecar = Sidecar::default();
+        for id in ["live", "gone", "older"] {
+            sidecar.set_name(id, "n");
+            sidecar.edit_tags(id, "t");
+        }
+        sidecar.hidden.insert("gone".into());
+        sidecar.bookmarks.insert("older".into(), BTreeMap::from([('a', 1)]));
+        m.save_sidecar(&sidecar).unwrap();
+        m.save_scan_times(&BTreeMap::from([("-p".to_string(), 5), ("-removed".to_string(), 7)])).unwrap();
+        m.audit.record(crate::audit::AuditEntry::new("delete"));
+        let mut index = m.load_search_index();
+        for id in ["live", "gone"] { index.update(id, 1, SystemTime::UNIX_EPOCH, HashSet::from(["hi".to_string()])); }
+        m.save_search_index(&mut index).unwrap();
+        fs::remove_file(fx.path("projects/-p/gone.jsonl")).unwrap();
+
+        let stale: Vec<_> = m.artifacts().iter().map(|a| (a.name, a.stale)).collect();
+        assert_eq!(stale, [("metadata cache", Some(1)), ("names, tags, hidden, bookmarks", Some(2)), ("scan times", Some(1)), ("search index", Some(1)), ("audit log", None)]);
+
+        let dropped = m.compact_artifacts().unwrap();
+        assert_eq!(dropped, [("metadata cache", 1), ("names, tags, hidden, bookmarks", 2), ("scan times", 1), ("search index", 1)]);
+        assert_eq!(m.load_search_index().len(), 1);
+        assert_eq!(m.load_cache().into_keys().collect::<Vec<_>>(), ["live"]);
+        let sidecar = m.load_sidecar();
+        assert_eq!(sidecar.names.keys().collect::<Vec<_>>(), ["live"]);
+        assert_eq!(sidecar.tags.keys().collect::<Vec<_>>(), ["live"]);
+        assert!(sidecar.hidden.is_empty() && sidecar.bookmarks.is_empty());
+        assert_eq!(m.load_scan_times().into_keys().collect::<Vec<_>>(), ["-p"]);
+        assert!(!m.audit.path().exists(), "the audit log was rotated");
+        assert_eq!(m.audit.recent(5).len(), 1, "rotated entries are still readable");
+        assert!(m.artifacts().iter().all(|a| a.stale.unwrap_or(0) == 0));
+    }
+
+    #[test]
+    fn last_reply_from_cached_offset_or_tail() {
+        let fx = crate::test_support::Fixture::new();
+        let path = fx.session("-p", "s1", &["first", "second"], 10);
+        let m = fx.manager();
+        let s = &m.load_sessions().unwrap()[0];
+        assert!(s.last_reply_at.is_some());
+        assert_eq!(m.last_reply(&path, s.last_reply_at).unwrap().as_deref(), Some("Answer to second"));
+        assert_eq!(m.last_reply(&path, Some(0)).unwrap().as_deref(), Some("Answer to second"));
+
+        let big = format!("{}\n", serde_json::json!({"type": "user", "message": {"role": "user", "content": "x".repeat(300_000)}}));
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(big.as_bytes()).unwrap();
+        assert_eq!(m.last_reply(&path, None).unwrap().as_deref(), Some("Answer to second"));
+        let empty = fx.write("projects/-p/none.jsonl", &big);
+        assert_eq!(m.last_reply(&empty, None).unwrap(), None);
+    }
+
+    #[test]
+    fn summarizes_a_continue_session() {
+        use serde_json::json;
+        let fx = crate::test_support::Fixture::new();
+        let user = |t: &str| json!({"type": "user", "cwd": "/home/me/api", "message": {"role": "user", "content": t}});
+        let reply = |content: Value| json!({"type": "assistant", "message": {"role": "assistant", "content": content}});
+        let edit = |tool: &str, path: &str| json!({"type": "tool_use", "name": tool, "input": {"file_path": path}});
+        let lines = [
+            user("continue where we left off"),
+            reply(json!([{"type": "text", "text": "Picking up the retry work."}, edit("Edit", "/home/me/api/src/retry.rs")])),
+            user("Make the HTTP client retry idempotent requests with exponential backoff, capped at five tries"),
+            reply(json!([edit("Write", "/home/me/api/tests/retry.rs"), edit("Edit", "/home/me/api/src/retry.rs"), edit("Read", "/home/me/api/Cargo.toml")])),
+            user("ok"),
+            reply(json!([{"type": "text", "text": "Both tests pass now. Backoff doubles from 100ms.\n\nLet me know if you want jitter too!\n\n---"}])),
+        ];
+        let mut lines = lines.to_vec();
+        for (id, input) in [("m1", 100), ("m1", 100), ("m2", 40)] {
+            lines[1]["message"]["id"] = json!(id);
+            lines[1]["message"]["usage"] = json!({"input_tokens": input, "cache_read_input_tokens": 5000, "output_tokens": 10});
+            lines[1]["message"]["model"] = json!(if id == "m2" { "claude-opus-4-1-20250805" } else { "claude-sonnet-4-5-20250929" });
+            lines.insert(2, lines[1].clone());
+        }
+        fx.write("projects/-home-me-api/s1.jsonl", &lines.iter().map(|l| l.to_string() + "\n").collect::<String>());
+        let m = fx.manager();
+        let s = m.load_sessions().unwrap().remove(0);
+        assert_eq!(s.first_message, "continue where we left off");
+        let expected = Summary {
+            focus: Some("Make the HTTP client retry idempotent requests with exponential backoff, capped at five tries".into()),
+            files: vec!["/home/me/api/src/retry.rs".into(), "/home/me/api/tests/retry.rs".into()],
+            outcome: Some("Let me know if you want jitter too!".into()),
+        };
+        assert_eq!(s.summary.as_ref(), Some(&expected));
+        assert_eq!(s.tokens, 110 + 50, "usage repeated across one message's records counts once");
+        assert_eq!((s.usage.input, s.usage.output, s.usage.cache_read), (140, 20, 10_000));
+        let sonnet = 100.0 * 3.0 + 10.0 * 15.0 + 5000.0 * 0.3;
+        let opus = 40.0 * 15.0 + 10.0 * 75.0 + 5000.0 * 1.5;
+        assert!((s.usage.cost - (sonnet + opus) / 1e6).abs() < 1e-12);
+        assert_eq!(describe_models(&s.models), "opus-4-1: 1 msg, sonnet-4-5: 1 msg");
+        assert_eq!(short_model("claude-3-5-haiku-20241022"), "3-5-haiku");
+        assert_eq!([0.0108, 0.003, 1.234].map(format_cost), ["$0.01", "<$0.01", "$1.23"]);
+        assert_eq!(s.summary.unwrap().files_relative_to(s.cwd.as_deref()), ["src/retry.rs", "tests/retry.rs"]);
+        assert_eq!(m.load_sessions().unwrap()[0].summary, Some(expected), "summary survives the cache");
+        assert_eq!(Summary::last_sentence("Done. See v1.2 notes"), Some("See v1.2 notes".into()));
+
+        let mut m = m;
+        m.pricing.insert("opus-4".into(), Price { input: 1.0, output: 1.0, cache_write: None, cache_read: Some(0.0) });
+        let cached = m.load_sessions().unwrap().remove(0);
+        assert!((cached.usage.cost - (sonnet + 40.0 + 10.0) / 1e6).abs() < 1e-12, "the configured price applies to cached usage");
+        assert_eq!(price_for(&m.pricing, "opus-4-1").map(|p| p.input), Some(1.0), "a configured fragment beats a longer default");
+        assert_eq!(price_for(&m.pricing, "sonnet-4-5").map(|p| p.input), Some(3.0));
+        assert_eq!(price_for(&m.pricing, "gpt-4"), None);
+    }
+
+    #[test]
+    fn classifies_code_prompts() {
+        assert_eq!(PromptKind::classify("```rust\nfn main() {}\n```"), PromptKind::Code);
+        assert_eq!(PromptKind::classify("diff --git a/x b/x\n--- a/x\n+++ b/x\n@@ -1 +1 @@"), PromptKind::Code);
+        assert_eq!(PromptKind::classify("fn main() {\n    let x = 1;\n    run(x);\n}"), PromptKind::Code);
+    }
+
+    #[test]
+    fn classifies_questions() {
+        assert_eq!(PromptKind::classify("Why does the parser drop trailing commas?"), PromptKind::Question);
+        assert_eq!(PromptKind::classify("how do I resume a session"), PromptKind::Question);
+    }
+
+    #[test]
+    fn classifies_tasks() {
+        assert_eq!(PromptKind::classify("/review src/main.rs"), PromptKind::Task);
+        assert_eq!(PromptKind::classify("Fix the failing tests"), PromptKind::Task);
+    }
+
+    #[test]
+    fn long_prose_is_other() {
+        let prose = "I have been looking at the cache layer and I think the invalidation is wrong because the mtime is truncated to seconds.\nLet's talk about options.";
+        assert_eq!(PromptKind::classify(prose), PromptKind::Other);
+        assert_eq!(PromptKind::classify("   "), PromptKind::Other);
+    }
+
+    #[test]
+    fn kind_round_trips_through_byte() {
+        for k in [PromptKind::Other, PromptKind::Code, PromptKind::Question, PromptKind::Task] {
+            assert_eq!(PromptKind::from_u8(k as u8), k);
+        }
     }
 }
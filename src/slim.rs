@@ -0,0 +1,145 @@
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Result of slimming a transcript: the rewritten text and how many lines changed.
+pub struct Slimmed {
+    pub content: String,
+    pub records_changed: usize,
+}
+
+fn placeholder(bytes: usize) -> Value {
+    Value::String(format!("[truncated, {} bytes removed]", bytes))
+}
+
+/// Replaces `value` with a placeholder when its serialized form exceeds `threshold` bytes.
+fn truncate(value: &mut Value, threshold: usize) -> bool {
+    let size = value.to_string().len();
+    if size <= threshold { return false; }
+    *value = placeholder(size);
+    true
+}
+
+/// Shrinks oversized tool_result payloads (and the matching `toolUseResult` copy) in one jsonl
+/// record. Returns None when the record is left alone so it can be kept byte-for-byte.
+fn slim_record(line: &str, threshold: usize) -> Option<String> {
+    let mut v: Value = serde_json::from_str(line).ok()?;
+    let mut changed = false;
+    if let Some(items) = v.pointer_mut("/message/content").and_then(Value::as_array_mut) {
+        for item in items.iter_mut().filter(|i| i.get("type").and_then(Value::as_str) == Some("tool_result")) {
+            if let Some(content) = item.get_mut("content") { changed |= truncate(content, threshold); }
+        }
+    }
+    if let Some(r) = v.get_mut("toolUseResult") { changed |= truncate(r, threshold); }
+    changed.then(|| v.to_string())
+}
+
+/// Rewrites a session transcript with every tool result larger than `threshold` bytes replaced
+/// by a placeholder. Records without oversized results are copied verbatim.
+pub fn slim_jsonl(content: &str, threshold: usize) -> Slimmed {
+    let mut out = String::with_capacity(content.len());
+    let mut records_changed = 0;
+    for line in content.lines() {
+        match slim_record(line, threshold) {
+            Some(slimmed) => { out.push_str(&slimmed); records_changed += 1; }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    Slimmed { content: out, records_changed }
+}
+
+/// Text left in place of a redacted message.
+pub const REDACTED: &str = "[redacted]";
+
+/// Blanks the message in one record while keeping its ids: text and thinking become the
+/// placeholder, tool calls keep their `id` with an empty input, and tool results keep their
+/// `tool_use_id`, so the call/result pairing a resume relies on survives.
+fn redact_record(line: &str) -> Option<String> {
+    let mut v: Value = serde_json::from_str(line).ok()?;
+    let content = v.pointer_mut("/message/content")?;
+    match content {
+        Value::Array(items) => for item in items.iter_mut() {
+            let Some(obj) = item.as_object_mut() else { continue };
+            match obj.get("type").and_then(Value::as_str) {
+                Some("text") => { obj.insert("text".into(), REDACTED.into()); }
+                Some("thinking") => { obj.insert("thinking".into(), REDACTED.into()); obj.remove("signature"); }
+                Some("tool_use") => { obj.insert("input".into(), Value::Object(Default::default())); }
+                Some("tool_result") => { obj.insert("content".into(), REDACTED.into()); }
+                _ => {}
+            }
+        },
+        other => *other = REDACTED.into(),
+    }
+    if let Some(r) = v.get_mut("toolUseResult") { *r = REDACTED.into(); }
+    Some(v.to_string())
+}
+
+/// Rewrites a session transcript with the messages on the given 0-based jsonl lines redacted.
+/// Every other record is copied verbatim.
+pub fn redact_jsonl(content: &str, records: &HashSet<usize>) -> Slimmed {
+    let mut out = String::with_capacity(content.len());
+    let mut records_changed = 0;
+    for (n, line) in content.lines().enumerate() {
+        match records.contains(&n).then(|| redact_record(line)).flatten() {
+            Some(redacted) => { out.push_str(&redacted); records_changed += 1; }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    Slimmed { content: out, records_changed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../tests/fixtures/tool_heavy.jsonl");
+
+    #[test]
+    fn untouched_records_are_byte_identical() {
+        let slimmed = slim_jsonl(FIXTURE, 1024);
+        assert_eq!(slimmed.records_changed, 2);
+        let before: Vec<&str> = FIXTURE.lines().collect();
+        let after: Vec<&str> = slimmed.content.lines().collect();
+        assert_eq!(before.len(), after.len());
+        for (b, a) in before.iter().zip(&after) {
+            if !b.contains("tool_result") || b.len() < 1024 { assert_eq!(b, a); }
+        }
+    }
+
+    #[test]
+    fn slimmed_records_keep_structure() {
+        let slimmed = slim_jsonl(FIXTURE, 1024);
+        assert!(slimmed.content.len() < FIXTURE.len() / 4);
+        for (b, a) in FIXTURE.lines().zip(slimmed.content.lines()) {
+            let (b, a): (Value, Value) = (serde_json::from_str(b).unwrap(), serde_json::from_str(a).unwrap());
+            assert_eq!(b["uuid"], a["uuid"]);
+            assert_eq!(b["type"], a["type"]);
+            assert_eq!(b.pointer("/message/content/0/tool_use_id"), a.pointer("/message/content/0/tool_use_id"));
+        }
+        assert!(slimmed.content.contains("[truncated, 9002 bytes removed]"));
+    }
+
+    #[test]
+    fn huge_threshold_leaves_fixture_unchanged() {
+        let slimmed = slim_jsonl(FIXTURE, usize::MAX);
+        assert_eq!(slimmed.records_changed, 0);
+        assert_eq!(slimmed.content, FIXTURE);
+    }
+
+    #[test]
+    fn redacted_records_keep_ids_and_order() {
+        let records: HashSet<usize> = (0..FIXTURE.lines().count()).filter(|&n| n % 2 == 1).collect();
+        let redacted = redact_jsonl(FIXTURE, &records);
+        assert_eq!(redacted.records_changed, records.len());
+        for (n, (b, a)) in FIXTURE.lines().zip(redacted.content.lines()).enumerate() {
+            if !records.contains(&n) { assert_eq!(b, a); continue; }
+            let (b, a): (Value, Value) = (serde_json::from_str(b).unwrap(), serde_json::from_str(a).unwrap());
+            for key in ["uuid", "parentUuid", "sessionId", "type"] { assert_eq!(b[key], a[key]); }
+            assert_eq!(b.pointer("/message/content/0/tool_use_id"), a.pointer("/message/content/0/tool_use_id"));
+            assert_eq!(b.pointer("/message/content/0/id"), a.pointer("/message/content/0/id"));
+            assert!(a.to_string().contains(REDACTED));
+        }
+        assert!(redacted.content.len() < FIXTURE.len());
+    }
+}
@@ -0,0 +1,132 @@
+//! Totals saved on exit so the next launch can say how much the sessions grew in between.
+
+use crate::sessions::{format_size, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Projects listed by size change on the stats screen.
+const DELTA_PROJECTS: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Unix seconds.
+    pub taken: i64,
+    /// The `~/.claude` the totals were taken from.
+    pub root: String,
+    pub total: u64,
+    pub count: usize,
+    pub projects: BTreeMap<String, u64>,
+}
+
+/// Change between two snapshots.
+#[derive(Debug, PartialEq)]
+pub struct Delta {
+    pub elapsed: i64,
+    pub bytes: i64,
+    pub sessions: i64,
+    /// Projects whose size changed, largest change first.
+    pub projects: Vec<(String, i64)>,
+}
+
+impl Snapshot {
+    pub fn of(root: &Path, sessions: &[Session], taken: i64) -> Self {
+        let mut projects = BTreeMap::new();
+        for s in sessions { *projects.entry(s.project.clone()).or_default() += s.size; }
+        Snapshot {
+            taken,
+            root: root.to_string_lossy().into_owned(),
+            total: sessions.iter().map(|s| s.size).sum(),
+            count: sessions.len(),
+            projects,
+        }
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// What changed since `prev`; nothing when it describes another root or claims to be
+    /// from the future.
+    pub fn since(&self, prev: &Snapshot) -> Option<Delta> {
+        if prev.root != self.root || prev.taken > self.taken { return None; }
+        let names: BTreeSet<&String> = self.projects.keys().chain(prev.projects.keys()).collect();
+        let mut projects: Vec<(String, i64)> = names.into_iter()
+            .map(|p| (p.clone(), *self.projects.get(p).unwrap_or(&0) as i64 - *prev.projects.get(p).unwrap_or(&0) as i64))
+            .filter(|(_, d)| *d != 0)
+            .collect();
+        projects.sort_by_key(|(p, d)| (std::cmp::Reverse(d.abs()), p.clone()));
+        Some(Delta {
+            elapsed: self.taken - prev.taken,
+            bytes: self.total as i64 - prev.total as i64,
+            sessions: self.count as i64 - prev.count as i64,
+            projects,
+        })
+    }
+}
+
+/// `+1.5MB`, `-12KB`.
+pub fn signed_size(bytes: i64) -> String {
+    format!("{}{}", if bytes < 0 { "-" } else { "+" }, format_size(bytes.unsigned_abs()))
+}
+
+impl Delta {
+    /// One line for the status bar and the stats screen.
+    pub fn describe(&self) -> String {
+        let when = match self.elapsed {
+            s if s < 3600 => format!("{}m", s / 60),
+            s if s < 86400 => format!("{}h", s / 3600),
+            s => format!("{}d", s / 86400),
+        };
+        let change = match self.bytes {
+            0 => "did not change in size".to_string(),
+            b if b > 0 => format!("grew by {}", format_size(b as u64)),
+            b => format!("shrank by {}", format_size(b.unsigned_abs())),
+        };
+        format!("Sessions {} ({:+} sessions) since the last launch {} ago", change, self.sessions, when)
+    }
+
+    /// Stats screen lines: the summary and the projects that changed most.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.describe()];
+        lines.extend(self.projects.iter().take(DELTA_PROJECTS).map(|(p, d)| format!("  {:>9}  {}", signed_size(*d), p)));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(taken: i64, root: &str, projects: &[(&str, u64)]) -> Snapshot {
+        Snapshot {
+            taken,
+            root: root.into(),
+            total: projects.iter().map(|p| p.1).sum(),
+            count: projects.len(),
+            projects: projects.iter().map(|(p, s)| (p.to_string(), *s)).collect(),
+        }
+    }
+
+    #[test]
+    fn delta_ranks_projects_and_skips_other_roots_and_skew() {
+        let before = snap(1000, "/h/.claude", &[("a", 1 << 20), ("b", 4096), ("gone", 8192)]);
+        let now = snap(1000 + 7200, "/h/.claude", &[("a", 3 << 20), ("b", 4096), ("new", 2048)]);
+        let d = now.since(&before).unwrap();
+        assert_eq!(d.bytes, (2 << 20) - 8192 + 2048);
+        assert_eq!(d.sessions, 0);
+        assert_eq!(d.projects, [("a".to_string(), 2 << 20), ("gone".to_string(), -8192), ("new".to_string(), 2048)]);
+        assert_eq!(d.describe(), "Sessions grew by 2.0MB (+0 sessions) since the last launch 2h ago");
+        assert_eq!(d.lines()[2], "       -8KB  gone");
+
+        assert!(now.since(&snap(1000, "/other/.claude", &[])).is_none());
+        assert!(before.since(&now).is_none(), "a snapshot from the future means the clock moved");
+    }
+}
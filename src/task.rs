@@ -0,0 +1,147 @@
+//! Batch work on a worker thread, with progress the UI can poll and cooperative cancellation
+//! between items.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Snapshot of a running task.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    /// Label of the item being worked on.
+    pub current: String,
+    /// Bytes reported by finished items.
+    pub bytes: u64,
+}
+
+/// What a finished or cancelled task got through.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub completed: Vec<String>,
+    pub failed: Vec<(String, String)>,
+    /// Items never started because the task was cancelled.
+    pub skipped: Vec<String>,
+    pub bytes: u64,
+}
+
+impl Outcome {
+    /// A report for the message popup, e.g. "Exported 3 of 10 (cancelled, 7 skipped)".
+    pub fn summary(&self, verb: &str) -> String {
+        let total = self.completed.len() + self.failed.len() + self.skipped.len();
+        let mut out = format!("{} {} of {}", verb, self.completed.len(), total);
+        if !self.skipped.is_empty() { out.push_str(&format!(" (cancelled, {} skipped)", self.skipped.len())); }
+        out.push('.');
+        for (label, err) in &self.failed {
+            out.push_str(&format!("\n- {}: {}", label, err));
+        }
+        out
+    }
+}
+
+pub struct Task {
+    pub title: String,
+    /// Past-tense verb for the summary ("Exported").
+    pub verb: &'static str,
+    progress: Arc<Mutex<Progress>>,
+    cancel: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Outcome>>,
+}
+
+impl Task {
+    /// Runs `work` over `items` in order on a worker thread. Each item carries a label for
+    /// progress and reporting; `work` returns the number of bytes it handled.
+    pub fn spawn<T, F>(title: impl Into<String>, verb: &'static str, items: Vec<(String, T)>, work: F) -> Task
+    where
+        T: Send + 'static,
+        F: Fn(T) -> io::Result<u64> + Send + 'static,
+    {
+        let progress = Arc::new(Mutex::new(Progress { total: items.len(), ..Default::default() }));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (p, c) = (progress.clone(), cancel.clone());
+        let thread = thread::spawn(move || {
+            let mut outcome = Outcome::default();
+            let mut items = items.into_iter();
+            for (label, item) in items.by_ref() {
+                if c.load(Ordering::Relaxed) {
+                    outcome.skipped.push(label);
+                    break;
+                }
+                p.lock().unwrap().current = label.clone();
+                match work(item) {
+                    Ok(bytes) => { outcome.bytes += bytes; outcome.completed.push(label); }
+                    Err(e) => outcome.failed.push((label, e.to_string())),
+                }
+                let mut p = p.lock().unwrap();
+                p.done += 1;
+                p.bytes = outcome.bytes;
+            }
+            outcome.skipped.extend(items.map(|(label, _)| label));
+            outcome
+        });
+        Task { title: title.into(), verb, progress, cancel, thread: Some(thread) }
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    /// Asks the worker to stop once the current item is done.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelling(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    /// Waits for the worker and returns what it did. A panicking worker reports nothing done.
+    pub fn join(mut self) -> Outcome {
+        self.thread.take().and_then(|t| t.join().ok()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn runs_every_item_and_reports_failures() {
+        let items = (0..5).map(|i| (format!("item {}", i), i)).collect();
+        let task = Task::spawn("Test", "Did", items, |i| if i == 3 { Err(io::Error::other("boom")) } else { Ok(10) });
+        let outcome = task.join();
+        assert_eq!(outcome.completed.len(), 4);
+        assert_eq!(outcome.failed, vec![("item 3".to_string(), "boom".to_string())]);
+        assert_eq!(outcome.bytes, 40);
+        assert_eq!(outcome.summary("Did"), "Did 4 of 5.\n- item 3: boom");
+    }
+
+    #[test]
+    fn cancel_stops_after_the_current_item() {
+        let (started_tx, started) = mpsc::channel();
+        let (go, go_rx) = mpsc::channel::<()>();
+        let go_rx = Mutex::new(go_rx);
+        let items = (0..10).map(|i| (format!("item {}", i), i)).collect();
+        let task = Task::spawn("Test", "Did", items, move |i| {
+            started_tx.send(i).unwrap();
+            go_rx.lock().unwrap().recv().unwrap();
+            Ok(1)
+        });
+        for _ in 0..2 { started.recv().unwrap(); go.send(()).unwrap(); }
+        assert_eq!(started.recv().unwrap(), 2);
+        assert_eq!(task.progress().current, "item 2");
+        task.cancel();
+        go.send(()).unwrap();
+        let outcome = task.join();
+        assert_eq!(outcome.completed, ["item 0", "item 1", "item 2"]);
+        assert_eq!(outcome.skipped.len(), 7);
+        assert_eq!(outcome.summary("Did"), "Did 3 of 10 (cancelled, 7 skipped).");
+    }
+}
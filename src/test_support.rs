@@ -0,0 +1,90 @@
+//! Builds a throwaway `~/.claude` tree for tests.
+//! Compiled into both the library's and the binary's tests, which each use part of it.
+#![allow(dead_code)]
+
+use crate::sessions::{Config, SessionManager};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+pub struct Fixture {
+    _dir: TempDir,
+    pub root: PathBuf,
+    pub state: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join(".claude");
+        let state = dir.path().join("state");
+        fs::create_dir_all(root.join("projects")).unwrap();
+        Fixture { _dir: dir, root, state }
+    }
+
+    pub fn manager(&self) -> SessionManager {
+        SessionManager::new(self.root.clone(), self.state.clone())
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.root.join(rel)
+    }
+
+    pub fn exists(&self, rel: &str) -> bool {
+        self.path(rel).exists()
+    }
+
+    pub fn write(&self, rel: &str, contents: &str) -> PathBuf {
+        let p = self.path(rel);
+        fs::create_dir_all(p.parent().unwrap()).unwrap();
+        fs::write(&p, contents).unwrap();
+        p
+    }
+
+    /// Writes `projects/<project>/<id>.jsonl` with one user/assistant exchange per prompt,
+    /// modified `age_secs` ago.
+    pub fn session(&self, project: &str, id: &str, prompts: &[&str], age_secs: u64) -> PathBuf {
+        let mut lines = Vec::new();
+        for p in prompts {
+            lines.push(json!({"type": "user", "sessionId": id, "message": {"role": "user", "content": p}}).to_string());
+            lines.push(json!({"type": "assistant", "sessionId": id, "message": {"role": "assistant", "model": "claude-test",
+                "content": [{"type": "text", "text": format!("Answer to {}", p)}]}}).to_string());
+        }
+        let p = self.write(&format!("projects/{}/{}.jsonl", project, id), &(lines.join("\n") + "\n"));
+        set_age(&p, age_secs);
+        p
+    }
+
+    /// Creates the debug log, session-env, file-history and an agent todo for `id`.
+    pub fn related(&self, id: &str) {
+        self.write(&format!("debug/{}.txt", id), "debug");
+        self.write(&format!("session-env/{}/env", id), "env");
+        self.write(&format!("file-history/{}/snap", id), "snap");
+        self.write(&format!("todos/{}-agent-{}.json", id, id), r#"[{"content":"open item","status":"pending"}]"#);
+    }
+
+    pub fn history(&self, entries: &[(&str, &str)]) {
+        let lines: Vec<String> = entries.iter()
+            .map(|(id, display)| json!({"display": display, "project": "/repo", "timestamp": 1_700_000_000_000i64, "sessionId": id}).to_string())
+            .collect();
+        self.write("history.jsonl", &lines.join("\n"));
+    }
+}
+
+/// The config fixtures run under: deletes skip the OS trash, so test sessions don't pile up in
+/// the developer's real one.
+pub fn config() -> Config {
+    let mut config = Config::default();
+    config.hard_delete = Some(true);
+    config
+}
+
+pub fn set_age(path: &Path, age_secs: u64) {
+    set_mtime(path, SystemTime::now() - Duration::from_secs(age_secs));
+}
+
+pub fn set_mtime(path: &Path, t: SystemTime) {
+    fs::File::options().write(true).open(path).unwrap().set_modified(t).unwrap();
+}
@@ -0,0 +1,68 @@
+//! Display-width helpers. Terminal cells are not chars: CJK and most emoji take two columns,
+//! combining marks none, and an emoji sequence is one glyph spread over several chars. Anything
+//! that truncates or wraps text for the screen goes through here and cuts between grapheme
+//! clusters only.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "...";
+
+/// Columns `s` takes on screen.
+pub fn width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// The longest prefix of `s` that fits in `max` columns, ending on a grapheme boundary.
+fn prefix(s: &str, max: usize) -> &str {
+    let mut used = 0;
+    for (at, g) in s.grapheme_indices(true) {
+        used += width(g);
+        if used > max { return &s[..at]; }
+    }
+    s
+}
+
+/// `s` cut to at most `max` columns, with `...` in place of whatever did not fit.
+pub fn ellipsize(s: &str, max: usize) -> String {
+    if width(s) <= max { return s.to_string(); }
+    let keep = max.saturating_sub(ELLIPSIS.len());
+    format!("{}{}", prefix(s, keep), &ELLIPSIS[..max.min(ELLIPSIS.len())])
+}
+
+/// Splits `s` into pieces of at most `max` columns (at least one grapheme each), for wrapping
+/// words longer than a line.
+pub fn chunks(s: &str, max: usize) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut head = prefix(rest, max.max(1));
+        if head.is_empty() { head = rest.graphemes(true).next().unwrap_or(rest); }
+        out.push(head);
+        rest = &rest[head.len()..];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths_follow_the_terminal_not_the_char_count() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("e\u{301}"), 1, "combining accent takes no column");
+        assert_eq!(width("🦀"), 2);
+
+        assert_eq!(ellipsize("short", 10), "short");
+        assert_eq!(ellipsize("日本語のテキスト", 9), "日本語...");
+        assert_eq!(ellipsize("日本語のテキスト", 10), "日本語...", "a wide glyph that would straddle the edge is dropped");
+        assert_eq!(ellipsize("cafe\u{301} au lait", 7), "cafe\u{301}...", "the accent stays with its letter");
+        assert_eq!(ellipsize("👨‍👩‍👧 family", 5), "👨‍👩‍👧...", "a joined emoji is never split");
+        assert_eq!(ellipsize("abcdef", 2), "..");
+
+        assert_eq!(chunks("日本語ab", 3), ["日", "本", "語a", "b"]);
+        assert_eq!(chunks("日", 1), ["日"], "a glyph wider than the line still moves forward");
+    }
+}
@@ -0,0 +1,98 @@
+//! Every style the UI uses, by role. The no-color theme keeps the same roles but expresses
+//! them with modifiers only, for `NO_COLOR`, dumb terminals and `color: "never"`.
+
+use crate::sessions::{ColorMode, PromptKind};
+use ratatui::style::{Color, Modifier, Style};
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// Highlighted row in lists.
+    pub highlight: Style,
+    /// Secondary text: hints, ids, sizes.
+    pub muted: Style,
+    /// Text being typed, focused borders.
+    pub accent: Style,
+    pub warning: Style,
+    pub danger: Style,
+    pub status_bar: Style,
+    /// Background of modal popups.
+    pub popup: Style,
+    pub gauge: Style,
+    kinds: [Style; 4],
+    tags: Vec<Style>,
+}
+
+impl Theme {
+    pub fn color() -> Self {
+        let fg = |c| Style::default().fg(c);
+        Theme {
+            highlight: Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD),
+            muted: fg(Color::DarkGray),
+            accent: fg(Color::Yellow),
+            warning: fg(Color::Yellow),
+            danger: fg(Color::Red),
+            status_bar: Style::default().fg(Color::DarkGray).bg(Color::Black),
+            popup: Style::default().bg(Color::Black),
+            gauge: fg(Color::Cyan),
+            kinds: [fg(Color::DarkGray), fg(Color::Cyan), fg(Color::Yellow), fg(Color::Green)],
+            tags: [Color::Cyan, Color::Magenta, Color::Green, Color::Yellow, Color::Blue, Color::LightRed]
+                .into_iter().map(|c| Style::default().fg(Color::Black).bg(c)).collect(),
+        }
+    }
+
+    pub fn no_color() -> Self {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let reversed = Style::default().add_modifier(Modifier::REVERSED);
+        Theme {
+            highlight: reversed.add_modifier(Modifier::BOLD),
+            muted: Style::default(),
+            accent: Style::default().add_modifier(Modifier::UNDERLINED),
+            warning: bold,
+            danger: bold,
+            status_bar: Style::default(),
+            popup: Style::default(),
+            gauge: reversed,
+            kinds: [Style::default(); 4],
+            tags: vec![reversed],
+        }
+    }
+
+    /// The theme for `mode`, where `Auto` (the default) turns colors off when `NO_COLOR` is set
+    /// or `TERM` is `dumb`.
+    pub fn detect(mode: Option<ColorMode>) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let dumb = std::env::var("TERM").is_ok_and(|t| t == "dumb");
+        if wants_color(mode.unwrap_or_default(), no_color, dumb) { Self::color() } else { Self::no_color() }
+    }
+
+    pub fn kind(&self, kind: PromptKind) -> Style {
+        self.kinds[kind as usize]
+    }
+
+    /// A stable per-tag chip style.
+    pub fn tag(&self, tag: &str) -> Style {
+        self.tags[tag.bytes().map(usize::from).sum::<usize>() % self.tags.len()]
+    }
+}
+
+fn wants_color(mode: ColorMode, no_color: bool, dumb: bool) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => !no_color && !dumb,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_mode_honors_no_color_and_dumb_terminals() {
+        assert!(wants_color(ColorMode::Auto, false, false));
+        assert!(!wants_color(ColorMode::Auto, true, false));
+        assert!(!wants_color(ColorMode::Auto, false, true));
+        assert!(wants_color(ColorMode::Always, true, true));
+        assert!(!wants_color(ColorMode::Never, false, false));
+    }
+}
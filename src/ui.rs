@@ -0,0 +1,1168 @@
+//! Drawing. `ui` renders one frame from `App` and records where panes landed in `App::layout`.
+
+use crate::text;
+use crate::theme::Theme;
+use crate::{App, ChecklistKind, ListRow, Mode, ReportKind};
+use crate::columns::{Column, ColumnSpec};
+use crate::sessions::{describe_models, format_age, format_cost, format_size, format_tokens, project_stats, PromptKind, Session, SortBy};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Clear, Gauge, List, ListItem, Paragraph, Row, Table, TableState, Wrap},
+    Frame,
+};
+
+/// Where the panes of the last drawn frame ended up, for anything that needs to place itself
+/// relative to them (popups, mouse hits).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutInfo {
+    pub list: Rect,
+    pub preview: Rect,
+    pub status: Rect,
+    /// Screen row of each visible list item with its index into `App::rows`.
+    pub rows: Vec<(Rect, usize)>,
+    /// Header cell of each list column.
+    pub header: Vec<(Rect, Column)>,
+}
+
+impl LayoutInfo {
+    /// The screen row of `rows[idx]`, if it is scrolled into view.
+    pub fn row_of(&self, idx: usize) -> Option<Rect> {
+        self.rows.iter().find(|(_, i)| *i == idx).map(|(r, _)| *r)
+    }
+}
+
+/// Below this size the UI is replaced by a "terminal too small" notice.
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 12;
+/// Touched files listed in the preview before the rest are counted.
+const SUMMARY_FILES: usize = 5;
+/// Narrowest the message popup gets on small terminals.
+const MESSAGE_MIN_WIDTH: u16 = 40;
+/// The tags column grows to fit the chips on screen, up to this width.
+const TAGS_MAX_WIDTH: u16 = 24;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// `⠹ Loading sessions` while a load runs, turning a step every 100ms.
+fn loading_label(app: &App) -> Option<String> {
+    let started = app.loading.as_ref()?.started;
+    Some(format!("{} Loading sessions", SPINNER[(started.elapsed().as_millis() / 100) as usize % SPINNER.len()]))
+}
+
+/// List rows built on earlier frames, keyed by session id and reused while the selection mark
+/// and sort order match. `App::apply_filter` clears it, which covers every edit to a session,
+/// and so does changing the columns. A new width for the name column starts it over.
+#[derive(Default)]
+pub struct RowCache(HashMap<String, (bool, SortBy, String, Row<'static>)>, usize);
+
+impl RowCache {
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn fit_names(&mut self, name_width: usize) {
+        if self.1 != name_width { self.0.clear(); self.1 = name_width; }
+    }
+
+    fn row(&mut self, s: &Session, selected: bool, sort: SortBy, columns: &[ColumnSpec], theme: &Theme, now: SystemTime) -> Row<'static> {
+        let age = s.formatted_age(now);
+        if let Some((sel, so, a, row)) = self.0.get(&s.id) {
+            if *sel == selected && *so == sort && *a == age { return row.clone(); }
+        }
+        let row = Row::new(columns.iter().map(|c| cell(c.column, s, selected, self.1, theme, now)));
+        self.0.insert(s.id.clone(), (selected, sort, age, row.clone()));
+        row
+    }
+}
+
+/// One cell of a session row. The name is cut to `name_width` with an ellipsis, leaving room
+/// for the missing-project note, rather than clipped at the column edge.
+fn cell(column: Column, s: &Session, selected: bool, name_width: usize, theme: &Theme, now: SystemTime) -> Cell<'static> {
+    match column {
+        Column::Mark => {
+            let mark = if selected { "[x]" } else if s.protected { "[p]" } else if s.hidden { "[h]" } else { "[ ]" };
+            // A pin takes the place of the prompt kind.
+            let lead = if s.pinned { Span::styled("★ ", theme.warning) } else { kind_glyph(s.prompt_kind, theme) };
+            Line::from(vec![lead, Span::raw(mark)]).into()
+        }
+        Column::Name => {
+            const MISSING: &str = " missing project";
+            let room = if s.project_missing { name_width.saturating_sub(MISSING.len()).max(name_width.min(8)) } else { name_width };
+            let mut spans = vec![Span::raw(text::ellipsize(&s.display_name(), room))];
+            if s.project_missing { spans.push(Span::styled(MISSING, theme.danger)); }
+            Line::from(spans).into()
+        }
+        Column::Project => s.project.clone().into(),
+        Column::Id => s.id.clone().into(),
+        Column::Size => s.size_str().into(),
+        Column::Msgs => if s.message_count > 0 { s.message_count.to_string() } else { "empty".to_string() }.into(),
+        Column::Age => s.formatted_age(now).into(),
+        Column::Tokens => if s.tokens > 0 { format_tokens(s.tokens) } else { "-".to_string() }.into(),
+        Column::Cost => if s.usage.cost > 0.0 { format_cost(s.usage.cost) } else { "-".to_string() }.into(),
+        Column::Todos => match s.open_todos() { 0 => String::new(), n => n.to_string() }.into(),
+        Column::Tags => Line::from(tag_chips(&s.tags, theme).into_iter().skip(1).collect::<Vec<_>>()).into(),
+    }
+}
+
+fn chips_width(tags: &[String]) -> u16 {
+    tags.iter().map(|t| text::width(t) as u16 + 3).sum::<u16>().saturating_sub(1)
+}
+
+/// What to say in place of an empty list: where we looked when there are no sessions at all,
+/// or how to get back out of a filter that matches nothing.
+fn empty_state(app: &App) -> Vec<Line<'static>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    if let Some(label) = loading_label(app).filter(|_| app.sessions.is_empty()) {
+        return vec![Line::styled(format!("{}...", label), bold)];
+    }
+    let hidden = if app.show_hidden { 0 } else { app.hidden_count };
+    let Some(o) = &app.overview else {
+        if app.filter.is_empty() {
+            return vec![
+                Line::styled(format!("All {} sessions are hidden.", hidden), bold),
+                Line::from(""),
+                Line::from("Press H to show them."),
+            ];
+        }
+        let mut lines = vec![
+            Line::styled(format!("No matches for '{}'", app.filter), bold),
+            Line::from(""),
+            Line::from("Press Esc to clear the filter, or / to edit it."),
+        ];
+        if hidden > 0 {
+            lines.push(Line::styled(format!("{} hidden session{} not searched; H includes them.", hidden, if hidden == 1 { " is" } else { "s are" }), app.theme.muted));
+        }
+        return lines;
+    };
+    let mut lines = vec![
+        Line::styled("No Claude Code sessions found.", bold),
+        Line::from(""),
+        Line::from(format!("Looked in: {}", o.projects.display())),
+        Line::from(format!("Project directories: {}", o.project_dirs)),
+    ];
+    let note = if !o.exists {
+        "That directory does not exist yet. Sessions show up here once Claude Code has been used.".to_string()
+    } else if o.agent_files > 0 {
+        format!("Only sub-agent transcripts were found ({} agent-*.jsonl files); those are not listed as sessions.", o.agent_files)
+    } else if o.project_dirs > 0 {
+        "The project directories contain no session files.".to_string()
+    } else {
+        "Sessions show up here once Claude Code has been used.".to_string()
+    };
+    lines.extend([Line::styled(note, app.theme.muted), Line::from(""), Line::styled("Getting started", bold)]);
+    for (key, what) in [("I", "import archives from another machine"), ("z", "browse archived sessions"), ("i", "stats"), ("q", "quit")] {
+        lines.push(Line::from(vec![Span::styled(format!("  {:<3}", key), app.theme.accent), Span::raw(what)]));
+    }
+    lines
+}
+
+fn kind_glyph(kind: PromptKind, theme: &Theme) -> Span<'static> {
+    let glyph = match kind {
+        PromptKind::Code => "{ ",
+        PromptKind::Question => "? ",
+        PromptKind::Task => "> ",
+        PromptKind::Other => "· ",
+    };
+    Span::styled(glyph, theme.kind(kind))
+}
+
+fn tag_chips(tags: &[String], theme: &Theme) -> Vec<Span<'static>> {
+    tags.iter().flat_map(|t| [
+        Span::raw(" "),
+        Span::styled(format!(" {} ", t), theme.tag(t)),
+    ]).collect()
+}
+
+/// The preview pane's contents for `s`; the triage screen shows the same.
+fn preview_lines(app: &App, s: &Session) -> Vec<Line<'static>> {
+    let todos = s.get_todos();
+    let mut info = format!("ID: {}\nName from: {}\nProject: {}\nSize: {}\nModified: {}\n", 
+        s.id, s.name_source().describe(), s.project, s.size_str(), s.formatted_age(app.now()));
+
+    if s.message_count > 0 {
+        info.push_str(&format!("Messages: {}\n", s.message_count));
+    }
+    if s.tokens > 0 {
+        info.push_str(&format!("Tokens: {} (~{})\n", s.usage.describe(), format_cost(s.usage.cost)));
+    }
+    if !s.models.is_empty() {
+        info.push_str(&format!("Models: {}\n", describe_models(&s.models)));
+    }
+    if let Some(h) = s.history_prompt.as_deref().filter(|h| h.trim() != s.first_message.trim()) {
+        info.push_str(&format!("History prompt: {}\n", h));
+    }
+    if let Some(note) = &s.note {
+        info.push_str(&format!("\nNOTE:\n{}\n", note));
+    }
+    if !todos.is_empty() {
+        info.push_str(&format!("\nTODO:\n- {}\n", todos.join("\n- ")));
+    }
+    if let Some(found) = app.file_search.as_ref().and_then(|f| f.hits.lock().unwrap().get(&s.id).cloned()) {
+        info.push_str(&format!("\nMATCHED FILES:\n- {}\n", found.join("\n- ")));
+    }
+    if let Some(found) = app.content_search.as_ref().and_then(|c| c.hits.lock().unwrap().get(&s.id).cloned()) {
+        info.push_str(&format!("\nMATCHED TEXT:\n{}\n", found));
+    }
+    let summary = s.summary.clone().unwrap_or_default();
+    let focus = summary.focus.as_deref().filter(|f| *f != s.first_message.trim());
+    if focus.is_some() || !summary.files.is_empty() || summary.outcome.is_some() {
+        info.push_str("\nSUMMARY:\n");
+        if let Some(f) = focus { info.push_str(&format!("Asked: {}\n", f)); }
+        if !summary.files.is_empty() {
+            let files = summary.files_relative_to(s.cwd.as_deref());
+            let more = files.len().saturating_sub(SUMMARY_FILES);
+            info.push_str(&format!("Files: {}{}\n", files[..files.len() - more].join(", "), if more > 0 { format!(", +{} more", more) } else { String::new() }));
+        }
+        if let Some(o) = &summary.outcome { info.push_str(&format!("Ended: {}\n", o)); }
+    }
+    if let Some(p) = app.projects.iter().find(|p| p.name == s.project) {
+        info.push_str(&format!("\nPROJECT:\n{}\n", p.describe(app.now())));
+    }
+    if !s.first_message.is_empty() {
+        info.push_str(&format!("\nPROMPT:\n{}", s.first_message));
+    }
+    let mut lines: Vec<Line> = info.lines().map(|l| Line::from(l.to_string())).collect();
+    if !s.tags.is_empty() {
+        let mut tags = vec![Span::raw("Tags:")];
+        tags.extend(tag_chips(&s.tags, &app.theme));
+        lines.insert(4, Line::from(tags));
+    }
+    if let Some(cwd) = &s.cwd {
+        let mut path = vec![Span::raw(format!("Path: {}", cwd))];
+        if s.project_missing { path.push(Span::styled(" (missing project)", app.theme.danger)); }
+        lines.insert(2, Line::from(path));
+    }
+    lines
+}
+
+/// The preview for a project heading in the grouped view: the totals over all its sessions,
+/// then over the ones the filter lets through when that differs.
+fn project_preview(app: &App, name: &str) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::styled(format!("Project: {}", name), Style::default().add_modifier(Modifier::BOLD))];
+    if let Some(p) = app.projects.iter().find(|p| p.name == name) {
+        lines.push(Line::from(p.describe(app.now())));
+    }
+    let shown = app.filtered.iter().map(|&i| &app.sessions[i]).filter(|s| s.project == name);
+    if let Some(p) = project_stats(shown).pop().filter(|p| app.projects.iter().all(|q| q != p)) {
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!("Shown: {}", p.describe(app.now()))));
+    }
+    lines
+}
+
+pub fn ui(f: &mut Frame, app: &mut App) {
+    let area = f.area();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        let msg = format!("Terminal too small ({}x{}), need at least {}x{}", area.width, area.height, MIN_WIDTH, MIN_HEIGHT);
+        f.render_widget(Paragraph::new(msg).wrap(Wrap{trim:true}).alignment(Alignment::Center), area);
+        app.layout = LayoutInfo::default();
+        return;
+    }
+    let main_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(f.area());
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(main_layout[0]);
+    app.layout = LayoutInfo { list: chunks[0], preview: chunks[1], status: main_layout[1], ..Default::default() };
+
+    let hidden = app.hidden_count;
+    let total = if app.show_hidden { app.sessions.len() } else { app.sessions.len() - hidden };
+    let then = app.sort_then.filter(|&t| t != app.sort).map(|t| format!(", {:?}", t)).unwrap_or_default();
+    let mut title = format!(" Sessions ({}/{}) Filter:[{}] Sort:[{:?}{}] ", 
+        app.filtered.len(), total, app.filter, app.sort, then);
+    if hidden > 0 {
+        title.push_str(&format!("{} hidden{} ", hidden, if app.show_hidden { " (shown)" } else { "" }));
+    }
+    if let Some(base) = &app.compare_base {
+        title.push_str(&format!("Compare:[{:.8}] ", base));
+    }
+    if !app.deferred.is_empty() {
+        title.push_str(&format!("{} projects not scanned ", app.deferred.len()));
+    }
+    if let Some(label) = loading_label(app) {
+        title.push_str(&format!("{} ", label));
+    }
+
+    // Only the rows that fit are built; scrolling mirrors what `Table` does with a full row set.
+    let mut block = Block::default().borders(Borders::ALL).title(title).title_alignment(Alignment::Center);
+    if app.manager.read_only { block = block.title(Line::styled(" READ-ONLY ", app.theme.danger.add_modifier(Modifier::BOLD)).left_aligned()); }
+    let inner = block.inner(app.layout.list);
+    let height = inner.height.saturating_sub(1) as usize;
+    let mut offset = app.state.offset().min(app.rows.len().saturating_sub(1));
+    if let Some(sel) = app.state.selected() {
+        if sel < offset { offset = sel; }
+        else if height > 0 && sel >= offset + height { offset = sel + 1 - height; }
+    }
+    *app.state.offset_mut() = offset;
+    let end = (offset + height).min(app.rows.len());
+    let now = app.now();
+    let snippets = app.content_search.as_ref().map(|c| c.hits.lock().unwrap().clone()).unwrap_or_default();
+    let label_column = app.columns.iter().find(|c| c.column == Column::Name).or(app.columns.first()).map(|c| c.column);
+    let tags_width = app.rows[offset..end].iter()
+        .filter_map(|r| match r { ListRow::Session(pos) => Some(chips_width(&app.sessions[app.filtered[*pos]].tags)), _ => None })
+        .max().unwrap_or(0).min(TAGS_MAX_WIDTH);
+    // The sorted column is kept wide enough to show its arrow.
+    let widths: Vec<Constraint> = app.columns.iter().map(|c| match (c.width.or(c.column.default_width()), c.column) {
+        (Some(w), col) if col.sort() == Some(app.sort) => Constraint::Length(w.max(text::width(col.header()) as u16 + 2)),
+        (Some(w), _) => Constraint::Length(w),
+        (None, Column::Tags) => Constraint::Length(tags_width),
+        (None, _) => Constraint::Fill(1),
+    }).collect();
+    let arrow = if app.sort_reversed { " ▲" } else { " ▼" };
+    let header = Row::new(app.columns.iter().map(|c| {
+        let sorted = c.column.sort() == Some(app.sort);
+        format!("{}{}", c.column.header(), if sorted { arrow } else { "" })
+    })).style(Style::default().add_modifier(Modifier::BOLD));
+    // Same split `Table` makes, so clicks can be mapped back to columns.
+    let cells = Layout::horizontal(widths.clone()).flex(ratatui::layout::Flex::Start).spacing(1).split(Rect { height: 1, ..inner });
+    app.layout.header = cells.iter().zip(&app.columns).map(|(r, c)| (*r, c.column)).collect();
+    let name_width = app.layout.header.iter().find(|(_, c)| *c == Column::Name).map_or(usize::MAX, |(r, _)| r.width as usize);
+    app.row_cache.fit_names(name_width);
+    let rows: Vec<Row> = app.rows[offset..end].iter().map(|row| {
+        let i = match row {
+            ListRow::Session(pos) => app.filtered[*pos],
+            ListRow::Project { name, count, size, collapsed } => {
+                let label = format!("{} {}  {} session{}, {}", if *collapsed { "▸" } else { "▾" }, name, count, if *count == 1 { "" } else { "s" }, format_size(*size));
+                return Row::new(app.columns.iter().map(|c| if Some(c.column) == label_column { label.clone() } else { String::new() }))
+                    .style(app.theme.accent.add_modifier(Modifier::BOLD));
+            }
+        };
+        let s = &app.sessions[i];
+        match snippets.get(&s.id) {
+            // Rows carrying a `text:` match are not cached; the search is short-lived.
+            Some(snippet) => Row::new(app.columns.iter().map(|c| match c.column {
+                Column::Name => Line::from(vec![Span::raw(s.display_name()), Span::styled(format!("  {}", snippet), app.theme.muted)]).into(),
+                col => cell(col, s, app.selected.contains(&i), name_width, &app.theme, now),
+            })),
+            None => app.row_cache.row(s, app.selected.contains(&i), app.sort, &app.columns, &app.theme, now),
+        }
+    }).collect();
+    if app.filtered.is_empty() {
+        f.render_widget(Paragraph::new(empty_state(app)).wrap(Wrap{trim:false}).block(block.padding(ratatui::widgets::Padding::new(2, 2, 1, 0))), app.layout.list);
+    } else {
+        let mut window = TableState::default().with_selected(app.state.selected().map(|s| s - offset));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(block)
+            .row_highlight_style(app.theme.highlight);
+        f.render_stateful_widget(table, app.layout.list, &mut window);
+    }
+    app.layout.rows = (offset..end).enumerate()
+        .map(|(k, i)| (Rect { y: inner.y + 1 + k as u16, height: 1, ..inner }, i)).collect();
+
+    let preview_text = match app.current_heading() {
+        Some(name) => project_preview(app, name),
+        None => app.current().map(|i| preview_lines(app, &app.sessions[i])).unwrap_or_default(),
+    };
+
+    f.render_widget(Paragraph::new(preview_text).block(Block::default().borders(Borders::ALL).title(" Preview ")).wrap(Wrap{trim:true}), app.layout.preview);
+    
+    // Help bar
+    let help_text = "q:Quit j/k:Nav Tab:Peek r:Resume i:Stats P:Projects v:Saved filters o:Sort by *:Pin L:Protect N:Note t:Tree [/]:Project 1-3:Recent f:Files Space:Sel d:Del e:Exp a:Answer n:Name T:Tag Z:Archive z:Archived F2:Triage F5:Reload c:Dup =:Compare w:Slim x:Hide H:Hidden h:Check s/S:Sort/Reverse p:Prune /:Filt F:Search Enter:Open";
+    let gauge = format!(" {} R:Reclaim ", app.reclaimable());
+    let bar = Layout::default().direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(text::width(&gauge) as u16)]).split(app.layout.status);
+    match &app.status {
+        Some(s) => f.render_widget(Paragraph::new(s.as_str()).style(app.theme.status_bar.add_modifier(Modifier::BOLD)), bar[0]),
+        None => f.render_widget(Paragraph::new(help_text).style(app.theme.status_bar), bar[0]),
+    }
+    f.render_widget(Paragraph::new(gauge).style(app.theme.status_bar.patch(app.theme.accent)), bar[1]);
+
+    // Popup logic
+    match app.mode {
+        Mode::Peek => {
+             if let (Some(p), Some(row)) = (&app.peek, app.state.selected().and_then(|i| app.layout.row_of(i))) {
+                 let width = 70.min(area.width);
+                 let inner_w = width.saturating_sub(2).max(1) as usize;
+                 let wrapped = |s: &str| text::width(s).div_ceil(inner_w).max(1) as u16;
+                 let body = wrapped(&p.name) + 1 + p.prompt.iter().map(|l| wrapped(l)).sum::<u16>();
+                 let r = near_row(row, width, body + 2, area);
+                 let mut lines = vec![Line::styled(p.name.as_str(), Style::default().add_modifier(Modifier::BOLD)), Line::from("")];
+                 lines.extend(p.prompt.iter().map(|l| Line::from(l.as_str())));
+                 f.render_widget(Clear, r);
+                 f.render_widget(Paragraph::new(lines).wrap(Wrap{trim:false})
+                     .block(Block::default().borders(Borders::ALL).border_style(app.theme.accent)), r);
+             }
+        },
+        Mode::Filter | Mode::Rename | Mode::Tag | Mode::Import | Mode::SaveFilter | Mode::Note => {
+             // Filter sits at the top of the list; rename and tag open just below the row they edit.
+             let list = app.layout.list;
+             let below = match app.mode {
+                 Mode::Filter | Mode::Import | Mode::SaveFilter => None,
+                 _ => app.state.selected().and_then(|i| app.layout.row_of(i)).map(|r| r.y + 1),
+             };
+             let r = anchored(list, 80, 3, below.unwrap_or(list.y + 1));
+             f.render_widget(Clear, r);
+             let title = match app.mode {
+                 Mode::Rename => " Rename Session (empty to reset) ",
+                 Mode::Note => " Note (empty to remove) ",
+                 Mode::Tag => " Tags: a, b adds / -a removes ",
+                 Mode::Import => " Import archive or folder of archives ",
+                 Mode::SaveFilter => " Save the filter as ",
+                 _ if app.input.split_whitespace().any(|w| w.starts_with("re:")) => " Filter Sessions (regex, Ctrl+R for text) ",
+                 _ => " Filter Sessions (Ctrl+R for regex) ",
+             };
+             let b = Block::default().borders(Borders::ALL).title(title);
+             let inner_area = b.inner(r);
+             f.render_widget(b, r);
+             f.render_widget(Paragraph::new(app.input.as_str()).style(app.theme.accent), inner_area);
+        },
+        Mode::Confirm => {
+             let r = centered(60, 60, area);
+             f.render_widget(Clear, r);
+             let b = Block::default().borders(Borders::ALL).title(" Confirm Action ").style(app.theme.popup);
+             let inner_area = b.inner(r);
+             f.render_widget(b, r);
+
+             let width = inner_area.width as usize;
+             let (with_todos, sessions) = app.open_todo_summary();
+             let mut header: Vec<Line> = wrap_text(&app.msg, width).into_iter()
+                 .map(|l| Line::styled(l, app.theme.danger.add_modifier(Modifier::BOLD))).collect();
+             if with_todos > 0 {
+                 header.extend(wrap_text(&format!("{} of {} sessions have open todos", with_todos, sessions), width)
+                     .into_iter().map(|l| Line::styled(l, app.theme.warning)));
+             }
+             let header_height = (header.len() as u16).min(inner_area.height / 2);
+             let l = Layout::default()
+                 .constraints([Constraint::Length(header_height), Constraint::Min(0), Constraint::Length(2)])
+                 .split(inner_area);
+             f.render_widget(Paragraph::new(header).alignment(Alignment::Center), l[0]);
+
+             // Entries are wrapped here rather than by the widget so long paths stay whole and
+             // the scroll range is known.
+             let mut lines = Vec::new();
+             for row in &app.to_delete {
+                 for (k, part) in wrap_text(&row.label, width.saturating_sub(2)).into_iter().enumerate() {
+                     lines.push(Line::from(vec![Span::styled(if k == 0 { "- " } else { "  " }, app.theme.muted), Span::raw(part)]));
+                 }
+                 if let Some(n) = row.open_todos.filter(|&n| n > 0) {
+                     let warn = Span::styled(format!("  ⚠ {} open todo{}", n, if n == 1 { "" } else { "s" }), app.theme.warning);
+                     match lines.last_mut() {
+                         Some(last) if last.width() + warn.width() <= width => last.push_span(warn),
+                         _ => lines.push(Line::from(warn)),
+                     }
+                 }
+             }
+             let visible = l[1].height.saturating_sub(1);
+             app.popup_scroll = app.popup_scroll.min((lines.len() as u16).saturating_sub(visible));
+             let title = if lines.len() as u16 > visible { " Items to delete (j/k to scroll) " } else { " Items to delete " };
+             f.render_widget(Paragraph::new(lines).scroll((app.popup_scroll, 0))
+                 .block(Block::default().borders(Borders::TOP).title(title)), l[1]);
+
+             let hint = match app.typed_confirm() {
+                 Some(n) => vec![
+                     Line::styled(format!("Type {} and press Enter to confirm, Esc to cancel", n), app.theme.warning),
+                     Line::styled(format!("> {}_", app.confirm_typed), app.theme.accent),
+                 ],
+                 None if app.needs_capital_y() => vec![Line::styled("Press Shift+Y to Confirm (open todos), N to Cancel", app.theme.muted)],
+                 None => vec![Line::styled("Press Y to Confirm, N to Cancel", app.theme.muted)],
+             };
+             f.render_widget(Paragraph::new(hint).alignment(Alignment::Center), l[2]);
+        },
+        Mode::Message => {
+             // As tall as the wrapped text needs, up to 80% of the screen; beyond that it scrolls.
+             let width = (area.width / 2).max(MESSAGE_MIN_WIDTH).min(area.width);
+             let lines = wrap_text(&app.msg, width.saturating_sub(6) as usize);
+             let height = (lines.len() as u16 + 4).min(area.height * 4 / 5);
+             let r = Rect { x: area.x + (area.width - width) / 2, y: area.y + (area.height - height) / 2, width, height };
+             let visible = height.saturating_sub(4);
+             app.popup_scroll = app.popup_scroll.min((lines.len() as u16).saturating_sub(visible));
+             let mut b = Block::default().borders(Borders::ALL).title(" Information ").padding(ratatui::widgets::Padding::new(2,2,1,1));
+             if lines.len() as u16 > visible { b = b.title_bottom(" j/k:Scroll Enter:Close "); }
+             f.render_widget(Clear, r);
+             f.render_widget(Paragraph::new(lines.into_iter().map(Line::from).collect::<Vec<_>>()).scroll((app.popup_scroll, 0)).block(b), r);
+        },
+        Mode::Report => {
+             let r = centered(80, 70, area);
+             f.render_widget(Clear, r);
+             let hint = if app.report_kind != ReportKind::Health || app.repairable.is_empty() { " e:Export Esc:Close " } else { " e:Export r:Repair Esc:Close " };
+             let b = Block::default().borders(Borders::ALL).title(format!(" {} ", app.report_kind.title())).title_bottom(hint);
+             let lines: Vec<Line> = app.report.iter().skip(app.report_offset).map(|l| Line::from(l.as_str())).collect();
+             f.render_widget(Paragraph::new(lines).block(b).wrap(Wrap{trim:false}), r);
+        },
+        Mode::Checklist => {
+             if let Some(c) = app.checklist.as_mut() {
+                 let r = centered(80, 70, area);
+                 f.render_widget(Clear, r);
+                 let items: Vec<ListItem> = c.items.iter().map(|(label, on)| ListItem::new(Line::from(vec![
+                     Span::styled(if *on { "[x] " } else { "[ ] " }, if *on { app.theme.danger } else { app.theme.muted }),
+                     Span::raw(label.as_str()),
+                 ]))).collect();
+                 let export = if c.kind == ChecklistKind::History { "e:Export " } else { "" };
+                 let b = Block::default().borders(Borders::ALL)
+                     .title(format!(" {} ({}/{} checked) ", c.title, c.checked().len(), c.items.len()))
+                     .title_bottom(format!(" Space:Toggle a:All {}Enter:Apply Esc:Cancel ", export));
+                 let list = List::new(items).block(b).highlight_style(app.theme.highlight);
+                 f.render_stateful_widget(list, r, &mut c.state);
+             }
+        },
+        Mode::History => {
+             let r = centered(85, 75, area);
+             f.render_widget(Clear, r);
+             let b = Block::default().borders(Borders::ALL).title(" Recent Reports ").title_bottom(" j/k:Select PgUp/PgDn:Scroll y:Copy Esc:Close ");
+             let inner = b.inner(r);
+             f.render_widget(b, r);
+             let cols = Layout::default().direction(Direction::Horizontal)
+                 .constraints([Constraint::Length(32), Constraint::Min(0)]).split(inner);
+             let items: Vec<ListItem> = app.history.iter().map(|h| ListItem::new(Line::from(vec![
+                 Span::styled(h.at.format("%H:%M:%S ").to_string(), app.theme.muted),
+                 Span::raw(h.title().to_string()),
+             ]))).collect();
+             f.render_stateful_widget(List::new(items).highlight_style(app.theme.highlight)
+                 .block(Block::default().borders(Borders::RIGHT)), cols[0], &mut app.history_state);
+             if let Some(h) = app.history_state.selected().and_then(|i| app.history.get(i)) {
+                 let text = cols[1].inner(ratatui::layout::Margin::new(1, 0));
+                 let lines = wrap_text(&h.text, text.width as usize);
+                 app.popup_scroll = app.popup_scroll.min((lines.len() as u16).saturating_sub(text.height.saturating_sub(1)));
+                 let mut out = vec![Line::styled(h.at.format("%Y-%m-%d %H:%M:%S").to_string(), app.theme.muted)];
+                 out.extend(lines.into_iter().skip(app.popup_scroll as usize).map(Line::from));
+                 f.render_widget(Paragraph::new(out), text);
+             }
+        },
+        Mode::Projects => {
+             let r = centered(80, 70, area);
+             f.render_widget(Clear, r);
+             let items: Vec<ListItem> = app.picker_order().iter().map(|p| ListItem::new(Line::from(vec![
+                 Span::raw(format!("{:>8}  {:>4} sessions  {:>3} empty  {:>9}  ", format_size(p.size), p.count, p.empty, format_age(p.newest, app.now()))),
+                 Span::styled(p.name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+             ]))).chain(app.deferred.iter().map(|p| ListItem::new(Line::from(vec![
+                 Span::styled(format!("{:>8}  slow ({}ms), press Enter to scan  ", "-", app.scan_times.get(p).copied().unwrap_or_default()), app.theme.muted),
+                 Span::styled(p.clone(), Style::default().add_modifier(Modifier::BOLD)),
+             ])))).collect();
+             let order = if app.projects_by_name { "name" } else { "size" };
+             let b = Block::default().borders(Borders::ALL)
+                 .title(format!(" Projects ({}, by {}) - size, sessions, empty, newest ", app.projects.len(), order))
+                 .title_bottom(format!(" j/k:Select s:Sort by {} Enter:Open Esc:Close ", if app.projects_by_name { "size" } else { "name" }));
+             let list = List::new(items).block(b).highlight_style(app.theme.highlight);
+             f.render_stateful_widget(list, r, &mut app.project_state);
+        },
+        Mode::SortMenu => {
+             let r = centered(40, 40, area);
+             f.render_widget(Clear, r);
+             let items: Vec<ListItem> = SortBy::ALL.iter().map(|&o| {
+                 let mark = if o == app.sort {
+                     format!("1 {}", if app.sort_reversed { "asc " } else { "desc" })
+                 } else if app.sort_then == Some(o) { "2 desc".to_string() } else { String::new() };
+                 ListItem::new(Line::from(vec![
+                     Span::styled(format!("{:<7} ", mark), app.theme.accent),
+                     Span::raw(format!("{:?}", o)),
+                 ]))
+             }).collect();
+             let b = Block::default().borders(Borders::ALL).title(" Sort by ")
+                 .title_bottom(" Enter:Primary (again flips) 2:Then by Esc:Close ");
+             let list = List::new(items).block(b).highlight_style(app.theme.highlight);
+             f.render_stateful_widget(list, r, &mut app.sort_state);
+        },
+        Mode::Filters => {
+             let r = centered(70, 50, area);
+             f.render_widget(Clear, r);
+             let b = Block::default().borders(Borders::ALL)
+                 .title(format!(" Saved filters ({}) ", app.config.saved_filters.len()))
+                 .title_bottom(" j/k:Select Enter:Apply a:Save current d:Delete Esc:Close ");
+             if app.config.saved_filters.is_empty() {
+                 let hint = if app.filter.trim().is_empty() { "No saved filters. Set a filter with /, then press a here to keep it." } else { "No saved filters. Press a to keep the current one." };
+                 f.render_widget(Paragraph::new(Line::styled(hint, app.theme.muted)).wrap(Wrap { trim: true }).block(b), r);
+             } else {
+                 let width = app.config.saved_filters.keys().map(|n| text::width(n)).max().unwrap_or(0);
+                 let items: Vec<ListItem> = app.config.saved_filters.iter().map(|(name, query)| ListItem::new(Line::from(vec![
+                     Span::styled(format!("{}{}  ", name, " ".repeat(width - text::width(name))), Style::default().add_modifier(Modifier::BOLD)),
+                     Span::styled(query.clone(), if *query == app.filter { app.theme.accent } else { Style::default() }),
+                 ]))).collect();
+                 let list = List::new(items).block(b).highlight_style(app.theme.highlight);
+                 f.render_stateful_widget(list, r, &mut app.filter_state);
+             }
+        },
+        Mode::Stats => {
+             let r = centered(60, 70, area);
+             f.render_widget(Clear, r);
+             let mut lines: Vec<Line> = app.stats.iter().map(|l| Line::from(l.as_str())).collect();
+             lines.push(Line::from(""));
+             lines.push(Line::styled(format!("debug: {} frames drawn, {} in the last second", app.frames.total, app.frames.per_second()), app.theme.muted));
+             let b = Block::default().borders(Borders::ALL).title(" Stats ").title_bottom(" c:Compact own files Esc:Close ").padding(ratatui::widgets::Padding::horizontal(1));
+             f.render_widget(Paragraph::new(lines).block(b), r);
+        },
+        Mode::Progress => {
+             if let Some(t) = &app.task {
+                 let p = t.progress();
+                 let r = centered(60, 30, area);
+                 f.render_widget(Clear, r);
+                 let hint = if t.is_cancelling() { " Cancelling after the current item... " } else { " Esc:Cancel " };
+                 let b = Block::default().borders(Borders::ALL).title(format!(" {} ", t.title)).title_bottom(hint);
+                 let inner = b.inner(r);
+                 f.render_widget(b, r);
+                 let l = Layout::default().constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Length(1), Constraint::Min(0)])
+                     .margin(1).split(inner);
+                 let ratio = if p.total == 0 { 0.0 } else { p.done as f64 / p.total as f64 };
+                 f.render_widget(Gauge::default().ratio(ratio).gauge_style(app.theme.gauge)
+                     .label(format!("{}/{}", p.done, p.total)), l[0]);
+                 f.render_widget(Paragraph::new(p.current.as_str()), l[1]);
+                 f.render_widget(Paragraph::new(format_size(p.bytes)).style(app.theme.muted), l[2]);
+             }
+        },
+        Mode::Answer => {
+             if let Some(a) = app.answer.as_mut() {
+                 let r = centered(70, 70, area);
+                 f.render_widget(Clear, r);
+                 let name = app.sessions.get(a.session).map(|s| s.display_name()).unwrap_or_default();
+                 let b = Block::default().borders(Borders::ALL).title(format!(" Last reply: {} ", name))
+                     .title_bottom(" j/k:Scroll y:Copy Enter:Open in viewer Esc:Close ");
+                 let lines = a.text.lines().count() as u16;
+                 a.offset = a.offset.min(lines.saturating_sub(1));
+                 f.render_widget(Paragraph::new(a.text.as_str()).block(b).wrap(Wrap{trim:false}).scroll((a.offset, 0)), r);
+             }
+        },
+        Mode::QuitConfirm => {
+             let r = centered(50, 20, area);
+             f.render_widget(Clear, r);
+             let text = vec![
+                 Line::styled(app.msg.as_str(), app.theme.warning.add_modifier(Modifier::BOLD)),
+                 Line::from(""),
+                 Line::styled("y/q: Quit   n/Esc: Stay", app.theme.muted),
+             ];
+             f.render_widget(Paragraph::new(text).wrap(Wrap{trim:true}).alignment(Alignment::Center)
+                 .block(Block::default().borders(Borders::ALL).title(" Quit? ").padding(ratatui::widgets::Padding::new(2,2,1,1))), r);
+        },
+        Mode::LockConflict => {
+             if let Some(h) = &app.lock_holder {
+                 let r = centered(60, 30, area);
+                 f.render_widget(Clear, r);
+                 let started = chrono::DateTime::parse_from_rfc3339(&h.started)
+                     .map(|t| t.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|_| "an unknown time".into());
+                 let (what, keys) = if h.is_alive() {
+                     (format!("Another instance is running (pid {}, started {}).", h.pid, started), "r: Continue read-only   q: Quit")
+                 } else {
+                     (format!("A lock was left by pid {} (started {}), which is no longer running.", h.pid, started), "t: Take over   r: Read-only   q: Quit")
+                 };
+                 let text = vec![
+                     Line::styled(what, app.theme.warning.add_modifier(Modifier::BOLD)),
+                     Line::from(""),
+                     Line::from("Read-only mode disables deletes, prunes, renames, tags and cache writes."),
+                     Line::from(""),
+                     Line::styled(keys, app.theme.muted),
+                 ];
+                 f.render_widget(Paragraph::new(text).wrap(Wrap{trim:true}).alignment(Alignment::Center)
+                     .block(Block::default().borders(Borders::ALL).title(" Instance Lock ").padding(ratatui::widgets::Padding::new(2,2,1,1))), r);
+             }
+        },
+        Mode::ImportConflict => {
+             let r = centered(50, 20, area);
+             f.render_widget(Clear, r);
+             let text = vec![
+                 Line::styled(app.msg.as_str(), app.theme.warning.add_modifier(Modifier::BOLD)),
+                 Line::from(""),
+                 Line::styled("r: Import under a new id   s: Skip   Esc: Skip the rest", app.theme.muted),
+             ];
+             f.render_widget(Paragraph::new(text).wrap(Wrap{trim:true}).alignment(Alignment::Center)
+                 .block(Block::default().borders(Borders::ALL).title(" Session Exists ").padding(ratatui::widgets::Padding::new(2,2,1,1))), r);
+        },
+        Mode::PruneSelection => {
+             let r = centered(40, 60, area);
+             f.render_widget(Clear, r);
+             let b = Block::default().title(" Prune Options ").borders(Borders::ALL);
+             let inner_area = b.inner(r);
+             f.render_widget(b, r);
+             let text = vec![
+                 Line::from(" [1] Empty Sessions"),
+                 Line::from(" [2] Orphaned Files"),
+                 Line::from(" [3] Both"),
+                 Line::from(" [4] Prune History"),
+                 Line::from(" [5] Duplicate Sessions"),
+                 Line::from(" [6] Health Check (filtered)"),
+                 Line::from(" [7] Review Dangling History"),
+                 Line::from(" [8] Sessions for Deleted Projects"),
+                 Line::from(" [9] Apply Retention Policy"),
+                 Line::from(format!(" [t] Empty Trash Older Than {} Days", app.config.trash_retention_days.unwrap_or(crate::DEFAULT_TRASH_DAYS))),
+                 Line::from(""),
+                 Line::from(format!(" [k] Skip 'keep' tagged: {}", if app.config.prune_skip_keep.unwrap_or(true) { "on" } else { "off" })),
+                 Line::from(Span::styled(" Esc to Cancel", app.theme.muted)),
+             ];
+             f.render_widget(Paragraph::new(text).block(Block::default().padding(ratatui::widgets::Padding::new(2,2,2,1))), inner_area);
+        },
+        Mode::Expanded => {
+             if let Some(t) = &app.transcript {
+                 let lines = &t.lines;
+                 let h = area.height.saturating_sub(2) as usize;
+                 if app.offset == usize::MAX { app.offset = lines.len().saturating_sub(h); }
+                 app.offset = app.offset.min(lines.len().saturating_sub(h));
+                 
+                 let v = t.view(app.offset, h, &app.theme);
+                 
+                 f.render_widget(Clear, area);
+                 let marks = if t.marks.is_empty() { String::new() } else { format!(" {} marked,", t.marks.len()) };
+                 let named: String = t.bookmarks.keys().collect();
+                 let named = if named.is_empty() { String::new() } else { format!(" Bookmarks:[{}]", named) };
+                 let keys = if t.focus.is_some() { "y:Copy c:Copy code M:Lines" } else { "v:Mark c:Duplicate m/':Bookmark M:Messages" };
+                 let note = t.note.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+                 let picked = if app.viewed().is_some_and(|i| app.selected.contains(&i)) { " [x]" } else { "" };
+                 let queued = if app.queued.is_empty() { String::new() } else { format!(" {} queued", app.queued.len()) };
+                 let title = match &t.debug {
+                     Some(d) if d.shown < d.size => format!(" Debug Log: {} (Line {}/{}, last {} of {}) f:Load all ", t.session_id, app.offset, lines.len(), format_size(d.shown), format_size(d.size)),
+                     Some(_) => format!(" Debug Log: {} (Line {}/{}) ", t.session_id, app.offset, lines.len()),
+                     None => {
+                         let window = match &t.window {
+                             Some(w) if w.start > 0 => format!(", last {} of {}, scroll up for more", format_size(w.size.saturating_sub(w.start)), format_size(w.size)),
+                             _ => String::new(),
+                         };
+                         format!(" Full Log{} (Line {}/{}{}){}{}{}{} {} ", picked, app.offset, lines.len(), window, marks, queued, named, note, keys)
+                     }
+                 };
+                 let mut b = Block::default().borders(Borders::ALL).title(title);
+                 if t.debug.is_none() { b = b.title_bottom(" Space:Select d:Queue delete </>:Prev/next [/]:In project Esc:Back "); }
+                 let inner = b.inner(area);
+                 f.render_widget(b, area);
+                 let cols = Layout::default().direction(Direction::Horizontal)
+                     .constraints([Constraint::Min(0), Constraint::Length(1)]).split(inner);
+                 f.render_widget(Paragraph::new(v).wrap(Wrap{trim:false}), cols[0]);
+                 // Gutter: each bookmark at its relative position in the whole transcript.
+                 for (letter, &record) in &t.bookmarks {
+                     let y = cols[1].y + (t.bookmark_line(record) * cols[1].height as usize / lines.len().max(1)) as u16;
+                     f.buffer_mut().set_string(cols[1].x, y.min(cols[1].bottom().saturating_sub(1)), letter.to_string(), app.theme.accent);
+                 }
+             }
+        },
+        Mode::Triage => {
+             if let (Some(t), Some(s)) = (&app.triage, app.triaged().and_then(|i| app.sessions.get(i))) {
+                 f.render_widget(Clear, area);
+                 let b = Block::default().borders(Borders::ALL)
+                     .title(format!(" Triage {}/{}: {} ", t.pos + 1, t.ids.len(), t.tally()))
+                     .title_bottom(" k:Keep d:Delete a:Archive s:Skip u:Back Up/Down:Scroll Esc:Finish ");
+                 let inner = b.inner(area);
+                 f.render_widget(b, area);
+                 let cols = Layout::default().direction(Direction::Horizontal)
+                     .constraints([Constraint::Percentage(40), Constraint::Percentage(60)]).split(inner);
+                 f.render_widget(Paragraph::new(preview_lines(app, s)).wrap(Wrap{trim:true})
+                     .block(Block::default().borders(Borders::RIGHT).title(format!(" {} ", s.display_name()))), cols[0]);
+                 let mut lines = Vec::new();
+                 for e in &t.exchange {
+                     lines.push(Line::styled(e.role.to_uppercase(), app.theme.accent.add_modifier(Modifier::BOLD)));
+                     lines.extend(e.text.lines().map(|l| Line::from(l.to_string())));
+                     lines.push(Line::from(""));
+                 }
+                 if lines.is_empty() { lines.push(Line::styled("No messages.", app.theme.muted)); }
+                 f.render_widget(Paragraph::new(lines).wrap(Wrap{trim:false}).scroll((t.offset, 0))
+                     .block(Block::default().title(" Last exchange ").padding(ratatui::widgets::Padding::horizontal(1))), cols[1]);
+             }
+        },
+        Mode::Compare => {
+             if let Some(c) = &app.compare {
+                 f.render_widget(Clear, area);
+                 let rows = Layout::default().direction(Direction::Vertical)
+                     .constraints([Constraint::Length(6), Constraint::Min(0)]).split(area);
+                 let cols = |r: Rect| Layout::default().direction(Direction::Horizontal)
+                     .constraints([Constraint::Percentage(50), Constraint::Percentage(50)]).split(r);
+                 let (head, body) = (cols(rows[0]), cols(rows[1]));
+                 for p in 0..2 {
+                     let (idx, t) = &c.panes[p];
+                     let s = &app.sessions[*idx];
+                     let meta = vec![
+                         Line::from(s.display_name()),
+                         Line::from(format!("Size: {}  Messages: {}", s.size_str(), s.message_count)),
+                         Line::from(format!("Modified: {}  Project: {}", s.formatted_age(app.now()), s.project)),
+                         Line::from(format!("Models: {}", t.models().join(", "))),
+                     ];
+                     f.render_widget(Paragraph::new(meta).block(Block::default().borders(Borders::ALL).title(format!(" {} ", s.id))), head[p]);
+                     let h = body[p].height.saturating_sub(2) as usize;
+                     let style = if p == c.focus { app.theme.accent } else { Style::default() };
+                     let b = Block::default().borders(Borders::ALL).border_style(style)
+                         .title(format!(" Line {}/{} ", c.offsets[p], t.lines.len()));
+                     f.render_widget(Paragraph::new(t.view(c.offsets[p], h, &app.theme)).block(b).wrap(Wrap{trim:false}), body[p]);
+                 }
+                 let hint = format!(" Tab:Focus s:Sync[{}] Esc:Close ", if c.sync { "on" } else { "off" });
+                 f.render_widget(Paragraph::new(hint).alignment(Alignment::Right), Rect { height: 1, ..rows[1] });
+             }
+        },
+        Mode::Archived => {
+             f.render_widget(Clear, area);
+             let items: Vec<ListItem> = app.archives.iter().map(|a| {
+                 ListItem::new(Line::from(vec![
+                     Span::styled(format!("{:.8} ", a.manifest.id), app.theme.muted),
+                     Span::raw(format!("{} ", a.manifest.name)),
+                     Span::styled(format!("({}, {})", format_size(a.size), a.archived_str()), app.theme.muted),
+                 ]))
+             }).collect();
+             let b = Block::default().borders(Borders::ALL)
+                 .title(format!(" Archived Sessions ({}) - Enter to restore, Esc to close ", app.archives.len()))
+                 .title_alignment(Alignment::Center);
+             let list = List::new(items).block(b)
+                 .highlight_style(app.theme.highlight);
+             f.render_stateful_widget(list, area, &mut app.archive_state);
+        },
+        _ => {}
+    }
+}
+
+/// A `px`% wide, `height` rows tall popup centered horizontally over `pane` with its top at
+/// `y`, pulled back up if it would spill past the pane's bottom edge.
+fn anchored(pane: Rect, px: u16, height: u16, y: u16) -> Rect {
+    let width = (pane.width as u32 * px as u32 / 100) as u16;
+    let height = height.min(pane.height);
+    let y = y.clamp(pane.y, pane.bottom() - height);
+    Rect { x: pane.x + (pane.width - width) / 2, y, width, height }
+}
+
+/// A `width`×`height` popup just below `row`, or above it when there is no room below,
+/// shifted and shrunk as needed to stay inside `frame`.
+fn near_row(row: Rect, width: u16, height: u16, frame: Rect) -> Rect {
+    let (width, height) = (width.min(frame.width), height.min(frame.height));
+    let below = row.bottom();
+    let y = if below + height <= frame.bottom() { below } else { row.y.saturating_sub(height).max(frame.y) };
+    let x = (row.x + 2).min(frame.right() - width).max(frame.x);
+    Rect { x, y, width, height }
+}
+
+/// Word-wraps `text` to `width` columns, keeping its line breaks. Words longer than a line
+/// (paths, ids) are split where they reach the edge instead of being cut off.
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut out = Vec::new();
+    for para in text.split('\n') {
+        let (mut line, mut len) = (String::new(), 0);
+        for word in para.split(' ') {
+            let word_len = text::width(word);
+            if len > 0 && len + 1 + word_len > width { out.push(std::mem::take(&mut line)); len = 0; }
+            if len > 0 { line.push(' '); len += 1; }
+            for piece in text::chunks(word, width) {
+                if len > 0 && len + text::width(piece) > width { out.push(std::mem::take(&mut line)); len = 0; }
+                line.push_str(piece);
+                len += text::width(piece);
+            }
+        }
+        out.push(line);
+    }
+    out
+}
+
+fn centered(px: u16, py: u16, r: Rect) -> Rect {
+    let v = Layout::default().direction(Direction::Vertical).constraints([Constraint::Percentage((100-py)/2), Constraint::Percentage(py), Constraint::Percentage((100-py)/2)]).split(r);
+    Layout::default().direction(Direction::Horizontal).constraints([Constraint::Percentage((100-px)/2), Constraint::Percentage(px), Constraint::Percentage((100-px)/2)]).split(v[1])[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_tests::{loaded, settle};
+    use crate::clock::ManualClock;
+    use crate::test_support::{config, Fixture};
+    use crate::sessions::Sidecar;
+    use ratatui::{backend::TestBackend, buffer::Buffer, style::Color, Terminal};
+
+    fn draw(app: &mut App, w: u16, h: u16) -> Buffer {
+        let mut term = Terminal::new(TestBackend::new(w, h)).unwrap();
+        term.draw(|f| ui(f, app)).unwrap();
+        term.backend().buffer().clone()
+    }
+
+    fn text(buf: &Buffer, r: Rect) -> String {
+        (r.x..r.right()).map(|x| buf[(x, r.y)].symbol()).collect()
+    }
+
+    fn app_with(n: usize) -> (Fixture, App) {
+        let fx = Fixture::new();
+        for i in 0..n {
+            fx.session("-repo", &format!("s{:02}", i), &[&format!("prompt number {}", i)], 1000 - i as u64);
+        }
+        let app = loaded(fx.manager(), config());
+        (fx, app)
+    }
+
+    #[test]
+    fn records_pane_rects() {
+        let (_fx, mut app) = app_with(3);
+        draw(&mut app, 100, 30);
+        assert_eq!(app.layout.list, Rect::new(0, 0, 60, 29));
+        assert_eq!(app.layout.preview, Rect::new(60, 0, 40, 29));
+        assert_eq!(app.layout.status, Rect::new(0, 29, 100, 1));
+
+        draw(&mut app, 40, 10);
+        assert_eq!(app.layout, LayoutInfo::default());
+    }
+
+    #[test]
+    fn rows_map_to_what_was_drawn() {
+        let (_fx, mut app) = app_with(20);
+        app.state.select(Some(15));
+        let buf = draw(&mut app, 80, 12);
+        let rows = &app.layout.rows;
+        assert_eq!(rows.len(), 8, "one line goes to the column header");
+        assert_eq!(rows.last().unwrap().1, 15);
+        assert_eq!(rows[0].1, app.state.offset());
+        for (r, i) in rows {
+            let name = app.sessions[app.filtered[*i]].display_name();
+            assert!(text(&buf, *r).contains(&name), "row {:?} should show {}", r, name);
+        }
+    }
+
+    fn synthetic(n: usize) -> (Fixture, App) {
+        let (fx, mut app) = app_with(0);
+        let now = std::time::SystemTime::now();
+        app.sessions = (0..n).map(|i| Session {
+            id: format!("{:08x}", i), path: Default::default(), project: "-bench".into(), size: 1000 + i as u64,
+            message_count: i % 40, first_message: format!("synthetic prompt {}", i), prompt_kind: PromptKind::Other,
+            modified: now - std::time::Duration::from_secs(i as u64), custom_name: None, user_name: None,
+            tags: if i % 7 == 0 { vec!["keep".into()] } else { Vec::new() }, hidden: false, pinned: false, protected: false, note: None, related_files: Vec::new(),
+            cwd: None, project_missing: false, last_reply_at: None, summary: None, tokens: 0, usage: Default::default(), models: Default::default(), summary_title: None, history_prompt: None,
+        }).collect();
+        app.apply_filter();
+        app.state.select(Some(n / 2));
+        (fx, app)
+    }
+
+    /// Best of several draws, so one slow frame on a busy machine does not decide the test.
+    fn draw_time(app: &mut App) -> std::time::Duration {
+        draw(app, 120, 40);
+        (0..5).map(|_| {
+            let t = std::time::Instant::now();
+            draw(app, 120, 40);
+            t.elapsed()
+        }).min().unwrap()
+    }
+
+    #[test]
+    fn draw_cost_does_not_follow_session_count() {
+        let (_a, mut small) = synthetic(1_000);
+        let (_b, mut large) = synthetic(10_000);
+        let (t_small, t_large) = (draw_time(&mut small), draw_time(&mut large));
+        assert_eq!(large.row_cache.0.len(), 36, "only visible rows are built");
+        assert_eq!(large.layout.rows.len(), 36);
+        assert!(t_large < t_small * 4, "10k sessions drew in {:?}, 1k in {:?}", t_large, t_small);
+    }
+
+    #[test]
+    fn virtual_window_follows_wraparound_and_selection() {
+        let (_fx, mut app) = synthetic(500);
+        app.theme = Theme::color();
+        app.state.select(Some(0));
+        draw(&mut app, 100, 20);
+        assert_eq!(app.layout.rows.first().map(|r| r.1), Some(0));
+        app.move_sel(-1);
+        let buf = draw(&mut app, 100, 20);
+        assert_eq!(app.layout.rows.last().map(|r| r.1), Some(499));
+        let row = app.layout.rows.last().unwrap().0;
+        assert_eq!(buf[(row.x, row.y)].bg, Color::DarkGray, "highlight lands on the last row");
+        app.toggle();
+        let buf = draw(&mut app, 100, 20);
+        assert!(text(&buf, row).contains("[x] synthetic prompt 499"));
+    }
+
+    #[test]
+    fn list_ages_move_with_the_clock() {
+        let (_fx, mut app) = synthetic(3);
+        app.columns.push(crate::columns::ColumnSpec { column: crate::columns::Column::Age, width: None });
+        let start = app.sessions[0].modified + std::time::Duration::from_secs(5 * 60);
+        let clock = ManualClock::at(start);
+        app.set_clock(clock.clone());
+        draw(&mut app, 160, 20);
+        let row = app.layout.rows[0].0;
+        assert!(text(&draw(&mut app, 160, 20), row).contains(" 5m "));
+        clock.advance(std::time::Duration::from_secs(3600));
+        assert!(text(&draw(&mut app, 160, 20), row).contains(" 5m "), "the screen keeps its reading until the next tick");
+        app.tick();
+        assert!(text(&draw(&mut app, 160, 20), row).contains(" 1h "), "a cached row is rebuilt once its age changes");
+    }
+
+    #[test]
+    fn no_color_theme_emits_no_colors() {
+        let (fx, _) = app_with(4);
+        let mut sidecar = Sidecar::default();
+        sidecar.edit_tags("s01", "keep, bug");
+        fx.manager().save_sidecar(&sidecar).unwrap();
+        let mut app = loaded(fx.manager(), config());
+        app.theme = Theme::no_color();
+        app.selected.insert(1);
+        for mode in [Mode::Normal, Mode::Filter, Mode::Confirm, Mode::PruneSelection, Mode::QuitConfirm] {
+            app.mode = mode;
+            let buf = draw(&mut app, 100, 30);
+            for cell in buf.content() {
+                assert_eq!((cell.fg, cell.bg), (Color::Reset, Color::Reset), "colored cell {:?}", cell);
+            }
+        }
+        let row = app.layout.rows[app.state.selected().unwrap()].0;
+        let buf = draw(&mut app, 100, 30);
+        assert!(buf[(row.x, row.y)].modifier.contains(Modifier::REVERSED));
+    }
+
+    #[test]
+    fn popups_anchor_to_the_list() {
+        let (_fx, mut app) = app_with(5);
+        app.mode = Mode::Filter;
+        let buf = draw(&mut app, 100, 30);
+        assert!(text(&buf, Rect { y: 1, ..app.layout.list }).contains("Filter Sessions"));
+
+        app.state.select(Some(2));
+        app.mode = Mode::Rename;
+        let buf = draw(&mut app, 100, 30);
+        let row = app.layout.row_of(2).unwrap();
+        assert!(text(&buf, Rect { y: row.y + 1, ..app.layout.list }).contains("Rename Session"));
+        assert!(!text(&buf, Rect { y: 1, ..app.layout.preview }).contains("Rename"));
+    }
+
+    #[test]
+    fn configured_columns_drive_the_header_and_rows() {
+        let fx = Fixture::new();
+        fx.session("-repo", "abcdef0123", &["hello"], 10);
+        let mut config = config();
+        config.columns = Some(["id", "age", "size:9", "bogus"].map(String::from).into());
+        let mut app = loaded(fx.manager(), config);
+        assert!(matches!(app.mode, Mode::Message) && app.msg.contains("bogus"), "{}", app.msg);
+        app.mode = Mode::Normal;
+        let buf = draw(&mut app, 100, 20);
+        let row = app.layout.rows[0].0;
+        let header = text(&buf, Rect { y: row.y - 1, ..row });
+        assert!(header.starts_with("Id       Age ▼     Size"), "{:?}", header);
+        assert!(text(&buf, row).starts_with("abcdef01 10s       "));
+        assert!(!text(&buf, row).contains("hello"));
+    }
+
+    #[test]
+    fn header_shows_the_sort_and_clicks_sort_by_column() {
+        let fx = Fixture::new();
+        fx.session("-repo", "small", &["hi"], 10);
+        fx.session("-repo", "large", &[&"long prompt ".repeat(200)], 20);
+        let mut config = config();
+        config.columns = Some(["id", "age", "size:9", "msgs"].map(String::from).into());
+        let mut app = loaded(fx.manager(), config);
+        draw(&mut app, 100, 20);
+        let (size, _) = *app.layout.header.iter().find(|(_, c)| *c == Column::Size).unwrap();
+        let header = |app: &mut App| { let buf = draw(app, 100, 20); text(&buf, Rect { width: 100 - size.x, ..size }) };
+        assert!(header(&mut app).starts_with("Size      Msgs"), "cells line up with the drawn header");
+
+        app.click(size.x + 2, size.y);
+        assert_eq!((app.sort, app.sessions[0].id.as_str()), (SortBy::Size, "large"));
+        assert!(header(&mut app).starts_with("Size ▼"));
+        app.click(size.x, size.y);
+        assert_eq!(app.sessions[0].id, "small", "a second click reverses");
+        assert!(header(&mut app).starts_with("Size ▲"));
+        app.click(size.x, size.y + 1);
+        assert!(app.sort_reversed, "clicks outside the header do nothing");
+
+        app.sort = crate::columns::next_sort(&app.columns, app.sort);
+        app.apply_sort();
+        let buf = draw(&mut app, 100, 20);
+        assert!(text(&buf, Rect { y: size.y, ..app.layout.list }).contains("Msgs ▲"), "the keyboard sort keeps the direction and the header follows");
+    }
+
+    #[test]
+    fn mixed_width_names_keep_columns_aligned() {
+        let fx = Fixture::new();
+        let names = ["plain ascii prompt", "日本語のプロンプトでテーブルの幅を確かめる長い名前です、もっと長く続きます",
+            "cafe\u{301} au lait with combining accents", "🦀 crab and 👨‍👩‍👧 family emoji 🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉", "한국어 세션 이름"];
+        for (i, n) in names.iter().enumerate() { fx.session("-repo", &format!("w{}", i), &[n], 100 + i as u64); }
+        let mut app = loaded(fx.manager(), config());
+        app.sessions[1].tags = vec!["日本".into()];
+        app.apply_filter();
+        app.state.select(Some(1));
+        let buf = draw(&mut app, 100, 20);
+        let header_y = app.layout.rows[0].0.y - 1;
+        let size_x = (0..app.layout.list.right()).find(|&x| buf[(x, header_y)].symbol() == "S").unwrap();
+        assert_eq!(text(&buf, Rect::new(size_x, header_y, 4, 1)), "Size");
+        for (r, i) in &app.layout.rows {
+            let s = &app.sessions[app.filtered[*i]];
+            assert_eq!(text(&buf, Rect::new(size_x, r.y, s.size_str().len() as u16, 1)), s.size_str(), "{} is misaligned", s.id);
+            assert_eq!(buf[(size_x - 1, r.y)].symbol(), " ", "{} spills into the size column", s.id);
+            assert_eq!(buf[(app.layout.list.right() - 1, r.y)].symbol(), "│");
+        }
+
+        for line in wrap_text(&names.join(" "), 15) { assert!(text::width(&line) <= 15, "{:?} overflows", line); }
+        assert_eq!(wrap_text("日本語のテキスト", 7), ["日本語", "のテキ", "スト"]);
+    }
+
+    /// The text inside `r`, line by line, with borders, spaces and list markers stripped.
+    fn flattened(buf: &Buffer, r: Rect) -> String {
+        (r.y..r.bottom()).map(|y| text(buf, Rect { y, height: 1, ..r }))
+            .flat_map(|l| l.split(['│', '─', ' ']).map(String::from).collect::<Vec<_>>())
+            .filter(|w| *w != "-")
+            .collect()
+    }
+
+    #[test]
+    fn wraps_long_words_at_the_edge() {
+        assert_eq!(wrap_text("rm /a/very/long/path now", 10), ["rm", "/a/very/lo", "ng/path", "now"]);
+        assert_eq!(wrap_text("one\n\ntwo three", 20), ["one", "", "two three"]);
+    }
+
+    #[test]
+    fn long_paths_wrap_in_confirm_and_message_popups() {
+        let (_fx, mut app) = app_with(1);
+        let path = format!("/home/me/{}/session.jsonl", "deeply-nested-directory/".repeat(12));
+        app.to_delete = (0..3).map(|i| crate::ConfirmRow { label: format!("{}{}", path, i), open_todos: Some(2) }).collect();
+        app.msg = "Delete 3 sessions?".into();
+        app.mode = Mode::Confirm;
+        let popup = centered(60, 60, Rect::new(0, 0, 60, 40));
+        let buf = draw(&mut app, 60, 40);
+        let all = flattened(&buf, popup);
+        assert!(all.contains(&format!("{}0⚠2opentodos", path)), "first entry whole: {}", all);
+        assert!(!all.contains(&format!("{}2", path)), "last entry scrolled out of view");
+        app.popup_scroll = u16::MAX;
+        let all = flattened(&draw(&mut app, 60, 40), popup);
+        assert!(all.contains(&format!("{}2", path)), "scrolling reaches the last entry: {}", all);
+
+        app.mode = Mode::Message;
+        app.popup_scroll = 0;
+        app.msg = format!("Delete failed:\n- {}: permission denied", path);
+        let popup = Rect::new(10, 0, 40, 24);
+        let buf = draw(&mut app, 60, 24);
+        let all = flattened(&buf, popup);
+        assert!(all.contains(&format!("{}:permissiondenied", path)), "{}", all);
+        let rows = (0..24).filter(|&y| text(&buf, Rect { y, height: 1, ..popup }).starts_with('│')).count();
+        assert!(rows > 8 && rows < 20, "popup sized to its text, got {} rows", rows);
+
+        app.msg = path.repeat(8);
+        let all = flattened(&draw(&mut app, 60, 24), popup);
+        assert!(all.contains("j/k:Scroll") && !all.ends_with("session.jsonl"));
+    }
+
+    #[test]
+    fn peek_popup_sits_by_the_row_and_stays_on_screen() {
+        let frame = Rect::new(0, 0, 80, 24);
+        assert_eq!(near_row(Rect::new(1, 3, 40, 1), 30, 5, frame), Rect::new(3, 4, 30, 5));
+        assert_eq!(near_row(Rect::new(1, 21, 40, 1), 30, 5, frame), Rect::new(3, 16, 30, 5));
+        assert_eq!(near_row(Rect::new(60, 3, 20, 1), 30, 5, frame), Rect::new(50, 4, 30, 5));
+        assert_eq!(near_row(Rect::new(0, 2, 80, 1), 100, 40, frame), frame);
+
+        let (fx, mut app) = app_with(3);
+        fx.session("-repo", "long", &["a very long first prompt that the list has to cut short\nsecond line"], 0);
+        app.spawn_load(false);
+        settle(&mut app);
+        let idx = app.filtered.iter().position(|&i| app.sessions[i].id == "long").unwrap();
+        app.state.select(Some(idx));
+        app.open_peek();
+        let buf = draw(&mut app, 60, 20);
+        let row = app.layout.row_of(idx).unwrap();
+        let below: String = (row.y + 2..row.y + 6).map(|y| text(&buf, Rect { y, ..buf.area })).collect();
+        assert!(below.contains("cut short"), "{}", below);
+        assert!(below.contains("second line"), "{}", below);
+    }
+
+    #[test]
+    fn empty_list_explains_itself() {
+        let (fx, mut app) = app_with(0);
+        fx.write("projects/-repo/agent-1234.jsonl", "{}\n");
+        app.spawn_load(false);
+        settle(&mut app);
+        let buf = draw(&mut app, 100, 30);
+        let list = flattened(&buf, app.layout.list);
+        assert!(list.contains("NoClaudeCodesessionsfound."), "{}", list);
+        assert!(list.contains("Projectdirectories:1"), "{}", list);
+        assert!(list.contains("sub-agenttranscripts"), "{}", list);
+        assert!(app.layout.rows.is_empty());
+
+        let (_fx, mut app) = app_with(2);
+        app.filter = "xyz".into();
+        app.apply_filter();
+        let list = flattened(&draw(&mut app, 100, 30), app.layout.list);
+        assert!(list.contains("Nomatchesfor'xyz'"), "{}", list);
+        assert!(list.contains("PressEsctoclear"), "{}", list);
+        assert!(!list.contains("sessionsfound"), "{}", list);
+        app.filter.clear();
+        app.apply_filter();
+        assert_eq!(app.state.selected(), Some(0), "widening the filter selects again");
+    }
+}
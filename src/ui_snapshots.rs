@@ -0,0 +1,146 @@
+//! Whole-frame snapshots of the main screens, compared with the text files in `src/snapshots/`.
+//! After an intended change to the UI, `UPDATE_SNAPSHOTS=1 cargo test ui_snapshots` rewrites
+//! them; review the diff before committing it. Ages are pinned with a `ManualClock`.
+
+use crate::app_tests::loaded;
+use crate::clock::ManualClock;
+use crate::test_support::{config, set_mtime, Fixture};
+use crate::{text, ui, Action, App, Mode};
+use ratatui::{backend::TestBackend, buffer::Buffer, Terminal};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// 2026-01-15 12:00:00 UTC.
+const NOW: u64 = 1_768_478_400;
+
+fn fixture() -> (Fixture, App) {
+    let fx = Fixture::new();
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(NOW);
+    let sessions: [(&str, &str, &[&str], u64); 4] = [
+        ("-repo-alpha", "a1", &["Fix the parser", "Add tests"], 300),
+        ("-repo-alpha", "a2", &["Refactor the alpha cache layer for speed"], 5400),
+        ("-repo-beta", "b1", &["Why is beta slow?", "Profile it", "Ship it"], 40),
+        ("-repo-beta", "b2", &["日本語のプロンプト"], 7200),
+    ];
+    for (project, id, prompts, age) in sessions {
+        set_mtime(&fx.session(project, id, prompts, 0), now - Duration::from_secs(age));
+    }
+    set_mtime(&fx.write("projects/-repo-beta/empty.jsonl", ""), now - Duration::from_secs(60 * 60 * 20));
+    let mut app = loaded(fx.manager(), config());
+    app.set_clock(ManualClock::at(now));
+    app.state.select(Some(1));
+    (fx, app)
+}
+
+fn draw(app: &mut App, w: u16, h: u16) -> Buffer {
+    let mut term = Terminal::new(TestBackend::new(w, h)).unwrap();
+    term.draw(|f| ui(f, app)).unwrap();
+    term.backend().buffer().clone()
+}
+
+/// The frame's characters, one line per row, trailing blanks trimmed. The cell after a wide
+/// glyph is its right half, so it is skipped rather than printed as a space.
+fn frame_text(buf: &Buffer) -> String {
+    let mut out = String::new();
+    for y in 0..buf.area.height {
+        let (mut line, mut x) = (String::new(), 0);
+        while x < buf.area.width {
+            let sym = buf[(x, y)].symbol();
+            line.push_str(sym);
+            x += text::width(sym).max(1) as u16;
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn assert_snapshot(name: &str, buf: &Buffer) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/snapshots").join(format!("{}.txt", name));
+    let actual = frame_text(buf);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("no snapshot at {}; run with UPDATE_SNAPSHOTS=1 to record it", path.display()));
+    if expected == actual { return; }
+    let diff: Vec<String> = expected.lines().zip(actual.lines()).enumerate()
+        .filter(|(_, (e, a))| e != a)
+        .map(|(n, (e, a))| format!("line {}:\n  - {}\n  + {}", n + 1, e, a))
+        .collect();
+    panic!("{} differs from {}:\n{}\n\nFull frame:\n{}", name, path.display(), diff.join("\n"), actual);
+}
+
+#[test]
+fn main_screen() {
+    let (_fx, mut app) = fixture();
+    assert_snapshot("main_100x30", &draw(&mut app, 100, 30));
+    assert_snapshot("main_80x24", &draw(&mut app, 80, 24));
+}
+
+#[test]
+fn grouped_by_project() {
+    let (_fx, mut app) = fixture();
+    app.toggle_grouped();
+    app.fold(Some(true));
+    assert_snapshot("grouped", &draw(&mut app, 100, 30));
+}
+
+#[test]
+fn peek_and_filter() {
+    let (_fx, mut app) = fixture();
+    app.open_peek();
+    assert_snapshot("peek", &draw(&mut app, 100, 30));
+    app.peek = None;
+    app.mode = Mode::Filter;
+    app.filter = "beta".into();
+    app.apply_filter();
+    assert_snapshot("filter", &draw(&mut app, 100, 30));
+}
+
+#[test]
+fn confirm_and_message_popups() {
+    let (_fx, mut app) = fixture();
+    app.selected = [0, 3].into();
+    app.to_delete = app.selection().into_iter().map(|i| app.delete_row(i, app.sessions[i].display_name())).collect();
+    app.msg = "Delete 2 sessions?".into();
+    app.action = Action::Delete;
+    app.mode = Mode::Confirm;
+    assert_snapshot("confirm_delete", &draw(&mut app, 100, 30));
+    app.msg = "Deleted:\n- projects/-repo-alpha/a1.jsonl\n- projects/-repo-beta/b2.jsonl".into();
+    app.mode = Mode::Message;
+    assert_snapshot("message", &draw(&mut app, 100, 30));
+}
+
+#[test]
+fn menus_and_pickers() {
+    let (_fx, mut app) = fixture();
+    app.mode = Mode::PruneSelection;
+    assert_snapshot("prune_menu", &draw(&mut app, 100, 30));
+    app.open_column_picker();
+    assert_snapshot("column_picker", &draw(&mut app, 100, 30));
+    app.checklist = None;
+    app.open_project_picker();
+    assert_snapshot("project_picker", &draw(&mut app, 100, 30));
+}
+
+#[test]
+fn expanded_viewer() {
+    let (_fx, mut app) = fixture();
+    let b1 = app.sessions.iter().position(|s| s.id == "b1").unwrap();
+    app.open_transcript(b1, false);
+    assert_snapshot("expanded", &draw(&mut app, 100, 30));
+    app.offset = 0;
+    assert_snapshot("expanded_top_80x24", &draw(&mut app, 80, 24));
+}
+
+#[test]
+fn triage_screen() {
+    let (_fx, mut app) = fixture();
+    app.start_triage();
+    app.decide(crate::Verdict::Keep);
+    assert_snapshot("triage", &draw(&mut app, 100, 30));
+}
@@ -0,0 +1,49 @@
+//! Watches `~/.claude/projects` and `~/.claude/todos` so sessions written while the TUI is open
+//! show up without a restart. Events only mark the list stale; the app reloads on its own pace.
+
+use ::notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+pub struct Watcher {
+    rx: mpsc::Receiver<::notify::Result<Event>>,
+    /// Kept alive for as long as the events should flow.
+    _watcher: RecommendedWatcher,
+}
+
+/// Whether an event can change the list: a transcript or todo file created, written, renamed
+/// or removed. Opens, reads and metadata touches are left out.
+fn relevant(event: &Event) -> bool {
+    let kind = match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    };
+    kind && event.paths.iter().any(|p| p.extension().is_none_or(|e| e == "jsonl" || e == "json"))
+}
+
+impl Watcher {
+    /// Watches `projects` recursively and `todos` flat. A missing directory is skipped.
+    pub fn new(projects: &Path, todos: &Path) -> ::notify::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = ::notify::recommended_watcher(move |res| { tx.send(res).ok(); })?;
+        let dirs: [(PathBuf, RecursiveMode); 2] = [(projects.into(), RecursiveMode::Recursive), (todos.into(), RecursiveMode::NonRecursive)];
+        for (dir, mode) in dirs.iter().filter(|(d, _)| d.is_dir()) {
+            watcher.watch(dir, *mode)?;
+        }
+        Ok(Watcher { rx, _watcher: watcher })
+    }
+
+    /// Drains the events so far, returning whether any of them matter.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(res) = self.rx.try_recv() {
+            match res {
+                Ok(event) => changed |= relevant(&event),
+                Err(e) => log::warn!("file watcher: {}", e),
+            }
+        }
+        changed
+    }
+}